@@ -0,0 +1,157 @@
+//! Atomic file writes for configuration files
+
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+/// Backup file suffix, the previous file contents are kept as `<path>.bak`
+pub const BACKUP_SUFFIX: &str = ".bak";
+
+/// Atomically replace a file, keeping a backup of the previous contents
+///
+/// Data is written to a temporary file in the same directory, synced, then
+/// renamed over the target, so readers see either the old or new contents
+/// and never a truncated file. Existing permissions and ownership are preserved.
+pub fn write_atomic(path: impl AsRef<Path>, data: &[u8]) -> io::Result<()> {
+    write_atomic_with(path.as_ref(), data, |_| Ok(()))
+}
+
+/// Steps in replacing a file, used to inject failures in tests
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Step {
+    /// Temporary file written and synced
+    Written,
+    /// Permissions preserved and backup written
+    Backup,
+}
+
+fn write_atomic_with(path: &Path, data: &[u8], step: impl Fn(Step) -> io::Result<()>) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(".tmp.{}", std::process::id()));
+    let tmp = dir.join(tmp_name);
+
+    let r = write_replace(path, &tmp, data, step);
+
+    // Clean up the temporary file on failure
+    if r.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+
+    r?;
+
+    // Sync directory so the rename is durable
+    if let Err(e) = File::open(&dir).and_then(|d| d.sync_all()) {
+        debug!("Failed to sync directory {}: {:?}", dir.display(), e);
+    }
+
+    Ok(())
+}
+
+fn write_replace(path: &Path, tmp: &Path, data: &[u8], step: impl Fn(Step) -> io::Result<()>) -> io::Result<()> {
+    // Write and sync new contents
+    let mut f = OpenOptions::new().write(true).create(true).truncate(true).open(tmp)?;
+    f.write_all(data)?;
+    f.sync_all()?;
+    drop(f);
+    step(Step::Written)?;
+
+    if let Ok(meta) = std::fs::metadata(path) {
+        // Preserve existing permissions and ownership
+        std::fs::set_permissions(tmp, std::fs::Permissions::from_mode(meta.mode() & 0o7777))?;
+
+        let p = CString::new(tmp.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if unsafe { libc::chown(p.as_ptr(), meta.uid(), meta.gid()) } < 0 {
+            warn!("Failed to preserve ownership of {}: {:?}", path.display(), io::Error::last_os_error());
+        }
+
+        // Keep a backup of the previous contents
+        let mut bak = path.as_os_str().to_owned();
+        bak.push(BACKUP_SUFFIX);
+        std::fs::copy(path, &bak)?;
+    }
+    step(Step::Backup)?;
+
+    std::fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    /// Files in a directory, sorted by name
+    fn files(d: &Path) -> Vec<String> {
+        let mut f: Vec<_> = std::fs::read_dir(d).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        f.sort();
+        f
+    }
+
+    #[test]
+    fn write_new_file() {
+        let d = test_dir("atomic", "new");
+        let p = d.join("config.toml");
+
+        write_atomic(&p, b"new").unwrap();
+
+        assert_eq!(std::fs::read(&p).unwrap(), b"new");
+        assert_eq!(files(&d), vec!["config.toml"]);
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn replace_keeps_backup_and_mode() {
+        let d = test_dir("atomic", "replace");
+        let p = d.join("config.toml");
+
+        std::fs::write(&p, b"old").unwrap();
+        std::fs::set_permissions(&p, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+        write_atomic(&p, b"new").unwrap();
+
+        assert_eq!(std::fs::read(&p).unwrap(), b"new");
+        assert_eq!(std::fs::read(d.join("config.toml.bak")).unwrap(), b"old");
+        assert_eq!(std::fs::metadata(&p).unwrap().mode() & 0o7777, 0o640);
+        assert_eq!(files(&d), vec!["config.toml", "config.toml.bak"]);
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn failure_keeps_original() {
+        for s in [Step::Written, Step::Backup] {
+            let d = test_dir("atomic", &format!("fail-{:?}", s));
+            let p = d.join("config.toml");
+            std::fs::write(&p, b"old").unwrap();
+
+            let r = write_atomic_with(&p, b"new", |step| match step == s {
+                true => Err(io::Error::new(io::ErrorKind::Other, "injected")),
+                false => Ok(()),
+            });
+            assert_eq!(r.unwrap_err().to_string(), "injected", "{:?}", s);
+
+            // Original contents are intact and the temporary file is removed
+            assert_eq!(std::fs::read(&p).unwrap(), b"old", "{:?}", s);
+            assert!(files(&d).iter().all(|f| !f.contains(".tmp.")), "{:?}: {:?}", s, files(&d));
+
+            let _ = std::fs::remove_dir_all(&d);
+        }
+    }
+}
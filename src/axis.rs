@@ -1,14 +1,20 @@
+use std::fmt;
+use std::marker::PhantomData;
 use std::ops::{Index, IndexMut};
+use std::str::FromStr;
 
 use evdev_rs::{
     enums::{EventCode, EV_REL},
     InputEvent,
 };
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use structopt::StructOpt;
 use strum::{Display, EnumString, EnumVariantNames};
 
-use crate::AXIS_MAX;
+use crate::{AxisRange, AXIS_RANGE};
 
 /// Axis kind enumeration
 #[derive(
@@ -23,7 +29,9 @@ use crate::AXIS_MAX;
     EnumVariantNames,
     Serialize,
     Deserialize,
+    JsonSchema,
 )]
+#[strum(ascii_case_insensitive)]
 pub enum Axis {
     X,
     Y,
@@ -33,6 +41,20 @@ pub enum Axis {
     RZ,
 }
 
+impl Axis {
+    /// Lowercase axis name, used as the serialized key in [`AxisCollection`]s
+    pub fn key(&self) -> &'static str {
+        match self {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+            Axis::RX => "rx",
+            Axis::RY => "ry",
+            Axis::RZ => "rz",
+        }
+    }
+}
+
 /// List of axes (useful for iteration)
 pub const AXIS: &[Axis] = &[Axis::X, Axis::Y, Axis::Z, Axis::RX, Axis::RY, Axis::RZ];
 
@@ -71,21 +93,57 @@ pub struct AxisValue {
     pub v: f32,
 }
 
-/// Helper to create an [`AxisValue`] from an evdev [`InputEvent`]
-impl TryFrom<InputEvent> for AxisValue {
-    type Error = ();
+/// Per-axis raw input ranges for a device
+pub type DeviceRange = AxisCollection<AxisRange>;
+
+impl Default for DeviceRange {
+    fn default() -> Self {
+        Self::with_axis(|_| AXIS_RANGE)
+    }
+}
 
-    /// Convert an input event into an AxisValue object
-    fn try_from(evt: InputEvent) -> Result<Self, Self::Error> {
-        let a = Axis::try_from(evt.event_code)?;
-        let v = evt.value as f32 / AXIS_MAX as f32;
-        Ok(Self { a, v })
+impl AxisValue {
+    /// Create an axis value from an evdev [`InputEvent`], normalised to -1.0 to 1.0
+    /// using the device range for the axis
+    pub fn from_event(evt: &InputEvent, range: &DeviceRange) -> Result<Self, AxisValueError> {
+        let a = Axis::try_from(evt.event_code).map_err(|_| AxisValueError::UnsupportedCode(evt.event_code))?;
+        Ok(Self { a, v: range[a].normalise(evt.value) })
     }
 }
 
+/// Errors creating an [`AxisValue`] from an input event
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AxisValueError {
+    /// Event code is not an axis input
+    UnsupportedCode(EventCode),
+}
+
+impl fmt::Display for AxisValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AxisValueError::UnsupportedCode(c) => write!(f, "unsupported axis event code {:?}", c),
+        }
+    }
+}
+
+impl std::error::Error for AxisValueError {}
+
+/// Axis state, normalised raw input and transformed output values
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct AxisState {
+    /// Normalised (-1.0 -> 1.0) raw input values
+    pub raw: AxisCollection<f32>,
+    /// Output values after deadzone / curve / scale transformation
+    pub output: AxisCollection<f32>,
+}
+
 /// Generic collection of axes with associated values of type T
-#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
-#[serde(default)]
+///
+/// Serialized as a map keyed by lowercase axis names, deserialization accepts axis names in
+/// any case and rejects unknown axes. Missing axes use `T::default()`.
+#[derive(Copy, Clone, PartialEq, Debug, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+#[schemars(bound = "T: JsonSchema + Serialize + Default, AxisCollection<T>: Default")]
 pub struct AxisCollection<T> {
     #[serde(default)]
     pub x: T,
@@ -113,8 +171,148 @@ impl<T> AxisCollection<T> {
             rz: f(Axis::RZ),
         }
     }
+
+    /// Iterate over axes and values, in [`AXIS`] order
+    pub fn iter(&self) -> impl Iterator<Item = (Axis, &T)> {
+        AXIS.iter().map(move |a| (*a, &self[*a]))
+    }
+
+    /// Iterate over axes and mutable values, in [`AXIS`] order
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Axis, &mut T)> {
+        [
+            (Axis::X, &mut self.x),
+            (Axis::Y, &mut self.y),
+            (Axis::Z, &mut self.z),
+            (Axis::RX, &mut self.rx),
+            (Axis::RY, &mut self.ry),
+            (Axis::RZ, &mut self.rz),
+        ]
+        .into_iter()
+    }
+
+    /// Build a new collection by applying a function to each axis and value
+    pub fn map<U>(&self, f: impl Fn(Axis, &T) -> U) -> AxisCollection<U> {
+        AxisCollection::with_axis(|a| f(a, &self[a]))
+    }
+
+    /// Pair values with those of another collection
+    pub fn zip<'a, U>(&'a self, other: &'a AxisCollection<U>) -> AxisCollection<(&'a T, &'a U)> {
+        AxisCollection::with_axis(|a| (&self[a], &other[a]))
+    }
+
+    /// Build a collection from axis and value pairs, returning the first missing axis
+    /// on failure. Later values replace earlier values for the same axis.
+    pub fn try_from_iter(iter: impl IntoIterator<Item = (Axis, T)>) -> Result<Self, Axis> {
+        let mut c = AxisCollection::<Option<T>> { x: None, y: None, z: None, rx: None, ry: None, rz: None };
+        for (a, v) in iter {
+            c[a] = Some(v);
+        }
+
+        Ok(Self {
+            x: c.x.ok_or(Axis::X)?,
+            y: c.y.ok_or(Axis::Y)?,
+            z: c.z.ok_or(Axis::Z)?,
+            rx: c.rx.ok_or(Axis::RX)?,
+            ry: c.ry.ok_or(Axis::RY)?,
+            rz: c.rz.ok_or(Axis::RZ)?,
+        })
+    }
+}
+
+impl<T: Serialize> Serialize for AxisCollection<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut m = s.serialize_map(Some(AXIS.len()))?;
+        for (a, v) in self {
+            m.serialize_entry(a.key(), v)?;
+        }
+        m.end()
+    }
 }
 
+impl<'de, T: Deserialize<'de> + Default> Deserialize<'de> for AxisCollection<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_map(AxisCollectionVisitor(PhantomData))
+    }
+}
+
+/// Visitor for axis keyed maps, also accepting the previous struct layout (lowercase field names)
+struct AxisCollectionVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de> + Default> Visitor<'de> for AxisCollectionVisitor<T> {
+    type Value = AxisCollection<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of axis names (x, y, z, rx, ry, rz) to values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut m: A) -> Result<Self::Value, A::Error> {
+        let mut c = AxisCollection::<Option<T>>::with_axis(|_| None);
+
+        while let Some(k) = m.next_key::<String>()? {
+            let a = Axis::from_str(&k)
+                .map_err(|_| de::Error::custom(format!("unknown axis '{}', expected one of x, y, z, rx, ry, rz", k)))?;
+
+            if c[a].is_some() {
+                return Err(de::Error::custom(format!("duplicate axis '{}'", k)));
+            }
+            c[a] = Some(m.next_value()?);
+        }
+
+        Ok(c.into_iter().map(|(a, v)| (a, v.unwrap_or_default())).collect())
+    }
+}
+
+/// Collect axis and value pairs
+///
+/// Panics if any axis is missing, use [`AxisCollection::try_from_iter`] for untrusted input.
+impl<T> FromIterator<(Axis, T)> for AxisCollection<T> {
+    fn from_iter<I: IntoIterator<Item = (Axis, T)>>(iter: I) -> Self {
+        match Self::try_from_iter(iter) {
+            Ok(c) => c,
+            Err(a) => panic!("AxisCollection missing value for axis {}", a),
+        }
+    }
+}
+
+impl<T> IntoIterator for AxisCollection<T> {
+    type Item = (Axis, T);
+    type IntoIter = std::array::IntoIter<(Axis, T), 6>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        [
+            (Axis::X, self.x),
+            (Axis::Y, self.y),
+            (Axis::Z, self.z),
+            (Axis::RX, self.rx),
+            (Axis::RY, self.ry),
+            (Axis::RZ, self.rz),
+        ]
+        .into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a AxisCollection<T> {
+    type Item = (Axis, &'a T);
+    type IntoIter = Box<dyn Iterator<Item = (Axis, &'a T)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl<T> Index<&Axis> for AxisCollection<T> {
+    type Output = T;
+
+    fn index(&self, index: &Axis) -> &Self::Output {
+        &self[*index]
+    }
+}
+
+impl<T> IndexMut<&Axis> for AxisCollection<T> {
+    fn index_mut(&mut self, index: &Axis) -> &mut Self::Output {
+        &mut self[*index]
+    }
+}
 
 impl<T> Index<Axis> for AxisCollection<T> {
     type Output = T;
@@ -143,3 +341,150 @@ impl<T> IndexMut<Axis> for AxisCollection<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use evdev_rs::TimeVal;
+
+    use super::*;
+
+    fn counting() -> AxisCollection<u32> {
+        AxisCollection::with_axis(|a| AXIS.iter().position(|b| *b == a).unwrap() as u32)
+    }
+
+    #[test]
+    fn iter_in_axis_order() {
+        let c = counting();
+
+        let v: Vec<_> = c.iter().map(|(a, v)| (a, *v)).collect();
+        assert_eq!(v, AXIS.iter().copied().zip(0..).collect::<Vec<_>>());
+
+        // By reference and by value iterate in the same order
+        assert_eq!((&c).into_iter().map(|(a, v)| (a, *v)).collect::<Vec<_>>(), v);
+        assert_eq!(c.into_iter().collect::<Vec<_>>(), v);
+    }
+
+    #[test]
+    fn iter_mut_updates_values() {
+        let mut c = counting();
+        for (a, v) in c.iter_mut() {
+            if AXIS_ROT.contains(&a) {
+                *v += 10;
+            }
+        }
+
+        assert_eq!(c.iter().map(|(_a, v)| *v).collect::<Vec<_>>(), vec![0, 1, 2, 13, 14, 15]);
+    }
+
+    #[test]
+    fn map_and_zip() {
+        let c = counting();
+
+        let m = c.map(|a, v| format!("{}={}", a, v));
+        assert_eq!(m[Axis::X], "X=0");
+        assert_eq!(m[Axis::RZ], "RZ=5");
+
+        let z = c.zip(&m);
+        for a in AXIS {
+            assert_eq!(z[a], (&c[a], &m[a]));
+        }
+    }
+
+    #[test]
+    fn index_by_reference() {
+        let mut c = counting();
+        for a in AXIS {
+            assert_eq!(c[a], c[*a]);
+            c[a] += 1;
+        }
+
+        assert_eq!(c, AxisCollection::with_axis(|a| counting()[a] + 1));
+    }
+
+    #[test]
+    fn from_iter_collects_all_axes() {
+        let c: AxisCollection<u32> = counting().into_iter().collect();
+        assert_eq!(c, counting());
+
+        // Later values replace earlier values
+        let pairs = counting().into_iter().chain([(Axis::Y, 7)]);
+        assert_eq!(AxisCollection::try_from_iter(pairs).unwrap()[Axis::Y], 7);
+    }
+
+    #[test]
+    fn try_from_iter_reports_missing_axis() {
+        let pairs = counting().into_iter().filter(|(a, _v)| *a != Axis::RY);
+        assert_eq!(AxisCollection::try_from_iter(pairs), Err(Axis::RY));
+    }
+
+    #[test]
+    #[should_panic(expected = "missing value for axis Z")]
+    fn from_iter_panics_on_missing_axis() {
+        let _c: AxisCollection<u32> = [(Axis::X, 1), (Axis::Y, 2)].into_iter().collect();
+    }
+
+    #[test]
+    fn round_trip() {
+        let c = counting();
+
+        let s = serde_json::to_string(&c).unwrap();
+        assert_eq!(s, r#"{"x":0,"y":1,"z":2,"rx":3,"ry":4,"rz":5}"#);
+        assert_eq!(serde_json::from_str::<AxisCollection<u32>>(&s).unwrap(), c);
+
+        let s = toml::to_string(&c).unwrap();
+        assert_eq!(toml::from_str::<AxisCollection<u32>>(&s).unwrap(), c);
+
+        let b = bincode::serialize(&c).unwrap();
+        assert_eq!(bincode::deserialize::<AxisCollection<u32>>(&b).unwrap(), c);
+    }
+
+    #[test]
+    fn legacy_layout() {
+        // Previous struct layout, with missing axes defaulted
+        let c: AxisCollection<u32> = toml::from_str("x = 1\nrz = 6\n").unwrap();
+        assert_eq!(c, AxisCollection { x: 1, rz: 6, ..AxisCollection::with_axis(|_| 0) });
+
+        // Axis names in any case
+        let c: AxisCollection<u32> = toml::from_str("X = 1\nRx = 4\nrZ = 6\n").unwrap();
+        assert_eq!((c[Axis::X], c[Axis::RX], c[Axis::RZ]), (1, 4, 6));
+    }
+
+    #[test]
+    fn unknown_and_duplicate_axes_rejected() {
+        let e = toml::from_str::<AxisCollection<u32>>("x = 1\nw = 2\n").unwrap_err();
+        assert!(e.to_string().contains("unknown axis 'w'"), "{}", e);
+
+        let e = serde_json::from_str::<AxisCollection<u32>>(r#"{"rx":1,"RX":2}"#).unwrap_err();
+        assert!(e.to_string().contains("duplicate axis 'RX'"), "{}", e);
+    }
+
+    #[test]
+    fn from_event_within_range() {
+        let event = |value| InputEvent { time: TimeVal::new(0, 0), event_code: EventCode::EV_REL(EV_REL::REL_RX), value };
+
+        // Default, calibrated, one-sided and degenerate ranges
+        let ranges = [(AXIS_RANGE.min, AXIS_RANGE.max), (-1000, 20), (0, 500), (-500, 0), (0, 0), (10, -10), (i32::MIN, i32::MAX)];
+
+        let edges = [i32::MIN, i32::MIN + 1, -1, 0, 1, i32::MAX - 1, i32::MAX];
+        let values = edges.into_iter().chain((i32::MIN..=i32::MAX).step_by(65_537));
+
+        for v in values {
+            for r in ranges {
+                let range = DeviceRange::with_axis(|_| AxisRange::from(r));
+                let a = AxisValue::from_event(&event(v), &range).unwrap();
+
+                assert_eq!(a.a, Axis::RX);
+                assert!(a.v.is_finite() && (-1.0..=1.0).contains(&a.v), "{} with range {:?} normalised to {}", v, r, a.v);
+            }
+        }
+    }
+
+    #[test]
+    fn from_event_rejects_other_codes() {
+        let e = InputEvent { time: TimeVal::new(0, 0), event_code: EventCode::EV_REL(EV_REL::REL_WHEEL), value: 1 };
+        assert_eq!(
+            AxisValue::from_event(&e, &DeviceRange::default()),
+            Err(AxisValueError::UnsupportedCode(EventCode::EV_REL(EV_REL::REL_WHEEL)))
+        );
+    }
+}
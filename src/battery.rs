@@ -0,0 +1,142 @@
+//! Battery status for wireless devices, read from the kernel `power_supply` class
+
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+/// Default sysfs mount point
+pub const SYSFS_ROOT: &str = "/sys";
+
+/// Number of parent devices searched for a power supply, the HID device
+/// (parent of input and hidraw nodes) is usually the first or second
+const SEARCH_DEPTH: usize = 4;
+
+/// Find the `power_supply` entry for a device node (`/dev/input/eventN` or `/dev/hidrawN`)
+pub fn find_power_supply(path: &str) -> Option<PathBuf> {
+    find_power_supply_in(Path::new(SYSFS_ROOT), path)
+}
+
+/// Find the `power_supply` entry for a device node under a sysfs root
+///
+/// The device's sysfs entry and its parents are searched for a `power_supply`
+/// directory containing an entry reporting `capacity`.
+pub fn find_power_supply_in(sysfs: &Path, path: &str) -> Option<PathBuf> {
+    let name = Path::new(path).file_name()?.to_str()?;
+    let class = match name {
+        n if n.starts_with("hidraw") => "hidraw",
+        n if n.starts_with("event") => "input",
+        _ => return None,
+    };
+
+    let sysfs = sysfs.canonicalize().ok()?;
+    let mut dev = sysfs.join("class").join(class).join(name).join("device").canonicalize().ok()?;
+
+    for _ in 0..SEARCH_DEPTH {
+        if let Ok(entries) = read_dir(dev.join("power_supply")) {
+            let mut supplies: Vec<_> = entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.join("capacity").exists())
+                .collect();
+            supplies.sort();
+
+            if let Some(s) = supplies.into_iter().next() {
+                return Some(s);
+            }
+        }
+
+        if !dev.pop() || !dev.starts_with(&sysfs) {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Read the battery level (0-100%) from a `power_supply` entry
+pub fn read_capacity(supply: &Path) -> Option<u8> {
+    let v = read_to_string(supply.join("capacity")).ok()?;
+    v.trim().parse::<u8>().ok().map(|v| v.min(100))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+    use std::os::unix::fs::symlink;
+
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    /// Mock sysfs with a wireless receiver (battery on the HID device) and a wired device,
+    /// each with input and hidraw nodes linked from the class directories
+    fn sysfs(name: &str) -> PathBuf {
+        let root = test_dir("battery", name);
+        create_dir_all(root.join("class/input")).unwrap();
+        create_dir_all(root.join("class/hidraw")).unwrap();
+
+        for (hid, input, event, hidraw, supply) in [
+            ("0003:256F:C652.0001", "input5", "event5", "hidraw2", true),
+            ("0003:256F:C635.0002", "input6", "event6", "hidraw3", false),
+        ] {
+            let hid_dir = Path::new("devices/pci0000:00/usb1/1-1").join(hid);
+
+            let input_dir = hid_dir.join("input").join(input);
+            create_dir_all(root.join(&input_dir).join(event)).unwrap();
+            symlink(format!("../../{}", input), root.join(&input_dir).join(event).join("device")).unwrap();
+            symlink(Path::new("../..").join(&input_dir).join(event), root.join("class/input").join(event)).unwrap();
+
+            let hidraw_dir = hid_dir.join("hidraw").join(hidraw);
+            create_dir_all(root.join(&hidraw_dir)).unwrap();
+            symlink("../..", root.join(&hidraw_dir).join("device")).unwrap();
+            symlink(Path::new("../..").join(&hidraw_dir), root.join("class/hidraw").join(hidraw)).unwrap();
+
+            if supply {
+                // Entries without a capacity (eg. chargers) are skipped
+                create_dir_all(root.join(&hid_dir).join("power_supply/a-charger")).unwrap();
+                create_dir_all(root.join(&hid_dir).join("power_supply/hid-battery")).unwrap();
+                write(root.join(&hid_dir).join("power_supply/hid-battery/capacity"), "87\n").unwrap();
+            }
+        }
+
+        root
+    }
+
+    #[test]
+    fn supply_for_nodes() {
+        let root = sysfs("nodes");
+        let supply = root.canonicalize().unwrap().join("devices/pci0000:00/usb1/1-1/0003:256F:C652.0001/power_supply/hid-battery");
+
+        // Input and hidraw nodes of the same device share a supply
+        assert_eq!(find_power_supply_in(&root, "/dev/input/event5"), Some(supply.clone()));
+        assert_eq!(find_power_supply_in(&root, "/dev/hidraw2"), Some(supply.clone()));
+        assert_eq!(read_capacity(&supply), Some(87));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn no_supply() {
+        let root = sysfs("none");
+
+        // Wired devices, missing and unknown nodes have no supply
+        for p in ["/dev/input/event6", "/dev/hidraw3", "/dev/input/event9", "/dev/input/mouse0", "/dev/vmouse"] {
+            assert_eq!(find_power_supply_in(&root, p), None, "{}", p);
+        }
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn capacity_values() {
+        let d = test_dir("battery", "capacity");
+
+        for (v, expected) in [("0\n", Some(0)), ("100", Some(100)), ("120\n", Some(100)), ("", None), ("full", None), ("-1", None)] {
+            write(d.join("capacity"), v).unwrap();
+            assert_eq!(read_capacity(&d), expected, "{:?}", v);
+        }
+
+        std::fs::remove_file(d.join("capacity")).unwrap();
+        assert_eq!(read_capacity(&d), None);
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+}
@@ -0,0 +1,330 @@
+//! Blocking (synchronous) daemon client, shares framing with [`Client`](crate::Client)
+
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use tracing::{debug, trace};
+
+use crate::{AxisState, Command, Config, Decoder, Error, ErrorCode, PROTOCOL_VERSION};
+
+/// Default read / write timeout
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Blocking client for scripts and tools without an async runtime
+#[derive(Debug)]
+pub struct BlockingClient {
+    path: String,
+    stream: UnixStream,
+    decoder: Decoder,
+    version: u32,
+    features: Vec<String>,
+}
+
+impl BlockingClient {
+    /// Connect to the daemon socket with the default timeout
+    pub fn connect(path: &str) -> Result<Self, Error> {
+        Self::connect_with_timeout(path, DEFAULT_TIMEOUT)
+    }
+
+    /// Connect to the daemon socket with the provided read / write timeout
+    pub fn connect_with_timeout(path: &str, timeout: Duration) -> Result<Self, Error> {
+        let stream = UnixStream::connect(path).map_err(Error::SocketIo)?;
+
+        stream.set_read_timeout(Some(timeout)).map_err(Error::SocketIo)?;
+        stream.set_write_timeout(Some(timeout)).map_err(Error::SocketIo)?;
+
+        let mut c = Self {
+            path: path.to_string(),
+            stream,
+            decoder: Decoder::new(),
+            version: 0,
+            features: vec![],
+        };
+
+        c.hello()?;
+
+        Ok(c)
+    }
+
+    /// Exchange [`Command::Hello`] with the daemon, checking protocol compatibility
+    fn hello(&mut self) -> Result<(), Error> {
+        let invalid = |m: String| Error::SocketIo(std::io::Error::new(ErrorKind::InvalidData, m));
+
+        let hello = Command::Hello { version: PROTOCOL_VERSION, features: crate::protocol_features() };
+        let r = self.request(&hello)
+            .map_err(|e| invalid(format!("Handshake with '{}' failed: {}", self.path, e)))?;
+
+        match r {
+            Command::Hello { version, features } => {
+                debug!("Connected to daemon protocol v{} (features: {:?})", crate::protocol_string(version), features);
+                self.version = version;
+                self.features = features;
+                Ok(())
+            }
+            Command::Error(ErrorCode::VersionMismatch { daemon, client }) => Err(Error::VersionMismatch { daemon, client }),
+            r => Err(invalid(format!(
+                "Unexpected handshake response from '{}' ({:?}), vmoused may predate protocol versioning, please upgrade",
+                self.path, r
+            ))),
+        }
+    }
+
+    /// Daemon protocol version, from the connection handshake
+    pub fn daemon_version(&self) -> u32 {
+        self.version
+    }
+
+    /// Daemon features, from the connection handshake
+    pub fn daemon_features(&self) -> &[String] {
+        &self.features
+    }
+
+    /// Update read / write timeout
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.stream.set_read_timeout(Some(timeout)).map_err(Error::SocketIo)?;
+        self.stream.set_write_timeout(Some(timeout)).map_err(Error::SocketIo)
+    }
+
+    /// Send a command
+    pub fn send(&mut self, cmd: &Command) -> Result<(), Error> {
+        let encoded = crate::encode(cmd)?;
+
+        debug!("Send: {:?}", cmd);
+
+        self.stream.write_all(&encoded).map_err(Error::SocketIo)?;
+
+        Ok(())
+    }
+
+    /// Receive the next command, blocking until available or timeout
+    pub fn recv(&mut self) -> Result<Command, Error> {
+        let mut buff = [0u8; 1024];
+
+        loop {
+            match self.decoder.decode()? {
+                // Acknowledge daemon keepalive pings transparently
+                Some(Command::Ping) => {
+                    trace!("Keepalive ping from '{}'", self.path);
+                    self.send(&Command::Ok)?;
+                    continue;
+                }
+                Some(c) => {
+                    trace!("Receive: {:?}", c);
+                    return Ok(c);
+                }
+                None => (),
+            }
+
+            let n = match self.stream.read(&mut buff) {
+                Ok(0) => {
+                    let e = std::io::Error::new(ErrorKind::UnexpectedEof, format!("Connection to '{}' closed", self.path));
+                    return Err(Error::SocketIo(e));
+                }
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::SocketIo(e)),
+            };
+
+            self.decoder.push(&buff[..n]);
+        }
+    }
+
+    /// Send a command and await the response
+    pub fn request(&mut self, cmd: &Command) -> Result<Command, Error> {
+        self.send(cmd)?;
+        self.recv()
+    }
+
+    /// Ping the daemon
+    pub fn ping(&mut self) -> Result<(), Error> {
+        match self.request(&Command::Ping)? {
+            Command::Ok => Ok(()),
+            r => Err(Error::UnexpectedResponse(Box::new(r))),
+        }
+    }
+
+    /// Fetch aggregate axis state
+    pub fn get_state(&mut self) -> Result<AxisState, Error> {
+        match self.request(&Command::GetState { device: None })? {
+            Command::State { state, .. } => Ok(state),
+            r => Err(Error::UnexpectedResponse(Box::new(r))),
+        }
+    }
+
+    /// Fetch daemon config
+    pub fn get_config(&mut self) -> Result<Config, Error> {
+        match self.request(&Command::GetConfig)? {
+            Command::SetConfig(c) => Ok(c),
+            r => Err(Error::UnexpectedResponse(Box::new(r))),
+        }
+    }
+
+    /// Update daemon config
+    pub fn set_config(&mut self, c: Config) -> Result<(), Error> {
+        match self.request(&Command::SetConfig(c))? {
+            Command::Ok => Ok(()),
+            r => Err(Error::UnexpectedResponse(Box::new(r))),
+        }
+    }
+
+    /// Enable or disable daemon output, for all devices or a specific device
+    pub fn enable(&mut self, enabled: bool, device: Option<&str>) -> Result<(), Error> {
+        match self.request(&Command::Enable { enabled, device: device.map(String::from) })? {
+            Command::Ok => Ok(()),
+            r => Err(Error::UnexpectedResponse(Box::new(r))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::thread::JoinHandle;
+
+    use crate::client::tests::{read_frame, socket_path};
+
+    use super::*;
+
+    /// Fake daemon answering the handshake, then running `f` on the connection
+    fn spawn_peer<F>(path: &str, f: F) -> JoinHandle<()>
+    where
+        F: FnOnce(&mut UnixStream, &mut Decoder) + Send + 'static,
+    {
+        let listener = UnixListener::bind(path).unwrap();
+
+        std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut d = Decoder::new();
+
+            match read_frame(&mut s, &mut d).map(|f| f.cmd) {
+                Some(Command::Hello { .. }) => (),
+                c => panic!("unexpected handshake: {:?}", c),
+            }
+            reply(&mut s, Command::Hello { version: PROTOCOL_VERSION, features: vec![] });
+
+            f(&mut s, &mut d)
+        })
+    }
+
+    fn reply(s: &mut UnixStream, c: Command) {
+        s.write_all(&crate::encode(&c).unwrap()).unwrap();
+    }
+
+    fn expect(s: &mut UnixStream, d: &mut Decoder, c: Command) {
+        assert_eq!(read_frame(s, d).map(|f| f.cmd), Some(c));
+    }
+
+    #[test]
+    fn typed_requests() {
+        let path = socket_path("blocking");
+        let peer = spawn_peer(&path, |s, d| {
+            expect(s, d, Command::Ping);
+            reply(s, Command::Ok);
+
+            expect(s, d, Command::GetConfig);
+            reply(s, Command::SetConfig(Config::default()));
+
+            expect(s, d, Command::SetConfig(Config::default()));
+            reply(s, Command::Ok);
+
+            expect(s, d, Command::Enable { enabled: false, device: Some("256f:c635".to_string()) });
+            reply(s, Command::Ok);
+
+            // Unexpected responses are reported rather than misread
+            expect(s, d, Command::GetState { device: None });
+            reply(s, Command::Ok);
+        });
+
+        let mut c = BlockingClient::connect(&path).unwrap();
+        assert_eq!(c.daemon_version(), PROTOCOL_VERSION);
+
+        c.ping().unwrap();
+        assert_eq!(c.get_config().unwrap(), Config::default());
+        c.set_config(Config::default()).unwrap();
+        c.enable(false, Some("256f:c635")).unwrap();
+        assert!(matches!(c.get_state(), Err(Error::UnexpectedResponse(r)) if *r == Command::Ok));
+
+        peer.join().unwrap();
+    }
+
+    #[test]
+    fn keepalive_acknowledged() {
+        let path = socket_path("blocking-keepalive");
+        let peer = spawn_peer(&path, |s, d| {
+            expect(s, d, Command::GetConfig);
+
+            // Pings received while awaiting a response are answered transparently
+            reply(s, Command::Ping);
+            expect(s, d, Command::Ok);
+
+            reply(s, Command::SetConfig(Config::default()));
+        });
+
+        let mut c = BlockingClient::connect(&path).unwrap();
+        assert_eq!(c.request(&Command::GetConfig).unwrap(), Command::SetConfig(Config::default()));
+
+        peer.join().unwrap();
+    }
+
+    #[test]
+    fn request_timeout() {
+        let path = socket_path("blocking-timeout");
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        let peer = spawn_peer(&path, move |s, d| {
+            // Hold the connection open without answering
+            expect(s, d, Command::GetConfig);
+            let _ = done_rx.recv();
+        });
+
+        let mut c = BlockingClient::connect_with_timeout(&path, Duration::from_millis(50)).unwrap();
+        match c.request(&Command::GetConfig) {
+            Err(Error::SocketIo(e)) => assert!(matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut), "{}", e),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        done_tx.send(()).unwrap();
+        peer.join().unwrap();
+    }
+
+    #[test]
+    fn connection_closed() {
+        let path = socket_path("blocking-closed");
+        let peer = spawn_peer(&path, |s, d| {
+            expect(s, d, Command::Ping);
+        });
+
+        let mut c = BlockingClient::connect(&path).unwrap();
+        match c.request(&Command::Ping) {
+            Err(Error::SocketIo(e)) => assert_eq!(e.kind(), ErrorKind::UnexpectedEof),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        peer.join().unwrap();
+    }
+
+    #[test]
+    fn handshake_version_mismatch() {
+        let path = socket_path("blocking-mismatch");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let daemon = PROTOCOL_VERSION + (1 << 16);
+        let peer = std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut d = Decoder::new();
+
+            let client = match read_frame(&mut s, &mut d).map(|f| f.cmd) {
+                Some(Command::Hello { version, .. }) => version,
+                c => panic!("unexpected handshake: {:?}", c),
+            };
+            reply(&mut s, Command::Error(ErrorCode::VersionMismatch { daemon, client }));
+        });
+
+        match BlockingClient::connect(&path) {
+            Err(Error::VersionMismatch { daemon: d, client }) => assert_eq!((d, client), (daemon, PROTOCOL_VERSION)),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        peer.join().unwrap();
+    }
+}
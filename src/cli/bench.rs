@@ -0,0 +1,253 @@
+//! End-to-end latency benchmark for `vmousectl bench`
+//!
+//! Synthetic samples are injected through the daemon socket with the send time as the
+//! source event time, then matched against raw value broadcasts (by source time) and,
+//! where readable, the virtual pointer device node. Other pointer input while running
+//! will be counted as emitted samples, so the bench is best run with devices idle.
+
+use std::fs::File;
+use std::io::ErrorKind;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use evdev_rs::enums::{EventCode, EV_REL};
+use evdev_rs::{Device, ReadFlag};
+use log::{debug, warn};
+use serde::Serialize;
+
+use vmouse::{Axis, AxisCollection, BlockingClient, Command, OutputRole, Topic};
+
+/// Timeout for the daemon acknowledgement and raw value broadcast of each sample
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Timeout for output events on the virtual device node
+const EMIT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Injected X value, alternating in sign so the pointer does not drift
+const BENCH_VALUE: f32 = 0.5;
+
+/// Latency summary (us)
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min: u64,
+    pub median: u64,
+    pub p95: u64,
+    pub max: u64,
+}
+
+impl LatencyStats {
+    /// Summarise latency samples, `None` if empty
+    pub fn new(mut samples: Vec<u64>) -> Option<Self> {
+        samples.sort_unstable();
+
+        let n = samples.len();
+        let rank = |p: f32| samples[((n - 1) as f32 * p).round() as usize];
+
+        match n {
+            0 => None,
+            _ => Some(Self { count: n, min: samples[0], median: rank(0.5), p95: rank(0.95), max: samples[n - 1] }),
+        }
+    }
+}
+
+/// Benchmark results
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct BenchReport {
+    /// Samples injected
+    pub samples: usize,
+    /// Samples without a matching raw value broadcast
+    pub lost: usize,
+    /// Bench duration (s)
+    pub elapsed: f32,
+    /// Completed samples per second
+    pub throughput: f32,
+    /// Device node used for emission timing
+    pub devnode: Option<String>,
+    /// Inject to daemon processing, including socket ingest
+    pub pipeline: Option<LatencyStats>,
+    /// Daemon processing to broadcast receipt
+    pub broadcast: Option<LatencyStats>,
+    /// Inject to broadcast receipt
+    pub round_trip: Option<LatencyStats>,
+    /// Inject to virtual device output
+    pub emit: Option<LatencyStats>,
+}
+
+/// Inject samples and report end-to-end latency
+pub fn run(socket: &str, count: usize, interval: u64, devnode: Option<String>, json: bool) -> anyhow::Result<()> {
+    let mut client = BlockingClient::connect(socket)?;
+
+    // Locate and open the pointer output, emission timing is skipped where unavailable
+    let devnode = match devnode {
+        Some(d) => Some(d),
+        None => match client.request(&Command::GetDevnode)? {
+            Command::Devnodes(d) => d.into_iter().find(|d| d.role == OutputRole::Pointer).and_then(|d| d.devnode),
+            r => return Err(anyhow::anyhow!("Unexpected response: {:?}", r)),
+        },
+    };
+    let emit_rx = match devnode.as_deref().map(watch) {
+        Some(Ok(rx)) => Some(rx),
+        Some(Err(e)) => {
+            warn!("Skipping emission timing, failed to open '{}': {}", devnode.as_deref().unwrap_or_default(), e);
+            None
+        }
+        None => {
+            warn!("Skipping emission timing, no pointer output device");
+            None
+        }
+    };
+    let devnode = emit_rx.as_ref().and(devnode);
+
+    match client.request(&Command::Listen { topics: vec![Topic::RawValues] })? {
+        Command::Ok => (),
+        r => return Err(anyhow::anyhow!("Listen failed: {:?}", r)),
+    }
+
+    client.set_timeout(RESPONSE_TIMEOUT)?;
+
+    let (mut pipeline, mut broadcast, mut round_trip, mut emit) = (vec![], vec![], vec![], vec![]);
+    let mut lost = 0;
+
+    let start = Instant::now();
+
+    for i in 0..count {
+        let mut axes = AxisCollection::<f32>::default();
+        axes[Axis::X] = if i % 2 == 0 { BENCH_VALUE } else { -BENCH_VALUE };
+
+        let sent = vmouse::now_us();
+        client.send(&Command::Inject { axes, time_us: sent })?;
+
+        // Await the acknowledgement and the matching raw value, in either order
+        let (mut acked, mut raw) = (false, None);
+        while !acked || raw.is_none() {
+            match client.recv() {
+                Ok(Command::Ok) => acked = true,
+                Ok(Command::Error(e)) => return Err(anyhow::anyhow!("Inject failed: {}", e)),
+                Ok(Command::RawValue(v, t)) if v.a == Axis::X && t.source_us == sent => raw = Some((t, vmouse::now_us())),
+                Ok(_) => (),
+                Err(e) if matches!(e.io_kind(), Some(ErrorKind::WouldBlock | ErrorKind::TimedOut)) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        match raw {
+            Some((t, recv)) => {
+                pipeline.push(t.processed_us.saturating_sub(t.source_us));
+                broadcast.push(recv.saturating_sub(t.processed_us));
+                round_trip.push(recv.saturating_sub(sent));
+            }
+            None => {
+                debug!("No raw value for sample {}", i);
+                lost += 1;
+                continue;
+            }
+        }
+
+        // Output events from earlier samples are older than the send time and skipped
+        if let Some(rx) = &emit_rx {
+            while let Ok(t) = rx.recv_timeout(EMIT_TIMEOUT) {
+                if t >= sent {
+                    emit.push(t - sent);
+                    break;
+                }
+            }
+        }
+
+        std::thread::sleep(Duration::from_millis(interval));
+    }
+
+    let elapsed = start.elapsed().as_secs_f32();
+
+    let r = BenchReport {
+        samples: count,
+        lost,
+        elapsed,
+        throughput: (count - lost) as f32 / elapsed.max(f32::EPSILON),
+        devnode,
+        pipeline: LatencyStats::new(pipeline),
+        broadcast: LatencyStats::new(broadcast),
+        round_trip: LatencyStats::new(round_trip),
+        emit: LatencyStats::new(emit),
+    };
+
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&r)?),
+        false => print(&r),
+    }
+
+    Ok(())
+}
+
+/// Read `REL_X` event times (us) from an output device node on a background thread
+fn watch(path: &str) -> anyhow::Result<mpsc::Receiver<u64>> {
+    let d = Device::new_from_file(File::open(path)?)?;
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || loop {
+        match d.next_event(ReadFlag::NORMAL) {
+            Ok((_status, e)) if e.event_code == EventCode::EV_REL(EV_REL::REL_X) => {
+                if tx.send(vmouse::time_us(&e.time)).is_err() {
+                    break;
+                }
+            }
+            Ok(_) => (),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+            Err(e) => {
+                debug!("Output device read failed: {}", e);
+                break;
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+/// Print a latency summary line
+fn stats(name: &str, s: &Option<LatencyStats>) {
+    match s {
+        Some(s) => println!("{:<11} min {}us median {}us p95 {}us max {}us", name, s.min, s.median, s.p95, s.max),
+        None => println!("{:<11} -", name),
+    }
+}
+
+/// Pretty-print benchmark results
+fn print(r: &BenchReport) {
+    println!("samples:    {} ({} lost) in {:.2}s ({:.1}/s)", r.samples, r.lost, r.elapsed, r.throughput);
+    stats("pipeline:", &r.pipeline);
+    stats("broadcast:", &r.broadcast);
+    stats("round trip:", &r.round_trip);
+    stats("emit:", &r.emit);
+
+    if let Some(d) = &r.devnode {
+        println!("devnode:    {}", d);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_empty() {
+        assert_eq!(LatencyStats::new(vec![]), None);
+    }
+
+    #[test]
+    fn stats_single_sample() {
+        assert_eq!(LatencyStats::new(vec![42]), Some(LatencyStats { count: 1, min: 42, median: 42, p95: 42, max: 42 }));
+    }
+
+    #[test]
+    fn stats_nearest_rank() {
+        // Unsorted 1..=100
+        let samples: Vec<u64> = (1..=100).map(|i| (i * 37) % 100 + 1).collect();
+        assert_eq!(LatencyStats::new(samples), Some(LatencyStats { count: 100, min: 1, median: 51, p95: 95, max: 100 }));
+
+        let s = LatencyStats::new((1..=20).rev().collect()).unwrap();
+        assert_eq!((s.median, s.p95), (11, 19));
+
+        let s = LatencyStats::new(vec![10, 1000, 20]).unwrap();
+        assert_eq!((s.min, s.median, s.p95, s.max), (10, 20, 1000, 1000));
+    }
+}
@@ -0,0 +1,40 @@
+//! Device binding for `vmousectl bind`
+
+use std::time::Duration;
+
+use log::debug;
+
+use vmouse::{BindTarget, BlockingClient, Command, ErrorCode, UsbDevice, DEFAULT_TIMEOUT};
+
+/// Bind a device by path or id, optionally waiting for it to appear
+pub fn run(socket: &str, event: Option<String>, id: Option<UsbDevice>, wait: Option<u64>) -> anyhow::Result<()> {
+    let target = match (event, id) {
+        (_, Some(id)) => BindTarget::Id(id),
+        (Some(p), None) => BindTarget::Path(p),
+        (None, None) => return Err(anyhow::anyhow!("Device path or --id required")),
+    };
+
+    // The daemon holds the response while waiting, extend the read timeout to match
+    let timeout = DEFAULT_TIMEOUT + Duration::from_secs(wait.unwrap_or(0));
+    let mut client = BlockingClient::connect_with_timeout(socket, timeout)?;
+
+    debug!("Binding {:?} (wait: {:?})", target, wait);
+
+    match client.request(&Command::Bind { target: target.clone(), wait })? {
+        Command::Ok => {
+            println!("Bound {}", describe(&target));
+            Ok(())
+        }
+        Command::Error(ErrorCode::DeviceNotFound) => Err(anyhow::anyhow!("No unbound device found for {}", describe(&target))),
+        Command::Error(ErrorCode::PermissionDenied) => Err(anyhow::anyhow!("Permission denied binding {}", describe(&target))),
+        r => Err(anyhow::anyhow!("Failed to bind {}: {:?}", describe(&target), r)),
+    }
+}
+
+/// Describe a bind target for messages
+fn describe(t: &BindTarget) -> String {
+    match t {
+        BindTarget::Path(p) => p.clone(),
+        BindTarget::Id(d) => d.to_string(),
+    }
+}
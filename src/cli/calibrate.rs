@@ -0,0 +1,53 @@
+//! Guided axis calibration for `vmousectl calibrate`
+
+use std::io::BufRead;
+
+use log::debug;
+
+use vmouse::{BlockingClient, CalibrateAction, Command, UsbDevice, AXIS};
+
+/// Run calibration for a device, prompting the user to move all axes to their extremes
+pub fn run(socket: &str, device: &str) -> anyhow::Result<()> {
+    let mut client = BlockingClient::connect(socket)?;
+
+    // Normalise device name (or alias) to the daemon `vid:pid` form
+    let device = &client.get_config()?.resolve(device).parse::<UsbDevice>()?.to_string();
+
+    let calibrate = |action| Command::Calibrate { device: device.to_string(), action };
+
+    match client.request(&calibrate(CalibrateAction::Start))? {
+        Command::Ok => (),
+        r => return Err(anyhow::anyhow!("Failed to start calibration: {:?}", r)),
+    }
+
+    println!("Calibrating device: {}", device);
+    println!("Move all axes to their extremes in both directions, then press enter (or ctrl-d to cancel)");
+
+    let mut line = String::new();
+    let n = std::io::stdin().lock().read_line(&mut line);
+
+    // Cancel on EOF or read failure
+    if !matches!(n, Ok(n) if n > 0) {
+        debug!("Cancelling calibration");
+        client.request(&calibrate(CalibrateAction::Cancel))?;
+        println!("Calibration cancelled");
+        return Ok(());
+    }
+
+    match client.request(&calibrate(CalibrateAction::Finish))? {
+        Command::Calibrated(r) => {
+            for a in AXIS {
+                match r[*a] {
+                    (0, 0) => println!("{:>3}: not calibrated (no movement)", a),
+                    (min, 0) => println!("{:>3}: {:+5} .. (unchanged)", a, min),
+                    (0, max) => println!("{:>3}: (unchanged) .. {:+5}", a, max),
+                    (min, max) => println!("{:>3}: {:+5} .. {:+5}", a, min, max),
+                }
+            }
+            println!("Calibration applied, run `vmousectl write-config` to persist");
+        }
+        r => return Err(anyhow::anyhow!("Failed to finish calibration: {:?}", r)),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+//! Config file validation for `vmousectl check-config`
+
+use vmouse::{Config, ConfigFile};
+
+/// Check a config file, printing errors and warnings
+///
+/// Returns `true` if the config contains no hard errors.
+pub fn run(path: &str) -> anyhow::Result<bool> {
+    let f = ConfigFile::load(path)?;
+
+    let errors = Config::from(&f).validate().err().unwrap_or_default();
+
+    for e in &errors {
+        match e.is_warning() {
+            true => println!("warning: {}", e),
+            false => println!("error: {}", e),
+        }
+    }
+
+    let ok = errors.iter().all(|e| e.is_warning());
+    if ok {
+        println!("'{}' OK ({} warnings)", path, errors.len());
+    }
+
+    Ok(ok)
+}
@@ -0,0 +1,62 @@
+//! Virtual output device lookup for `vmousectl devnode`
+
+use std::time::{Duration, Instant};
+
+use log::debug;
+
+use vmouse::{BlockingClient, Command, OutputDevice};
+
+/// Interval between checks when waiting for output devices
+const WAIT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Print virtual output device nodes, optionally waiting for the daemon and devices to appear
+pub fn run(socket: &str, wait: Option<u64>, json: bool) -> anyhow::Result<()> {
+    let deadline = wait.map(|w| Instant::now() + Duration::from_secs(w));
+
+    let devices = loop {
+        let r = fetch(socket);
+
+        let retry = match (&r, deadline) {
+            (Ok(d), Some(t)) => d.is_empty() && Instant::now() < t,
+            (Err(_), Some(t)) => Instant::now() < t,
+            (_, None) => false,
+        };
+
+        if !retry {
+            break r?;
+        }
+
+        debug!("Waiting for output devices: {:?}", r);
+        std::thread::sleep(WAIT_INTERVAL);
+    };
+
+    if devices.is_empty() {
+        return Err(anyhow::anyhow!("No output devices (released while idle?)"));
+    }
+
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&devices)?),
+        false => {
+            for d in &devices {
+                println!(
+                    "{} {} {}",
+                    d.role,
+                    d.devnode.as_deref().unwrap_or("-"),
+                    d.syspath.as_deref().unwrap_or("-")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetch output devices from the daemon
+fn fetch(socket: &str) -> anyhow::Result<Vec<OutputDevice>> {
+    let mut client = BlockingClient::connect(socket)?;
+
+    match client.request(&Command::GetDevnode)? {
+        Command::Devnodes(d) => Ok(d),
+        r => Err(anyhow::anyhow!("Unexpected response: {:?}", r)),
+    }
+}
@@ -0,0 +1,334 @@
+//! Environment diagnostics for `vmousectl doctor`
+
+use std::ffi::CString;
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde::Serialize;
+use strum::Display;
+
+use vmouse::{Client, Command, Config, ConfigFile, ConfigFormat};
+
+/// Timeout for daemon ping checks
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Check result status
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, Serialize)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Individual check report
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct Check {
+    /// Check name
+    pub name: String,
+    /// Check result
+    pub status: Status,
+    /// Result details
+    pub message: String,
+    /// Suggested fix for failed checks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &str, message: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            status: Status::Pass,
+            message: message.to_string(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &str, message: impl ToString, fix: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            status: Status::Warn,
+            message: message.to_string(),
+            fix: Some(fix.to_string()),
+        }
+    }
+
+    fn fail(name: &str, message: impl ToString, fix: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            status: Status::Fail,
+            message: message.to_string(),
+            fix: Some(fix.to_string()),
+        }
+    }
+}
+
+/// Run all checks
+pub async fn run(socket: &str, config: &str) -> Vec<Check> {
+    vec![
+        check_uinput_module(Path::new("/sys/module/uinput"), Path::new("/dev/uinput")),
+        check_uinput_access(Path::new("/dev/uinput")),
+        check_input_access(Path::new("/dev/input")),
+        check_config(config),
+        check_socket(socket).await,
+    ]
+}
+
+/// Print check results, as JSON if requested
+pub fn report(checks: &[Check], json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(checks)?);
+        return Ok(());
+    }
+
+    for c in checks {
+        println!("[{}] {}: {}", c.status, c.name, c.message);
+        if let Some(f) = &c.fix {
+            println!("       fix: {}", f);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the uinput module is loaded (or built in)
+pub fn check_uinput_module(module: &Path, dev: &Path) -> Check {
+    const NAME: &str = "uinput module";
+
+    if module.exists() || dev.exists() {
+        Check::pass(NAME, "uinput available")
+    } else {
+        Check::fail(NAME, "uinput module not loaded", "run `modprobe uinput` and add `uinput` to /etc/modules-load.d")
+    }
+}
+
+/// Check the current user can open the uinput device
+pub fn check_uinput_access(dev: &Path) -> Check {
+    const NAME: &str = "uinput access";
+
+    if !dev.exists() {
+        return Check::fail(NAME, format!("{} not found", dev.display()), "load the uinput module");
+    }
+
+    if accessible(dev, libc::R_OK | libc::W_OK) {
+        Check::pass(NAME, format!("{} read/write OK", dev.display()))
+    } else {
+        Check::warn(
+            NAME,
+            format!("no read/write permission on {}", dev.display()),
+            "install 99-vmouse.rules and add the daemon user to the `input` group (not required if vmoused runs as root)",
+        )
+    }
+}
+
+/// Check the current user can read input event devices
+pub fn check_input_access(dir: &Path) -> Check {
+    const NAME: &str = "input device access";
+
+    let entries = match read_dir(dir) {
+        Ok(e) => e,
+        Err(e) => return Check::fail(NAME, format!("failed to list {}: {}", dir.display(), e), "check /dev/input exists"),
+    };
+
+    let events: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with("event"))
+        .map(|e| e.path())
+        .collect();
+
+    let readable = events.iter().filter(|p| accessible(p, libc::R_OK)).count();
+
+    match (events.len(), readable) {
+        (0, _) => Check::warn(NAME, "no input event devices found", "connect a device"),
+        (n, r) if r == n => Check::pass(NAME, format!("{} of {} event devices readable", r, n)),
+        (n, r) => Check::warn(
+            NAME,
+            format!("{} of {} event devices readable", r, n),
+            "add the daemon user to the `input` group (not required if vmoused runs as root)",
+        ),
+    }
+}
+
+/// Check the config file exists, parses, and contains valid values
+pub fn check_config(path: &str) -> Check {
+    const NAME: &str = "config";
+
+    let s = match read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => return Check::warn(NAME, format!("failed to read '{}': {}", path, e), "vmoused will use default config"),
+    };
+
+    match ConfigFile::parse(&s, ConfigFormat::from_path(path)) {
+        Ok(c) => match Config::from(&c).errors() {
+            e if e.is_empty() => Check::pass(NAME, format!("'{}' OK, {} devices", path, c.devices.len())),
+            e => {
+                let e: Vec<_> = e.iter().map(|e| e.to_string()).collect();
+                Check::fail(NAME, format!("invalid config '{}': {}", path, e.join(", ")), "run `vmousectl check-config` and fix the listed errors")
+            }
+        },
+        Err(e) => Check::fail(NAME, format!("failed to parse '{}': {}", path, e), "fix or remove the config file"),
+    }
+}
+
+/// Check the daemon socket exists and responds to pings
+pub async fn check_socket(path: &str) -> Check {
+    const NAME: &str = "daemon";
+
+    if !Path::new(path).exists() {
+        return Check::fail(NAME, format!("socket '{}' not found", path), "start vmoused (`systemctl start vmouse`)");
+    }
+
+    let mut c = match Client::connect(path.to_string()).await {
+        Ok(c) => c,
+        Err(e) if e.io_kind() == Some(std::io::ErrorKind::ConnectionRefused) => {
+            return Check::fail(NAME, format!("stale socket '{}'", path), "remove the socket file and restart vmoused");
+        }
+        Err(e) => return Check::fail(NAME, format!("failed to connect to '{}': {}", path, e), "check socket permissions"),
+    };
+
+    let ping = async {
+        c.send(Command::Ping).await?;
+        c.next().await.transpose()
+    };
+
+    match async_std::future::timeout(PING_TIMEOUT, ping).await {
+        Ok(Ok(Some(Command::Ok))) => Check::pass(NAME, format!("'{}' responding", path)),
+        Ok(Ok(r)) => Check::fail(NAME, format!("unexpected ping response: {:?}", r), "restart vmoused"),
+        Ok(Err(e)) => Check::fail(NAME, format!("ping failed: {}", e), "restart vmoused"),
+        Err(_) => Check::fail(NAME, "ping timed out", "restart vmoused"),
+    }
+}
+
+/// Check access to a path for the current (real) user
+fn accessible(p: &Path, mode: libc::c_int) -> bool {
+    let p = match CString::new(p.to_string_lossy().as_bytes()) {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+
+    unsafe { libc::access(p.as_ptr(), mode) == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    #[test]
+    fn uinput_module() {
+        let d = test_dir("doctor", "module");
+        let (module, dev) = (d.join("uinput"), d.join("dev-uinput"));
+
+        assert_eq!(check_uinput_module(&module, &dev).status, Status::Fail);
+
+        // Built in modules only provide the device
+        std::fs::write(&dev, "").unwrap();
+        assert_eq!(check_uinput_module(&module, &dev).status, Status::Pass);
+
+        std::fs::remove_file(&dev).unwrap();
+        std::fs::create_dir(&module).unwrap();
+        assert_eq!(check_uinput_module(&module, &dev).status, Status::Pass);
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn uinput_access() {
+        let d = test_dir("doctor", "access");
+        let dev = d.join("uinput");
+
+        let c = check_uinput_access(&dev);
+        assert_eq!(c.status, Status::Fail);
+        assert!(c.fix.is_some());
+
+        std::fs::write(&dev, "").unwrap();
+        assert_eq!(check_uinput_access(&dev).status, Status::Pass);
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn input_access() {
+        let d = test_dir("doctor", "input");
+
+        assert_eq!(check_input_access(&d.join("missing")).status, Status::Fail);
+
+        // Only event devices are counted
+        std::fs::write(d.join("mice"), "").unwrap();
+        let c = check_input_access(&d);
+        assert_eq!(c.status, Status::Warn);
+        assert_eq!(c.message, "no input event devices found");
+
+        std::fs::write(d.join("event0"), "").unwrap();
+        std::fs::write(d.join("event1"), "").unwrap();
+        let c = check_input_access(&d);
+        assert_eq!(c.status, Status::Pass);
+        assert_eq!(c.message, "2 of 2 event devices readable");
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn config() {
+        let d = test_dir("doctor", "config");
+        let p = |n: &str| d.join(n).to_string_lossy().to_string();
+
+        // Missing configs fall back to defaults
+        assert_eq!(check_config(&p("missing.toml")).status, Status::Warn);
+
+        std::fs::write(p("ok.toml"), "devices = []\n").unwrap();
+        let c = check_config(&p("ok.toml"));
+        assert_eq!(c.status, Status::Pass, "{}", c.message);
+        assert!(c.message.ends_with("0 devices"), "{}", c.message);
+
+        std::fs::write(p("ok.json"), r#"{"devices": []}"#).unwrap();
+        assert_eq!(check_config(&p("ok.json")).status, Status::Pass);
+
+        // Device tables keyed by vid:pid
+        std::fs::write(p("keyed.toml"), "[devices.\"256f:c635\".x]\nmap = \"X\"\nscale = 1.0\ndeadzone = 0.1\n").unwrap();
+        let c = check_config(&p("keyed.toml"));
+        assert_eq!(c.status, Status::Pass, "{}", c.message);
+        assert!(c.message.ends_with("1 devices"), "{}", c.message);
+
+        std::fs::write(p("keyed.json"), r#"{"devices": {}}"#).unwrap();
+        assert_eq!(check_config(&p("keyed.json")).status, Status::Pass);
+
+        std::fs::write(p("parse.toml"), "devices = [").unwrap();
+        let c = check_config(&p("parse.toml"));
+        assert_eq!(c.status, Status::Fail);
+        assert!(c.message.starts_with("failed to parse"), "{}", c.message);
+
+        std::fs::write(p("invalid.toml"), "[[devices]]\nvid = 0x256f\npid = 0xc635\n\n[devices.x]\nmap = \"X\"\nscale = 1.0\ndeadzone = 2.0\n").unwrap();
+        let c = check_config(&p("invalid.toml"));
+        assert_eq!(c.status, Status::Fail);
+        assert!(c.message.starts_with("invalid config"), "{}", c.message);
+
+        std::fs::write(p("invalid-keyed.toml"), "[devices.\"256f:c635\".x]\nmap = \"X\"\nscale = 1.0\ndeadzone = 2.0\n").unwrap();
+        let c = check_config(&p("invalid-keyed.toml"));
+        assert_eq!(c.status, Status::Fail);
+        assert!(c.message.starts_with("invalid config"), "{}", c.message);
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn socket() {
+        let d = test_dir("doctor", "socket");
+        let p = d.join("vmouse.sock").to_string_lossy().to_string();
+
+        let c = async_std::task::block_on(check_socket(&p));
+        assert_eq!(c.status, Status::Fail);
+        assert!(c.message.contains("not found"), "{}", c.message);
+
+        // Socket files left without a listener are stale
+        drop(std::os::unix::net::UnixListener::bind(&p).unwrap());
+        let c = async_std::task::block_on(check_socket(&p));
+        assert_eq!(c.status, Status::Fail);
+        assert!(c.message.starts_with("stale socket"), "{}", c.message);
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+}
@@ -0,0 +1,99 @@
+//! Config import / export for `vmousectl export-config`, `import-config`, `get-config`, `save-config`, and `diff-config`
+
+use log::debug;
+
+use vmouse::{BlockingClient, Command, Config, ConfigFile, ConfigFormat, ErrorCode, SocketConfig};
+
+/// Fetch the running daemon config and write it to a file or stdout
+///
+/// Socket options are not available to clients so no `[socket]` section is written.
+pub fn export(socket: &str, format: ConfigFormat, output: Option<&str>) -> anyhow::Result<()> {
+    let mut client = BlockingClient::connect(socket)?;
+
+    let c = client.get_config()?;
+    let f = ConfigFile::new(&c, SocketConfig::default());
+
+    match output {
+        Some(p) => {
+            debug!("Writing {} config to '{}'", format, p);
+            f.save(p, format)?;
+        },
+        None => println!("{}", f.encode(format)?),
+    }
+
+    Ok(())
+}
+
+/// Write the running daemon config, to the active config path or the provided path
+pub fn save(socket: &str, path: Option<&str>, set_active: bool) -> anyhow::Result<()> {
+    let mut client = BlockingClient::connect(socket)?;
+
+    let (path, cmd) = match path {
+        // The daemon requires absolute paths, resolve relative paths against the working directory
+        Some(p) => {
+            let p = std::env::current_dir()?.join(p).to_string_lossy().to_string();
+            (p.clone(), Command::SaveConfigAs { path: p, set_active })
+        }
+        None => match client.request(&Command::GetConfigPath)? {
+            Command::ConfigPath(p) => (p, Command::WriteConfig),
+            r => return Err(anyhow::anyhow!("Unexpected response: {:?}", r)),
+        },
+    };
+
+    debug!("Saving config to '{}'", path);
+
+    match client.request(&cmd)? {
+        Command::Ok => println!("Wrote config to '{}'", path),
+        Command::Error(ErrorCode::InvalidPath) => {
+            return Err(anyhow::anyhow!("Invalid path '{}', the parent directory must exist and the path must not be a directory", path));
+        }
+        Command::Error(ErrorCode::WriteDenied) => return Err(anyhow::anyhow!("vmoused does not have permission to write '{}'", path)),
+        Command::Error(ErrorCode::PermissionDenied) => {
+            return Err(anyhow::anyhow!("Permission denied saving config to '{}', only root may save outside the config directory", path));
+        }
+        r => return Err(anyhow::anyhow!("Failed to write config to '{}': {:?}", path, r)),
+    }
+
+    Ok(())
+}
+
+/// Load and validate a config file, then apply it to the running daemon
+pub fn import(socket: &str, path: &str) -> anyhow::Result<()> {
+    let f = ConfigFile::load(path)?;
+
+    let c = Config::from(&f);
+    if let Err(e) = c.validate() {
+        let errors: Vec<_> = e.iter().filter(|e| !e.is_warning()).map(|e| e.to_string()).collect();
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!("Invalid config '{}': {}", path, errors.join(", ")));
+        }
+    }
+
+    let mut client = BlockingClient::connect(socket)?;
+    client.set_config(c)?;
+
+    println!("Imported '{}' (use `vmousectl write-config` to persist)", path);
+
+    Ok(())
+}
+
+/// Compare a config file with the running daemon config, printing changed fields
+pub fn diff(socket: &str, path: &str) -> anyhow::Result<()> {
+    let f = Config::from(&ConfigFile::load(path)?);
+
+    let mut client = BlockingClient::connect(socket)?;
+    let live = client.get_config()?;
+
+    let deltas = f.diff(&live);
+    if deltas.is_empty() {
+        println!("Running config matches '{}'", path);
+        return Ok(());
+    }
+
+    println!("Changes from '{}' to running config:", path);
+    for d in &deltas {
+        println!("  {}", d);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! Event subscription output for `vmousectl listen`
+
+use std::io::{ErrorKind, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::{FutureExt, StreamExt};
+use log::debug;
+use strum::{Display, EnumString};
+
+use vmouse::{Client, Command, Topic, AXIS};
+
+/// Listen output formats
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum ListenFormat {
+    /// Debug formatted messages
+    Debug,
+    /// One JSON object per message
+    Json,
+    /// CSV rows for aggregate state updates, or raw values when subscribed with `--raw`
+    Csv,
+}
+
+/// Subscribe to daemon topics and print messages until Ctrl-C or the output is closed
+pub async fn run(socket: &str, topics: Vec<Topic>, raw: bool, format: ListenFormat) -> anyhow::Result<()> {
+    let mut client = Client::connect(socket.to_string()).await?;
+
+    debug!("Subscribing to topics: {:?}", topics);
+
+    client.send(Command::Listen { topics }).await?;
+    match client.next().await {
+        Some(Ok(Command::Ok)) => (),
+        Some(Ok(Command::Error(e))) => return Err(anyhow::anyhow!("Listen failed: {}", e)),
+        Some(Ok(r)) => return Err(anyhow::anyhow!("Unexpected response: {:?}", r)),
+        Some(Err(e)) => return Err(e.into()),
+        None => return Err(anyhow::anyhow!("Daemon disconnected")),
+    }
+
+    let mut exit = async_ctrlc::CtrlC::new()?.fuse();
+
+    if let Some(h) = header(format, raw) {
+        if !write_line(&h)? {
+            return Ok(());
+        }
+    }
+
+    loop {
+        let m = futures::select!(
+            m = client.next().fuse() => match m {
+                Some(m) => m?,
+                None => break,
+            },
+            _e = exit => {
+                debug!("Exiting listen");
+                break;
+            },
+        );
+
+        if let Some(l) = line(format, raw, timestamp(), &m) {
+            if !write_line(&l)? {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write and flush a line to stdout, returns false if the output has been closed
+fn write_line(l: &str) -> anyhow::Result<bool> {
+    let mut out = std::io::stdout().lock();
+
+    match writeln!(out, "{}", l).and_then(|_| out.flush()) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Seconds since the unix epoch
+fn timestamp() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or_default()
+}
+
+/// Column header for a format, if required
+pub fn header(format: ListenFormat, raw: bool) -> Option<String> {
+    match (format, raw) {
+        (ListenFormat::Csv, false) => Some(format!(
+            "t,{}",
+            AXIS.iter().map(|a| a.key()).collect::<Vec<_>>().join(",")
+        )),
+        (ListenFormat::Csv, true) => Some("t,axis,value".to_string()),
+        _ => None,
+    }
+}
+
+/// Format a message received at `t` (seconds since the unix epoch), `None` if it is not
+/// shown in this format
+///
+/// CSV rows contain output values from aggregate state updates, or raw input values
+/// when `raw` is set.
+pub fn line(format: ListenFormat, raw: bool, t: f64, m: &Command) -> Option<String> {
+    match (format, m) {
+        (ListenFormat::Debug, Command::RawValue(v, _)) => Some(format!("{:>3} {:+.4}", v.a.to_string(), v.v)),
+        (ListenFormat::Debug, m) => Some(format!("{:?}", m)),
+        (ListenFormat::Json, m) => {
+            let v = serde_json::json!({ "t": t, "message": m });
+            Some(v.to_string())
+        }
+        (ListenFormat::Csv, Command::State { device: None, state }) if !raw => {
+            let values: Vec<_> = AXIS.iter().map(|a| format!("{}", state.output[*a])).collect();
+            Some(format!("{:.6},{}", t, values.join(",")))
+        }
+        (ListenFormat::Csv, Command::RawValue(v, _)) if raw => Some(format!("{:.6},{},{}", t, v.a.key(), v.v)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmouse::{AxisState, AxisValue, Axis, RawTiming, UsbDevice};
+
+    use super::*;
+
+    fn state() -> AxisState {
+        let mut s = AxisState::default();
+        s.output[Axis::X] = 0.5;
+        s.output[Axis::RZ] = -0.25;
+        s
+    }
+
+    #[test]
+    fn csv_header() {
+        assert_eq!(header(ListenFormat::Csv, false).unwrap(), "t,x,y,z,rx,ry,rz");
+        assert_eq!(header(ListenFormat::Csv, true).unwrap(), "t,axis,value");
+        assert_eq!(header(ListenFormat::Json, false), None);
+        assert_eq!(header(ListenFormat::Debug, true), None);
+    }
+
+    #[test]
+    fn csv_state_line() {
+        let m = Command::State { device: None, state: state() };
+        let l = line(ListenFormat::Csv, false, 12.5, &m).unwrap();
+
+        assert_eq!(l, "12.500000,0.5,0,0,0,0,-0.25");
+        assert_eq!(l.split(',').count(), header(ListenFormat::Csv, false).unwrap().split(',').count());
+
+        // Per-device state and raw values are not shown
+        let d = Command::State { device: Some(UsbDevice { vid: 0x256f, pid: 0xc635, name: None }), state: state() };
+        assert_eq!(line(ListenFormat::Csv, false, 12.5, &d), None);
+        assert_eq!(line(ListenFormat::Csv, true, 12.5, &m), None);
+    }
+
+    #[test]
+    fn csv_raw_line() {
+        let m = Command::RawValue(AxisValue { a: Axis::RY, v: -0.125 }, RawTiming::default());
+
+        assert_eq!(line(ListenFormat::Csv, true, 1.0, &m).unwrap(), "1.000000,ry,-0.125");
+        assert_eq!(line(ListenFormat::Csv, false, 1.0, &m), None);
+    }
+
+    #[test]
+    fn json_line() {
+        let l = line(ListenFormat::Json, false, 2.0, &Command::Ok).unwrap();
+        let v: serde_json::Value = serde_json::from_str(&l).unwrap();
+
+        assert_eq!(v["t"], 2.0);
+        assert_eq!(v["message"], "Ok");
+        assert!(!l.contains('\n'));
+    }
+
+    #[test]
+    fn debug_raw_line() {
+        let m = Command::RawValue(AxisValue { a: Axis::X, v: 0.5 }, RawTiming::default());
+        assert_eq!(line(ListenFormat::Debug, true, 0.0, &m).unwrap(), "  X +0.5000");
+    }
+}
@@ -6,57 +6,388 @@ use futures::StreamExt;
 use structopt::StructOpt;
 
 use log::{debug, info, LevelFilter};
-use simplelog::{Config as LogConfig, SimpleLogger};
+use simplelog::{ColorChoice, Config as LogConfig, TermLogger, TerminalMode};
 
-use vmouse::{Client, Command};
+use vmouse::{Client, Command, ConfigFormat, Topic, UsbDevice};
+
+mod bench;
+mod bind;
+mod calibrate;
+mod check;
+mod devnode;
+mod doctor;
+mod export;
+mod listen;
+mod monitor;
+mod presets;
+mod profile;
+mod reset;
+mod status;
+mod metrics;
+mod top;
+mod tune;
+
+#[cfg(test)]
+#[path = "../testutil.rs"]
+mod testutil;
 
 #[derive(Clone, PartialEq, Debug, StructOpt)]
 pub struct Options {
     #[structopt(subcommand)]
-    pub command: Command,
+    pub operation: Operation,
 
     /// Socket for daemon connections
-    #[structopt(long, default_value = "/var/run/vmouse.sock")]
-    pub socket: String,
+    /// (defaults to $XDG_RUNTIME_DIR/vmouse.sock if present, otherwise /var/run/vmouse.sock)
+    #[structopt(long)]
+    pub socket: Option<String>,
 
-    /// Log verbosity
-    #[structopt(long, default_value = "debug")]
+    /// Log verbosity, logs are written to stderr so command output may be piped
+    #[structopt(long, default_value = "warn")]
     pub log_level: LevelFilter,
 }
 
+#[derive(Clone, PartialEq, Debug, StructOpt)]
+pub enum Operation {
+    #[structopt(flatten)]
+    Command(Command),
+
+    /// Bind an event or hidraw input to vmoused, by path or `vid:pid`
+    Bind {
+        /// Device path (`/dev/input/eventN` or `/dev/hidrawN`)
+        #[structopt(required_unless = "id")]
+        event: Option<String>,
+
+        /// Bind the first unbound device matching `vid:pid` (eg. 256f:c635)
+        #[structopt(long, conflicts_with = "event")]
+        id: Option<UsbDevice>,
+
+        /// Seconds to wait for a device matching `--id` to appear
+        #[structopt(long, requires = "id")]
+        wait: Option<u64>,
+    },
+
+    /// Diagnose common environment problems
+    Doctor {
+        /// Configuration file to check
+        /// (defaults to /etc/vmouse/vmouse.toml)
+        #[structopt(long)]
+        config: Option<String>,
+
+        /// Output report as JSON
+        #[structopt(long)]
+        json: bool,
+    },
+
+    /// Subscribe to and print events from vmoused
+    Listen {
+        /// Topics to subscribe to (state, raw-values, config-changes, device-events), all if not provided
+        #[structopt(long)]
+        topics: Vec<Topic>,
+
+        /// Print raw input values as they arrive (subscribes to raw-values)
+        #[structopt(long)]
+        raw: bool,
+
+        /// Output format (debug, json, or csv)
+        #[structopt(long, default_value = "debug")]
+        format: listen::ListenFormat,
+    },
+
+    /// Display daemon status
+    Status {
+        /// Output status as JSON
+        #[structopt(long)]
+        json: bool,
+    },
+
+    /// Print virtual output device nodes (role, devnode, and sysfs path)
+    Devnode {
+        /// Seconds to wait for the daemon and output devices to appear
+        #[structopt(long)]
+        wait: Option<u64>,
+
+        /// Output devices as JSON
+        #[structopt(long)]
+        json: bool,
+    },
+
+    /// Display daemon event and latency metrics
+    Metrics {
+        /// Output metrics as JSON
+        #[structopt(long)]
+        json: bool,
+    },
+
+    /// Measure end-to-end latency by injecting samples into vmoused (requires admin access)
+    Bench {
+        /// Samples to inject
+        #[structopt(long, default_value = "200")]
+        count: usize,
+
+        /// Interval between samples (ms)
+        #[structopt(long, default_value = "10")]
+        interval: u64,
+
+        /// Output device node for emission timing (defaults to the pointer output)
+        #[structopt(long)]
+        devnode: Option<String>,
+
+        /// Output results as JSON
+        #[structopt(long)]
+        json: bool,
+    },
+
+    /// Display a live table of bound devices, input rates, and output values
+    Top {
+        /// Print the table once and exit
+        #[structopt(long)]
+        once: bool,
+    },
+
+    /// Display live axis values from vmoused
+    Monitor {
+        /// Device to monitor, matched by `vid:pid` or name with `*` / `?` wildcards
+        /// (aggregate state if not provided)
+        #[structopt(long)]
+        device: Option<String>,
+    },
+
+    /// Interactively tune axis scale, deadzone, and curve with live feedback
+    Tune,
+
+    /// Validate a config file, exits non-zero on errors
+    CheckConfig {
+        /// Configuration file to check
+        file: String,
+    },
+
+    /// Interactively calibrate device axis ranges
+    #[structopt(name = "calibrate")]
+    CalibrateDevice {
+        /// Device to calibrate (`vid:pid` or alias)
+        #[structopt(long)]
+        device: String,
+    },
+
+    /// Export the running daemon config
+    ExportConfig {
+        /// Output format (toml or json)
+        #[structopt(long, default_value = "toml")]
+        format: ConfigFormat,
+
+        /// Output file (defaults to stdout)
+        #[structopt(long)]
+        output: Option<String>,
+    },
+
+    /// Validate a config file (toml or json) and apply it to the running daemon
+    ImportConfig {
+        /// Configuration file to import
+        file: String,
+    },
+
+    /// Print the running daemon config
+    GetConfig {
+        /// Output format (toml or json)
+        #[structopt(long, default_value = "toml")]
+        format: ConfigFormat,
+    },
+
+    /// Write the running daemon config to the active config file, or another path
+    SaveConfig {
+        /// File to write (format detected by extension), the active config file if not provided
+        #[structopt(long)]
+        path: Option<String>,
+
+        /// Use this path for subsequent writes
+        #[structopt(long, requires = "path")]
+        set_active: bool,
+    },
+
+    /// Reset the running daemon config to defaults
+    ResetConfig {
+        /// Device to reset (`default`, `vid:pid`, or event path), the whole config if not provided
+        #[structopt(long)]
+        device: Option<String>,
+
+        /// Write the reset config to the daemon config file
+        #[structopt(long)]
+        write: bool,
+    },
+
+    /// Print a JSON Schema for config files, for editor tooling
+    Schema,
+
+    /// List built-in device presets
+    Presets,
+
+    /// Apply a built-in preset to a device in the running daemon config
+    ApplyPreset {
+        /// Device to configure (`vid:pid` or alias)
+        #[structopt(long)]
+        device: String,
+
+        /// Preset name (see `vmousectl presets`)
+        #[structopt(long)]
+        preset: String,
+    },
+
+    /// Export a device's axis config as a shareable profile file
+    ExportProfile {
+        /// Device to export (`default`, `vid:pid` or alias)
+        #[structopt(long)]
+        device: String,
+
+        /// Output file (eg. `blender.vmprofile`, defaults to stdout)
+        #[structopt(long)]
+        output: Option<String>,
+
+        /// Profile description
+        #[structopt(long)]
+        description: Option<String>,
+    },
+
+    /// Validate a shareable profile file and apply it to a device in the running daemon config
+    ImportProfile {
+        /// Device to configure (`default`, `vid:pid` or alias)
+        #[structopt(long)]
+        device: String,
+
+        /// Profile file to import
+        #[structopt(long)]
+        input: String,
+
+        /// Write the updated config to the daemon config file
+        #[structopt(long)]
+        write: bool,
+    },
+
+    /// Compare the running daemon config with a config file
+    DiffConfig {
+        /// Configuration file to compare
+        /// (defaults to /etc/vmouse/vmouse.toml)
+        #[structopt(long)]
+        file: Option<String>,
+    },
+}
+
 #[async_std::main]
 async fn main() -> anyhow::Result<()> {
     // Parse command line arguments
     let opts = Options::from_args();
 
     // Setup logging
-    let _ = SimpleLogger::init(opts.log_level, LogConfig::default());
+    let _ = TermLogger::init(opts.log_level, LogConfig::default(), TerminalMode::Stderr, ColorChoice::Auto);
 
     info!("Starting vmousectl");
 
-    debug!("Connecting to socket: {}", opts.socket);
+    let socket = opts.socket.clone().unwrap_or_else(vmouse::default_socket_path);
+
+    let command = match opts.operation {
+        Operation::Command(c) => c,
+        Operation::Listen { mut topics, raw, format } => {
+            if raw && !topics.contains(&Topic::RawValues) {
+                topics.push(Topic::RawValues);
+            }
+            return listen::run(&socket, topics, raw, format).await;
+        }
+        Operation::Doctor { config, json } => {
+            let config = config.unwrap_or_else(|| vmouse::SYSTEM_CONFIG.to_string());
+
+            let checks = doctor::run(&socket, &config).await;
+            doctor::report(&checks, json)?;
+
+            // Exit non-zero on hard failures
+            if checks.iter().any(|c| c.status == doctor::Status::Fail) {
+                std::process::exit(1);
+            }
+
+            return Ok(());
+        }
+        Operation::Bind { event, id, wait } => {
+            return bind::run(&socket, event, id, wait);
+        }
+        Operation::Status { json } => {
+            return status::run(&socket, json);
+        }
+        Operation::Devnode { wait, json } => {
+            return devnode::run(&socket, wait, json);
+        }
+        Operation::Metrics { json } => {
+            return metrics::run(&socket, json);
+        }
+        Operation::Bench { count, interval, devnode, json } => {
+            return bench::run(&socket, count, interval, devnode, json);
+        }
+        Operation::Top { once } => {
+            return top::run(&socket, once);
+        }
+        Operation::Monitor { device } => {
+            return monitor::run(&socket, device.as_deref()).await;
+        }
+        Operation::Tune => {
+            return tune::run(&socket).await;
+        }
+        Operation::CheckConfig { file } => {
+            if !check::run(&file)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Operation::CalibrateDevice { device } => {
+            return calibrate::run(&socket, &device);
+        }
+        Operation::ExportConfig { format, output } => {
+            return export::export(&socket, format, output.as_deref());
+        }
+        Operation::ImportConfig { file } => {
+            return export::import(&socket, &file);
+        }
+        Operation::GetConfig { format } => {
+            return export::export(&socket, format, None);
+        }
+        Operation::SaveConfig { path, set_active } => {
+            return export::save(&socket, path.as_deref(), set_active);
+        }
+        Operation::ResetConfig { device, write } => {
+            return reset::config(&socket, device, write);
+        }
+        Operation::Schema => {
+            println!("{}", serde_json::to_string_pretty(&vmouse::config_schema())?);
+            return Ok(());
+        }
+        Operation::Presets => {
+            presets::list();
+            return Ok(());
+        }
+        Operation::ApplyPreset { device, preset } => {
+            return presets::apply(&socket, &device, &preset);
+        }
+        Operation::ExportProfile { device, output, description } => {
+            return profile::export(&socket, &device, output.as_deref(), description);
+        }
+        Operation::ImportProfile { device, input, write } => {
+            return profile::import(&socket, &device, &input, write);
+        }
+        Operation::DiffConfig { file } => {
+            let file = file.unwrap_or_else(|| vmouse::SYSTEM_CONFIG.to_string());
+            return export::diff(&socket, &file);
+        }
+    };
+
+    debug!("Connecting to socket: {}", socket);
 
     // Connect to daemon socket
-    let mut client = Client::connect(opts.socket.clone()).await?;
+    let mut client = Client::connect(socket).await?;
 
-    debug!("Writing command: {:?}", opts.command);
+    debug!("Writing command: {:?}", command);
 
     // Write command
-    client.send(opts.command.clone()).await?;
+    client.send(command).await?;
 
     // Await response
     let r = client.next().await;
 
-    match opts.command {
-        Command::Listen => {
-            loop {
-                let m = client.next().await;
-                info!("Received: {:?}", m);
-            }
-        },
-        _ => (),
-    }
-
     debug!("Received response: {:?}", r);
 
     Ok(())
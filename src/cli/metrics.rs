@@ -0,0 +1,51 @@
+//! Daemon metrics for `vmousectl metrics`
+
+use vmouse::{BlockingClient, Command, MetricsSnapshot};
+
+/// Fetch and print daemon metrics
+pub fn run(socket: &str, json: bool) -> anyhow::Result<()> {
+    let mut client = BlockingClient::connect(socket)?;
+
+    let m = match client.request(&Command::GetMetrics)? {
+        Command::Metrics(m) => m,
+        r => return Err(anyhow::anyhow!("Unexpected response: {:?}", r)),
+    };
+
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&m)?),
+        false => print(&m),
+    }
+
+    Ok(())
+}
+
+/// Format an optional latency value
+fn us(v: Option<u64>) -> String {
+    match v {
+        Some(v) => format!("{}us", v),
+        None => "-".to_string(),
+    }
+}
+
+/// Pretty-print daemon metrics
+fn print(m: &MetricsSnapshot) {
+    let h = &m.latency_us;
+
+    println!("uptime:   {}s, {} clients", m.uptime, m.clients);
+    println!("input:    {} events ({:.1}/s)", m.events_in, m.rate_in);
+    println!("output:   {} mapped, {} written ({:.1}/s)", m.events_mapped, m.events_out, m.rate_out);
+    println!("dropped:  {} client messages", m.broadcasts_dropped);
+    println!("clamped:  {} output values", m.outputs_clamped);
+    println!(
+        "latency:  mean {} p50 {} p90 {} p99 {}",
+        us(h.mean()),
+        us(h.percentile(0.5)),
+        us(h.percentile(0.9)),
+        us(h.percentile(0.99))
+    );
+    println!("devices:  {}", m.devices.len());
+
+    for (d, v) in &m.devices {
+        println!("    {} {} events ({:.1}/s)", d, v.events_in, v.rate_in);
+    }
+}
@@ -0,0 +1,161 @@
+//! Live axis monitor for `vmousectl monitor`
+
+use futures::{FutureExt, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{debug, info, warn};
+
+use vmouse::{AxisCollection, AxisState, ClientEvent, Command, ReconnectingClient, UsbDevice, AXIS};
+
+/// Bar length, maps -1.0..1.0 to 0..BAR_LEN
+const BAR_LEN: u64 = 200;
+
+/// Subscribe to daemon state and render axes as live bars until Ctrl-C
+///
+/// Shows aggregate state, or state for devices matching the `device` pattern.
+pub async fn run(socket: &str, device: Option<&str>) -> anyhow::Result<()> {
+    let m = MultiProgress::with_draw_target(ProgressDrawTarget::stdout());
+    let style = ProgressStyle::default_bar()
+        .template("{prefix:>3} [{bar:60}] {msg}")
+        .progress_chars("=> ");
+
+    let bars = AxisCollection::with_axis(|a| {
+        let b = m.add(ProgressBar::new(BAR_LEN));
+        b.set_style(style.clone());
+        b.set_prefix(a.to_string());
+        b.set_position(BAR_LEN / 2);
+        b
+    });
+
+    // MultiProgress renders from the joining thread
+    let draw = std::thread::spawn(move || m.join());
+
+    let mut exit = async_ctrlc::CtrlC::new()?.fuse();
+
+    let client = ReconnectingClient::new(socket.to_string(), true);
+    let mut events = client.events().fuse();
+
+    loop {
+        futures::select!(
+            e = events.next() => match e {
+                Some(ClientEvent::Connected) => info!("Connected to daemon: {}", socket),
+                Some(ClientEvent::Reconnecting(d, e)) => warn!("Daemon connection lost ({}), reconnecting in {:?}", e, d),
+                Some(ClientEvent::Message(Command::State { device: d, state })) => {
+                    let show = match (device, &d) {
+                        (None, None) => true,
+                        (Some(p), Some(d)) => device_matches(p, d),
+                        _ => false,
+                    };
+                    if show {
+                        update(&bars, &state);
+                    }
+                },
+                Some(ClientEvent::Message(_)) => (),
+                None => break,
+            },
+            _e = exit => {
+                debug!("Exiting monitor");
+                break;
+            },
+        )
+    }
+
+    client.close().await;
+
+    // Clear bars to restore the terminal
+    for a in AXIS {
+        bars[*a].finish_and_clear();
+    }
+    let _ = draw.join();
+
+    Ok(())
+}
+
+/// Update bars with normalised axis values and transformed outputs
+fn update(bars: &AxisCollection<ProgressBar>, s: &AxisState) {
+    for a in AXIS {
+        let v = s.raw[*a].clamp(-1.0, 1.0);
+
+        bars[*a].set_position(((v + 1.0) / 2.0 * BAR_LEN as f32) as u64);
+        bars[*a].set_message(format!("raw: {:+.3} out: {:+.3}", v, s.output[*a]));
+    }
+}
+
+/// Match a device by `vid:pid` or name against a pattern with `*` / `?` wildcards
+pub fn device_matches(pattern: &str, d: &UsbDevice) -> bool {
+    glob(pattern, &d.to_string()) || d.name.as_deref().map(|n| glob(pattern, n)).unwrap_or(false)
+}
+
+/// Case-insensitive wildcard match, `*` matches any sequence and `?` any single character
+fn glob(pattern: &str, s: &str) -> bool {
+    let p: Vec<char> = pattern.to_lowercase().chars().collect();
+    let s: Vec<char> = s.to_lowercase().chars().collect();
+
+    // Track the last `*` to backtrack on mismatch
+    let (mut pi, mut si) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while si < s.len() {
+        match p.get(pi) {
+            Some('*') => {
+                star = Some((pi, si));
+                pi += 1;
+            }
+            Some(c) if *c == '?' || *c == s[si] => {
+                pi += 1;
+                si += 1;
+            }
+            _ => match star {
+                Some((sp, ss)) => {
+                    pi = sp + 1;
+                    si = ss + 1;
+                    star = Some((sp, ss + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob("256f:c635", "256f:c635"));
+        assert!(glob("256f:*", "256f:c635"));
+        assert!(glob("*:c635", "256f:c635"));
+        assert!(glob("256f:c63?", "256f:c635"));
+        assert!(glob("*", ""));
+        assert!(glob("a*b*c", "aXbYbZc"));
+
+        assert!(!glob("256f:c63?", "256f:c63"));
+        assert!(!glob("256f:*", "046d:c626"));
+        assert!(!glob("?", ""));
+        assert!(!glob("a*b*c", "aXbYbZ"));
+    }
+
+    #[test]
+    fn glob_case_insensitive() {
+        assert!(glob("256F:C635", "256f:c635"));
+        assert!(glob("*spacemouse*", "3Dconnexion SpaceMouse Compact"));
+    }
+
+    #[test]
+    fn match_by_id_or_name() {
+        let d = UsbDevice { vid: 0x256f, pid: 0xc635, name: Some("3Dconnexion SpaceMouse Compact".to_string()) };
+
+        assert!(device_matches("256f:c635", &d));
+        assert!(device_matches("256f:*", &d));
+        assert!(device_matches("*Compact", &d));
+        assert!(!device_matches("046d:*", &d));
+        assert!(!device_matches("*Pro", &d));
+
+        // Unnamed devices match by id only
+        let d = UsbDevice { name: None, ..d };
+        assert!(device_matches("*:c635", &d));
+        assert!(!device_matches("*Compact", &d));
+    }
+}
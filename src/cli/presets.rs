@@ -0,0 +1,35 @@
+//! Built-in device presets for `vmousectl presets` and `apply-preset`
+
+use log::debug;
+
+use vmouse::{BlockingClient, UsbDevice, PRESETS};
+
+/// List built-in presets
+pub fn list() {
+    for p in PRESETS {
+        let ids: Vec<_> = p.ids.iter().map(|(vid, pid)| format!("{:04x}:{:04x}", vid, pid)).collect();
+        println!("{:<20} {:<32} {}", p.name, p.model, ids.join(", "));
+    }
+}
+
+/// Apply a preset to a device in the running daemon config, replacing any existing device config
+pub fn apply(socket: &str, device: &str, name: &str) -> anyhow::Result<()> {
+    let p = vmouse::preset(name).ok_or_else(|| anyhow::anyhow!("Unknown preset '{}' (see `vmousectl presets`)", name))?;
+
+    let mut client = BlockingClient::connect(socket)?;
+    let mut c = client.get_config()?;
+
+    // Resolve aliases to `vid:pid`
+    let device = &c.resolve(device).parse::<UsbDevice>()?;
+
+    debug!("Applying preset '{}' to {}", p.name, device.to_string());
+
+    c.devices.retain(|k, _| k.vid != device.vid || k.pid != device.pid);
+    c.devices.insert(UsbDevice { name: None, ..device.clone() }, p.axes_for(c.version));
+
+    client.set_config(c)?;
+
+    println!("Applied preset '{}' to {} (use `vmousectl write-config` to persist)", p.name, device.to_string());
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! Shareable device profiles for `vmousectl export-profile` and `import-profile`
+
+use log::{debug, warn};
+
+use vmouse::{BlockingClient, Command, ProfileFile, UsbDevice};
+
+/// Write a device's axis configuration from the running daemon to a profile file or stdout
+pub fn export(socket: &str, device: &str, output: Option<&str>, description: Option<String>) -> anyhow::Result<()> {
+    let mut client = BlockingClient::connect(socket)?;
+    let c = client.get_config()?;
+
+    let name = c.resolve(device);
+    let axes = c.get(&name).ok_or_else(|| anyhow::anyhow!("No config for device '{}'", device))?;
+    let p = ProfileFile::new(&name, *axes, description);
+
+    match output {
+        Some(o) => {
+            debug!("Writing profile for {} to '{}'", name, o);
+            p.save(o)?;
+            println!("Exported profile for {} to '{}'", name, o);
+        }
+        None => println!("{}", p.encode()?),
+    }
+
+    Ok(())
+}
+
+/// Load and validate a profile file, then apply it to a device in the running daemon config
+pub fn import(socket: &str, device: &str, input: &str, write: bool) -> anyhow::Result<()> {
+    let p = ProfileFile::load(input)?;
+
+    let mut client = BlockingClient::connect(socket)?;
+    let mut c = client.get_config()?;
+
+    let name = c.resolve(device);
+    if p.meta.device != name {
+        warn!("Profile was exported from {}, applying to {}", p.meta.device, name);
+    }
+
+    // Replace the device config, adding it where not yet configured
+    match name.as_str() {
+        "default" => c.default = p.axes,
+        _ => {
+            let d = name.parse::<UsbDevice>()?;
+            c.devices.retain(|k, _| k.vid != d.vid || k.pid != d.pid);
+            c.devices.insert(d, p.axes);
+        }
+    }
+
+    if let Err(e) = c.validate() {
+        let errors: Vec<_> = e.iter().filter(|e| !e.is_warning()).map(|e| e.to_string()).collect();
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!("Profile '{}' is invalid for {}: {}", input, name, errors.join(", ")));
+        }
+    }
+
+    client.set_config(c)?;
+
+    if let Some(d) = &p.meta.description {
+        println!("{}", d);
+    }
+
+    if !write {
+        println!("Imported '{}' for {} (use `vmousectl write-config` or --write to persist)", input, name);
+        return Ok(());
+    }
+
+    match client.request(&Command::WriteConfig)? {
+        Command::Ok => println!("Imported '{}' for {} and wrote config", input, name),
+        r => return Err(anyhow::anyhow!("Failed to write config: {:?}", r)),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+//! Config reset for `vmousectl reset-config`
+
+use log::debug;
+
+use vmouse::{BlockingClient, Command, ErrorCode};
+
+/// Reset the running config for a device (or all devices) to defaults, optionally writing it to disk
+pub fn config(socket: &str, device: Option<String>, write: bool) -> anyhow::Result<()> {
+    let mut client = BlockingClient::connect(socket)?;
+    let target = device.clone().unwrap_or_else(|| "all devices".to_string());
+
+    debug!("Resetting config for {}", target);
+
+    match client.request(&Command::ResetConfig { device })? {
+        Command::Ok => (),
+        Command::Error(ErrorCode::PermissionDenied) => return Err(anyhow::anyhow!("Permission denied resetting config")),
+        r => return Err(anyhow::anyhow!("Failed to reset config for {}: {:?}", target, r)),
+    }
+
+    if !write {
+        println!("Reset config for {} (use `vmousectl write-config` or --write to persist)", target);
+        return Ok(());
+    }
+
+    match client.request(&Command::WriteConfig)? {
+        Command::Ok => println!("Reset and wrote config for {}", target),
+        r => return Err(anyhow::anyhow!("Failed to write config: {:?}", r)),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+//! Daemon status for `vmousectl status`
+
+use vmouse::{BlockingClient, Command, StatusInfo};
+
+/// Fetch and print daemon status
+pub fn run(socket: &str, json: bool) -> anyhow::Result<()> {
+    let mut client = BlockingClient::connect(socket)?;
+
+    let s = match client.request(&Command::GetStatus)? {
+        Command::Status(s) => s,
+        r => return Err(anyhow::anyhow!("Unexpected response: {:?}", r)),
+    };
+
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&s)?),
+        false => print(&s),
+    }
+
+    Ok(())
+}
+
+/// Pretty-print daemon status
+fn print(s: &StatusInfo) {
+    let up = s.uptime;
+
+    println!("vmoused {} (protocol v{})", s.version, vmouse::protocol_string(s.protocol));
+    println!("  uptime:   {}h {:02}m {:02}s", up / 3600, up / 60 % 60, up % 60);
+    println!("  output:   {}{}", if s.enabled { "enabled" } else { "disabled" }, if s.idle { " (idle)" } else { "" });
+    println!("  devnodes: {}", s.devnodes.join(", "));
+    println!("  clients:  {} ({} listening)", s.clients, s.listening);
+    println!("  events:   {} in, {} mapped, {} out", s.events_in, s.events_mapped, s.events_out);
+    println!("  latency:  {}us avg, {}us max", s.latency_us, s.latency_max_us);
+    println!("  devices:  {}", s.devices.len());
+
+    for d in &s.devices {
+        let id = d.device.to_string();
+        let mut state = match s.device_enabled.get(&id) {
+            Some(false) => " (disabled)".to_string(),
+            _ => String::new(),
+        };
+        if let Some(b) = d.battery {
+            state.push_str(&format!(" (battery {}%)", b));
+        }
+
+        match &d.device.name {
+            Some(n) => println!("    {} {} @ {} [{}]{}", id, n, d.path, d.identity, state),
+            None => println!("    {} @ {} [{}]{}", id, d.path, d.identity, state),
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Live device table for `vmousectl top`
+
+use std::io::Write;
+use std::time::Duration;
+
+use vmouse::{AxisCollection, BlockingClient, Command, MetricsSnapshot, StatusInfo, AXIS};
+
+/// Table refresh interval
+const REFRESH: Duration = Duration::from_secs(1);
+
+/// Device name column width
+const NAME_WIDTH: usize = 24;
+
+/// Table row for a bound device
+#[derive(Clone, PartialEq, Debug)]
+pub struct Row {
+    /// Device `vid:pid`
+    pub id: String,
+    /// Device name (or alias)
+    pub name: String,
+    /// Whether output is enabled for the device
+    pub enabled: bool,
+    /// Input events per second, if reported
+    pub rate_in: Option<f32>,
+    /// Current output values, `None` before any input
+    pub output: Option<AxisCollection<f32>>,
+}
+
+/// Display the device table, refreshing in place until interrupted (or once)
+pub fn run(socket: &str, once: bool) -> anyhow::Result<()> {
+    let mut client = BlockingClient::connect(socket)?;
+
+    loop {
+        let (status, metrics, rows) = fetch(&mut client)?;
+        let table = render(&status, &metrics, &rows);
+
+        // Clear the screen and redraw from the top left
+        match once {
+            true => print!("{}", table),
+            false => print!("\x1b[H\x1b[2J{}", table),
+        }
+        std::io::stdout().flush()?;
+
+        if once {
+            return Ok(());
+        }
+
+        std::thread::sleep(REFRESH);
+    }
+}
+
+/// Fetch status, metrics, and per-device state, devices removed between requests have no state
+fn fetch(client: &mut BlockingClient) -> anyhow::Result<(StatusInfo, MetricsSnapshot, Vec<Row>)> {
+    let status = match client.request(&Command::GetStatus)? {
+        Command::Status(s) => s,
+        r => return Err(anyhow::anyhow!("Unexpected response: {:?}", r)),
+    };
+
+    let metrics = match client.request(&Command::GetMetrics)? {
+        Command::Metrics(m) => m,
+        r => return Err(anyhow::anyhow!("Unexpected response: {:?}", r)),
+    };
+
+    let rows = rows(&status, &metrics, |id| {
+        match client.request(&Command::GetState { device: Some(id.to_string()) })? {
+            Command::State { state, .. } => Ok(Some(state.output)),
+            _ => Ok(None),
+        }
+    })?;
+
+    Ok((status, metrics, rows))
+}
+
+/// Build device rows, fetching output values by device `vid:pid`
+fn rows<F>(status: &StatusInfo, metrics: &MetricsSnapshot, mut output: F) -> anyhow::Result<Vec<Row>>
+where
+    F: FnMut(&str) -> anyhow::Result<Option<AxisCollection<f32>>>,
+{
+    let mut rows: Vec<Row> = vec![];
+    for d in status.devices.iter().map(|d| &d.device) {
+        // Nodes of the same device share state, list each device once
+        let id = d.to_string();
+        if rows.iter().any(|r| r.id == id) {
+            continue;
+        }
+
+        rows.push(Row {
+            name: d.name.clone().unwrap_or_default(),
+            enabled: status.device_enabled.get(&id).copied().unwrap_or(true),
+            rate_in: metrics.devices.get(&id).map(|m| m.rate_in),
+            output: output(&id)?,
+            id,
+        });
+    }
+
+    Ok(rows)
+}
+
+/// Render the summary line and device table
+pub fn render(status: &StatusInfo, metrics: &MetricsSnapshot, rows: &[Row]) -> String {
+    let up = status.uptime;
+
+    let mut s = format!(
+        "vmoused {}  up {}h {:02}m {:02}s  output {}{}  in {:.1}/s  out {:.1}/s  {} clients\n\n",
+        status.version,
+        up / 3600,
+        up / 60 % 60,
+        up % 60,
+        if status.enabled { "enabled" } else { "disabled" },
+        if status.idle { " (idle)" } else { "" },
+        metrics.rate_in,
+        metrics.rate_out,
+        status.clients,
+    );
+
+    s.push_str(&header());
+    s.push('\n');
+
+    for r in rows {
+        s.push_str(&format_row(r));
+        s.push('\n');
+    }
+
+    if rows.is_empty() {
+        s.push_str("(no devices bound)\n");
+    }
+
+    s
+}
+
+/// Column headings
+pub fn header() -> String {
+    let mut s = format!("{:<9} {:<w$} {:>3} {:>8}", "DEVICE", "NAME", "OUT", "IN/s", w = NAME_WIDTH);
+    for a in AXIS {
+        s.push_str(&format!(" {:>7}", a.to_string()));
+    }
+    s
+}
+
+/// Format a device row, aligned with [`header`]
+pub fn format_row(r: &Row) -> String {
+    let rate = match r.rate_in {
+        Some(v) => format!("{:.1}", v),
+        None => "-".to_string(),
+    };
+
+    let mut s = format!(
+        "{:<9} {:<w$} {:>3} {:>8}",
+        r.id,
+        truncate(&r.name, NAME_WIDTH),
+        if r.enabled { "on" } else { "off" },
+        rate,
+        w = NAME_WIDTH
+    );
+
+    for a in AXIS {
+        match &r.output {
+            Some(o) => s.push_str(&format!(" {:>+7.3}", o[*a])),
+            None => s.push_str(&format!(" {:>7}", "-")),
+        }
+    }
+
+    s
+}
+
+/// Truncate a string to `n` characters, marking truncation with `…`
+fn truncate(s: &str, n: usize) -> String {
+    match s.chars().count() > n {
+        true => format!("{}…", s.chars().take(n - 1).collect::<String>()),
+        false => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use vmouse::{Axis, DeviceMetrics, DeviceStatus, Histogram, UsbDevice};
+
+    use super::*;
+
+    fn device(pid: u16, name: Option<&str>) -> UsbDevice {
+        UsbDevice { vid: 0x256f, pid, name: name.map(|n| n.to_string()) }
+    }
+
+    fn status(devices: &[UsbDevice]) -> StatusInfo {
+        StatusInfo {
+            version: "0.1.0".to_string(),
+            protocol: vmouse::PROTOCOL_VERSION,
+            uptime: 3723,
+            enabled: true,
+            device_enabled: HashMap::from([("256f:c631".to_string(), false)]),
+            devices: devices.iter().enumerate()
+                .map(|(i, d)| DeviceStatus { device: d.clone(), path: format!("/dev/input/event{}", i), identity: d.to_string(), battery: None })
+                .collect(),
+            clients: 2,
+            listening: 1,
+            devnodes: vec![],
+            events_in: 0,
+            events_mapped: 0,
+            events_out: 0,
+            latency_us: 0,
+            latency_max_us: 0,
+            idle: false,
+        }
+    }
+
+    fn metrics(rates: &[(&str, f32)]) -> MetricsSnapshot {
+        MetricsSnapshot {
+            uptime: 3723,
+            clients: 2,
+            events_in: 0,
+            rate_in: 250.0,
+            events_mapped: 0,
+            events_out: 0,
+            rate_out: 125.5,
+            broadcasts_dropped: 0,
+            outputs_clamped: 0,
+            devices: rates.iter().map(|(d, r)| (d.to_string(), DeviceMetrics { events_in: 0, rate_in: *r })).collect::<BTreeMap<_, _>>(),
+            latency_us: Histogram::new(&[]),
+        }
+    }
+
+    #[test]
+    fn rows_per_device() {
+        let devices = [device(0xc635, Some("SpaceMouse Compact")), device(0xc631, None), device(0xc635, Some("SpaceMouse Compact"))];
+        let mut requested = vec![];
+
+        let rows = rows(&status(&devices), &metrics(&[("256f:c635", 120.0)]), |id| {
+            requested.push(id.to_string());
+
+            // Devices removed between requests have no state
+            match id {
+                "256f:c635" => Ok(Some(AxisCollection::default())),
+                _ => Ok(None),
+            }
+        }).unwrap();
+
+        // Nodes of the same device are listed once
+        assert_eq!(requested, vec!["256f:c635", "256f:c631"]);
+        assert_eq!(rows, vec![
+            Row { id: "256f:c635".to_string(), name: "SpaceMouse Compact".to_string(), enabled: true, rate_in: Some(120.0), output: Some(AxisCollection::default()) },
+            Row { id: "256f:c631".to_string(), name: String::new(), enabled: false, rate_in: None, output: None },
+        ]);
+    }
+
+    #[test]
+    fn rows_aligned() {
+        let mut output = AxisCollection::<f32>::default();
+        output[Axis::X] = 0.25;
+        output[Axis::RZ] = -1.0;
+
+        let rows = [
+            Row { id: "256f:c635".to_string(), name: "SpaceMouse Compact".to_string(), enabled: true, rate_in: Some(120.0), output: Some(output) },
+            Row { id: "256f:c631".to_string(), name: "A device name longer than the column".to_string(), enabled: false, rate_in: None, output: None },
+        ];
+
+        let h = header();
+        for r in &rows {
+            let f = format_row(r);
+            assert_eq!(f.chars().count(), h.chars().count(), "{}", f);
+        }
+
+        let f = format_row(&rows[0]);
+        assert!(f.starts_with("256f:c635 SpaceMouse Compact"), "{}", f);
+        assert!(f.contains(" on    120.0 ") && f.contains(" +0.250 ") && f.ends_with(" -1.000"), "{}", f);
+
+        let f = format_row(&rows[1]);
+        assert!(f.contains("A device name longer th… off        - "), "{}", f);
+        assert!(f.ends_with("       -"), "{}", f);
+    }
+
+    #[test]
+    fn render_summary() {
+        let s = render(&status(&[]), &metrics(&[]), &[]);
+        let lines: Vec<_> = s.lines().collect();
+
+        assert_eq!(lines[0], "vmoused 0.1.0  up 1h 02m 03s  output enabled  in 250.0/s  out 125.5/s  2 clients");
+        assert_eq!(lines[2], header());
+        assert_eq!(lines[3], "(no devices bound)");
+    }
+
+    #[test]
+    fn truncate_chars() {
+        assert_eq!(truncate("SpaceMouse", 10), "SpaceMouse");
+        assert_eq!(truncate("SpaceMouse", 6), "Space…");
+        assert_eq!(truncate("ŜpäçéMöüse", 4), "Ŝpä…");
+    }
+}
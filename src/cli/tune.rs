@@ -0,0 +1,354 @@
+//! Interactive terminal tuning for `vmousectl tune`
+
+use std::collections::HashMap;
+use std::io::Stdout;
+
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use futures::{FutureExt, StreamExt};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Gauge, Paragraph};
+use tui::{Frame, Terminal};
+
+use vmouse::{Axis, AxisConfig, AxisState, BlockingClient, Client, Command, Config, CurveKind, ErrorCode, Topic, UsbDevice, AXIS, DEADZONE_RANGE, SCALE_RANGE};
+
+type Backend = CrosstermBackend<Stdout>;
+
+/// Editable axis fields
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Field {
+    Scale,
+    Deadzone,
+    Curve,
+}
+
+impl Field {
+    fn next(self) -> Self {
+        match self {
+            Field::Scale => Field::Deadzone,
+            Field::Deadzone => Field::Curve,
+            Field::Curve => Field::Scale,
+        }
+    }
+}
+
+/// Actions resulting from key presses
+enum Action {
+    None,
+    Apply,
+    Write,
+    Quit,
+}
+
+/// Tuning state
+struct Tune {
+    /// Working config, sent to the daemon on each change
+    config: Config,
+    /// Last config accepted by the daemon, restored if a change is rejected
+    applied: Config,
+    /// Selectable devices, `None` for the default config
+    devices: Vec<Option<UsbDevice>>,
+    device: usize,
+    axis: usize,
+    field: Field,
+    state: HashMap<Option<UsbDevice>, AxisState>,
+    /// Set when the daemon rejects config changes
+    read_only: bool,
+    status: String,
+}
+
+impl Tune {
+    fn new(config: Config, devices: Vec<UsbDevice>) -> Self {
+        Self {
+            applied: config.clone(),
+            config,
+            devices: std::iter::once(None).chain(devices.into_iter().map(Some)).collect(),
+            device: 0,
+            axis: 0,
+            field: Field::Scale,
+            state: HashMap::new(),
+            read_only: false,
+            status: "Ready".to_string(),
+        }
+    }
+
+    fn selected_device(&self) -> Option<&UsbDevice> {
+        self.devices[self.device].as_ref()
+    }
+
+    /// Fetch the config for the selected device
+    fn axes(&self) -> &vmouse::AxisCollection<AxisConfig> {
+        match self.selected_device() {
+            Some(d) => self.config.device(d),
+            None => &self.config.default,
+        }
+    }
+
+    /// Fetch a mutable axis config for the selected device, creating a device entry if required
+    fn axis_mut(&mut self, a: Axis) -> &mut AxisConfig {
+        let d = match self.devices[self.device].clone() {
+            Some(d) => d,
+            None => return &mut self.config.default[a],
+        };
+
+        let key = match self.config.devices.keys().find(|k| k.vid == d.vid && k.pid == d.pid) {
+            Some(k) => k.clone(),
+            None => {
+                let axes = self.config.default;
+                let k = UsbDevice { name: None, ..d };
+                self.config.devices.insert(k.clone(), axes);
+                k
+            }
+        };
+
+        &mut self.config.devices.get_mut(&key).unwrap()[a]
+    }
+
+    /// Adjust the selected field by `steps`
+    fn adjust(&mut self, steps: f32) -> Action {
+        if self.read_only {
+            self.status = "Read-only, config changes rejected by daemon".to_string();
+            return Action::None;
+        }
+
+        let a = AXIS[self.axis];
+        let field = self.field;
+        let c = self.axis_mut(a);
+
+        match field {
+            Field::Scale => c.scale = (c.scale + steps * 0.05).clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end()),
+            Field::Deadzone => c.deadzone = (c.deadzone + steps * 0.01).clamp(*DEADZONE_RANGE.start(), *DEADZONE_RANGE.end()),
+            Field::Curve => {
+                let r = c.curve.range();
+                match &mut c.curve {
+                    CurveKind::CubicBlend(v) => *v = (*v + steps * 0.05).clamp(*r.start(), *r.end()),
+                    CurveKind::Power(k) => *k = (*k + steps * 0.1).clamp(*r.start(), *r.end()),
+                    CurveKind::Table(_) => {
+                        self.status = "Table curves can't be adjusted here".to_string();
+                        return Action::None;
+                    }
+                }
+            }
+        }
+
+        Action::Apply
+    }
+
+    /// Handle a key press
+    fn key(&mut self, k: KeyEvent) -> Action {
+        match k.code {
+            KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+            KeyCode::Char('c') if k.modifiers.contains(KeyModifiers::CONTROL) => Action::Quit,
+            KeyCode::Up => {
+                self.axis = (self.axis + AXIS.len() - 1) % AXIS.len();
+                Action::None
+            }
+            KeyCode::Down => {
+                self.axis = (self.axis + 1) % AXIS.len();
+                Action::None
+            }
+            KeyCode::Left => {
+                self.device = (self.device + self.devices.len() - 1) % self.devices.len();
+                Action::None
+            }
+            KeyCode::Right => {
+                self.device = (self.device + 1) % self.devices.len();
+                Action::None
+            }
+            KeyCode::Tab => {
+                self.field = self.field.next();
+                Action::None
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') => self.adjust(1.0),
+            KeyCode::Char('-') => self.adjust(-1.0),
+            KeyCode::Char('w') if !self.read_only => Action::Write,
+            _ => Action::None,
+        }
+    }
+
+    /// Handle a daemon message
+    fn message(&mut self, m: Command) {
+        match m {
+            Command::State { device, state } => {
+                self.state.insert(device, state);
+            }
+            Command::Ok => {
+                self.applied = self.config.clone();
+                self.status = "Applied".to_string();
+            }
+            Command::Error(ErrorCode::PermissionDenied) => {
+                self.config = self.applied.clone();
+                self.read_only = true;
+                self.status = "Permission denied, monitoring only".to_string();
+            }
+            Command::Error(e) => {
+                self.config = self.applied.clone();
+                self.status = format!("Rejected: {}", e);
+            }
+            _ => (),
+        }
+    }
+
+    fn draw(&self, f: &mut Frame<Backend>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(AXIS.len() as u16), Constraint::Length(3)])
+            .split(f.size());
+
+        // Header, selected device and mode
+        let name = match self.selected_device() {
+            Some(d) => format!("{} {}", d.to_string(), d.name.as_deref().unwrap_or("")),
+            None => "default".to_string(),
+        };
+        let mode = if self.read_only { " (read-only)" } else { "" };
+        let header = Paragraph::new(Spans::from(vec![
+            Span::styled(name, Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("  [{}/{}]{}", self.device + 1, self.devices.len(), mode)),
+        ]))
+        .block(Block::default().borders(Borders::ALL).title("vmouse tune"));
+        f.render_widget(header, rows[0]);
+
+        // Axis bars, one row per axis
+        let bars = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(AXIS.iter().map(|_| Constraint::Length(1)).collect::<Vec<_>>())
+            .split(rows[1]);
+
+        let state = self.state.get(&self.selected_device().cloned()).copied().unwrap_or_default();
+        let axes = self.axes();
+
+        for (i, a) in AXIS.iter().enumerate() {
+            let c = &axes[*a];
+            let v = state.output[*a].clamp(-1.0, 1.0);
+
+            let field = |fl: Field, s: String| match i == self.axis && fl == self.field {
+                true => format!("[{}]", s),
+                false => format!(" {} ", s),
+            };
+            let label = format!(
+                "{:>3} {:+.3} {} {} {}",
+                a.to_string(),
+                v,
+                field(Field::Scale, format!("scale {:.2}", c.scale)),
+                field(Field::Deadzone, format!("dz {:.2}", c.deadzone)),
+                field(Field::Curve, curve_label(&c.curve)),
+            );
+
+            let style = match i == self.axis {
+                true => Style::default().fg(Color::Yellow),
+                false => Style::default().fg(Color::Cyan),
+            };
+
+            let g = Gauge::default()
+                .gauge_style(style)
+                .ratio(((v + 1.0) / 2.0) as f64)
+                .label(label);
+            f.render_widget(g, bars[i]);
+        }
+
+        // Status and key help
+        let help = Paragraph::new(format!(
+            "{}  |  ←/→ device  ↑/↓ axis  tab field  +/- adjust  w write  q quit",
+            self.status
+        ))
+        .block(Block::default().borders(Borders::ALL));
+        f.render_widget(help, rows[2]);
+    }
+}
+
+/// Format a curve for display
+fn curve_label(c: &CurveKind) -> String {
+    match c {
+        CurveKind::CubicBlend(v) => format!("cubic {:.2}", v),
+        CurveKind::Power(k) => format!("power {:.1}", k),
+        CurveKind::Table(_) => "table".to_string(),
+    }
+}
+
+/// Raw mode and alternate screen guard, restores the terminal on drop
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> anyhow::Result<Self> {
+        enable_raw_mode()?;
+        crossterm::execute!(std::io::stdout(), EnterAlternateScreen)?;
+
+        // Restore before panic messages are printed
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore();
+            hook(info);
+        }));
+
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+fn restore() {
+    let _ = disable_raw_mode();
+    let _ = crossterm::execute!(std::io::stdout(), LeaveAlternateScreen);
+}
+
+/// Run the tuning interface until quit
+pub async fn run(socket: &str) -> anyhow::Result<()> {
+    // Fetch initial config and bound devices
+    let mut c = BlockingClient::connect(socket)?;
+    let config = c.get_config()?;
+    let devices = match c.request(&Command::ListDevices)? {
+        Command::Devices(d) => d,
+        _ => vec![],
+    };
+    drop(c);
+
+    let mut client = Client::connect(socket.to_string()).await?;
+    client.send(Command::Listen { topics: vec![Topic::State] }).await?;
+
+    let mut tune = Tune::new(config, devices);
+
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    let mut keys = EventStream::new().fuse();
+
+    loop {
+        // Layout is recomputed from the frame size on each draw, so resizes only need a redraw
+        terminal.draw(|f| tune.draw(f))?;
+
+        let action = futures::select!(
+            m = client.next().fuse() => match m {
+                Some(Ok(m)) => {
+                    tune.message(m);
+                    Action::None
+                }
+                Some(Err(e)) => return Err(e.into()),
+                None => return Err(anyhow::anyhow!("Daemon disconnected")),
+            },
+            k = keys.next() => match k {
+                Some(Ok(Event::Key(k))) => tune.key(k),
+                Some(Ok(_)) => Action::None,
+                Some(Err(e)) => return Err(e.into()),
+                None => Action::Quit,
+            },
+        );
+
+        match action {
+            Action::None => (),
+            Action::Apply => client.send(Command::SetConfig(tune.config.clone())).await?,
+            Action::Write => {
+                tune.status = "Writing config".to_string();
+                client.send(Command::WriteConfig).await?;
+            }
+            Action::Quit => break,
+        }
+    }
+
+    Ok(())
+}
@@ -1,63 +1,295 @@
 
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
 use std::os::unix::prelude::AsRawFd;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::task::Poll;
+use std::time::Duration;
 
 use async_std::os::unix::net::UnixStream;
-use futures::{AsyncRead, AsyncWriteExt, Stream};
+use futures::channel::oneshot;
+use futures::{AsyncRead, AsyncWriteExt, FutureExt, Stream, StreamExt};
 use log::{trace, debug};
 
-use crate::Command;
+use crate::{Command, Decoder, Error, ErrorCode, Frame, Request, BROADCAST_ID, FEATURE_REQUEST_IDS, PROTOCOL_VERSION};
 
+/// Timeout for the daemon to answer [`Command::Hello`]
+const HELLO_TIMEOUT: Duration = Duration::from_secs(1);
 
 #[derive(Clone, Debug)]
 pub struct Client {
     path: String,
     stream: UnixStream,
+    decoder: Decoder,
+    version: u32,
+    features: Vec<String>,
+    pending: Arc<Mutex<Pending>>,
+    _guard: Arc<StreamGuard>,
+}
+
+/// Requests awaiting responses, shared between [`Client`] clones
+#[derive(Debug, Default)]
+struct Pending {
+    /// Last allocated request id, ids start at 1 as 0 is [`BROADCAST_ID`]
+    last_id: u64,
+    requests: HashMap<u64, oneshot::Sender<Command>>,
+    /// Messages read while awaiting a response in [`Client::request`], returned by
+    /// subsequent stream polls
+    backlog: VecDeque<Command>,
+}
+
+/// Shuts down the stream write half once the last [`Client`] clone is dropped,
+/// so the daemon observes the disconnect promptly
+#[derive(Debug)]
+struct StreamGuard(UnixStream);
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        let _ = self.0.shutdown(std::net::Shutdown::Write);
+    }
 }
 
 impl Client {
-    pub async fn connect(path: String) -> Result<Self, std::io::Error> {
+    pub async fn connect(path: String) -> Result<Self, Error> {
         // Connect to daemon socket
-        let stream = UnixStream::connect(&path).await?;
+        let stream = match UnixStream::connect(&path).await {
+            Ok(s) => s,
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                return Err(Error::SocketIo(std::io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!(
+                        "Permission denied connecting to '{}', check the daemon socket group (--socket-group) and that this user is a member",
+                        path
+                    ),
+                )));
+            }
+            Err(e) => return Err(Error::SocketIo(e)),
+        };
+
+        let _guard = Arc::new(StreamGuard(stream.clone()));
+
+        let mut c = Self { path, stream, decoder: Decoder::new(), version: 0, features: vec![], pending: Default::default(), _guard };
+
+        c.hello().await?;
+
+        Ok(c)
+    }
+
+    /// Exchange [`Command::Hello`] with the daemon, checking protocol compatibility
+    async fn hello(&mut self) -> Result<(), Error> {
+        let invalid = |m: String| Error::SocketIo(std::io::Error::new(ErrorKind::InvalidData, m));
+
+        let hello = async {
+            self.send(Command::Hello { version: PROTOCOL_VERSION, features: crate::protocol_features() }).await?;
+            self.next().await.transpose()
+        };
+
+        let r = match async_std::future::timeout(HELLO_TIMEOUT, hello).await {
+            Ok(Ok(r)) => r,
+            Ok(Err(e)) => return Err(invalid(format!("Handshake with '{}' failed: {}", self.path, e))),
+            Err(_) => return Err(Error::SocketIo(std::io::Error::new(ErrorKind::TimedOut, format!("Handshake with '{}' timed out", self.path)))),
+        };
+
+        match r {
+            Some(Command::Hello { version, features }) => {
+                debug!("Connected to daemon protocol v{} (features: {:?})", crate::protocol_string(version), features);
+                self.version = version;
+                self.features = features;
+                Ok(())
+            }
+            Some(Command::Error(ErrorCode::VersionMismatch { daemon, client })) => Err(Error::VersionMismatch { daemon, client }),
+            r => Err(invalid(format!(
+                "Unexpected handshake response from '{}' ({:?}), vmoused may predate protocol versioning, please upgrade",
+                self.path, r
+            ))),
+        }
+    }
+
+    /// Acknowledge a daemon keepalive ping without blocking the reader
+    fn ack_ping(&self) {
+        let mut stream = self.stream.clone();
+
+        async_std::task::spawn(async move {
+            if let Ok(b) = crate::encode(&Command::Ok) {
+                let _ = stream.write_all(&b).await;
+            }
+        });
+    }
+
+    /// Daemon protocol version, from the connection handshake
+    pub fn daemon_version(&self) -> u32 {
+        self.version
+    }
+
+    /// Daemon features, from the connection handshake
+    pub fn daemon_features(&self) -> &[String] {
+        &self.features
+    }
+
+    /// Whether the daemon supports request ids, allowing multiple requests in flight
+    pub fn supports_request_ids(&self) -> bool {
+        self.features.iter().any(|f| f == FEATURE_REQUEST_IDS)
+    }
+
+    /// Send a command with a unique request id, returning a receiver resolved with the
+    /// matching response
+    ///
+    /// Responses are matched as the connection is read, by [`Client::request`] or by polling
+    /// this client (or a clone) as a stream. The receiver is cancelled if the connection closes.
+    pub async fn send_request(&mut self, cmd: Command) -> Result<oneshot::Receiver<Command>, Error> {
+        if !self.supports_request_ids() {
+            return Err(Error::SocketIo(std::io::Error::new(
+                ErrorKind::Unsupported,
+                format!("Daemon at '{}' does not support request ids", self.path),
+            )));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let id = {
+            let mut p = self.pending.lock().unwrap();
+            p.last_id += 1;
+            let id = p.last_id;
+            p.requests.insert(id, tx);
+            id
+        };
+
+        debug!("Send request {}: {:?}", id, cmd);
 
-        Ok(Self { path, stream })
+        let encoded = crate::encode_request(&Request { id, cmd })?;
+        if let Err(e) = self.stream.write_all(&encoded).await {
+            self.pending.lock().unwrap().requests.remove(&id);
+            return Err(Error::SocketIo(e));
+        }
+
+        Ok(rx)
+    }
+
+    /// Send a command and await the matching response
+    ///
+    /// Other messages read while waiting are returned by subsequent stream polls. Where another
+    /// task reads the connection, use [`Client::send_request`] and await the receiver instead.
+    /// Daemons predating request ids return the next message received.
+    pub async fn request(&mut self, cmd: Command) -> Result<Command, Error> {
+        let closed = || Error::SocketIo(std::io::Error::new(ErrorKind::UnexpectedEof, "Connection closed awaiting response"));
+
+        if !self.supports_request_ids() {
+            self.send(cmd).await?;
+            return self.next().await.unwrap_or_else(|| Err(closed()));
+        }
+
+        let mut rx = self.send_request(cmd).await?;
+
+        loop {
+            futures::select_biased!(
+                r = rx => return r.map_err(|_| closed()),
+                // Read the connection directly, as stream polls return the backlog first
+                m = futures::future::poll_fn(|cx| self.poll_message(cx)).fuse() => match m {
+                    Some(Ok(c)) => self.pending.lock().unwrap().backlog.push_back(c),
+                    // The response may have been read before the connection closed
+                    m => return match rx.try_recv() {
+                        Ok(Some(r)) => Ok(r),
+                        _ => Err(m.and_then(Result::err).unwrap_or_else(closed)),
+                    },
+                },
+            )
+        }
+    }
+
+    /// Drop requests awaiting responses, cancelling their receivers
+    fn cancel_pending(&self) {
+        self.pending.lock().unwrap().requests.clear();
     }
 
-    pub async fn send(&mut self, cmd: Command) -> Result<(), anyhow::Error> {
-        let encoded: Vec<u8> = bincode::serialize(&cmd)?;
+    /// Gracefully close the connection, signalling disconnect to the daemon
+    pub async fn close(&mut self) -> Result<(), Error> {
+        self.send(Command::Disconnect).await?;
+        self.shutdown().map_err(Error::SocketIo)?;
+
+        Ok(())
+    }
+
+    /// Shut down the underlying stream, pending reads will return end of stream
+    pub fn shutdown(&self) -> Result<(), std::io::Error> {
+        self.stream.shutdown(std::net::Shutdown::Both)
+    }
+
+    pub async fn send(&mut self, cmd: Command) -> Result<(), Error> {
+        let encoded: Vec<u8> = crate::encode(&cmd)?;
 
         debug!("Send: {:?}", cmd);
 
-        let _n = self.stream.write_all(&encoded).await?;
+        self.stream.write_all(&encoded).await.map_err(Error::SocketIo)?;
 
         Ok(())
     }
+
+    /// Read the next message from the connection, resolving pending requests
+    fn poll_message(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Option<Result<Command, Error>>> {
+        let mut buff = [0u8; 1024];
+
+        loop {
+            // Return buffered frames before reading more data
+            match self.decoder.decode_frame() {
+                // Acknowledge daemon keepalive pings transparently
+                Ok(Some(Frame { cmd: Command::Ping, .. })) => {
+                    trace!("Keepalive ping from '{}'", self.path);
+                    self.ack_ping();
+                    continue;
+                }
+                // Resolve pending requests, unmatched responses are returned as messages
+                Ok(Some(Frame { id: Some(id), cmd })) if id != BROADCAST_ID => {
+                    match self.pending.lock().unwrap().requests.remove(&id) {
+                        Some(tx) => {
+                            trace!("Response {}: {:?}", id, cmd);
+                            let _ = tx.send(cmd);
+                            continue;
+                        }
+                        None => {
+                            trace!("Receive (unmatched response {}): {:?}", id, cmd);
+                            return Poll::Ready(Some(Ok(cmd)));
+                        }
+                    }
+                }
+                Ok(Some(f)) => {
+                    trace!("Receive: {:?}", f.cmd);
+                    return Poll::Ready(Some(Ok(f.cmd)));
+                }
+                Ok(None) => (),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+
+            let n = match Pin::new(&mut self.stream).poll_read(cx, &mut buff) {
+                Poll::Ready(Ok(0)) => {
+                    self.cancel_pending();
+                    return Poll::Ready(None);
+                }
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(e)) => {
+                    self.cancel_pending();
+                    return Poll::Ready(Some(Err(Error::SocketIo(e))));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            self.decoder.push(&buff[..n]);
+        }
+    }
 }
 
 impl Stream for Client {
-    type Item = Result<Command, anyhow::Error>;
+    type Item = Result<Command, Error>;
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        let mut buff = vec![0u8; 1024];
-
-        let n = match Pin::new(&mut self.stream).poll_read(cx, &mut buff) {
-            Poll::Ready(Ok(n)) => n,
-            Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
-            Poll::Pending => return Poll::Pending,
-        };
-
-        let decoded: Command = match bincode::deserialize(&buff[..n]) {
-            Ok(d) => d,
-            Err(e) => return Poll::Ready(Some(Err(e.into()))),
-        };
-
-        trace!("Receive: {:?}", decoded);
+        // Return messages read while awaiting a response first
+        if let Some(c) = self.pending.lock().unwrap().backlog.pop_front() {
+            return Poll::Ready(Some(Ok(c)));
+        }
 
-        Poll::Ready(Some(Ok(decoded)))
+        self.poll_message(cx)
     }
 }
 
@@ -67,3 +299,278 @@ impl std::hash::Hash for Client {
         self.stream.as_raw_fd().hash(state);
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{Read, Write};
+    use std::os::unix::net::{UnixListener, UnixStream as StdUnixStream};
+    use std::thread::JoinHandle;
+
+    use crate::testutil::test_dir;
+    use crate::{encode_response, Response};
+
+    use super::*;
+
+    /// Fake daemon socket path, unique per test
+    pub(crate) fn socket_path(name: &str) -> String {
+        let p = test_dir("client", name).join("vmoused.sock");
+        p.to_string_lossy().to_string()
+    }
+
+    /// Read the next frame from the client
+    pub(crate) fn read_frame(s: &mut StdUnixStream, d: &mut Decoder) -> Option<Frame> {
+        let mut buff = [0u8; 1024];
+        loop {
+            if let Some(f) = d.decode_frame().unwrap() {
+                return Some(f);
+            }
+            match s.read(&mut buff).unwrap() {
+                0 => return None,
+                n => d.push(&buff[..n]),
+            }
+        }
+    }
+
+    /// Broadcast sent by [`spawn_peer`] between responses
+    pub(crate) fn broadcast() -> Command {
+        Command::ActiveProfile { device: "default".to_string(), profile: Some("test".to_string()) }
+    }
+
+    /// Response sent by [`spawn_peer`] for a request
+    pub(crate) fn response(cmd: &Command) -> Command {
+        Command::ConfigPath(format!("{:?}", cmd))
+    }
+
+    /// Fake daemon supporting request ids, awaiting `n` (at least 2) requests then answering
+    /// in reverse order with a broadcast after the first response, then closing the connection
+    pub(crate) fn spawn_peer(path: &str, n: usize) -> JoinHandle<()> {
+        let listener = UnixListener::bind(path).unwrap();
+
+        std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut d = Decoder::new();
+
+            match read_frame(&mut s, &mut d).map(|f| f.cmd) {
+                Some(Command::Hello { .. }) => (),
+                c => panic!("unexpected handshake: {:?}", c),
+            }
+            let hello = Command::Hello { version: PROTOCOL_VERSION, features: vec![FEATURE_REQUEST_IDS.to_string()] };
+            s.write_all(&crate::encode(&hello).unwrap()).unwrap();
+
+            let mut requests = vec![];
+            while requests.len() < n {
+                match read_frame(&mut s, &mut d) {
+                    Some(Frame { id: Some(id), cmd }) => requests.push((id, cmd)),
+                    f => panic!("unexpected frame: {:?}", f),
+                }
+            }
+
+            for (i, (id, cmd)) in requests.iter().rev().enumerate() {
+                if i == 1 {
+                    s.write_all(&encode_response(&Response::broadcast(broadcast())).unwrap()).unwrap();
+                }
+                s.write_all(&encode_response(&Response { id: *id, cmd: response(cmd) }).unwrap()).unwrap();
+            }
+        })
+    }
+
+    #[test]
+    fn send_request_out_of_order() {
+        let path = socket_path("send-request");
+        let cmds = [Command::GetConfig, Command::GetStatus, Command::ListDevices];
+        let peer = spawn_peer(&path, cmds.len());
+
+        async_std::task::block_on(async {
+            let mut c = Client::connect(path.clone()).await.unwrap();
+            assert!(c.supports_request_ids());
+
+            let mut pending = vec![];
+            for cmd in &cmds {
+                pending.push(c.send_request(cmd.clone()).await.unwrap());
+            }
+
+            // Reading the connection resolves responses, returning only broadcasts
+            let messages: Vec<_> = c.by_ref().map(Result::unwrap).collect().await;
+            assert_eq!(messages, vec![broadcast()]);
+
+            for (cmd, rx) in cmds.iter().zip(pending) {
+                assert_eq!(rx.await.unwrap(), response(cmd));
+            }
+        });
+
+        peer.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn request_with_request_in_flight() {
+        let path = socket_path("request");
+        let peer = spawn_peer(&path, 2);
+
+        async_std::task::block_on(async {
+            let mut c = Client::connect(path.clone()).await.unwrap();
+
+            // Answered after the awaited request, with a broadcast in between
+            let first = c.send_request(Command::GetConfig).await.unwrap();
+            assert_eq!(c.request(Command::GetStatus).await.unwrap(), response(&Command::GetStatus));
+
+            // Broadcasts read while awaiting are kept for the stream
+            assert_eq!(c.next().await.unwrap().unwrap(), broadcast());
+            assert!(c.next().await.is_none());
+
+            assert_eq!(first.await.unwrap(), response(&Command::GetConfig));
+        });
+
+        peer.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn request_with_broadcast_before_response() {
+        let path = socket_path("request-broadcast");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let peer = std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut d = Decoder::new();
+
+            read_frame(&mut s, &mut d).unwrap();
+            let hello = Command::Hello { version: PROTOCOL_VERSION, features: vec![FEATURE_REQUEST_IDS.to_string()] };
+            s.write_all(&crate::encode(&hello).unwrap()).unwrap();
+
+            // Broadcasts sent ahead of the response
+            let Frame { id, cmd } = read_frame(&mut s, &mut d).unwrap();
+            for _i in 0..2 {
+                s.write_all(&encode_response(&Response::broadcast(broadcast())).unwrap()).unwrap();
+            }
+            s.write_all(&encode_response(&Response { id: id.unwrap(), cmd: response(&cmd) }).unwrap()).unwrap();
+        });
+
+        async_std::task::block_on(async {
+            let mut c = Client::connect(path.clone()).await.unwrap();
+
+            let r = async_std::future::timeout(Duration::from_secs(1), c.request(Command::GetStatus)).await;
+            assert_eq!(r.expect("request timed out").unwrap(), response(&Command::GetStatus));
+
+            // Broadcasts are returned in order, then the connection closes
+            assert_eq!(c.next().await.unwrap().unwrap(), broadcast());
+            assert_eq!(c.next().await.unwrap().unwrap(), broadcast());
+            assert!(c.next().await.is_none());
+        });
+
+        peer.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn pending_cancelled_on_close() {
+        let path = socket_path("cancel");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let peer = std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut d = Decoder::new();
+
+            read_frame(&mut s, &mut d).unwrap();
+            let hello = Command::Hello { version: PROTOCOL_VERSION, features: vec![FEATURE_REQUEST_IDS.to_string()] };
+            s.write_all(&crate::encode(&hello).unwrap()).unwrap();
+
+            // Close without answering
+            read_frame(&mut s, &mut d).unwrap();
+        });
+
+        async_std::task::block_on(async {
+            let mut c = Client::connect(path.clone()).await.unwrap();
+            let rx = c.send_request(Command::GetConfig).await.unwrap();
+
+            assert!(c.next().await.is_none());
+            assert!(rx.await.is_err());
+        });
+
+        peer.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Fake daemon answering the handshake, returning frames received until the client closes
+    fn spawn_closing_peer(path: &str) -> JoinHandle<Vec<Command>> {
+        let listener = UnixListener::bind(path).unwrap();
+
+        std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut d = Decoder::new();
+
+            read_frame(&mut s, &mut d).unwrap();
+            s.write_all(&crate::encode(&Command::Hello { version: PROTOCOL_VERSION, features: vec![] }).unwrap()).unwrap();
+
+            std::iter::from_fn(|| read_frame(&mut s, &mut d).map(|f| f.cmd)).collect()
+        })
+    }
+
+    #[test]
+    fn close_sends_disconnect() {
+        let path = socket_path("close");
+        let peer = spawn_closing_peer(&path);
+
+        async_std::task::block_on(async {
+            let mut c = Client::connect(path.clone()).await.unwrap();
+            c.close().await.unwrap();
+
+            // Both halves are shut down
+            assert!(c.next().await.is_none());
+        });
+
+        assert_eq!(peer.join().unwrap(), vec![Command::Disconnect]);
+    }
+
+    #[test]
+    fn drop_shuts_down_stream() {
+        let path = socket_path("drop");
+        let peer = spawn_closing_peer(&path);
+
+        async_std::task::block_on(async {
+            let c = Client::connect(path.clone()).await.unwrap();
+
+            // The stream stays open while any clone is alive
+            let mut clone = c.clone();
+            drop(c);
+            clone.send(Command::Ping).await.unwrap();
+
+            drop(clone);
+        });
+
+        // The peer sees end of stream without the client closing explicitly
+        assert_eq!(peer.join().unwrap(), vec![Command::Ping]);
+    }
+
+    #[test]
+    fn hello_version_mismatch() {
+        let path = socket_path("mismatch");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        // Daemon with an incompatible major version
+        let daemon = PROTOCOL_VERSION + (1 << 16);
+        let peer = std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut d = Decoder::new();
+
+            let client = match read_frame(&mut s, &mut d).map(|f| f.cmd) {
+                Some(Command::Hello { version, .. }) => version,
+                c => panic!("unexpected handshake: {:?}", c),
+            };
+            let r = Command::Error(ErrorCode::VersionMismatch { daemon, client });
+            s.write_all(&crate::encode(&r).unwrap()).unwrap();
+        });
+
+        let r = async_std::task::block_on(Client::connect(path.clone()));
+        peer.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        match r {
+            Err(Error::VersionMismatch { daemon: d, client }) => {
+                assert_eq!(d, daemon);
+                assert_eq!(client, PROTOCOL_VERSION);
+            }
+            r => panic!("unexpected result: {:?}", r.map(|c| c.daemon_version())),
+        }
+    }
+}
@@ -1,48 +1,234 @@
 
+use std::collections::HashMap;
+
 use structopt::StructOpt;
 use serde::{Serialize, Deserialize};
+use strum::{Display, EnumString};
+
+use super::{AxisCollection, AxisValue, AxisState, Config, MetricsSnapshot, OutputDevice, UsbDevice};
+
+/// Wire protocol major version, bump on incompatible changes to [`Command`]
+/// (or any type it contains), such as removing or changing variants and fields
+pub const PROTOCOL_MAJOR: u16 = 1;
+
+/// Wire protocol minor version, bump on additive changes such as new variants
+/// or optional fields, peers should check [`Command::Hello`] features before
+/// relying on additions
+pub const PROTOCOL_MINOR: u16 = 0;
+
+/// Wire protocol version exchanged in [`Command::Hello`], major in the upper 16 bits
+/// and minor in the lower 16 bits
+///
+/// Versions predating the split (a single counter, up to 2) decode as major 0.
+pub const PROTOCOL_VERSION: u32 = (PROTOCOL_MAJOR as u32) << 16 | PROTOCOL_MINOR as u32;
+
+/// Fetch the major component of a protocol version
+pub fn protocol_major(version: u32) -> u16 {
+    (version >> 16) as u16
+}
+
+/// Fetch the minor component of a protocol version
+pub fn protocol_minor(version: u32) -> u16 {
+    version as u16
+}
+
+/// Format a protocol version as `major.minor`
+pub fn protocol_string(version: u32) -> String {
+    format!("{}.{}", protocol_major(version), protocol_minor(version))
+}
+
+/// Check whether a peer protocol version is compatible with this build,
+/// peers are compatible where the major versions match
+pub fn protocol_compatible(version: u32) -> bool {
+    protocol_major(version) == PROTOCOL_MAJOR
+}
 
-use super::{AxisValue, AxisCollection, Config};
+/// Feature flag for [`Request`](crate::Request) / [`Response`](crate::Response) envelopes
+pub const FEATURE_REQUEST_IDS: &str = "request-ids";
+
+/// Optional features enabled in this build, exchanged in [`Command::Hello`]
+pub fn protocol_features() -> Vec<String> {
+    let mut f = vec![FEATURE_REQUEST_IDS.to_string()];
+    if cfg!(feature = "dbus") {
+        f.push("dbus".to_string());
+    }
+    if cfg!(feature = "systemd") {
+        f.push("systemd".to_string());
+    }
+    if cfg!(feature = "metrics") {
+        f.push("metrics".to_string());
+    }
+    f
+}
 
 
 #[derive(Clone, PartialEq, Debug, StructOpt, Serialize, Deserialize)]
 
 pub enum Command {
+    /// Protocol handshake, sent by clients on connection and answered by vmoused
+    #[structopt(skip)]
+    Hello {
+        version: u32,
+        features: Vec<String>,
+    },
+
     /// Ping the vmouse daemon (vmoused)
     Ping,
-    /// Bind an event input to vmoused
+    /// Bind an event or hidraw input to vmoused (see `vmousectl bind`)
+    #[structopt(skip)]
     Bind {
-        /// Device event name
-        event: String,
+        /// Device to bind
+        target: BindTarget,
+        /// Seconds to wait for a device matching an [`BindTarget::Id`] to appear,
+        /// the response is sent once bound or on timeout
+        wait: Option<u64>,
     },
-    /// Subscribe to events from vmoused
-    Listen,
+    /// Subscribe to events from vmoused (see `vmousectl listen`), all topics if empty
+    #[structopt(skip)]
+    Listen {
+        topics: Vec<Topic>,
+    },
+    /// Unsubscribe from events without disconnecting
+    Unlisten,
 
     /// Fetch current state from vmoused
-    GetState,
+    GetState {
+        /// Device to fetch state for (`vid:pid`), aggregate state if not provided
+        #[structopt(long)]
+        device: Option<String>,
+    },
 
-    /// Fetch current config from vmoused
+    /// Fetch current config from vmoused (see `vmousectl get-config`)
+    #[structopt(skip)]
     GetConfig,
 
+    /// List devices known to vmoused
+    ListDevices,
+
+    /// Fetch daemon status (output state, devices, clients, and event counters)
+    GetStatus,
+
+    /// Fetch virtual output device nodes (see `vmousectl devnode`)
+    #[structopt(skip)]
+    GetDevnode,
+
+    /// Zero aggregate and per-device axis state (eg. after a device is removed mid-deflection)
+    ResetState,
+
+    /// Reset config to defaults (see `vmousectl reset-config`)
+    #[structopt(skip)]
+    ResetConfig {
+        /// Device to reset (`default` or `vid:pid`), the whole config if not provided
+        device: Option<String>,
+    },
+
+    /// Fetch daemon event rate and latency metrics
+    GetMetrics,
+
+    /// Inject a synthetic input sample (see `vmousectl bench`), processed as an external
+    /// device with the provided source time (microseconds since the unix epoch)
+    #[structopt(skip)]
+    Inject {
+        axes: AxisCollection<f32>,
+        time_us: u64,
+    },
+
+    /// Calibrate device axis ranges (see `vmousectl calibrate`)
+    #[structopt(skip)]
+    Calibrate {
+        /// Device name (`vid:pid`)
+        device: String,
+        /// Calibration action
+        action: CalibrateAction,
+    },
+
+    /// Activate a saved profile for a device
+    SelectProfile {
+        /// Device name (`default` or `vid:pid`)
+        #[structopt(long, default_value = "default")]
+        device: String,
+        /// Profile name
+        profile: String,
+    },
+
     /// Enable or disable vmoused output (useful when changing configuration)
     Enable {
         #[structopt(long)]
         enabled: bool,
+        /// Device to enable or disable (`vid:pid` or event path), all output if not provided
+        #[structopt(long)]
+        device: Option<String>,
     },
 
     #[structopt(skip)]
     Ok,
 
+    /// Generic failure, superseded by [`Command::Error`] and no longer sent by vmoused.
+    /// Retained so variant indices (and older daemon responses) still decode.
+    #[deprecated(note = "vmoused replies with Command::Error")]
     #[structopt(skip)]
     Failed,
 
-    /// Raw value update message
+    /// Error response
+    #[structopt(skip)]
+    Error(ErrorCode),
+
+    /// Raw value update message, with source and daemon processing times
+    #[structopt(skip)]
+    RawValue(AxisValue, RawTiming),
+
+    /// State update message, contains raw and transformed values
+    /// for a specific device or the aggregate of all devices
+    #[structopt(skip)]
+    State {
+        device: Option<UsbDevice>,
+        state: AxisState,
+    },
+
+    /// Device removal notification
+    #[structopt(skip)]
+    Removed(UsbDevice),
+
+    /// Config file changed on disk but could not be applied, the current config is kept
+    #[structopt(skip)]
+    ConfigInvalid {
+        path: String,
+        errors: Vec<String>,
+    },
+
+    /// Low battery notification for a wireless device
+    #[structopt(skip)]
+    BatteryLow {
+        device: UsbDevice,
+        percent: u8,
+    },
+
+    /// Device list response
+    #[structopt(skip)]
+    Devices(Vec<UsbDevice>),
+
+    /// Status response, also broadcast to listening clients when output is enabled or disabled
+    #[structopt(skip)]
+    Status(StatusInfo),
+
+    /// Metrics response
+    #[structopt(skip)]
+    Metrics(MetricsSnapshot),
+
+    /// Virtual output devices response, empty while output devices are released
+    #[structopt(skip)]
+    Devnodes(Vec<OutputDevice>),
+
+    /// Calibration result, (min, max) raw values per axis
     #[structopt(skip)]
-    RawValue(AxisValue),
+    Calibrated(AxisCollection<(i32, i32)>),
 
-    /// State update message
+    /// Active profile notification, broadcast to listening clients when a profile is activated
     #[structopt(skip)]
-    State(AxisCollection<f32>),
+    ActiveProfile {
+        device: String,
+        profile: Option<String>,
+    },
 
     /// Send updated config to vmoused
     #[structopt(skip)]
@@ -51,7 +237,235 @@ pub enum Command {
     /// Write updated config to configured file
     WriteConfig,
 
+    /// Fetch the active config file path, used by [`Command::WriteConfig`]
+    #[structopt(skip)]
+    GetConfigPath,
+
+    /// Active config file path response
+    #[structopt(skip)]
+    ConfigPath(String),
+
+    /// Write the current config to a file (see `vmousectl save-config`)
+    #[structopt(skip)]
+    SaveConfigAs {
+        /// Absolute path to write, the parent directory must exist. Non-root callers are limited
+        /// to `.toml` / `.json` files in the config directory or socket `config_dirs`
+        path: String,
+        /// Use this path for subsequent [`Command::WriteConfig`]s
+        set_active: bool,
+    },
+
     /// Signal disconnect for a client
     #[structopt(skip)]
     Disconnect,
+
+    /// Daemon shutdown notification, sent to all clients before the connection is closed
+    #[structopt(skip)]
+    ShuttingDown,
+}
+
+impl Command {
+    /// Check whether a command mutates daemon state and requires authorisation
+    pub fn is_privileged(&self) -> bool {
+        matches!(
+            self,
+            Command::Bind { .. }
+                | Command::Enable { .. }
+                | Command::Calibrate { .. }
+                | Command::SelectProfile { .. }
+                | Command::SetConfig(_)
+                | Command::WriteConfig
+                | Command::SaveConfigAs { .. }
+                | Command::ResetState
+                | Command::ResetConfig { .. }
+                | Command::Inject { .. }
+        )
+    }
+}
+
+/// Raw value timing for [`Command::RawValue`], in microseconds since the unix epoch
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct RawTiming {
+    /// Input event (source) time
+    pub source_us: u64,
+    /// Daemon processing time, when the value was mapped and broadcast
+    pub processed_us: u64,
+}
+
+/// Bound input device status for [`StatusInfo`]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DeviceStatus {
+    /// Input device
+    pub device: UsbDevice,
+    /// Input device path
+    pub path: String,
+    /// Physical identity, nodes of the same device share an identity
+    pub identity: String,
+    /// Battery level (%), `None` for devices without a battery
+    pub battery: Option<u8>,
+}
+
+/// Daemon status for [`Command::Status`]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StatusInfo {
+    /// Daemon package version
+    pub version: String,
+    /// Daemon protocol version
+    pub protocol: u32,
+    /// Seconds since daemon start
+    pub uptime: u64,
+    /// Whether output is enabled
+    pub enabled: bool,
+    /// Per-device output enabled flags by `vid:pid`, devices not listed are enabled
+    pub device_enabled: HashMap<String, bool>,
+    /// Bound input devices
+    pub devices: Vec<DeviceStatus>,
+    /// Connected clients
+    pub clients: usize,
+    /// Connected clients subscribed to updates
+    pub listening: usize,
+    /// Virtual output device nodes (eg. `/dev/input/eventN`), pointer first
+    pub devnodes: Vec<String>,
+    /// Input events processed since start
+    pub events_in: u64,
+    /// Mapped output events since start, before coalescing
+    pub events_mapped: u64,
+    /// Output events written since start
+    pub events_out: u64,
+    /// Smoothed latency from source event to output (us)
+    pub latency_us: u64,
+    /// Maximum latency from source event to output (us)
+    pub latency_max_us: u64,
+    /// Whether output is idle after `idle_timeout_s` without input
+    pub idle: bool,
+}
+
+/// Subscription topics for [`Command::Listen`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Topic {
+    /// Periodic [`Command::State`] updates
+    State,
+    /// [`Command::RawValue`] for every input event
+    RawValues,
+    /// Profile, output enable, and config changes
+    /// ([`Command::ActiveProfile`], [`Command::Status`], [`Command::SetConfig`], [`Command::ConfigInvalid`])
+    ConfigChanges,
+    /// Device bind, removal and battery ([`Command::Devices`], [`Command::Removed`], [`Command::BatteryLow`])
+    DeviceEvents,
+}
+
+impl Topic {
+    /// Fetch the topic for a broadcast command, `None` for non-broadcast commands
+    pub fn for_command(c: &Command) -> Option<Topic> {
+        match c {
+            Command::State { .. } => Some(Topic::State),
+            Command::RawValue(..) => Some(Topic::RawValues),
+            Command::ActiveProfile { .. } | Command::Status(_) | Command::SetConfig(_) | Command::ConfigInvalid { .. } => Some(Topic::ConfigChanges),
+            Command::Devices(_) | Command::Removed(_) | Command::BatteryLow { .. } => Some(Topic::DeviceEvents),
+            _ => None,
+        }
+    }
+}
+
+/// Device targets for [`Command::Bind`]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum BindTarget {
+    /// Device path (`/dev/input/eventN` or `/dev/hidrawN`)
+    Path(String),
+    /// First unbound device matching `vid:pid`
+    Id(UsbDevice),
+}
+
+/// Calibration actions for [`Command::Calibrate`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, Serialize, Deserialize)]
+pub enum CalibrateAction {
+    /// Start recording raw axis extremes
+    Start,
+    /// Finish calibration, storing recorded ranges in the device config
+    Finish,
+    /// Cancel calibration, discarding recorded ranges
+    Cancel,
+}
+
+/// Error codes for [`Command::Error`] responses
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// Client is not authorised to issue this command
+    PermissionDenied,
+    /// Config failed validation
+    InvalidConfig,
+    /// No matching device found (or appeared before the bind timeout)
+    DeviceNotFound,
+    /// Config path is not absolute, has no existing parent directory, or is a directory
+    InvalidPath,
+    /// Daemon does not have permission to write the config path
+    WriteDenied,
+    /// Writing the config file failed
+    WriteFailed,
+    /// Client protocol version is not supported, the daemon closes the connection
+    VersionMismatch {
+        daemon: u32,
+        client: u32,
+    },
+    /// Device could not be opened or attached
+    BindFailed,
+    /// Device is not a bound event path, `vid:pid`, or alias
+    InvalidDevice,
+    /// Request contained invalid (eg. non-finite) values
+    InvalidValue,
+    /// No calibration is in progress for the device
+    NotCalibrating,
+    /// No matching profile for the device
+    ProfileNotFound,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broadcast_topics() {
+        let topic = |c: Command| Topic::for_command(&c);
+
+        assert_eq!(topic(Command::State { device: None, state: Default::default() }), Some(Topic::State));
+        assert_eq!(topic(Command::ActiveProfile { device: "default".to_string(), profile: None }), Some(Topic::ConfigChanges));
+        assert_eq!(topic(Command::SetConfig(Default::default())), Some(Topic::ConfigChanges));
+        assert_eq!(topic(Command::Devices(vec![])), Some(Topic::DeviceEvents));
+
+        // Responses are never broadcast
+        for c in [Command::Ok, Command::Ping, Command::GetConfig, Command::ShuttingDown] {
+            assert_eq!(topic(c), None);
+        }
+    }
+
+    #[test]
+    fn topic_names() {
+        for (t, s) in [(Topic::State, "state"), (Topic::RawValues, "raw-values"), (Topic::ConfigChanges, "config-changes"), (Topic::DeviceEvents, "device-events")] {
+            assert_eq!(t.to_string(), s);
+            assert_eq!(s.parse::<Topic>().unwrap(), t);
+        }
+        assert!("states".parse::<Topic>().is_err());
+    }
+
+    #[test]
+    fn protocol_version_components() {
+        assert_eq!(protocol_major(PROTOCOL_VERSION), PROTOCOL_MAJOR);
+        assert_eq!(protocol_minor(PROTOCOL_VERSION), PROTOCOL_MINOR);
+        assert_eq!(protocol_string(1 << 16 | 3), "1.3");
+    }
+
+    #[test]
+    fn protocol_compatible_by_major() {
+        let major = (PROTOCOL_MAJOR as u32) << 16;
+
+        assert!(protocol_compatible(PROTOCOL_VERSION));
+        assert!(protocol_compatible(major));
+        assert!(protocol_compatible(major | 0xffff));
+
+        // Major bumps and legacy counter versions are rejected
+        assert!(!protocol_compatible(major + (1 << 16)));
+        assert!(!protocol_compatible(2));
+        assert!(!protocol_compatible(0));
+    }
 }
@@ -1,38 +1,656 @@
 //! Configuration objects and helpers
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::Duration;
 
+use evdev_rs::enums::{EventCode, EV_REL};
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
+use strum::{Display, EnumString};
 
-use crate::{UsbDevice, AxisCollection, Map};
+use crate::{Error, LedPattern, UsbDevice, Axis, AxisCollection, CurveKind, DeviceRange, Map, AxisRange, AXIS, AXIS_RANGE, MAPPINGS};
 
 /// Mouse re-mapping configuration
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct Config {
-    #[serde(default)]
+    /// Per-device axis configuration, keyed by `vid:pid` in human readable formats
+    #[serde(default, with = "device_keys::map")]
+    #[schemars(with = "HashMap<String, AxisCollection<AxisConfig>>")]
     pub devices: HashMap<UsbDevice, AxisCollection<AxisConfig>>,
 
+    /// Friendly device names, usable anywhere a `vid:pid` device name is accepted
+    #[serde(default, with = "device_keys::aliases")]
+    #[schemars(with = "HashMap<String, String>")]
+    pub aliases: HashMap<String, UsbDevice>,
+
     pub default: AxisCollection<AxisConfig>,
+
+    /// Saved axis configuration profiles
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+
+    /// Active profile by device name (`default` or `vid:pid`)
+    #[serde(default)]
+    pub active: HashMap<String, String>,
+
+    /// Route scroll output to a separate virtual device, applied on daemon start
+    #[serde(default)]
+    pub split_outputs: bool,
+
+    /// Joystick axis range (±) for [`Map::Abs`] outputs, defaults to [`ABS_RANGE_DEFAULT`](crate::ABS_RANGE_DEFAULT)
+    #[serde(default)]
+    pub abs_range: Option<i32>,
+
+    /// Absolute pointer options for [`Map::AbsX`] / [`Map::AbsY`] outputs
+    #[serde(default)]
+    pub abs_pointer: Option<AbsPointerConfig>,
+
+    /// Stop writing output after this many seconds without input events
+    #[serde(default)]
+    pub idle_timeout_s: Option<u64>,
+
+    /// Destroy virtual output devices when idle, recreating them on the next input event
+    #[serde(default)]
+    pub idle_destroy: bool,
+
+    /// Maximum output rate, outputs are coalesced between writes, applied on daemon start
+    #[serde(default)]
+    pub max_output_hz: Option<u32>,
+
+    /// Devices (`vid:pid` or alias) mirrored to a raw passthrough device, applied when bound
+    #[serde(default)]
+    pub passthrough: Vec<String>,
+
+    /// Dominant axis snap ratio by device (`default`, `vid:pid` or alias), within an input frame
+    /// the smaller of the X / Y pointer outputs is dropped once the larger exceeds it by this ratio
+    #[serde(default)]
+    pub axis_snap: HashMap<String, f32>,
+
+    /// Input device kind by device (`default`, `vid:pid` or alias), defaults to [`InputKind::SpaceMouse`]
+    #[serde(default)]
+    pub input_kind: HashMap<String, InputKind>,
+
+    /// Mouse delta (per event) normalised to 1.0 for [`InputKind::Mouse`] devices,
+    /// defaults to [`MOUSE_DIVISOR_DEFAULT`]
+    #[serde(default)]
+    pub mouse_divisor: Option<f32>,
+
+    /// Per-event output value limits, defaults to [`OutputLimits::default`]
+    #[serde(default)]
+    pub output_limits: Option<OutputLimits>,
+
+    /// Aggregation policy by output (eg. `X`, `Abs(Rx)`, or `default`) where several devices
+    /// map to the same output, defaults to [`Aggregate::Sum`]
+    #[serde(default)]
+    pub aggregate: HashMap<String, Aggregate>,
+
+    /// Zero outputs left nonzero without input for this many milliseconds,
+    /// defaults to [`AUTO_ZERO_MS_DEFAULT`], `0` to disable
+    #[serde(default)]
+    pub auto_zero_ms: Option<u64>,
+
+    /// Reload the config file when it changes on disk, defaults to enabled, applied on daemon start
+    #[serde(default)]
+    pub watch_config: Option<bool>,
+
+    /// Device LED patterns by device (`vid:pid` or alias) and profile name,
+    /// the `default` pattern applies while no profile is active
+    #[serde(default)]
+    pub feedback: HashMap<String, HashMap<String, LedPattern>>,
+
+    /// Config version, selecting default axis configs (see [`CONFIG_VERSION`])
+    pub version: u32,
+}
+
+/// Current config version, written to saved config files
+///
+/// Config files without a version predate the zoom defaults, so keep Z unmapped.
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Default [`Config::mouse_divisor`]
+pub const MOUSE_DIVISOR_DEFAULT: f32 = 32.0;
+
+/// Default [`Config::auto_zero_ms`]
+pub const AUTO_ZERO_MS_DEFAULT: u64 = 200;
+
+/// Input device kinds, selecting how raw axis values are normalised
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, EnumString, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum InputKind {
+    /// Absolute deflection within the (calibrated) axis range
+    SpaceMouse,
+    /// Relative motion (mouse, trackpoint), deltas are treated as velocity and scaled by
+    /// [`Config::mouse_divisor`] without clamping. Devices are grabbed while bound
+    Mouse,
+}
+
+impl Default for InputKind {
+    fn default() -> Self {
+        Self::SpaceMouse
+    }
+}
+
+/// Output aggregation policies, applied where several devices map to the same output
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregate {
+    /// Outputs from all devices are written (relative outputs sum, latched outputs follow the last event)
+    Sum,
+    /// Only the highest priority device with nonzero input is written, by device
+    /// (`vid:pid` or alias) highest first, unlisted devices rank last
+    Priority(Vec<String>),
+    /// Only the device most recently moved from rest is written, until it returns to rest
+    Latest,
+}
+
+impl Default for Aggregate {
+    fn default() -> Self {
+        Self::Sum
+    }
+}
+
+/// Absolute pointer position modes
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, EnumString, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AbsPointerMode {
+    /// Axis value maps directly to position (centre at rest)
+    Direct,
+    /// Axis value sets pointer velocity, position is integrated and clamped to bounds
+    Velocity,
+}
+
+/// Absolute pointer configuration
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AbsPointerConfig {
+    /// Virtual horizontal resolution
+    pub width: i32,
+    /// Virtual vertical resolution
+    pub height: i32,
+    /// Position mode
+    pub mode: AbsPointerMode,
+    /// Velocity mode speed, in screen widths / heights per second at full deflection
+    pub speed: f32,
+    /// Snap back to centre when the axis is in its deadzone, otherwise hold position
+    pub recenter: bool,
+}
+
+impl Default for AbsPointerConfig {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            mode: AbsPointerMode::Direct,
+            speed: 1.0,
+            recenter: true,
+        }
+    }
+}
+
+/// Per-event output value limits (±), values are saturated to these bounds before writing
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct OutputLimits {
+    /// `REL_X` / `REL_Y` limit
+    pub pointer: i32,
+    /// `REL_WHEEL` / `REL_HWHEEL` limit
+    pub wheel: i32,
+    /// `REL_WHEEL_HI_RES` / `REL_HWHEEL_HI_RES` limit, 120 per wheel detent
+    pub wheel_hi_res: i32,
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        Self {
+            pointer: 127,
+            wheel: 8,
+            wheel_hi_res: 120 * 8,
+        }
+    }
+}
+
+impl OutputLimits {
+    /// Fetch the limit for an output event code, `None` for unlimited codes
+    pub fn limit_for(&self, code: &EventCode) -> Option<i32> {
+        match code {
+            EventCode::EV_REL(EV_REL::REL_X | EV_REL::REL_Y) => Some(self.pointer),
+            EventCode::EV_REL(EV_REL::REL_WHEEL | EV_REL::REL_HWHEEL) => Some(self.wheel),
+            EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES | EV_REL::REL_HWHEEL_HI_RES) => Some(self.wheel_hi_res),
+            _ => None,
+        }
+    }
+
+    /// Saturate a value to the limit for its code, returning the value and whether it was clamped
+    pub fn clamp(&self, code: &EventCode, value: i32) -> (i32, bool) {
+        match self.limit_for(code) {
+            Some(l) => {
+                let v = value.clamp(-l, l);
+                (v, v != value)
+            }
+            None => (value, false),
+        }
+    }
+}
+
+impl From<&ConfigFile> for Config {
+    fn from(f: &ConfigFile) -> Self {
+        Self {
+            devices: f.devices.iter()
+                .map(|e| (UsbDevice{ vid: e.vid, pid: e.pid, name: None }, e.axes))
+                .collect(),
+            aliases: f.aliases.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            default: f.default.unwrap_or_else(|| AxisCollection::defaults_for(f.version)),
+            profiles: f.profiles.clone(),
+            active: f.active.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            split_outputs: f.split_outputs,
+            abs_range: f.abs_range,
+            abs_pointer: f.abs_pointer,
+            idle_timeout_s: f.idle_timeout_s,
+            idle_destroy: f.idle_destroy,
+            max_output_hz: f.max_output_hz,
+            passthrough: f.passthrough.clone(),
+            watch_config: f.watch_config,
+            output_limits: f.output_limits,
+            axis_snap: f.axis_snap.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            input_kind: f.input_kind.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            mouse_divisor: f.mouse_divisor,
+            aggregate: f.aggregate.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            auto_zero_ms: f.auto_zero_ms,
+            feedback: f.feedback.iter()
+                .map(|(d, p)| (d.clone(), p.iter().map(|(k, v)| (k.clone(), *v)).collect()))
+                .collect(),
+            version: f.version,
+        }
+    }
+}
+
+/// Config validation errors and warnings, with device / axis context
+#[derive(Clone, PartialEq, Debug)]
+pub enum ConfigError {
+    /// Axis value non-finite or outside valid range (error)
+    InvalidValue { device: String, axis: Axis, reason: String },
+    /// Multiple axes mapped to the same output (warning)
+    DuplicateMapping { device: String, map: Map, axes: Vec<Axis> },
+    /// Active profile does not exist (error)
+    UnknownProfile { device: String, profile: String },
+    /// Profile for a device without a config (warning)
+    UnknownProfileDevice { device: String, profile: String },
+    /// Device with an invalid vid:pid (error)
+    InvalidDevice { device: String },
+    /// Invalid daemon output option (error)
+    InvalidOption { option: String, reason: String },
+    /// Alias name is reserved or a `vid:pid`, or points at an invalid device (error)
+    InvalidAlias { alias: String, reason: String },
+    /// Alias for a device without a config (warning)
+    UnusedAlias { alias: String, device: String },
+}
+
+impl ConfigError {
+    /// Check whether this is a warning rather than a hard error
+    pub fn is_warning(&self) -> bool {
+        matches!(self, ConfigError::DuplicateMapping { .. } | ConfigError::UnknownProfileDevice { .. } | ConfigError::UnusedAlias { .. })
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::InvalidValue { device, axis, reason } => write!(f, "device {} axis {}: {}", device, axis, reason),
+            ConfigError::DuplicateMapping { device, map, axes } => {
+                let axes: Vec<_> = axes.iter().map(|a| a.to_string()).collect();
+                write!(f, "device {}: axes {} all map to {}", device, axes.join(", "), map)
+            }
+            ConfigError::UnknownProfile { device, profile } => write!(f, "device {}: active profile '{}' does not exist", device, profile),
+            ConfigError::UnknownProfileDevice { device, profile } => write!(f, "profile '{}': no config for device {}", profile, device),
+            ConfigError::InvalidDevice { device } => write!(f, "device {}: invalid vid:pid", device),
+            ConfigError::InvalidOption { option, reason } => write!(f, "{}: {}", option, reason),
+            ConfigError::InvalidAlias { alias, reason } => write!(f, "alias '{}': {}", alias, reason),
+            ConfigError::UnusedAlias { alias, device } => write!(f, "alias '{}': no config for device {}", alias, device),
+        }
+    }
 }
 
 impl Config {
-    /// Fetch device config by name (`default` or `pid:vid`)
+    /// Validate config values, returning all errors and warnings found
+    ///
+    /// Use [`ConfigError::is_warning`] to distinguish warnings from hard errors.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = vec![];
+
+        for (device, axes) in self.iter() {
+            // Per-axis values
+            for a in AXIS {
+                if let Err(reason) = axes[*a].validate() {
+                    errors.push(ConfigError::InvalidValue { device: device.clone(), axis: *a, reason });
+                }
+            }
+
+            // Conflicting output mappings
+            for m in MAPPINGS.iter().filter(|m| **m != Map::None) {
+                let mapped: Vec<_> = AXIS.iter().filter(|a| axes[**a].map == *m).copied().collect();
+                if mapped.len() > 1 {
+                    errors.push(ConfigError::DuplicateMapping { device: device.clone(), map: *m, axes: mapped });
+                }
+            }
+        }
+
+        for d in self.devices.keys().filter(|d| d.vid == 0 && d.pid == 0) {
+            errors.push(ConfigError::InvalidDevice { device: d.to_string() });
+        }
+
+        // Aliases
+        let mut aliases: Vec<_> = self.aliases.iter().collect();
+        aliases.sort_by(|a, b| a.0.cmp(b.0));
+        for (alias, d) in aliases {
+            let reason = match alias.as_str() {
+                "" => Some("name is empty".to_string()),
+                "default" => Some("name is reserved".to_string()),
+                a if a.parse::<UsbDevice>().is_ok() => Some("name is a vid:pid".to_string()),
+                _ if d.vid == 0 && d.pid == 0 => Some(format!("invalid device {}", d.to_string())),
+                _ => None,
+            };
+            match reason {
+                Some(reason) => errors.push(ConfigError::InvalidAlias { alias: alias.clone(), reason }),
+                None if !self.has_device(d) => errors.push(ConfigError::UnusedAlias { alias: alias.clone(), device: d.to_string() }),
+                None => (),
+            }
+        }
+
+        // Profiles
+        for p in &self.profiles {
+            if self.get(&p.device).is_none() {
+                errors.push(ConfigError::UnknownProfileDevice { device: p.device.clone(), profile: p.name.clone() });
+            }
+            for a in AXIS {
+                if let Err(reason) = p.axes[*a].validate() {
+                    errors.push(ConfigError::InvalidValue { device: format!("{} (profile '{}')", p.device, p.name), axis: *a, reason });
+                }
+            }
+        }
+        for (device, profile) in &self.active {
+            if self.profile(device, profile).is_none() {
+                errors.push(ConfigError::UnknownProfile { device: device.clone(), profile: profile.clone() });
+            }
+        }
+
+        // Output options
+        if let Some(r) = self.abs_range.filter(|r| *r <= 0) {
+            errors.push(ConfigError::InvalidOption { option: "abs_range".to_string(), reason: format!("{} must be positive", r) });
+        }
+        if self.max_output_hz == Some(0) {
+            errors.push(ConfigError::InvalidOption { option: "max_output_hz".to_string(), reason: "0 must be positive, omit to disable".to_string() });
+        }
+        if self.idle_timeout_s == Some(0) {
+            errors.push(ConfigError::InvalidOption { option: "idle_timeout_s".to_string(), reason: "0 must be positive, omit to disable".to_string() });
+        }
+        for d in self.passthrough.iter().filter(|d| self.resolve(d).parse::<UsbDevice>().is_err()) {
+            errors.push(ConfigError::InvalidOption { option: "passthrough".to_string(), reason: format!("unknown device '{}'", d) });
+        }
+        let mut feedback: Vec<_> = self.feedback.iter().collect();
+        feedback.sort_by(|a, b| a.0.cmp(b.0));
+        for (d, patterns) in feedback {
+            if self.resolve(d).parse::<UsbDevice>().is_err() {
+                errors.push(ConfigError::InvalidOption { option: "feedback".to_string(), reason: format!("unknown device '{}'", d) });
+                continue;
+            }
+            let mut names: Vec<_> = patterns.keys().filter(|p| *p != "default" && self.profile(&self.resolve(d), p).is_none()).collect();
+            names.sort();
+            for p in names {
+                errors.push(ConfigError::InvalidOption { option: format!("feedback.{}", d), reason: format!("unknown profile '{}'", p) });
+            }
+        }
+        let mut snap: Vec<_> = self.axis_snap.iter().collect();
+        snap.sort_by(|a, b| a.0.cmp(b.0));
+        for (d, r) in snap {
+            if self.get(d).is_none() {
+                errors.push(ConfigError::InvalidOption { option: format!("axis_snap.{}", d), reason: "unknown device".to_string() });
+            }
+            if !r.is_finite() || *r <= 1.0 {
+                errors.push(ConfigError::InvalidOption { option: format!("axis_snap.{}", d), reason: format!("ratio {} must be greater than 1.0", r) });
+            }
+        }
+        let mut kinds: Vec<_> = self.input_kind.keys().collect();
+        kinds.sort();
+        for d in kinds.into_iter().filter(|d| self.get(d).is_none()) {
+            errors.push(ConfigError::InvalidOption { option: format!("input_kind.{}", d), reason: "unknown device".to_string() });
+        }
+        let mut aggregate: Vec<_> = self.aggregate.iter().collect();
+        aggregate.sort_by(|a, b| a.0.cmp(b.0));
+        for (m, p) in aggregate {
+            if m != "default" && !matches!(Map::from_str(m), Ok(m) if m != Map::None) {
+                errors.push(ConfigError::InvalidOption { option: format!("aggregate.{}", m), reason: "unknown output".to_string() });
+            }
+            if let Aggregate::Priority(devices) = p {
+                for d in devices.iter().filter(|d| self.resolve(d).parse::<UsbDevice>().is_err()) {
+                    errors.push(ConfigError::InvalidOption { option: format!("aggregate.{}", m), reason: format!("unknown device '{}'", d) });
+                }
+            }
+        }
+        if let Some(v) = self.mouse_divisor.filter(|v| !v.is_finite() || *v <= 0.0) {
+            errors.push(ConfigError::InvalidOption { option: "mouse_divisor".to_string(), reason: format!("{} must be positive", v) });
+        }
+        if let Some(l) = &self.output_limits {
+            for (name, v) in [("pointer", l.pointer), ("wheel", l.wheel), ("wheel_hi_res", l.wheel_hi_res)] {
+                if v <= 0 {
+                    errors.push(ConfigError::InvalidOption { option: format!("output_limits.{}", name), reason: format!("{} must be positive", v) });
+                }
+            }
+        }
+        if let Some(p) = &self.abs_pointer {
+            if p.width < 2 || p.height < 2 {
+                errors.push(ConfigError::InvalidOption { option: "abs_pointer".to_string(), reason: format!("resolution {}x{} too small", p.width, p.height) });
+            }
+            if !p.speed.is_finite() || p.speed <= 0.0 {
+                errors.push(ConfigError::InvalidOption { option: "abs_pointer.speed".to_string(), reason: format!("{} must be positive", p.speed) });
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// Validate config, returning only hard errors
+    pub fn errors(&self) -> Vec<ConfigError> {
+        self.validate().err().unwrap_or_default().into_iter().filter(|e| !e.is_warning()).collect()
+    }
+
+    /// Fetch device config by name (`default`, `pid:vid`, or alias)
     pub fn get(&self, name: &str) -> Option<&AxisCollection<AxisConfig>> {
-        match name {
+        let name = self.resolve(name);
+        match name.as_str() {
             "default" => Some(&self.default),
             _ => self.devices.iter().find(|(n, _v)| n.to_string() == name ).map(|(_n, v)| v ),
         }
     }
 
-    /// Fetch device config by name (`default` or `pid:vid`)
+    /// Fetch device config by name (`default`, `pid:vid`, or alias)
     pub fn get_mut(&mut self, name: &str) -> Option<&mut AxisCollection<AxisConfig>> {
-        match name {
+        let name = self.resolve(name);
+        match name.as_str() {
             "default" => Some(&mut self.default),
             _ => self.devices.iter_mut().find(|(n, _v)| n.to_string() == name ).map(|(_n, v)| v ),
         }
     }
 
+    /// Resolve a device name to `default` or `vid:pid`, exact names take precedence over aliases
+    ///
+    /// Unknown names are returned unchanged.
+    pub fn resolve(&self, name: &str) -> String {
+        if name == "default" {
+            return name.to_string();
+        }
+        if let Ok(d) = name.parse::<UsbDevice>() {
+            return d.to_string();
+        }
+        match self.aliases.get(name) {
+            Some(d) => d.to_string(),
+            None => name.to_string(),
+        }
+    }
+
+    /// Fetch the alias for a device, matched by vid:pid (first by name if there are several)
+    pub fn alias_for(&self, d: &UsbDevice) -> Option<&str> {
+        self.aliases.iter()
+            .filter(|(_a, v)| v.vid == d.vid && v.pid == d.pid)
+            .map(|(a, _v)| a.as_str())
+            .min()
+    }
+
+    /// Device descriptor for display, named by alias where one exists
+    pub fn aliased(&self, d: &UsbDevice) -> UsbDevice {
+        match self.alias_for(d) {
+            Some(a) => UsbDevice { name: Some(a.to_string()), ..d.clone() },
+            None => d.clone(),
+        }
+    }
+
+    /// Check whether a device has its own config, matched by vid:pid
+    pub fn has_device(&self, d: &UsbDevice) -> bool {
+        self.devices.keys().any(|k| k.vid == d.vid && k.pid == d.pid)
+    }
+
+    /// Check whether raw passthrough is enabled for a device, matched by vid:pid
+    pub fn passthrough_for(&self, d: &UsbDevice) -> bool {
+        self.passthrough.iter()
+            .filter_map(|n| self.resolve(n).parse::<UsbDevice>().ok())
+            .any(|p| p.vid == d.vid && p.pid == d.pid)
+    }
+
+    /// Fetch the LED pattern for a device and its active profile, if configured
+    ///
+    /// Devices without their own config follow the `default` active profile.
+    pub fn feedback_for(&self, d: &UsbDevice) -> Option<LedPattern> {
+        let id = d.to_string();
+        let patterns = self.feedback.iter()
+            .find(|(k, _v)| self.resolve(k) == id)
+            .map(|(_k, v)| v)?;
+
+        let key = match self.has_device(d) {
+            true => id.as_str(),
+            false => "default",
+        };
+        let profile = self.active.get(key).map(String::as_str).unwrap_or("default");
+
+        patterns.get(profile).copied()
+    }
+
+    /// Fetch the axis snap ratio for a device, devices without their own config use `default`
+    pub fn axis_snap_for(&self, d: &UsbDevice) -> Option<f32> {
+        let name = match self.has_device(d) {
+            true => d.to_string(),
+            false => "default".to_string(),
+        };
+        self.axis_snap.iter().find(|(k, _v)| self.resolve(k) == name).map(|(_k, v)| *v)
+    }
+
+    /// Fetch the aggregation policy for an output, outputs without their own policy use `default`
+    pub fn aggregate_for(&self, m: Map) -> Option<&Aggregate> {
+        if m == Map::None {
+            return None;
+        }
+        self.aggregate.get(&m.to_string()).or_else(|| self.aggregate.get("default"))
+    }
+
+    /// Rank a device within an [`Aggregate::Priority`] list, lower ranks win
+    pub fn aggregate_rank(&self, devices: &[String], d: &UsbDevice) -> usize {
+        let name = d.to_string();
+        devices.iter().position(|k| self.resolve(k) == name).unwrap_or(devices.len())
+    }
+
+    /// Window without input after which nonzero outputs are zeroed, `None` where disabled
+    pub fn auto_zero_window(&self) -> Option<Duration> {
+        match self.auto_zero_ms.unwrap_or(AUTO_ZERO_MS_DEFAULT) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// Fetch the input kind for a device, devices without their own config use `default`
+    pub fn input_kind_for(&self, d: &UsbDevice) -> InputKind {
+        let name = match self.has_device(d) {
+            true => d.to_string(),
+            false => "default".to_string(),
+        };
+        self.input_kind.iter().find(|(k, _v)| self.resolve(k) == name).map(|(_k, v)| *v).unwrap_or_default()
+    }
+
+    /// Normalise a raw axis value for a device, mouse deltas are divided by
+    /// [`Config::mouse_divisor`] and may exceed 1.0
+    pub fn normalise(&self, d: &UsbDevice, axis: &AxisConfig, v: i32) -> f32 {
+        match self.input_kind_for(d) {
+            InputKind::SpaceMouse => axis.normalise(v),
+            InputKind::Mouse => v as f32 / self.mouse_divisor.unwrap_or(MOUSE_DIVISOR_DEFAULT),
+        }
+    }
+
+    /// Fetch the raw input ranges for a device
+    ///
+    /// Mouse ranges are ±[`Config::mouse_divisor`], larger deltas are clamped for display.
+    pub fn device_range(&self, d: &UsbDevice) -> DeviceRange {
+        if self.input_kind_for(d) == InputKind::Mouse {
+            let div = self.mouse_divisor.unwrap_or(MOUSE_DIVISOR_DEFAULT).round().max(1.0) as i32;
+            return DeviceRange::with_axis(|_a| AxisRange { min: -div, max: div });
+        }
+
+        let axes = self.device(d);
+        DeviceRange::with_axis(|a| axes[a].axis_range())
+    }
+
+    /// Fetch the config for a device, matched by vid:pid, falling back to default
+    pub fn device(&self, d: &UsbDevice) -> &AxisCollection<AxisConfig> {
+        self.devices
+            .iter()
+            .find(|(k, _v)| k.vid == d.vid && k.pid == d.pid)
+            .map(|(_k, v)| v)
+            .unwrap_or(&self.default)
+    }
+
+    /// Fetch a profile by device and profile name
+    pub fn profile(&self, device: &str, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.device == device && p.name == name)
+    }
+
+    /// Iterate through profiles for a device
+    pub fn device_profiles<'a>(&'a self, device: &'a str) -> impl Iterator<Item = &'a Profile> + 'a {
+        self.profiles.iter().filter(move |p| p.device == device)
+    }
+
+    /// Activate a profile, loading the profile axes into the device config
+    pub fn activate(&mut self, device: &str, name: &str) -> Result<(), Error> {
+        let device = &self.resolve(device);
+        let axes = self.profile(device, name)
+            .map(|p| p.axes)
+            .ok_or_else(|| Error::Config(format!("no profile '{}' for device '{}'", name, device)))?;
+
+        let c = self.get_mut(device)
+            .ok_or_else(|| Error::Config(format!("no config for device '{}'", device)))?;
+        *c = axes;
+
+        self.active.insert(device.to_string(), name.to_string());
+
+        Ok(())
+    }
+
+    /// Remove a profile, returning whether it was the active profile for the device
+    pub fn remove_profile(&mut self, device: &str, name: &str) -> bool {
+        self.profiles.retain(|p| !(p.device == device && p.name == name));
+
+        match self.active.get(device) {
+            Some(a) if a == name => {
+                self.active.remove(device);
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Iterate through configurations
     pub fn iter<'a>(&'a self) -> ConfigIter<'a> {
         ConfigIter {
@@ -67,120 +685,1312 @@ impl <'a> Iterator for ConfigIter<'a> {
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct ConfigFile {
+    /// Config version, `0` for files written before versioning
+    #[serde(default)]
+    pub version: u32,
+
+    /// Daemon socket configuration, omitted when unset
+    #[serde(default, skip_serializing_if = "SocketConfig::is_default")]
+    pub socket: SocketConfig,
+
+    /// Per-device axis configuration, written as `[devices."vid:pid"]` tables
+    /// (legacy `[[devices]]` lists with `vid` and `pid` fields are also accepted)
+    #[serde(with = "device_keys::list")]
     pub devices: Vec<DeviceConfig>,
-}
 
-#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
-pub struct DeviceConfig {
-    pub vid: u16,
-    pub pid: u16,
+    /// Axis configuration for devices without their own config, written only when changed
+    /// from the defaults for the config version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<AxisCollection<AxisConfig>>,
 
-    #[serde(flatten)]
-    pub axes: AxisCollection<AxisConfig>,
+    /// Friendly device names, device tables may be keyed by alias (`[devices.spacemouse]`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty", with = "device_keys::alias_list")]
+    pub aliases: BTreeMap<String, UsbDevice>,
+
+    /// Saved axis configuration profiles
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<Profile>,
+
+    /// Active profile by device name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub active: BTreeMap<String, String>,
+
+    /// Route scroll output to a separate "vmouse scroll" virtual device
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub split_outputs: bool,
+
+    /// Joystick axis range (±) for absolute outputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abs_range: Option<i32>,
+
+    /// Absolute pointer options
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abs_pointer: Option<AbsPointerConfig>,
+
+    /// Idle timeout in seconds before output stops
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout_s: Option<u64>,
+
+    /// Destroy virtual output devices while idle
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub idle_destroy: bool,
+
+    /// Maximum output rate in Hz
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_hz: Option<u32>,
+
+    /// Devices mirrored to a "vmouse passthrough" device with raw (untransformed) events
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub passthrough: Vec<String>,
+
+    /// Per-event output value limits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_limits: Option<OutputLimits>,
+
+    /// Dominant axis snap ratio by device
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub axis_snap: BTreeMap<String, f32>,
+
+    /// Input device kind by device (`mouse` or `space_mouse`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub input_kind: BTreeMap<String, InputKind>,
+
+    /// Mouse delta normalised to 1.0 for `mouse` input devices
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mouse_divisor: Option<f32>,
+
+    /// Aggregation policy by output (`sum`, `latest`, or `{ priority = ["vid:pid", ...] }`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aggregate: BTreeMap<String, Aggregate>,
+
+    /// Output watchdog window in milliseconds (`auto_zero_ms = 0` to disable)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_zero_ms: Option<u64>,
+
+    /// Reload the config file on changes (`watch_config = false` to disable)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_config: Option<bool>,
+
+    /// Device LED patterns by profile, written as `[feedback."vid:pid"]` tables
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub feedback: BTreeMap<String, BTreeMap<String, LedPattern>>,
 }
 
+/// Config file formats
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
 
-impl Default for AxisCollection<AxisConfig> {
-    fn default() -> Self {
-        Self {
-            x: AxisConfig {
-                map: Map::H,
-                scale: 0.005,
-                curve: 0.5,
-                deadzone: 0.0,
-            },
-            y: AxisConfig {
-                map: Map::V,
-                scale: 0.005,
-                curve: 0.5,
-                deadzone: 0.0,
-            },
-            z: Default::default(),
-            rx: AxisConfig {
-                map: Map::Y,
-                scale: 0.2,
-                curve: 1.0,
-                deadzone: 0.0,
-            },
-            ry: AxisConfig {
-                map: Map::X,
-                scale: -0.2,
-                curve: 1.0,
-                deadzone: 0.0,
-            },
-            rz: Default::default(),
+impl ConfigFormat {
+    /// Detect format from a file extension, defaults to TOML
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
         }
     }
 }
 
-impl Default for AxisCollection<f32> {
-    fn default() -> Self {
+impl ConfigFile {
+    /// Build a config file from a runtime config, ordered by vid:pid for stable output
+    ///
+    /// Devices with an alias are keyed by alias.
+    pub fn new(config: &Config, socket: SocketConfig) -> Self {
+        let mut devices: Vec<_> = config.devices.iter()
+            .map(|(d, axes)| DeviceConfig{ vid: d.vid, pid: d.pid, alias: config.alias_for(d).map(String::from), axes: *axes })
+            .collect();
+        devices.sort_by_key(|d| (d.vid, d.pid));
+
+        let mut profiles = config.profiles.clone();
+        profiles.sort_by(|a, b| (&a.device, &a.name).cmp(&(&b.device, &b.name)));
+
         Self {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-            rx: 0.0,
-            ry: 0.0,
-            rz: 0.0,
+            version: config.version,
+            socket,
+            devices,
+            default: Some(config.default).filter(|d| *d != AxisCollection::defaults_for(config.version)),
+            aliases: config.aliases.iter().map(|(k, v)| (k.clone(), UsbDevice { name: None, ..v.clone() })).collect(),
+            profiles,
+            active: config.active.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            split_outputs: config.split_outputs,
+            abs_range: config.abs_range,
+            abs_pointer: config.abs_pointer,
+            idle_timeout_s: config.idle_timeout_s,
+            idle_destroy: config.idle_destroy,
+            max_output_hz: config.max_output_hz,
+            passthrough: config.passthrough.clone(),
+            watch_config: config.watch_config,
+            output_limits: config.output_limits,
+            axis_snap: config.axis_snap.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            input_kind: config.input_kind.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            mouse_divisor: config.mouse_divisor,
+            aggregate: config.aggregate.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            auto_zero_ms: config.auto_zero_ms,
+            feedback: config.feedback.iter()
+                .map(|(d, p)| (d.clone(), p.iter().map(|(k, v)| (k.clone(), *v)).collect()))
+                .collect(),
         }
     }
-}
 
+    /// Parse a config file in the provided format
+    pub fn parse(s: &str, format: ConfigFormat) -> Result<Self, Error> {
+        let c = match format {
+            ConfigFormat::Toml => toml::from_str(s).map_err(|e| Error::Config(e.to_string()))?,
+            ConfigFormat::Json => serde_json::from_str(s).map_err(|e| Error::Config(e.to_string()))?,
+        };
+        Self::resolve_aliases(c)
+    }
 
-/// Axis configuration
-#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+    /// Resolve alias keyed device tables to their `vid:pid`
+    pub fn resolve_aliases(mut self) -> Result<Self, Error> {
+        for d in &mut self.devices {
+            let a = match &d.alias {
+                Some(a) => a,
+                None => continue,
+            };
+            let id = self.aliases.get(a)
+                .ok_or_else(|| Error::Config(format!("device key '{}' is not a vid:pid or alias", a)))?;
 
-pub struct AxisConfig {
+            d.vid = id.vid;
+            d.pid = id.pid;
+        }
+        Ok(self)
+    }
+
+    /// Encode a config file in the provided format
+    pub fn encode(&self, format: ConfigFormat) -> Result<String, Error> {
+        let s = match format {
+            // Encoded via `toml::Value` so tables (eg. curves) are emitted after plain values
+            ConfigFormat::Toml => toml::Value::try_from(self)
+                .and_then(|v| toml::to_string_pretty(&v))
+                .map_err(|e| Error::Config(e.to_string()))?,
+            ConfigFormat::Json => serde_json::to_string_pretty(self).map_err(|e| Error::Config(e.to_string()))?,
+        };
+        Ok(s)
+    }
+
+    /// Load a config file, detecting format by extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| Error::ConfigIo(path.to_owned(), e))?;
+
+        Self::parse(&s, ConfigFormat::from_path(path)).map_err(|e| match e {
+            Error::Config(m) => Error::Config(format!("failed to parse '{}': {}", path.display(), m)),
+            e => e,
+        })
+    }
+
+    /// Save a config file atomically in the provided format
+    pub fn save(&self, path: impl AsRef<Path>, format: ConfigFormat) -> Result<(), Error> {
+        let path = path.as_ref();
+        let s = self.encode(format)?;
+        crate::write_atomic(path, s.as_bytes())
+            .map_err(|e| Error::ConfigIo(path.to_owned(), e))?;
+        Ok(())
+    }
+}
+
+/// Daemon socket configuration
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct SocketConfig {
+    /// Socket file mode (eg. `0o660`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+
+    /// Socket owning group (name or gid)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// Additional users permitted to issue privileged commands
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub admin_uids: Vec<u32>,
+
+    /// Additional groups permitted to issue privileged commands
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub admin_gids: Vec<u32>,
+
+    /// Additional directories non-root clients may save configs to, alongside the
+    /// config file directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub config_dirs: Vec<String>,
+
+    /// Maximum raw value events per second sent to each client (defaults to [`RAW_RATE_DEFAULT`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_rate: Option<u32>,
+
+    /// Keepalive interval in seconds for idle listening clients, 0 to disable
+    /// (defaults to [`KEEPALIVE_DEFAULT`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive: Option<u64>,
+}
+
+impl SocketConfig {
+    /// Check whether no socket options are set
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Default maximum raw value events per second per client
+pub const RAW_RATE_DEFAULT: u32 = 1000;
+
+/// Default client keepalive interval in seconds
+pub const KEEPALIVE_DEFAULT: u64 = 30;
+
+/// Named axis configuration profile for a device
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Profile {
+    /// Device name (`default` or `vid:pid`)
+    pub device: String,
+
+    /// Profile name
+    pub name: String,
+
+    pub axes: AxisCollection<AxisConfig>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DeviceConfig {
+    pub vid: u16,
+    pub pid: u16,
+
+    /// Alias used as the device key, unresolved until [`ConfigFile::resolve_aliases`]
+    #[serde(skip)]
+    pub alias: Option<String>,
+
+    #[serde(flatten)]
+    pub axes: AxisCollection<AxisConfig>,
+}
+
+
+impl Default for AxisCollection<AxisConfig> {
+    fn default() -> Self {
+        Self::spacemouse_defaults()
+    }
+}
+
+impl AxisCollection<AxisConfig> {
+    /// Tuned defaults for SpaceMouse style devices, translation moves the pointer and
+    /// tilt scrolls, push / pull (Z) zooms and RZ is disabled
+    pub fn spacemouse_defaults() -> Self {
+        Self {
+            x: AxisConfig {
+                map: Map::H,
+                scale: 0.005,
+                curve: CurveKind::CubicBlend(0.5),
+                deadzone: 0.0,
+                scale_neg: None,
+                deadzone_neg: None,
+                range: None,
+            },
+            y: AxisConfig {
+                map: Map::V,
+                scale: 0.005,
+                curve: CurveKind::CubicBlend(0.5),
+                deadzone: 0.0,
+                scale_neg: None,
+                deadzone_neg: None,
+                range: None,
+            },
+            z: AxisConfig {
+                map: Map::Zoom,
+                scale: 0.003,
+                curve: CurveKind::CubicBlend(0.5),
+                deadzone: 0.1,
+                scale_neg: None,
+                deadzone_neg: None,
+                range: None,
+            },
+            rx: AxisConfig {
+                map: Map::Y,
+                scale: 0.2,
+                curve: CurveKind::CubicBlend(1.0),
+                deadzone: 0.0,
+                scale_neg: None,
+                deadzone_neg: None,
+                range: None,
+            },
+            ry: AxisConfig {
+                map: Map::X,
+                scale: -0.2,
+                curve: CurveKind::CubicBlend(1.0),
+                deadzone: 0.0,
+                scale_neg: None,
+                deadzone_neg: None,
+                range: None,
+            },
+            rz: AxisConfig::disabled(),
+        }
+    }
+
+    /// Defaults for a config version, Z is disabled for configs predating [`CONFIG_VERSION`] 1
+    pub fn defaults_for(version: u32) -> Self {
+        match version {
+            0 => Self { z: AxisConfig::disabled(), ..Self::spacemouse_defaults() },
+            _ => Self::spacemouse_defaults(),
+        }
+    }
+}
+
+impl Default for AxisCollection<f32> {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            rx: 0.0,
+            ry: 0.0,
+            rz: 0.0,
+        }
+    }
+}
+
+impl Default for AxisCollection<(i32, i32)> {
+    fn default() -> Self {
+        Self {
+            x: (0, 0),
+            y: (0, 0),
+            z: (0, 0),
+            rx: (0, 0),
+            ry: (0, 0),
+            rz: (0, 0),
+        }
+    }
+}
+
+
+/// Valid axis curve range
+pub const CURVE_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+
+/// Maximum axis deadzone, larger values leave too little travel for a usable response
+pub const DEADZONE_MAX: f32 = 0.95;
+
+/// Valid axis deadzone range
+pub const DEADZONE_RANGE: RangeInclusive<f32> = 0.0..=DEADZONE_MAX;
+
+/// Valid axis scale range
+pub const SCALE_RANGE: RangeInclusive<f32> = -10.0..=10.0;
+
+/// Axis scale value, finite and within [`SCALE_RANGE`]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ScaleValue(f32);
+
+impl ScaleValue {
+    /// Check a scale value is finite and within [`SCALE_RANGE`]
+    pub fn new(v: f32) -> Result<Self, ScaleParseError> {
+        match v.is_finite() && SCALE_RANGE.contains(&v) {
+            true => Ok(Self(v)),
+            false => Err(ScaleParseError::OutOfRange(v)),
+        }
+    }
+
+    /// Fetch the scale value
+    pub fn value(&self) -> f32 {
+        self.0
+    }
+}
+
+impl FromStr for ScaleValue {
+    type Err = ScaleParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let v = s.trim().parse::<f32>().map_err(|_| ScaleParseError::NotNumeric(s.to_string()))?;
+        Self::new(v)
+    }
+}
+
+/// Errors parsing a [`ScaleValue`]
+#[derive(Clone, PartialEq, Debug)]
+pub enum ScaleParseError {
+    /// Value is not a number
+    NotNumeric(String),
+    /// Value is non-finite or outside [`SCALE_RANGE`]
+    OutOfRange(f32),
+}
+
+impl std::fmt::Display for ScaleParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScaleParseError::NotNumeric(s) => write!(f, "non-numeric scale '{}'", s),
+            ScaleParseError::OutOfRange(v) => write!(f, "scale {} outside valid range {:?}", v, SCALE_RANGE),
+        }
+    }
+}
+
+impl std::error::Error for ScaleParseError {}
+
+/// Axis configuration
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+
+pub struct AxisConfig {
     /// Output axis mapping
+    #[serde(deserialize_with = "Map::deserialize_compat")]
     pub map: Map,
 
-    /// Output axis sensitivity curve (0.0=x 1.0=x^3)
-    pub curve: f32,
+    /// Output axis sensitivity curve
+    #[serde(default, deserialize_with = "CurveKind::deserialize_compat")]
+    #[schemars(with = "CurveKind")]
+    pub curve: CurveKind,
 
     /// Output axis scaling factor
+    #[schemars(schema_with = "crate::schema::scale")]
     pub scale: f32,
 
     /// Output axis deadzone
+    #[schemars(schema_with = "crate::schema::deadzone")]
     pub deadzone: f32,
+
+    /// Output axis scaling factor for negative inputs, defaults to `scale`
+    #[serde(default)]
+    #[schemars(schema_with = "crate::schema::scale_opt")]
+    pub scale_neg: Option<f32>,
+
+    /// Output axis deadzone for negative inputs, defaults to `deadzone`
+    #[serde(default)]
+    #[schemars(schema_with = "crate::schema::deadzone_opt")]
+    pub deadzone_neg: Option<f32>,
+
+    /// Calibrated raw input range (min, max), defaults to [`AXIS_RANGE`]
+    #[serde(default)]
+    pub range: Option<(i32, i32)>,
 }
 
 impl Default for AxisConfig {
     fn default() -> Self {
+        Self::disabled()
+    }
+}
+
+impl AxisConfig {
+    /// Disabled axis, unmapped with zero scale
+    pub const fn disabled() -> Self {
         Self {
-            scale: 0.5,
             map: Map::None,
-            curve: 0.0,
+            curve: CurveKind::CubicBlend(0.0),
+            scale: 0.0,
             deadzone: 0.0,
+            scale_neg: None,
+            deadzone_neg: None,
+            range: None,
         }
     }
-}
 
-impl AxisConfig {
+    /// Raw input range, calibrated where available
+    pub fn axis_range(&self) -> AxisRange {
+        self.range.map(AxisRange::from).unwrap_or(AXIS_RANGE)
+    }
+
+    /// Normalise a raw axis value to -1.0 to 1.0 using the calibrated range where available
+    pub fn normalise(&self, v: i32) -> f32 {
+        self.axis_range().normalise(v)
+    }
+
+    /// Deadzone applied to an input of the provided sign
+    pub fn deadzone_for(&self, r: f32) -> f32 {
+        match r < 0.0 {
+            true => self.deadzone_neg.unwrap_or(self.deadzone),
+            false => self.deadzone,
+        }
+    }
+
+    /// Scale applied to an input of the provided sign
+    pub fn scale_for(&self, r: f32) -> f32 {
+        match r < 0.0 {
+            true => self.scale_neg.unwrap_or(self.scale),
+            false => self.scale,
+        }
+    }
+
+    /// Check whether negative deadzone or scale overrides are set
+    pub fn is_asymmetric(&self) -> bool {
+        self.scale_neg.is_some() || self.deadzone_neg.is_some()
+    }
+
+    /// Check axis config values are finite and within valid ranges
+    pub fn validate(&self) -> Result<(), String> {
+        let deadzones = [("deadzone", Some(self.deadzone)), ("deadzone_neg", self.deadzone_neg)];
+        for (n, v) in deadzones.iter().filter_map(|(n, v)| v.map(|v| (n, v))) {
+            if !DEADZONE_RANGE.contains(&v) {
+                return Err(format!("{} {} outside valid range {:?}", n, v, DEADZONE_RANGE));
+            }
+        }
+
+        let scales = [("scale", Some(self.scale)), ("scale_neg", self.scale_neg)];
+        for (n, v) in scales.iter().filter_map(|(n, v)| v.map(|v| (n, v))) {
+            if ScaleValue::new(v).is_err() {
+                return Err(format!("{} {} outside valid range {:?}", n, v, SCALE_RANGE));
+            }
+        }
+
+        match self.curve {
+            CurveKind::Table(t) if t.points().iter().any(|p| !p.0.is_finite() || !p.1.is_finite()) => {
+                return Err("curve table contains non-finite points".to_string());
+            }
+            c => match c.param() {
+                Some(p) if !c.range().contains(&p) => {
+                    return Err(format!("curve {} outside valid range {:?}", p, c.range()));
+                }
+                _ => (),
+            },
+        }
+
+        Ok(())
+    }
+
     /// Apply transformation to raw (-1.0 to 1.0) axis value
+    ///
+    /// Invalid configs are guarded against, non-finite inputs or outputs are mapped to 0.0
+    /// and deadzones are clamped to [`DEADZONE_MAX`].
     pub fn transform(&self, mut r: f32) -> f32 {
+        if !r.is_finite() {
+            return 0.0;
+        }
+
+        let deadzone = match self.deadzone_for(r) {
+            d if d.is_finite() => d.clamp(0.0, DEADZONE_MAX),
+            _ => 0.0,
+        };
+        let scale = self.scale_for(r);
+
         // Apply deadzones if available
         if r > 0.0 {
-            if r < self.deadzone {
+            if r < deadzone {
                 r = 0.0;
             } else {
-                r = (r - self.deadzone) / (1.0 - self.deadzone);
+                r = (r - deadzone) / (1.0 - deadzone);
             }
         } else if r < 0.0 {
-            if r > -self.deadzone {
+            if r > -deadzone {
                 r = 0.0;
             } else {
-                r = (r + self.deadzone) / (1.0 - self.deadzone);
+                r = (r + deadzone) / (1.0 - deadzone);
             }
         }
 
         // Apply curve / scalar equation if available
         // https://www.chiefdelphi.com/t/paper-joystick-sensitivity-gain-adjustment/107280
-        r = self.curve * r.powi(3) + (1.0 - self.curve) * r;
+        r = self.curve.apply(r);
 
         // Apply scaling if available
-        r *= self.scale;
+        r *= scale;
 
-        r
+        match r.is_finite() {
+            true => r,
+            false => 0.0,
+        }
     }
 }
 
+/// Serde helpers for device maps keyed by `vid:pid` strings
+mod device_keys {
+    use std::collections::{BTreeMap, HashMap};
+    use std::fmt;
+
+    use serde::de::{self, MapAccess, SeqAccess, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{AxisConfig, DeviceConfig};
+    use crate::{AxisCollection, UsbDevice};
+
+    type Axes = AxisCollection<AxisConfig>;
+
+    /// Visitor accepting `vid:pid` (or alias) keyed maps or legacy [`DeviceConfig`] lists
+    struct DevicesVisitor;
+
+    impl<'de> Visitor<'de> for DevicesVisitor {
+        type Value = Vec<DeviceConfig>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a map of `vid:pid` or alias device keys or a list of devices")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut m: A) -> Result<Self::Value, A::Error> {
+            let mut devices = vec![];
+
+            while let Some((k, axes)) = m.next_entry::<String, Axes>()? {
+                let d = match k.parse::<UsbDevice>() {
+                    Ok(d) => DeviceConfig{ vid: d.vid, pid: d.pid, alias: None, axes },
+                    // Other keys are aliases, resolved once the alias table is available
+                    Err(_) if !k.contains(':') => DeviceConfig{ vid: 0, pid: 0, alias: Some(k), axes },
+                    Err(e) => return Err(de::Error::custom(format!("invalid device key '{}': {}", k, e))),
+                };
+
+                devices.push(d);
+            }
+
+            Ok(devices)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut s: A) -> Result<Self::Value, A::Error> {
+            let mut devices = vec![];
+
+            while let Some(d) = s.next_element::<DeviceConfig>()? {
+                devices.push(d);
+            }
+
+            Ok(devices)
+        }
+    }
+
+    fn to_keyed<'a>(devices: impl Iterator<Item = (UsbDevice, &'a Axes)>) -> BTreeMap<String, &'a Axes> {
+        devices.map(|(d, a)| (d.to_string(), a)).collect()
+    }
+
+    /// Parse alias values, `vid:pid` strings
+    fn parse_aliases<E: de::Error>(aliases: BTreeMap<String, String>) -> Result<Vec<(String, UsbDevice)>, E> {
+        aliases.into_iter()
+            .map(|(k, v)| match v.parse::<UsbDevice>() {
+                Ok(d) => Ok((k, d)),
+                Err(e) => Err(E::custom(format!("invalid device '{}' for alias '{}': {}", v, k, e))),
+            })
+            .collect()
+    }
+
+    /// [`super::ConfigFile`] device lists, keyed by alias if set, otherwise `vid:pid`
+    pub mod list {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(devices: &[DeviceConfig], s: S) -> Result<S::Ok, S::Error> {
+            let keyed: BTreeMap<_, _> = devices.iter()
+                .map(|d| match &d.alias {
+                    Some(a) => (a.clone(), &d.axes),
+                    None => (UsbDevice{ vid: d.vid, pid: d.pid, name: None }.to_string(), &d.axes),
+                })
+                .collect();
+            keyed.serialize(s)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<DeviceConfig>, D::Error> {
+            d.deserialize_any(DevicesVisitor)
+        }
+    }
+
+    /// [`super::Config`] device maps, keyed by `vid:pid` in human readable formats
+    ///
+    /// Non-self-describing formats (eg. bincode) use the native map to preserve device names.
+    pub mod map {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(devices: &HashMap<UsbDevice, Axes>, s: S) -> Result<S::Ok, S::Error> {
+            match s.is_human_readable() {
+                true => to_keyed(devices.iter().map(|(d, a)| (d.clone(), a))).serialize(s),
+                false => devices.serialize(s),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<HashMap<UsbDevice, Axes>, D::Error> {
+            if !d.is_human_readable() {
+                return HashMap::deserialize(d);
+            }
+
+            let devices = d.deserialize_any(DevicesVisitor)?;
+
+            devices.into_iter()
+                .map(|d| match d.alias {
+                    Some(a) => Err(de::Error::custom(format!("invalid device key '{}', aliases are only supported in config files", a))),
+                    None => Ok((UsbDevice{ vid: d.vid, pid: d.pid, name: None }, d.axes)),
+                })
+                .collect()
+        }
+    }
+
+    /// [`super::ConfigFile`] aliases, values written as `vid:pid`
+    pub mod alias_list {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(aliases: &BTreeMap<String, UsbDevice>, s: S) -> Result<S::Ok, S::Error> {
+            let keyed: BTreeMap<_, _> = aliases.iter().map(|(k, v)| (k, v.to_string())).collect();
+            keyed.serialize(s)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<BTreeMap<String, UsbDevice>, D::Error> {
+            Ok(parse_aliases(BTreeMap::deserialize(d)?)?.into_iter().collect())
+        }
+    }
+
+    /// [`super::Config`] aliases, values written as `vid:pid` in human readable formats
+    pub mod aliases {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(aliases: &HashMap<String, UsbDevice>, s: S) -> Result<S::Ok, S::Error> {
+            match s.is_human_readable() {
+                true => aliases.iter().map(|(k, v)| (k, v.to_string())).collect::<BTreeMap<_, _>>().serialize(s),
+                false => aliases.serialize(s),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<HashMap<String, UsbDevice>, D::Error> {
+            if !d.is_human_readable() {
+                return HashMap::deserialize(d);
+            }
+
+            Ok(parse_aliases(BTreeMap::deserialize(d)?)?.into_iter().collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev_rs::enums::{EV_ABS, EV_KEY};
+
+    use super::*;
+
+    /// Deterministic xorshift generator for property tests
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        /// Uniform value within a range
+        fn range(&mut self, r: RangeInclusive<f32>) -> f32 {
+            r.start() + (self.next() as f32 / u32::MAX as f32) * (r.end() - r.start())
+        }
+
+        /// Finite value across the full f32 range, including subnormals
+        fn finite(&mut self) -> f32 {
+            loop {
+                let v = f32::from_bits(self.next());
+                if v.is_finite() {
+                    return v;
+                }
+            }
+        }
+    }
+
+    /// Random axis config within the accepted ranges
+    fn axis_config(rng: &mut Rng) -> AxisConfig {
+        let curve = match rng.next() % 3 {
+            0 => CurveKind::CubicBlend(rng.range(CURVE_RANGE)),
+            1 => CurveKind::Power(rng.range(crate::POWER_RANGE)),
+            _ => {
+                let n = rng.next() as usize % crate::CURVE_TABLE_LEN + 1;
+                CurveKind::Table((0..n).map(|_| (rng.range(0.0..=1.0), rng.range(-2.0..=2.0))).collect::<Vec<_>>().into())
+            }
+        };
+        let opt = |rng: &mut Rng, v: f32| (rng.next() % 2 == 0).then_some(v);
+
+        let c = AxisConfig {
+            map: Map::X,
+            curve,
+            scale: rng.range(SCALE_RANGE),
+            deadzone: rng.range(DEADZONE_RANGE),
+            scale_neg: { let v = rng.range(SCALE_RANGE); opt(rng, v) },
+            deadzone_neg: { let v = rng.range(DEADZONE_RANGE); opt(rng, v) },
+            range: None,
+        };
+        assert_eq!(c.validate(), Ok(()), "{:?}", c);
+
+        c
+    }
+
+    #[test]
+    fn transform_is_finite() {
+        let mut rng = Rng(0x5eed_1234);
+        let edges = [0.0, -0.0, 1.0, -1.0, f32::MIN_POSITIVE, -f32::MIN_POSITIVE, 1e-45, f32::MAX, f32::MIN, DEADZONE_MAX, 1.0 - f32::EPSILON];
+
+        for _ in 0..1000 {
+            let c = axis_config(&mut rng);
+
+            let mut inputs = edges.to_vec();
+            inputs.extend((0..100).map(|_| rng.range(-1.0..=1.0)));
+            inputs.extend((0..100).map(|_| rng.finite()));
+
+            for r in inputs {
+                let v = c.transform(r);
+                assert!(v.is_finite(), "transform({}) = {} for {:?}", r, v, c);
+            }
+        }
+    }
+
+    #[test]
+    fn transform_guards_invalid_config() {
+        let c = AxisConfig { map: Map::X, scale: 1.0, deadzone: 1.0, ..AxisConfig::disabled() };
+        assert!(c.validate().is_err());
+
+        for r in [-1.0, -0.5, 0.0, 0.5, 1.0, f32::NAN, f32::INFINITY] {
+            assert!(c.transform(r).is_finite(), "transform({})", r);
+        }
+
+        let c = AxisConfig { map: Map::X, scale: f32::INFINITY, ..AxisConfig::disabled() };
+        assert!(c.validate().is_err());
+        assert_eq!(c.transform(0.5), 0.0);
+    }
+
+    /// Linear axis with asymmetric deadzone and scale
+    fn asymmetric() -> AxisConfig {
+        AxisConfig { map: Map::X, scale: 1.0, deadzone: 0.1, scale_neg: Some(2.0), deadzone_neg: Some(0.2), ..AxisConfig::disabled() }
+    }
+
+    #[test]
+    fn transform_deadzone_boundaries() {
+        let c = asymmetric();
+
+        // Inputs within and at the deadzone are zeroed on both sides
+        for r in [0.0, 0.05, 0.0999, 0.1, -0.1, -0.15, -0.1999, -0.2] {
+            assert_eq!(c.transform(r), 0.0, "transform({})", r);
+        }
+
+        // Inputs just beyond the deadzone produce small outputs of the same sign
+        let v = c.transform(0.1001);
+        assert!(v > 0.0 && v < 1e-3, "{}", v);
+        let v = c.transform(-0.2001);
+        assert!(v < 0.0 && v > -1e-3, "{}", v);
+
+        // Full deflection reaches the per-direction scale
+        assert_eq!(c.transform(1.0), 1.0);
+        assert_eq!(c.transform(-1.0), -2.0);
+    }
+
+    #[test]
+    fn transform_continuous_at_zero() {
+        for c in [asymmetric(), AxisConfig { deadzone: 0.0, deadzone_neg: None, ..asymmetric() }] {
+            assert_eq!(c.transform(0.0), 0.0);
+
+            // Outputs approach zero from either side, without a step at the deadzone edges
+            for step in [1e-2, 1e-3, 1e-4] {
+                let pos = c.transform(c.deadzone_for(1.0) + step);
+                let neg = c.transform(-c.deadzone_for(-1.0) - step);
+
+                assert!(pos > 0.0 && pos <= 2.0 * step, "{:?} +{} = {}", c, step, pos);
+                assert!(neg < 0.0 && neg >= -4.0 * step, "{:?} -{} = {}", c, step, neg);
+            }
+        }
+    }
+
+    #[test]
+    fn transform_symmetric_fallback() {
+        let c = AxisConfig { scale_neg: None, deadzone_neg: None, ..asymmetric() };
+
+        for r in [0.05, 0.1, 0.3, 0.7, 1.0] {
+            assert_eq!(c.transform(-r), -c.transform(r), "transform({})", r);
+        }
+    }
+
+    #[test]
+    fn scale_value_parse() {
+        for (s, v) in [("1", 1.0), ("-2.5", -2.5), (" 0.125 ", 0.125), ("10", 10.0), ("-10.0", -10.0), ("1e-3", 0.001)] {
+            assert_eq!(s.parse::<ScaleValue>().map(|v| v.value()), Ok(v), "{}", s);
+        }
+
+        for s in ["", "abc", "1.0x", "0x10", "--1"] {
+            assert_eq!(s.parse::<ScaleValue>(), Err(ScaleParseError::NotNumeric(s.to_string())));
+        }
+    }
+
+    #[test]
+    fn scale_value_range() {
+        assert!(ScaleValue::new(*SCALE_RANGE.start()).is_ok());
+        assert!(ScaleValue::new(*SCALE_RANGE.end()).is_ok());
+
+        for v in [10.001, -10.001, 1e9, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_eq!(ScaleValue::new(v), Err(ScaleParseError::OutOfRange(v)));
+        }
+        assert!(matches!("NaN".parse::<ScaleValue>(), Err(ScaleParseError::OutOfRange(v)) if v.is_nan()));
+        assert!(matches!("inf".parse::<ScaleValue>(), Err(ScaleParseError::OutOfRange(_))));
+    }
+
+    /// Devices keyed by `vid:pid`
+    const KEYED_TOML: &str = r#"
+[devices."256f:c635".x]
+map = "X"
+scale = 2.0
+deadzone = 0.1
+
+[devices."046d:c626".rz]
+map = "V"
+scale = -1.0
+deadzone = 0.0
+"#;
+
+    /// Legacy device list with `vid` and `pid` fields
+    const LEGACY_TOML: &str = r#"
+[[devices]]
+vid = 9583
+pid = 50741
+
+[devices.x]
+map = "X"
+scale = 2.0
+deadzone = 0.1
+
+[[devices]]
+vid = 1133
+pid = 50726
+
+[devices.rz]
+map = "V"
+scale = -1.0
+deadzone = 0.0
+"#;
+
+    const KEYED_JSON: &str = r#"{"devices": {
+        "256f:c635": {"x": {"map": "X", "scale": 2.0, "deadzone": 0.1}},
+        "046d:c626": {"rz": {"map": "V", "scale": -1.0, "deadzone": 0.0}}
+    }}"#;
+
+    const LEGACY_JSON: &str = r#"{"devices": [
+        {"vid": 9583, "pid": 50741, "x": {"map": "X", "scale": 2.0, "deadzone": 0.1}},
+        {"vid": 1133, "pid": 50726, "rz": {"map": "V", "scale": -1.0, "deadzone": 0.0}}
+    ]}"#;
+
+    /// Devices described by the test configs
+    fn test_devices() -> HashMap<UsbDevice, AxisCollection<AxisConfig>> {
+        let mut a = AxisCollection::with_axis(|_| AxisConfig::disabled());
+        a[Axis::X] = AxisConfig { map: Map::X, scale: 2.0, deadzone: 0.1, ..AxisConfig::disabled() };
+
+        let mut b = AxisCollection::with_axis(|_| AxisConfig::disabled());
+        b[Axis::RZ] = AxisConfig { map: Map::V, scale: -1.0, ..AxisConfig::disabled() };
+
+        HashMap::from([
+            (UsbDevice { vid: 0x256f, pid: 0xc635, name: None }, a),
+            (UsbDevice { vid: 0x046d, pid: 0xc626, name: None }, b),
+        ])
+    }
+
+    #[test]
+    fn device_keys_accept_both_representations() {
+        for (s, format) in [(KEYED_TOML, ConfigFormat::Toml), (LEGACY_TOML, ConfigFormat::Toml), (KEYED_JSON, ConfigFormat::Json), (LEGACY_JSON, ConfigFormat::Json)] {
+            let f = ConfigFile::parse(s, format).unwrap_or_else(|e| panic!("{}: {}", e, s));
+            assert_eq!(Config::from(&f).devices, test_devices(), "{}", s);
+        }
+
+        // Runtime configs (eg. from D-Bus clients) accept both in JSON
+        for s in [KEYED_JSON, LEGACY_JSON] {
+            assert_eq!(serde_json::from_str::<Config>(s).unwrap().devices, test_devices(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn device_keys_written_as_vid_pid() {
+        let c = Config { devices: test_devices(), ..Default::default() };
+        let s = ConfigFile::new(&c, SocketConfig::default()).encode(ConfigFormat::Toml).unwrap();
+
+        assert!(s.contains(r#"[devices."256f:c635".x]"#), "{}", s);
+        assert!(s.contains(r#"[devices."046d:c626".rz]"#), "{}", s);
+        assert!(!s.contains("vid"), "{}", s);
+    }
+
+    #[test]
+    fn device_keys_reject_invalid() {
+        let e = ConfigFile::parse(r#"{"devices": {"zz:c635": {}}}"#, ConfigFormat::Json).unwrap_err();
+        assert!(e.to_string().contains("'zz:c635'"), "{}", e);
+
+        let e = ConfigFile::parse("[devices.nope]\n", ConfigFormat::Toml).unwrap_err();
+        assert!(e.to_string().contains("'nope'"), "{}", e);
+
+        let e = serde_json::from_str::<Config>(r#"{"devices": {"256f:10000": {}}}"#).unwrap_err();
+        assert!(e.to_string().contains("'256f:10000'"), "{}", e);
+    }
+
+    #[test]
+    fn config_file_round_trip() {
+        let mut c = Config::default();
+
+        let mut a = AxisCollection::with_axis(|_| AxisConfig::disabled());
+        a[Axis::X] = AxisConfig { map: Map::X, scale: 2.0, deadzone: 0.1, ..AxisConfig::disabled() };
+        a[Axis::RY] = AxisConfig { map: Map::V, scale: -0.5, deadzone: 0.05, scale_neg: Some(-0.25), deadzone_neg: Some(0.2), ..AxisConfig::disabled() };
+
+        let d = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+        c.devices.insert(d.clone(), a);
+        c.devices.insert(UsbDevice { vid: 0x046d, pid: 0xc626, name: None }, c.default);
+        c.devices.insert(UsbDevice { vid: 0x046d, pid: 0xc62b, name: None }, AxisCollection::with_axis(|_| AxisConfig::disabled()));
+        c.aliases.insert("spacemouse".to_string(), d);
+
+        for format in [ConfigFormat::Toml, ConfigFormat::Json] {
+            let s = ConfigFile::new(&c, SocketConfig::default()).encode(format).unwrap();
+            let f = ConfigFile::parse(&s, format).unwrap_or_else(|e| panic!("{}: {}", e, s));
+
+            assert_eq!(f.devices.len(), 3, "{}", s);
+            assert_eq!(Config::from(&f), c, "{}", s);
+        }
+    }
+
+    #[test]
+    fn socket_section_omitted_when_unset() {
+        let c = Config::default();
+
+        for format in [ConfigFormat::Toml, ConfigFormat::Json] {
+            let s = ConfigFile::new(&c, SocketConfig::default()).encode(format).unwrap();
+            assert!(!s.contains("socket"), "{}", s);
+            assert_eq!(ConfigFile::parse(&s, format).unwrap().socket, SocketConfig::default());
+
+            let socket = SocketConfig { mode: Some(0o660), ..Default::default() };
+            let s = ConfigFile::new(&c, socket.clone()).encode(format).unwrap();
+            assert_eq!(ConfigFile::parse(&s, format).unwrap().socket, socket, "{}", s);
+        }
+    }
+
+    const DEVICE: UsbDevice = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+    /// Config with a single device using the default axes
+    fn device_config() -> Config {
+        let mut c = Config::default();
+        c.devices.insert(DEVICE, c.default);
+        c
+    }
+
+    #[test]
+    fn validate_accepts_defaults() {
+        assert_eq!(Config::default().validate(), Ok(()));
+        assert_eq!(device_config().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_axis_values() {
+        let invalid: &[fn(&mut AxisConfig)] = &[
+            |a| a.deadzone = 1.5,
+            |a| a.deadzone_neg = Some(-0.1),
+            |a| a.scale = f32::NAN,
+            |a| a.scale_neg = Some(f32::INFINITY),
+            |a| a.curve = CurveKind::CubicBlend(2.0),
+            |a| a.curve = CurveKind::Power(0.5),
+        ];
+
+        for f in invalid {
+            let mut c = device_config();
+            f(&mut c.devices.get_mut(&DEVICE).unwrap()[Axis::RY]);
+
+            match c.errors().as_slice() {
+                [ConfigError::InvalidValue { device, axis: Axis::RY, .. }] => assert_eq!(device, &DEVICE.to_string()),
+                e => panic!("unexpected errors: {:?} for {:?}", e, c.devices[&DEVICE][Axis::RY]),
+            }
+        }
+    }
+
+    #[test]
+    fn validate_warns_duplicate_mapping() {
+        let mut c = Config::default();
+        c.default[Axis::RX].map = c.default[Axis::X].map;
+
+        let e = c.validate().unwrap_err();
+        assert!(matches!(e.as_slice(), [ConfigError::DuplicateMapping { axes, .. }] if axes == &[Axis::X, Axis::RX]), "{:?}", e);
+        assert!(e[0].is_warning());
+        assert!(c.errors().is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_zero_device() {
+        let mut c = Config::default();
+        c.devices.insert(UsbDevice { vid: 0, pid: 0, name: None }, c.default);
+
+        assert!(matches!(c.errors().as_slice(), [ConfigError::InvalidDevice { device }] if device == "0000:0000"));
+    }
+
+    #[test]
+    fn validate_profiles() {
+        let profile = |device: &str, name: &str| Profile { device: device.to_string(), name: name.to_string(), axes: AxisCollection::default() };
+
+        // Profiles for devices without a config are warnings
+        let mut c = Config::default();
+        c.profiles.push(profile("046d:c626", "cad"));
+        let e = c.validate().unwrap_err();
+        assert!(matches!(e.as_slice(), [ConfigError::UnknownProfileDevice { .. }]), "{:?}", e);
+        assert!(c.errors().is_empty());
+
+        // Invalid profile values
+        let mut c = device_config();
+        let mut p = profile(&DEVICE.to_string(), "cad");
+        p.axes[Axis::Z].scale = f32::NAN;
+        c.profiles.push(p);
+        assert!(matches!(c.errors().as_slice(), [ConfigError::InvalidValue { axis: Axis::Z, .. }]), "{:?}", c.errors());
+
+        // Missing active profiles
+        let mut c = device_config();
+        c.active.insert(DEVICE.to_string(), "missing".to_string());
+        assert!(matches!(c.errors().as_slice(), [ConfigError::UnknownProfile { profile, .. }] if profile == "missing"));
+    }
+
+    #[test]
+    fn alias_resolution() {
+        let other = UsbDevice { vid: 0x046d, pid: 0xc626, name: None };
+
+        let mut c = device_config();
+        c.devices.insert(other.clone(), AxisCollection::spacemouse_defaults());
+        c.devices.get_mut(&other).unwrap()[Axis::X].scale = 0.25;
+        c.aliases.insert("spacemouse".to_string(), DEVICE);
+        c.aliases.insert("navigator".to_string(), other.clone());
+
+        // Ids and aliases resolve to vid:pid, unknown names are unchanged
+        assert_eq!(c.resolve("spacemouse"), "256f:c635");
+        assert_eq!(c.resolve("256F:C635"), "256f:c635");
+        assert_eq!(c.resolve("default"), "default");
+        assert_eq!(c.resolve("unknown"), "unknown");
+
+        assert_eq!(c.get("navigator").map(|a| a[Axis::X].scale), Some(0.25));
+        assert_eq!(c.get("spacemouse"), c.get("256f:c635"));
+        c.get_mut("navigator").unwrap()[Axis::Y].scale = 0.5;
+        assert_eq!(c.devices[&other][Axis::Y].scale, 0.5);
+        assert!(c.get("unknown").is_none());
+
+        // Exact names take precedence over aliases
+        c.aliases.insert("default".to_string(), other.clone());
+        assert_eq!(c.get("default"), Some(&c.default));
+
+        // Devices are reported by alias, the first by name where there are several
+        c.aliases.insert("compact".to_string(), DEVICE);
+        let named = UsbDevice { name: Some("SpaceMouse Compact".to_string()), ..DEVICE };
+        assert_eq!(c.alias_for(&named), Some("compact"));
+        assert_eq!(c.aliased(&named).name.as_deref(), Some("compact"));
+        assert_eq!(c.aliased(&UsbDevice { vid: 0x1234, pid: 0x5678, name: None }).name, None);
+    }
+
+    #[test]
+    fn axis_defaults() {
+        // Default and new axes do nothing
+        assert_eq!(AxisConfig::default(), AxisConfig::disabled());
+        assert_eq!((AxisConfig::default().map, AxisConfig::default().scale), (Map::None, 0.0));
+
+        // Default collections use the tuned SpaceMouse defaults, Z zooms and RZ is disabled
+        let d = AxisCollection::<AxisConfig>::default();
+        assert_eq!(d, AxisCollection::spacemouse_defaults());
+        assert_eq!(Config::default().default, d);
+        assert_eq!([d[Axis::X].map, d[Axis::Y].map, d[Axis::Z].map, d[Axis::RX].map, d[Axis::RY].map], [Map::H, Map::V, Map::Zoom, Map::Y, Map::X]);
+        assert_eq!(d[Axis::RZ], AxisConfig::disabled());
+
+        // Configs predating zoom leave Z disabled
+        assert_eq!(AxisCollection::defaults_for(0)[Axis::Z], AxisConfig::disabled());
+        assert_eq!(AxisCollection::defaults_for(CONFIG_VERSION), d);
+
+        // Unmapped axes have no scale
+        for v in [0, CONFIG_VERSION] {
+            let d = AxisCollection::defaults_for(v);
+            for a in AXIS.iter().filter(|a| d[**a].map == Map::None) {
+                assert_eq!(d[*a].scale, 0.0, "{}", a);
+            }
+        }
+    }
+
+    #[test]
+    fn passthrough_by_id_or_alias() {
+        let named = UsbDevice { name: Some("SpaceMouse Compact".to_string()), ..DEVICE };
+        let other = UsbDevice { vid: 0x046d, pid: 0xc626, name: None };
+
+        let mut c = device_config();
+        assert!(!c.passthrough_for(&DEVICE));
+
+        c.passthrough.push(DEVICE.to_string());
+        assert!(c.passthrough_for(&DEVICE) && c.passthrough_for(&named));
+        assert!(!c.passthrough_for(&other));
+
+        let mut c = device_config();
+        c.aliases.insert("navigator".to_string(), other.clone());
+        c.passthrough.push("navigator".to_string());
+        assert!(c.passthrough_for(&other));
+        assert!(!c.passthrough_for(&DEVICE));
+    }
+
+    #[test]
+    fn validate_aliases() {
+        for (alias, device) in [("", DEVICE), ("default", DEVICE), ("046d:c626", DEVICE), ("zero", UsbDevice { vid: 0, pid: 0, name: None })] {
+            let mut c = device_config();
+            c.aliases.insert(alias.to_string(), device);
+            assert!(matches!(c.errors().as_slice(), [ConfigError::InvalidAlias { .. }]), "'{}': {:?}", alias, c.errors());
+        }
+
+        // Aliases for devices without a config are warnings
+        let mut c = Config::default();
+        c.aliases.insert("spacemouse".to_string(), DEVICE);
+        let e = c.validate().unwrap_err();
+        assert!(matches!(e.as_slice(), [ConfigError::UnusedAlias { .. }]), "{:?}", e);
+        assert!(c.errors().is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_invalid_options() {
+        let invalid: &[(&str, fn(&mut Config))] = &[
+            ("abs_range", |c| c.abs_range = Some(0)),
+            ("max_output_hz", |c| c.max_output_hz = Some(0)),
+            ("idle_timeout_s", |c| c.idle_timeout_s = Some(0)),
+            ("passthrough", |c| c.passthrough.push("nope".to_string())),
+            ("feedback", |c| { c.feedback.insert("nope".to_string(), HashMap::new()); }),
+            ("feedback.256f:c635", |c| { c.feedback.insert(DEVICE.to_string(), HashMap::from([("missing".to_string(), LedPattern(1))])); }),
+            ("axis_snap.046d:c626", |c| { c.axis_snap.insert("046d:c626".to_string(), 2.0); }),
+            ("axis_snap.256f:c635", |c| { c.axis_snap.insert(DEVICE.to_string(), 1.0); }),
+            ("input_kind.046d:c626", |c| { c.input_kind.insert("046d:c626".to_string(), InputKind::Mouse); }),
+            ("aggregate.Nope", |c| { c.aggregate.insert("Nope".to_string(), Aggregate::Sum); }),
+            ("aggregate.X", |c| { c.aggregate.insert("X".to_string(), Aggregate::Priority(vec!["nope".to_string()])); }),
+            ("mouse_divisor", |c| c.mouse_divisor = Some(-1.0)),
+            ("output_limits.wheel", |c| c.output_limits = Some(OutputLimits { wheel: 0, ..Default::default() })),
+            ("abs_pointer", |c| c.abs_pointer = Some(AbsPointerConfig { width: 1, ..Default::default() })),
+            ("abs_pointer.speed", |c| c.abs_pointer = Some(AbsPointerConfig { speed: f32::NAN, ..Default::default() })),
+        ];
+
+        for (option, f) in invalid {
+            let mut c = device_config();
+            f(&mut c);
+
+            match c.errors().as_slice() {
+                [ConfigError::InvalidOption { option: o, .. }] => assert_eq!(o, option),
+                e => panic!("unexpected errors for {}: {:?}", option, e),
+            }
+        }
+    }
+
+    #[test]
+    fn feedback_for_active_profile() {
+        let mut c = device_config();
+        c.aliases.insert("compact".to_string(), DEVICE);
+        c.feedback.insert("compact".to_string(), [
+            ("default".to_string(), LedPattern(1)),
+            ("slow".to_string(), LedPattern(2)),
+        ].into_iter().collect());
+
+        assert_eq!(c.feedback_for(&DEVICE), Some(LedPattern(1)));
+
+        c.active.insert(DEVICE.to_string(), "slow".to_string());
+        assert_eq!(c.feedback_for(&DEVICE), Some(LedPattern(2)));
+
+        // Profiles without a pattern leave the LEDs unchanged
+        c.active.insert(DEVICE.to_string(), "fast".to_string());
+        assert_eq!(c.feedback_for(&DEVICE), None);
+
+        // Devices without their own config follow the default profile
+        c.devices.clear();
+        c.active.insert("default".to_string(), "slow".to_string());
+        assert_eq!(c.feedback_for(&DEVICE), Some(LedPattern(2)));
+
+        assert_eq!(c.feedback_for(&UsbDevice { vid: 0x046d, pid: 0xc626, name: None }), None);
+    }
+
+    #[test]
+    fn output_limits_clamp() {
+        let l = OutputLimits::default();
+        let x = EventCode::EV_REL(EV_REL::REL_X);
+        let wheel = EventCode::EV_REL(EV_REL::REL_HWHEEL);
+        let hi_res = EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES);
+
+        // Within limits, including the limits themselves
+        assert_eq!(l.clamp(&x, 0), (0, false));
+        assert_eq!(l.clamp(&x, 127), (127, false));
+        assert_eq!(l.clamp(&x, -127), (-127, false));
+
+        // Saturated at the max and min, symmetrically
+        assert_eq!(l.clamp(&x, 128), (127, true));
+        assert_eq!(l.clamp(&x, -128), (-127, true));
+        assert_eq!(l.clamp(&wheel, i32::MAX), (8, true));
+        assert_eq!(l.clamp(&wheel, i32::MIN), (-8, true));
+        assert_eq!(l.clamp(&hi_res, 1000), (960, true));
+        assert_eq!(l.clamp(&hi_res, -1000), (-960, true));
+
+        // Codes without limits pass through
+        for code in [EventCode::EV_ABS(EV_ABS::ABS_X), EventCode::EV_KEY(EV_KEY::KEY_LEFTCTRL), EventCode::EV_REL(EV_REL::REL_Z)] {
+            assert_eq!(l.limit_for(&code), None);
+            assert_eq!(l.clamp(&code, i32::MAX), (i32::MAX, false));
+            assert_eq!(l.clamp(&code, i32::MIN), (i32::MIN, false));
+        }
+
+        // Configured limits replace the defaults
+        let l = OutputLimits { pointer: 10, ..Default::default() };
+        assert_eq!(l.clamp(&x, 50), (10, true));
+        assert_eq!(l.clamp(&wheel, 50), (8, true));
+    }
+}
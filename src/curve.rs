@@ -0,0 +1,286 @@
+//! Axis response curves
+
+use std::ops::RangeInclusive;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use strum::{Display, EnumString, EnumVariantNames};
+
+use crate::CURVE_RANGE;
+
+/// Valid power curve exponent range
+pub const POWER_RANGE: RangeInclusive<f32> = 1.0..=5.0;
+
+/// Maximum number of curve table breakpoints
+pub const CURVE_TABLE_LEN: usize = 16;
+
+/// Axis response curve, applied to deadzone-adjusted (-1.0 to 1.0) values
+///
+/// All curves are odd functions, `f(-x) = -f(x)`.
+///
+/// Serialized as a single entry map (eg. `{ cubic_blend = 0.5 }`) in human readable formats,
+/// as TOML does not support enum newtype variants.
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveKind {
+    /// Blend between linear and cubic response (0.0=x 1.0=x^3)
+    CubicBlend(#[schemars(schema_with = "crate::schema::cubic_blend")] f32),
+    /// Power response, `sign(x) * |x|^k`
+    Power(#[schemars(schema_with = "crate::schema::power")] f32),
+    /// Piecewise-linear breakpoints for positive inputs, mirrored for negative inputs
+    Table(CurveTable),
+}
+
+/// Curve kinds without parameters, for selection
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, EnumString, EnumVariantNames)]
+pub enum CurveType {
+    CubicBlend,
+    Power,
+    Table,
+}
+
+pub const CURVE_TYPES: &[CurveType] = &[CurveType::CubicBlend, CurveType::Power, CurveType::Table];
+
+impl Default for CurveKind {
+    fn default() -> Self {
+        CurveKind::CubicBlend(0.0)
+    }
+}
+
+impl Serialize for CurveKind {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let (index, name) = match self {
+            CurveKind::CubicBlend(_) => (0, "cubic_blend"),
+            CurveKind::Power(_) => (1, "power"),
+            CurveKind::Table(_) => (2, "table"),
+        };
+
+        if !s.is_human_readable() {
+            return match self {
+                CurveKind::CubicBlend(v) | CurveKind::Power(v) => s.serialize_newtype_variant("CurveKind", index, name, v),
+                CurveKind::Table(t) => s.serialize_newtype_variant("CurveKind", index, name, t),
+            };
+        }
+
+        let mut m = s.serialize_map(Some(1))?;
+        match self {
+            CurveKind::CubicBlend(v) | CurveKind::Power(v) => m.serialize_entry(name, v)?,
+            CurveKind::Table(t) => m.serialize_entry(name, t)?,
+        }
+        m.end()
+    }
+}
+
+impl CurveKind {
+    /// Apply the curve to a (-1.0 to 1.0) value
+    pub fn apply(&self, r: f32) -> f32 {
+        match self {
+            CurveKind::CubicBlend(c) => c * r.powi(3) + (1.0 - c) * r,
+            CurveKind::Power(k) => r.signum() * r.abs().powf(*k),
+            CurveKind::Table(t) => r.signum() * t.eval(r.abs()),
+        }
+    }
+
+    /// Fetch the curve type
+    pub fn curve_type(&self) -> CurveType {
+        match self {
+            CurveKind::CubicBlend(_) => CurveType::CubicBlend,
+            CurveKind::Power(_) => CurveType::Power,
+            CurveKind::Table(_) => CurveType::Table,
+        }
+    }
+
+    /// Curve parameter (blend factor or exponent), `None` for tables
+    pub fn param(&self) -> Option<f32> {
+        match self {
+            CurveKind::CubicBlend(c) => Some(*c),
+            CurveKind::Power(k) => Some(*k),
+            CurveKind::Table(_) => None,
+        }
+    }
+
+    /// Update the curve parameter, ignored for tables
+    pub fn set_param(&mut self, v: f32) {
+        match self {
+            CurveKind::CubicBlend(c) => *c = v,
+            CurveKind::Power(k) => *k = v,
+            CurveKind::Table(_) => (),
+        }
+    }
+
+    /// Valid parameter range for the curve type
+    pub fn range(&self) -> RangeInclusive<f32> {
+        match self {
+            CurveKind::Power(_) => POWER_RANGE,
+            _ => CURVE_RANGE,
+        }
+    }
+
+    /// Deserialize a curve, accepting legacy `curve = 0.5` values as [`CurveKind::CubicBlend`]
+    /// in human readable formats
+    pub fn deserialize_compat<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Compat {
+            Legacy(f32),
+            Kind(CurveKind),
+        }
+
+        // Untagged enums are not supported by non-self-describing formats (eg. bincode)
+        if !d.is_human_readable() {
+            return CurveKind::deserialize(d);
+        }
+
+        match Compat::deserialize(d)? {
+            Compat::Legacy(c) => Ok(CurveKind::CubicBlend(c)),
+            Compat::Kind(k) => Ok(k),
+        }
+    }
+}
+
+impl CurveType {
+    /// Default curve of this type
+    pub fn default_kind(&self) -> CurveKind {
+        match self {
+            CurveType::CubicBlend => CurveKind::CubicBlend(0.0),
+            CurveType::Power => CurveKind::Power(2.0),
+            CurveType::Table => CurveKind::Table(CurveTable::from(vec![(1.0, 1.0)])),
+        }
+    }
+}
+
+/// Fixed capacity curve table, (x, y) breakpoints sorted by x with an implicit (0, 0) origin
+///
+/// Serialized as a list of breakpoints, extra breakpoints beyond [`CURVE_TABLE_LEN`] are discarded.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "Vec<(f32, f32)>", into = "Vec<(f32, f32)>")]
+pub struct CurveTable {
+    points: [(f32, f32); CURVE_TABLE_LEN],
+    len: usize,
+}
+
+impl CurveTable {
+    /// Fetch table breakpoints
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points[..self.len]
+    }
+
+    /// Evaluate the table at a (0.0 to 1.0) input, holding the last value beyond the final breakpoint
+    pub fn eval(&self, x: f32) -> f32 {
+        let mut last = (0.0, 0.0);
+
+        for p in self.points() {
+            if x <= p.0 {
+                let w = p.0 - last.0;
+                return match w > 0.0 {
+                    true => last.1 + (x - last.0) / w * (p.1 - last.1),
+                    false => p.1,
+                };
+            }
+            last = *p;
+        }
+
+        last.1
+    }
+}
+
+/// Schema for the serialized breakpoint list
+impl JsonSchema for CurveTable {
+    fn schema_name() -> String {
+        "CurveTable".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut s = gen.subschema_for::<Vec<(f32, f32)>>().into_object();
+        s.array().max_items = Some(CURVE_TABLE_LEN as u32);
+        s.into()
+    }
+}
+
+impl From<Vec<(f32, f32)>> for CurveTable {
+    fn from(mut v: Vec<(f32, f32)>) -> Self {
+        v.retain(|p| p.0.is_finite() && p.1.is_finite());
+        v.sort_by(|a, b| a.0.total_cmp(&b.0));
+        v.truncate(CURVE_TABLE_LEN);
+
+        let mut points = [(0.0, 0.0); CURVE_TABLE_LEN];
+        points[..v.len()].copy_from_slice(&v);
+
+        Self { points, len: v.len() }
+    }
+}
+
+impl From<CurveTable> for Vec<(f32, f32)> {
+    fn from(t: CurveTable) -> Self {
+        t.points().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Curves covering each kind across its parameter range
+    fn curves() -> Vec<CurveKind> {
+        vec![
+            CurveKind::CubicBlend(0.0),
+            CurveKind::CubicBlend(0.5),
+            CurveKind::CubicBlend(1.0),
+            CurveKind::Power(1.0),
+            CurveKind::Power(2.5),
+            CurveKind::Power(5.0),
+            CurveType::Table.default_kind(),
+            CurveKind::Table(CurveTable::from(vec![(0.25, 0.1), (0.5, 0.2), (0.75, 0.6), (1.0, 1.0)])),
+            // Flat segment and a final breakpoint short of full scale
+            CurveKind::Table(CurveTable::from(vec![(0.2, 0.3), (0.6, 0.3), (0.9, 0.8)])),
+        ]
+    }
+
+    /// Inputs across (-1.0 to 1.0)
+    fn inputs() -> impl Iterator<Item = f32> {
+        (-100..=100).map(|i| i as f32 / 100.0)
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        for c in curves() {
+            let mut last = c.apply(-1.0);
+            for x in inputs().skip(1) {
+                let y = c.apply(x);
+                assert!(y >= last, "{:?} decreasing at {} ({} < {})", c, x, y, last);
+                last = y;
+            }
+        }
+    }
+
+    #[test]
+    fn curves_are_odd() {
+        for c in curves() {
+            assert_eq!(c.apply(0.0), 0.0, "{:?}", c);
+
+            for x in inputs() {
+                assert_eq!(c.apply(-x), -c.apply(x), "{:?} not odd at {}", c, x);
+            }
+        }
+    }
+
+    #[test]
+    fn curves_preserve_full_scale() {
+        for c in [CurveKind::CubicBlend(0.3), CurveKind::Power(3.0), CurveType::Table.default_kind()] {
+            assert!((c.apply(1.0) - 1.0).abs() < 1e-6, "{:?}", c);
+            assert!((c.apply(-1.0) + 1.0).abs() < 1e-6, "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn table_from_unsorted_points() {
+        let t = CurveTable::from(vec![(1.0, 1.0), (f32::NAN, 0.5), (0.5, 0.25)]);
+
+        assert_eq!(t.points(), &[(0.5, 0.25), (1.0, 1.0)]);
+        assert_eq!(t.eval(0.25), 0.125);
+        assert_eq!(t.eval(0.75), 0.625);
+    }
+}
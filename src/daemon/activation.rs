@@ -0,0 +1,155 @@
+//! systemd socket activation support (`sd_listen_fds`)
+
+use std::os::unix::prelude::RawFd;
+
+use tracing::{debug, warn};
+
+/// First file descriptor passed by systemd
+pub const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Fetch the activated listener socket, if one was passed to this process
+///
+/// Checks `LISTEN_PID` / `LISTEN_FDS` and that the first descriptor is a
+/// listening unix stream socket, environment variables are cleared so they
+/// are not inherited by child processes.
+pub fn listen_fd() -> Option<RawFd> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let fds = std::env::var("LISTEN_FDS").ok();
+
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    activated_fd(pid, fds, SD_LISTEN_FDS_START)
+}
+
+/// Select the activated socket from `LISTEN_PID` / `LISTEN_FDS` values, starting at `fd`
+fn activated_fd(pid: Option<String>, fds: Option<String>, fd: RawFd) -> Option<RawFd> {
+    // Check descriptors are intended for this process
+    match pid.and_then(|p| p.parse::<u32>().ok()) {
+        Some(p) if p == std::process::id() => (),
+        _ => return None,
+    }
+
+    let n = fds.and_then(|n| n.parse::<i32>().ok()).unwrap_or(0);
+    if n < 1 {
+        return None;
+    }
+    if n > 1 {
+        warn!("Received {} activated sockets, only the first will be used", n);
+    }
+
+    if !is_unix_listener(fd) {
+        warn!("Activated fd {} is not a listening unix socket, ignoring", fd);
+        return None;
+    }
+
+    debug!("Using activated socket fd: {}", fd);
+
+    Some(fd)
+}
+
+/// Check whether a file descriptor is a listening unix stream socket
+pub fn is_unix_listener(fd: RawFd) -> bool {
+    let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+
+    let res = unsafe {
+        libc::getsockname(fd, &mut addr as *mut _ as *mut libc::sockaddr, &mut len)
+    };
+    if res < 0 || addr.ss_family as i32 != libc::AF_UNIX {
+        return false;
+    }
+
+    let mut v: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+    let res = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_ACCEPTCONN,
+            &mut v as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    res == 0 && v != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::{UnixDatagram, UnixListener, UnixStream};
+    use std::os::unix::prelude::AsRawFd;
+
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    fn pid() -> Option<String> {
+        Some(std::process::id().to_string())
+    }
+
+    fn listener(name: &str) -> (UnixListener, std::path::PathBuf) {
+        let p = test_dir("activation", name).join("vmoused.sock");
+        (UnixListener::bind(&p).unwrap(), p)
+    }
+
+    #[test]
+    fn unix_listener_detection() {
+        let (l, p) = listener("detect");
+        assert!(is_unix_listener(l.as_raw_fd()));
+
+        // Connected pairs and datagram sockets are not listening
+        let (a, _b) = UnixStream::pair().unwrap();
+        assert!(!is_unix_listener(a.as_raw_fd()));
+        let (d, _e) = UnixDatagram::pair().unwrap();
+        assert!(!is_unix_listener(d.as_raw_fd()));
+
+        // Nor are other socket families or files
+        let t = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(!is_unix_listener(t.as_raw_fd()));
+        let f = std::fs::File::open("/dev/null").unwrap();
+        assert!(!is_unix_listener(f.as_raw_fd()));
+        assert!(!is_unix_listener(-1));
+
+        let _ = std::fs::remove_file(&p);
+    }
+
+    #[test]
+    fn activated_fd_env() {
+        let (l, p) = listener("env");
+        let fd = l.as_raw_fd();
+        let n = |s: &str| Some(s.to_string());
+
+        assert_eq!(activated_fd(pid(), n("1"), fd), Some(fd));
+        assert_eq!(activated_fd(pid(), n("2"), fd), Some(fd));
+
+        // Descriptors for another process or missing counts are ignored
+        assert_eq!(activated_fd(None, n("1"), fd), None);
+        assert_eq!(activated_fd(n("1"), n("1"), fd), None);
+        assert_eq!(activated_fd(n("self"), n("1"), fd), None);
+        assert_eq!(activated_fd(pid(), None, fd), None);
+        assert_eq!(activated_fd(pid(), n("0"), fd), None);
+        assert_eq!(activated_fd(pid(), n("-1"), fd), None);
+
+        // As are descriptors that are not listening sockets
+        let (a, _b) = UnixStream::pair().unwrap();
+        assert_eq!(activated_fd(pid(), n("1"), a.as_raw_fd()), None);
+
+        let _ = std::fs::remove_file(&p);
+    }
+
+    #[test]
+    fn listen_fd_clears_env() {
+        std::env::set_var("LISTEN_PID", "1");
+        std::env::set_var("LISTEN_FDS", "1");
+        std::env::set_var("LISTEN_FDNAMES", "vmouse.socket");
+
+        assert_eq!(listen_fd(), None);
+
+        for v in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+            assert!(std::env::var(v).is_err(), "{} not cleared", v);
+        }
+    }
+}
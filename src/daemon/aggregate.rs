@@ -0,0 +1,176 @@
+//! Output aggregation where several devices map to the same output, see [`vmouse::Aggregate`]
+//!
+//! Each output holds a table of devices with nonzero values, refreshed on every input.
+//! Devices report continuously while deflected, so entries without fresh input are dropped.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use vmouse::{Aggregate, Config, Map, UsbDevice};
+
+/// Window after which a device without input no longer holds an output
+pub const AGGREGATE_HOLD: Duration = Duration::from_millis(250);
+
+/// Device input on an output
+#[derive(Clone, Debug)]
+struct Entry {
+    /// Time the device moved from rest
+    onset: Instant,
+    /// Time of the last nonzero input
+    last: Instant,
+}
+
+/// Per-output device value table
+#[derive(Clone, Debug, Default)]
+pub struct Aggregator {
+    outputs: HashMap<Map, HashMap<UsbDevice, Entry>>,
+}
+
+impl Aggregator {
+    /// Record a device output and check whether it is written under the output policy,
+    /// `rank` orders devices for [`Aggregate::Priority`]
+    pub fn admit(&mut self, policy: &Aggregate, rank: impl Fn(&UsbDevice) -> usize, dev: &UsbDevice, map: Map, val: f32, now: Instant) -> bool {
+        let devices = self.outputs.entry(map).or_default();
+
+        match val == 0.0 {
+            true => {
+                devices.remove(dev);
+            }
+            false => devices.entry(dev.clone()).or_insert(Entry { onset: now, last: now }).last = now,
+        }
+        devices.retain(|_d, e| now.duration_since(e.last) < AGGREGATE_HOLD);
+
+        let owner = match policy {
+            Aggregate::Sum => return true,
+            Aggregate::Priority(_) => devices.iter().min_by_key(|(d, e)| (rank(d), e.onset)).map(|(d, _e)| d),
+            Aggregate::Latest => devices.iter().max_by_key(|(_d, e)| e.onset).map(|(d, _e)| d),
+        };
+
+        // Outputs at rest are written by any device so latched outputs return to zero
+        owner.map(|o| o == dev).unwrap_or(true)
+    }
+
+    /// Check whether a device output would be written under the output policy, without recording it
+    pub fn peek(&self, policy: &Aggregate, rank: impl Fn(&UsbDevice) -> usize, dev: &UsbDevice, map: Map, val: f32, now: Instant) -> bool {
+        let devices = self.outputs.get(&map).cloned().unwrap_or_default();
+        let mut a = Aggregator { outputs: HashMap::from([(map, devices)]) };
+
+        a.admit(policy, rank, dev, map, val, now)
+    }
+
+    /// Drop a removed device
+    pub fn remove(&mut self, dev: &UsbDevice) {
+        for devices in self.outputs.values_mut() {
+            devices.remove(dev);
+        }
+    }
+
+    /// Drop all devices
+    pub fn clear(&mut self) {
+        self.outputs.clear();
+    }
+}
+
+/// Device ranking for a policy, ordering devices for [`Aggregate::Priority`]
+pub fn rank_for<'a>(config: &'a Config, policy: &'a Aggregate) -> impl Fn(&UsbDevice) -> usize + 'a {
+    move |d| match policy {
+        Aggregate::Priority(devices) => config.aggregate_rank(devices, d),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn devices() -> (UsbDevice, UsbDevice) {
+        (UsbDevice { vid: 0x256f, pid: 0xc635, name: None }, UsbDevice { vid: 0x046d, pid: 0xc626, name: None })
+    }
+
+    /// Scripted input from two devices on a single output, returning whether each value is written
+    fn script(policy: &Aggregate, events: &[(u64, &UsbDevice, f32)]) -> Vec<bool> {
+        let config = Config::default();
+        let (mut a, t0) = (Aggregator::default(), Instant::now());
+
+        events.iter()
+            .map(|(ms, d, v)| a.admit(policy, rank_for(&config, policy), d, Map::X, *v, t0 + Duration::from_millis(*ms)))
+            .collect()
+    }
+
+    #[test]
+    fn sum_writes_all_devices() {
+        let (a, b) = devices();
+
+        let r = script(&Aggregate::Sum, &[(0, &a, 0.5), (1, &b, -0.5), (2, &a, 0.2), (3, &b, 0.0), (4, &a, 0.0)]);
+        assert_eq!(r, vec![true; 5]);
+    }
+
+    #[test]
+    fn priority_prefers_listed_devices() {
+        let (a, b) = devices();
+        let policy = Aggregate::Priority(vec![a.to_string()]);
+
+        let r = script(&policy, &[
+            // Lower priority device writes while alone
+            (0, &b, 0.5),
+            // Higher priority device takes over while moving
+            (10, &a, 0.3),
+            (11, &b, 0.5),
+            // And releases once at rest, the zero is left to the remaining device
+            (20, &a, 0.0),
+            (21, &b, 0.5),
+            // Devices without input within the hold window are dropped
+            (30, &a, 0.3),
+            (31, &b, 0.5),
+            (30 + AGGREGATE_HOLD.as_millis() as u64, &b, 0.5),
+        ]);
+        assert_eq!(r, vec![true, true, false, false, true, true, false, true]);
+    }
+
+    #[test]
+    fn latest_follows_most_recent_onset() {
+        let (a, b) = devices();
+
+        let r = script(&Aggregate::Latest, &[
+            (0, &a, 0.5),
+            // Newly moved device takes over
+            (10, &b, -0.2),
+            (15, &a, 0.5),
+            (16, &b, -0.3),
+            // Earlier device resumes once the latest returns to rest
+            (20, &b, 0.0),
+            (25, &a, 0.5),
+            // Outputs at rest are written by any device
+            (30, &a, 0.0),
+            (31, &b, 0.0),
+        ]);
+        assert_eq!(r, vec![true, true, false, true, false, true, true, true]);
+    }
+
+    #[test]
+    fn peek_does_not_record() {
+        let (a, b) = devices();
+        let (config, policy, now) = (Config::default(), Aggregate::Latest, Instant::now());
+        let mut agg = Aggregator::default();
+
+        let later = now + Duration::from_millis(1);
+
+        // Newly moved device would take over, but is not recorded
+        assert!(agg.admit(&policy, rank_for(&config, &policy), &a, Map::X, 0.5, now));
+        assert!(agg.peek(&policy, rank_for(&config, &policy), &b, Map::X, 0.5, later));
+        assert!(agg.admit(&policy, rank_for(&config, &policy), &a, Map::X, 0.5, later));
+    }
+
+    #[test]
+    fn removed_devices_release_outputs() {
+        let (a, b) = devices();
+        let (config, policy, now) = (Config::default(), Aggregate::Priority(vec![a.to_string()]), Instant::now());
+        let mut agg = Aggregator::default();
+
+        assert!(agg.admit(&policy, rank_for(&config, &policy), &a, Map::X, 0.5, now));
+        assert!(!agg.admit(&policy, rank_for(&config, &policy), &b, Map::X, 0.5, now));
+
+        agg.remove(&a);
+        assert!(agg.admit(&policy, rank_for(&config, &policy), &b, Map::X, 0.5, now));
+    }
+}
@@ -0,0 +1,120 @@
+//! Client authorisation using unix socket peer credentials
+
+use std::fs::read_to_string;
+use std::os::unix::prelude::AsRawFd;
+
+use vmouse::SocketConfig;
+
+/// Client identity used for authorisation
+#[derive(Clone, PartialEq, Debug)]
+pub enum ClientAuth {
+    /// Internal client not tied to a socket (eg. D-Bus, metrics), privileged
+    /// commands require per-request caller credentials
+    #[cfg_attr(not(any(feature = "dbus", feature = "metrics")), allow(dead_code))]
+    Internal,
+    /// Socket client with peer credentials
+    Peer(PeerCred),
+    /// Socket client where peer credentials are unavailable
+    Unknown,
+}
+
+/// Peer credentials for a connected client
+#[derive(Clone, PartialEq, Debug)]
+pub struct PeerCred {
+    pub pid: i32,
+    pub uid: u32,
+    /// Primary and supplementary groups
+    pub gids: Vec<u32>,
+}
+
+impl PeerCred {
+    /// Fetch peer credentials for a unix socket using `SO_PEERCRED`
+    pub fn from_socket(s: &impl AsRawFd) -> Result<Self, std::io::Error> {
+        let mut c = libc::ucred { pid: 0, uid: 0, gid: 0 };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+        let res = unsafe {
+            libc::getsockopt(
+                s.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut c as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if res < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        // Supplementary groups are not included in SO_PEERCRED, fetch from procfs
+        let status = read_to_string(format!("/proc/{}/status", c.pid)).unwrap_or_default();
+
+        Ok(Self {
+            pid: c.pid,
+            uid: c.uid,
+            gids: groups(Some(c.gid), &status),
+        })
+    }
+
+    /// Check whether the peer is permitted to issue privileged commands
+    ///
+    /// Root and members of the socket-owning group are always permitted,
+    /// with additional users / groups configured via `admin_uids` / `admin_gids`
+    pub fn is_admin(&self, socket_gid: u32, c: &SocketConfig) -> bool {
+        self.uid == 0
+            || c.admin_uids.contains(&self.uid)
+            || self.gids.contains(&socket_gid)
+            || self.gids.iter().any(|g| c.admin_gids.contains(g))
+    }
+}
+
+/// Parse primary (real) and supplementary groups from `/proc/<pid>/status`
+fn groups(primary: Option<u32>, status: &str) -> Vec<u32> {
+    let field = |name: &str| status.lines()
+        .find_map(|l| l.strip_prefix(name))
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|g| g.parse::<u32>().ok())
+        .collect::<Vec<_>>();
+
+    let mut gids: Vec<_> = primary.into_iter().chain(field("Gid:").into_iter().take(1)).collect();
+    for g in field("Groups:") {
+        if !gids.contains(&g) {
+            gids.push(g);
+        }
+    }
+    gids.dedup();
+
+    gids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "Name:\tcat\nUid:\t1000\t1000\t1000\t1000\nGid:\t1000\t1000\t1000\t1000\nGroups:\t4 24 105 1000 \n";
+
+    #[test]
+    fn groups_include_primary_and_supplementary() {
+        assert_eq!(groups(None, STATUS), vec![1000, 4, 24, 105]);
+        assert_eq!(groups(Some(27), STATUS), vec![27, 1000, 4, 24, 105]);
+    }
+
+    #[test]
+    fn groups_without_status() {
+        assert_eq!(groups(Some(1000), ""), vec![1000]);
+        assert!(groups(None, "").is_empty());
+    }
+
+    #[test]
+    fn admin_rules() {
+        let c = SocketConfig { admin_uids: vec![1001], admin_gids: vec![105], ..Default::default() };
+        let cred = |uid, gids: &[u32]| PeerCred { pid: 1, uid, gids: gids.to_vec() };
+
+        assert!(cred(0, &[]).is_admin(27, &c));
+        assert!(cred(1001, &[]).is_admin(27, &c));
+        assert!(cred(1000, &[27]).is_admin(27, &c));
+        assert!(cred(1000, &[105]).is_admin(27, &c));
+        assert!(!cred(1000, &[1000, 4]).is_admin(27, &c));
+    }
+}
@@ -0,0 +1,83 @@
+//! Output watchdog, zeroing outputs left nonzero when input stops mid-deflection
+//!
+//! A nonzero output without fresh input within the window (eg. after a disconnect
+//! or dropped events) is treated as stale, as for aggregation.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use vmouse::{Axis, Map, UsbDevice};
+
+/// Nonzero outputs by device axis, with the time of the last input
+#[derive(Clone, Debug, Default)]
+pub struct AutoZero {
+    axes: HashMap<(UsbDevice, Axis), (Map, Instant)>,
+}
+
+impl AutoZero {
+    /// Record an output for a device axis, zero outputs clear the axis
+    pub fn record(&mut self, dev: &UsbDevice, axis: Axis, map: Map, val: f32, now: Instant) {
+        match val == 0.0 || map == Map::None {
+            true => self.axes.remove(&(dev.clone(), axis)),
+            false => self.axes.insert((dev.clone(), axis), (map, now)),
+        };
+    }
+
+    /// Remove and return device axes without input within `window`
+    pub fn expire(&mut self, window: Duration, now: Instant) -> Vec<(UsbDevice, Axis, Map)> {
+        let expired: Vec<_> = self.axes.iter()
+            .filter(|(_k, (_m, t))| now.duration_since(*t) >= window)
+            .map(|((d, a), (m, _t))| (d.clone(), *a, *m))
+            .collect();
+
+        for (d, a, _m) in &expired {
+            self.axes.remove(&(d.clone(), *a));
+        }
+
+        expired
+    }
+
+    /// Drop all axes
+    pub fn clear(&mut self) {
+        self.axes.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(100);
+
+    fn device() -> UsbDevice {
+        UsbDevice { vid: 0x046d, pid: 0xc626, name: None }
+    }
+
+    #[test]
+    fn abrupt_stop_expires() {
+        let (mut z, d, t0) = (AutoZero::default(), device(), Instant::now());
+
+        // Deflected input then nothing, as on a disconnect mid-deflection
+        for i in 0..5 {
+            z.record(&d, Axis::X, Map::X, 0.8, t0 + Duration::from_millis(i * 10));
+        }
+        let last = t0 + Duration::from_millis(40);
+
+        assert!(z.expire(WINDOW, last + WINDOW / 2).is_empty());
+        assert_eq!(z.expire(WINDOW, last + WINDOW), vec![(d.clone(), Axis::X, Map::X)]);
+
+        // Expired axes are only reported once
+        assert!(z.expire(WINDOW, last + WINDOW * 2).is_empty());
+    }
+
+    #[test]
+    fn zero_output_clears() {
+        let (mut z, d, t0) = (AutoZero::default(), device(), Instant::now());
+
+        z.record(&d, Axis::X, Map::X, 0.8, t0);
+        z.record(&d, Axis::Y, Map::None, 0.8, t0);
+        z.record(&d, Axis::X, Map::X, 0.0, t0);
+
+        assert!(z.expire(WINDOW, t0 + WINDOW).is_empty());
+    }
+}
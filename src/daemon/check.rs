@@ -0,0 +1,218 @@
+//! Startup checks for `vmoused --check`, validating config and environment then exiting
+//!
+//! Uses the same path resolution, config validation and socket options as daemon startup,
+//! without creating virtual devices or binding the socket.
+
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use vmouse::{Config, ConfigFile, SocketConfig};
+
+use crate::{resolve_group, resolve_paths, Options};
+
+/// uinput device node used for virtual output devices
+const UINPUT_PATH: &str = "/dev/uinput";
+
+/// Run startup checks and print a summary, returns the process exit code
+pub fn run(opts: &Options) -> i32 {
+    match checks(opts, Path::new(UINPUT_PATH)) {
+        true => 0,
+        false => 1,
+    }
+}
+
+/// Run startup checks against a uinput device node, returns `true` if all checks pass
+fn checks(opts: &Options, uinput: &Path) -> bool {
+    let (socket, config_file) = match resolve_paths(opts) {
+        Ok(v) => v,
+        Err(e) => return report("paths", Err(e.to_string())),
+    };
+
+    let (config, mut socket_config) = check_config(&config_file);
+    let config = report("config", config);
+
+    let uinput = report("uinput", check_uinput(uinput));
+
+    // Command line options override config, as at startup
+    if let Some(m) = opts.socket_mode {
+        socket_config.mode = Some(m);
+    }
+    if let Some(g) = &opts.socket_group {
+        socket_config.group = Some(g.clone());
+    }
+    let socket = report("socket", check_socket(&socket, &socket_config, opts.allow_insecure_socket));
+
+    let ok = config && uinput && socket;
+    println!("{}", if ok { "vmoused check OK" } else { "vmoused check failed" });
+
+    ok
+}
+
+/// Print a check result, returns `true` on success
+fn report(name: &str, r: Result<String, String>) -> bool {
+    match r {
+        Ok(m) => {
+            println!("[ OK ] {}: {}", name, m);
+            true
+        }
+        Err(e) => {
+            println!("[FAIL] {}: {}", name, e);
+            false
+        }
+    }
+}
+
+/// Load and validate the config file, a missing file is accepted as the daemon uses defaults
+fn check_config(path: &str) -> (Result<String, String>, SocketConfig) {
+    let f = match ConfigFile::load(path) {
+        Ok(f) => f,
+        Err(e) if e.io_kind() == Some(ErrorKind::NotFound) => {
+            return (Ok(format!("'{}' not found, using defaults", path)), SocketConfig::default());
+        }
+        Err(e) => return (Err(e.to_string()), SocketConfig::default()),
+    };
+
+    let errors = Config::from(&f).validate().err().unwrap_or_default();
+    for e in errors.iter().filter(|e| e.is_warning()) {
+        println!("       warning: {}", e);
+    }
+
+    let invalid: Vec<_> = errors.iter().filter(|e| !e.is_warning()).map(|e| e.to_string()).collect();
+    let r = match invalid.is_empty() {
+        true => Ok(format!("'{}' OK, {} devices ({} warnings)", path, f.devices.len(), errors.len())),
+        false => Err(format!("invalid config '{}': {}", path, invalid.join(", "))),
+    };
+
+    (r, f.socket)
+}
+
+/// Check uinput can be opened for writing, the device is released immediately
+fn check_uinput(dev: &Path) -> Result<String, String> {
+    match OpenOptions::new().read(true).write(true).open(dev) {
+        Ok(_f) => Ok(format!("{} read/write OK", dev.display())),
+        Err(e) => Err(format!("failed to open {}: {}", dev.display(), e)),
+    }
+}
+
+/// Check the socket directory is writable and socket options are valid
+fn check_socket(path: &str, c: &SocketConfig, allow_insecure: bool) -> Result<String, String> {
+    let dir = match Path::new(path).parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+
+    // Probe by creating and removing a file alongside the socket
+    let probe = dir.join(format!(".vmoused-check-{}", std::process::id()));
+    match OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_f) => {
+            let _ = std::fs::remove_file(&probe);
+        }
+        Err(e) => return Err(format!("socket directory '{}' not writable: {}", dir.display(), e)),
+    }
+
+    if let Some(g) = &c.group {
+        resolve_group(g).map_err(|e| e.to_string())?;
+    }
+
+    if let Some(m) = c.mode {
+        if m & 0o002 != 0 && !allow_insecure {
+            return Err(format!("world-writable socket mode {:04o}, use --allow-insecure-socket to override", m));
+        }
+    }
+
+    Ok(format!("'{}' directory writable", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use structopt::StructOpt;
+
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    /// Check options for a config and socket, with a writable file standing in for uinput
+    fn check(d: &Path, config: &Path, socket: &Path) -> bool {
+        let uinput = d.join("uinput");
+        std::fs::write(&uinput, "").unwrap();
+
+        let opts = Options::from_iter(["vmoused", "--check", "--config", config.to_str().unwrap(), "--socket", socket.to_str().unwrap()]);
+        checks(&opts, &uinput)
+    }
+
+    fn socket(d: &Path) -> PathBuf {
+        d.join("vmouse.sock")
+    }
+
+    #[test]
+    fn valid_config_passes() {
+        let d = test_dir("check", "valid");
+        let config = d.join("vmouse.toml");
+
+        // Missing configs use defaults
+        assert!(check(&d, &config, &socket(&d)));
+
+        std::fs::write(&config, "version = 1\n[devices]\n").unwrap();
+        assert!(check(&d, &config, &socket(&d)));
+
+        // Without leaving probe files or a socket behind
+        let mut files: Vec<_> = std::fs::read_dir(&d).unwrap().map(|e| e.unwrap().file_name()).collect();
+        files.sort();
+        assert_eq!(files, vec!["uinput", "vmouse.toml"]);
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn broken_config_fails() {
+        let d = test_dir("check", "broken");
+        let config = d.join("vmouse.toml");
+
+        std::fs::write(&config, "devices = 4").unwrap();
+        assert!(!check(&d, &config, &socket(&d)));
+
+        // Invalid values fail validation
+        std::fs::write(&config, "abs_range = -1\n[devices]\n").unwrap();
+        assert!(!check(&d, &config, &socket(&d)));
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn unwritable_socket_dir_fails() {
+        let d = test_dir("check", "socket");
+
+        // Socket directory is a file, so cannot be written even as root
+        let parent = d.join("file");
+        std::fs::write(&parent, "").unwrap();
+
+        assert!(!check(&d, &d.join("vmouse.toml"), &parent.join("vmouse.sock")));
+        assert!(!check(&d, &d.join("vmouse.toml"), &d.join("missing/vmouse.sock")));
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn missing_uinput_fails() {
+        let d = test_dir("check", "uinput");
+        let opts = Options::from_iter(["vmoused", "--check", "--config", d.join("vmouse.toml").to_str().unwrap(), "--socket", socket(&d).to_str().unwrap()]);
+
+        assert!(!checks(&opts, &d.join("uinput")));
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+
+    #[test]
+    fn insecure_socket_mode_fails() {
+        let d = test_dir("check", "mode");
+        let c = SocketConfig { mode: Some(0o666), ..Default::default() };
+
+        assert!(check_socket(socket(&d).to_str().unwrap(), &c, false).is_err());
+        assert!(check_socket(socket(&d).to_str().unwrap(), &c, true).is_ok());
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+}
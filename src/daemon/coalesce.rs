@@ -0,0 +1,160 @@
+//! Output event coalescing for `max_output_hz` rate limiting
+
+use evdev_rs::enums::EV_ABS;
+use evdev_rs::TimeVal;
+
+use vmouse::{Map, Outputs, AXIS_MAX};
+
+/// Pending output values, coalesced between flushes
+#[derive(Clone, Debug, Default)]
+pub struct Coalescer {
+    /// Relative deltas summed per mapping, including unwritten fractional remainders
+    rel: Vec<(Map, f32)>,
+    /// Latest joystick value per mapping
+    abs: Vec<(Map, f32)>,
+    /// Latest absolute pointer position per axis
+    pointer: Vec<(EV_ABS, i32)>,
+}
+
+impl Coalescer {
+    /// Add a mapped output value, summing relative deltas and keeping the latest absolute value
+    pub fn push(&mut self, map: Map, val: f32) {
+        match map {
+            // Absolute pointer positions are pushed after integration
+            Map::None | Map::AbsX | Map::AbsY => (),
+            Map::Abs(_) => set(&mut self.abs, map, val),
+            _ => match self.rel.iter_mut().find(|(m, _)| *m == map) {
+                Some((_, v)) => *v += val,
+                None => self.rel.push((map, val)),
+            },
+        }
+    }
+
+    /// Add an integrated absolute pointer position
+    pub fn push_pointer(&mut self, code: EV_ABS, pos: i32) {
+        set(&mut self.pointer, code, pos);
+    }
+
+    /// Drop pending values for a mapping, including relative remainders
+    pub fn clear(&mut self, map: Map) {
+        self.rel.retain(|(m, _)| *m != map);
+        self.abs.retain(|(m, _)| *m != map);
+    }
+
+    /// Write pending values to the output devices, returning the number of outputs written
+    pub fn flush(&mut self, o: &Outputs, ts: TimeVal) -> anyhow::Result<u64> {
+        let (values, pointer) = self.take();
+
+        for (m, v) in &values {
+            m.event(o, ts, *v)?;
+        }
+
+        for (c, p) in &pointer {
+            o.abs_pointer_event(ts, *c, *p)?;
+        }
+
+        Ok((values.len() + pointer.len()) as u64)
+    }
+
+    /// Take pending output values and absolute pointer positions for writing
+    fn take(&mut self) -> (Vec<(Map, f32)>, Vec<(EV_ABS, i32)>) {
+        let mut values = vec![];
+
+        for (m, v) in self.rel.iter_mut() {
+            // Write whole output units only, carrying the remainder so no delta is lost
+            let whole = (*v * AXIS_MAX as f32) as i32;
+            if whole == 0 {
+                continue;
+            }
+
+            let val = whole as f32 / AXIS_MAX as f32;
+            values.push((*m, val));
+
+            *v -= ((val * AXIS_MAX as f32) as i32) as f32 / AXIS_MAX as f32;
+        }
+
+        values.extend(self.abs.drain(..));
+
+        (values, self.pointer.drain(..).collect())
+    }
+}
+
+/// Replace or insert a keyed value
+fn set<K: PartialEq, V>(values: &mut Vec<(K, V)>, key: K, val: V) {
+    match values.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, v)) => *v = val,
+        None => values.push((key, val)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmouse::AbsAxis;
+
+    use super::*;
+
+    /// Output units for a value, as written to the output device
+    fn units(v: f32) -> i32 {
+        (v * AXIS_MAX as f32) as i32
+    }
+
+    #[test]
+    fn total_delta_preserved() {
+        let mut c = Coalescer::default();
+        let (mut input, mut emitted) = (0.0f64, 0i64);
+
+        // Fractional deltas in both directions, flushed at varying intervals
+        for i in 0..2000 {
+            let v = match i % 5 {
+                4 => -0.0037,
+                _ => 0.0011 * (i % 3 + 1) as f32,
+            };
+            c.push(Map::X, v);
+            input += v as f64;
+
+            if i % 7 == 0 {
+                let (values, _) = c.take();
+                emitted += values.iter().map(|(_m, v)| units(*v) as i64).sum::<i64>();
+            }
+        }
+        let (values, _) = c.take();
+        emitted += values.iter().map(|(_m, v)| units(*v) as i64).sum::<i64>();
+
+        // Emitted whole units and the carried remainder account for all input
+        let remainder = c.rel.iter().find(|(m, _v)| *m == Map::X).map(|(_m, v)| *v).unwrap_or_default();
+        // Rounding when converting back to output units may leave up to one further unit carried
+        assert!(remainder.abs() * (AXIS_MAX as f32) < 2.0, "remainder {}", remainder);
+
+        let input_units = input * AXIS_MAX as f64;
+        let total = emitted as f64 + (remainder * AXIS_MAX as f32) as f64;
+        assert!((total - input_units).abs() < 0.1, "emitted {} + remainder {} != input {}", emitted, remainder, input_units);
+        assert!((emitted as f64 - input_units).abs() < 2.0);
+    }
+
+    #[test]
+    fn relative_deltas_sum_per_mapping() {
+        let mut c = Coalescer::default();
+        c.push(Map::X, 0.5);
+        c.push(Map::Y, -0.25);
+        c.push(Map::X, 0.25);
+
+        let (values, pointer) = c.take();
+        assert_eq!(values, vec![(Map::X, 262.0 / AXIS_MAX as f32), (Map::Y, -87.0 / AXIS_MAX as f32)]);
+        assert!(pointer.is_empty());
+    }
+
+    #[test]
+    fn absolute_values_keep_latest() {
+        let mut c = Coalescer::default();
+        let m = Map::Abs(AbsAxis::X);
+        c.push(m, 0.5);
+        c.push(m, -0.2);
+        c.push_pointer(EV_ABS::ABS_X, 10);
+        c.push_pointer(EV_ABS::ABS_X, 20);
+
+        assert_eq!(c.take(), (vec![(m, -0.2)], vec![(EV_ABS::ABS_X, 20)]));
+
+        // Absolute values are written once
+        assert_eq!(c.take(), (vec![], vec![]));
+    }
+}
@@ -0,0 +1,195 @@
+//! Daemonization and pid-file support for non-systemd init systems
+
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+/// Detach from the controlling terminal using a double fork
+///
+/// Must be called before the async runtime, uinput devices, or sockets are created
+/// as only the calling thread and inheritable file descriptors survive the fork.
+pub fn daemonize() -> anyhow::Result<()> {
+    // First fork, parent exits so the child is not a process group leader
+    fork_exit_parent()?;
+
+    // Start a new session without a controlling terminal
+    if unsafe { libc::setsid() } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    // Second fork, the session leader exits so a terminal can never be reacquired
+    fork_exit_parent()?;
+
+    std::env::set_current_dir("/")?;
+
+    // Redirect standard streams to /dev/null
+    let null = OpenOptions::new().read(true).write(true).open("/dev/null")?;
+    let fd = std::os::unix::io::AsRawFd::as_raw_fd(&null);
+    for target in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        if unsafe { libc::dup2(fd, target) } < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Fork, exiting in the parent and returning in the child
+fn fork_exit_parent() -> anyhow::Result<()> {
+    match unsafe { libc::fork() } {
+        -1 => Err(std::io::Error::last_os_error().into()),
+        0 => Ok(()),
+        _ => std::process::exit(0),
+    }
+}
+
+/// Pid file, removed on drop
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Create a pid file for the current process
+    ///
+    /// Stale files (process no longer running) are replaced, fails if the recorded process is alive.
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        if let Some(pid) = Self::read(path)? {
+            // A recorded pid matching this process was left by an earlier process and reused
+            match pid as u32 != std::process::id() && process_alive(pid) {
+                true => {
+                    return Err(anyhow::anyhow!(
+                        "vmoused already running (pid {} in {}), stop it or remove the pid file",
+                        pid,
+                        path.display()
+                    ))
+                }
+                false => {
+                    warn!("Removing stale pid file {} (pid {})", path.display(), pid);
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+
+        // Exclusive create so concurrent starts cannot both succeed
+        let mut f = OpenOptions::new().write(true).create_new(true).open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to create pid file {}: {}", path.display(), e))?;
+        writeln!(f, "{}", std::process::id())?;
+
+        debug!("Created pid file: {}", path.display());
+
+        Ok(Self { path: path.to_path_buf() })
+    }
+
+    /// Read the pid from an existing pid file, `None` if missing
+    ///
+    /// Unparseable files are treated as stale.
+    pub fn read(path: &Path) -> anyhow::Result<Option<i32>> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => Ok(Some(s.trim().parse().unwrap_or(0))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Only remove the file if it still records this process
+        if let Ok(Some(pid)) = Self::read(&self.path) {
+            if pid as u32 == std::process::id() {
+                let _ = std::fs::remove_file(&self.path);
+            }
+        }
+    }
+}
+
+/// Check whether a process exists, processes owned by other users count as alive
+fn process_alive(pid: i32) -> bool {
+    if pid <= 0 {
+        return false;
+    }
+
+    match unsafe { libc::kill(pid, 0) } {
+        0 => true,
+        _ => std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    /// Pid beyond the kernel limit, never a running process
+    const DEAD_PID: i32 = i32::MAX;
+
+    #[test]
+    fn create_and_remove() {
+        let path = test_dir("daemonize", "create").join("vmoused.pid");
+
+        let f = PidFile::create(&path).unwrap();
+        assert_eq!(PidFile::read(&path).unwrap(), Some(std::process::id() as i32));
+
+        drop(f);
+        assert_eq!(PidFile::read(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn running_process_rejected() {
+        let path = test_dir("daemonize", "running").join("vmoused.pid");
+
+        // The parent test runner is alive and not this process
+        let parent = unsafe { libc::getppid() };
+        std::fs::write(&path, format!("{}\n", parent)).unwrap();
+
+        let e = PidFile::create(&path).unwrap_err().to_string();
+        assert!(e.contains(&format!("pid {}", parent)), "{}", e);
+        assert_eq!(PidFile::read(&path).unwrap(), Some(parent));
+    }
+
+    #[test]
+    fn stale_files_replaced() {
+        let d = test_dir("daemonize", "stale");
+
+        // Dead processes, this process (a reused pid) and unparseable contents
+        let files = [
+            ("dead", DEAD_PID.to_string()),
+            ("own", std::process::id().to_string()),
+            ("invalid", "vmoused".to_string()),
+        ];
+
+        for (name, contents) in files {
+            let path = d.join(name);
+            std::fs::write(&path, format!("{}\n", contents)).unwrap();
+
+            let _f = PidFile::create(&path).unwrap();
+            assert_eq!(PidFile::read(&path).unwrap(), Some(std::process::id() as i32), "{}", name);
+        }
+    }
+
+    #[test]
+    fn drop_keeps_replaced_file() {
+        let path = test_dir("daemonize", "replaced").join("vmoused.pid");
+
+        // Files rewritten by another process are left in place
+        let f = PidFile::create(&path).unwrap();
+        std::fs::write(&path, format!("{}\n", DEAD_PID)).unwrap();
+
+        drop(f);
+        assert_eq!(PidFile::read(&path).unwrap(), Some(DEAD_PID));
+    }
+
+    #[test]
+    fn process_liveness() {
+        assert!(process_alive(std::process::id() as i32));
+        assert!(!process_alive(DEAD_PID));
+        assert!(!process_alive(0));
+        assert!(!process_alive(-1));
+    }
+}
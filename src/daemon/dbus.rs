@@ -0,0 +1,274 @@
+//! D-Bus interface for desktop integration (`dbus` feature)
+//!
+//! Exports `org.vmouse.Daemon`, method calls are forwarded through the
+//! same control channel as socket clients with the caller credentials,
+//! so privileged methods follow the socket access rules. The bus policy
+//! (`org.vmouse.Daemon.conf`) restricts name ownership to root.
+
+use async_std::channel::Sender;
+use async_std::task::JoinHandle;
+use futures::stream::StreamExt as _;
+use tracing::{debug, info};
+use zbus::{dbus_interface, fdo, Connection, ConnectionBuilder, MessageHeader, SignalContext};
+
+use vmouse::{Command, Config, Response, Topic};
+
+use crate::{auth::{ClientAuth, PeerCred}, ClientHandle, CommandHandle, Daemon, RateLimit};
+
+/// D-Bus well-known name
+pub const DBUS_NAME: &str = "org.vmouse.Daemon";
+
+/// D-Bus object path
+pub const DBUS_PATH: &str = "/org/vmouse/Daemon";
+
+/// Encode a config as JSON for D-Bus clients, devices are keyed by `vid:pid`
+pub fn config_to_json(c: &Config) -> Result<String, serde_json::Error> {
+    serde_json::to_string(c)
+}
+
+/// Decode a JSON config from D-Bus clients
+pub fn config_from_json(s: &str) -> Result<Config, serde_json::Error> {
+    serde_json::from_str(s)
+}
+
+/// D-Bus interface object
+struct DbusDaemon {
+    id: u32,
+    ctl_tx: Sender<CommandHandle>,
+}
+
+impl DbusDaemon {
+    /// Fetch caller credentials from the bus
+    ///
+    /// Groups are resolved by the bus for the sender connection, as reading them
+    /// from procfs by pid is racy once the caller exits and the pid is reused.
+    async fn caller(conn: &Connection, hdr: &MessageHeader<'_>) -> fdo::Result<PeerCred> {
+        let sender = hdr.sender()
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?
+            .ok_or_else(|| fdo::Error::AccessDenied("Unknown caller".to_string()))?;
+
+        let bus = fdo::DBusProxy::new(conn).await?;
+        let c = bus.get_connection_credentials(sender.clone().into()).await?;
+
+        let uid = c.unix_user_id()
+            .ok_or_else(|| fdo::Error::AccessDenied("Unknown caller uid".to_string()))?;
+
+        Ok(PeerCred {
+            pid: c.process_id().unwrap_or_default() as i32,
+            uid,
+            gids: c.into_unix_group_ids().unwrap_or_default(),
+        })
+    }
+
+    /// Issue a command via the daemon control channel with the caller credentials and await the response
+    async fn request(&self, conn: &Connection, hdr: &MessageHeader<'_>, c: Command) -> fdo::Result<Command> {
+        let cred = Self::caller(conn, hdr).await?;
+        let (tx, rx) = async_std::channel::bounded(1);
+
+        self.ctl_tx
+            .send(CommandHandle { id: self.id, req: vmouse::BROADCAST_ID, c, tx, cred: Some(cred) })
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+
+        rx.recv()
+            .await
+            .map(|r| r.cmd)
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Issue a command expecting [`Command::Ok`]
+    async fn request_ok(&self, conn: &Connection, hdr: &MessageHeader<'_>, c: Command) -> fdo::Result<()> {
+        match self.request(conn, hdr, c).await? {
+            Command::Ok => Ok(()),
+            Command::Error(vmouse::ErrorCode::PermissionDenied) => {
+                Err(fdo::Error::AccessDenied("Permission denied".to_string()))
+            }
+            Command::Error(vmouse::ErrorCode::InvalidConfig) => {
+                Err(fdo::Error::InvalidArgs("Invalid config".to_string()))
+            }
+            r => Err(fdo::Error::Failed(format!("Unexpected response: {:?}", r))),
+        }
+    }
+}
+
+#[dbus_interface(name = "org.vmouse.Daemon")]
+impl DbusDaemon {
+    /// Fetch the current config as a JSON string
+    async fn get_config(
+        &self,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(header)] hdr: MessageHeader<'_>,
+    ) -> fdo::Result<String> {
+        match self.request(conn, &hdr, Command::GetConfig).await? {
+            Command::SetConfig(c) => config_to_json(&c).map_err(|e| fdo::Error::Failed(e.to_string())),
+            r => Err(fdo::Error::Failed(format!("Unexpected response: {:?}", r))),
+        }
+    }
+
+    /// Update the config from a JSON string, `ConfigChanged` is emitted once applied
+    async fn set_config(
+        &self,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(header)] hdr: MessageHeader<'_>,
+        config: String,
+    ) -> fdo::Result<()> {
+        let c = config_from_json(&config).map_err(|e| fdo::Error::InvalidArgs(e.to_string()))?;
+
+        self.request_ok(conn, &hdr, Command::SetConfig(c)).await
+    }
+
+    /// Enable or disable output
+    async fn enable(
+        &self,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(header)] hdr: MessageHeader<'_>,
+        enabled: bool,
+    ) -> fdo::Result<()> {
+        self.request_ok(conn, &hdr, Command::Enable { enabled, device: None }).await
+    }
+
+    /// Bind an event or hidraw input
+    async fn bind(
+        &self,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(header)] hdr: MessageHeader<'_>,
+        event: String,
+    ) -> fdo::Result<()> {
+        self.request_ok(conn, &hdr, Command::Bind { target: vmouse::BindTarget::Path(event), wait: None }).await
+    }
+
+    /// List known devices (`vid:pid`)
+    async fn list_devices(
+        &self,
+        #[zbus(connection)] conn: &Connection,
+        #[zbus(header)] hdr: MessageHeader<'_>,
+    ) -> fdo::Result<Vec<String>> {
+        match self.request(conn, &hdr, Command::ListDevices).await? {
+            Command::Devices(d) => Ok(d.iter().map(|d| d.to_string()).collect()),
+            r => Err(fdo::Error::Failed(format!("Unexpected response: {:?}", r))),
+        }
+    }
+
+    /// Axis state update, JSON encoded
+    #[dbus_interface(signal)]
+    async fn state_changed(ctx: &SignalContext<'_>, state: &str) -> zbus::Result<()>;
+
+    /// Config update, JSON encoded
+    #[dbus_interface(signal)]
+    async fn config_changed(ctx: &SignalContext<'_>, config: &str) -> zbus::Result<()>;
+}
+
+impl Daemon {
+    /// Export the D-Bus interface, attached as a listening client
+    pub(crate) async fn attach_dbus(&mut self, ctl_tx: Sender<CommandHandle>) -> anyhow::Result<()> {
+        let id = self.id;
+        self.id = self.id.wrapping_add(1);
+
+        let iface = DbusDaemon { id, ctl_tx };
+
+        let conn = ConnectionBuilder::system()?
+            .name(DBUS_NAME)?
+            .serve_at(DBUS_PATH, iface)?
+            .build()
+            .await?;
+
+        info!("Exported D-Bus interface: {} at {}", DBUS_NAME, DBUS_PATH);
+
+        let (tx, mut rx) = async_std::channel::unbounded::<Response>();
+
+        // Forward state updates and config changes (from any client or the config file) as signals
+        let h: JoinHandle<Result<(), anyhow::Error>> = async_std::task::spawn(async move {
+            let ctx = SignalContext::new(&conn, DBUS_PATH)?;
+
+            while let Some(r) = rx.next().await {
+                match r.cmd {
+                    Command::State { device, state } => {
+                        let s = serde_json::json!({
+                            "device": device.map(|d| d.to_string()),
+                            "state": state,
+                        });
+                        DbusDaemon::state_changed(&ctx, &s.to_string()).await?;
+                    }
+                    Command::SetConfig(c) => {
+                        DbusDaemon::config_changed(&ctx, &config_to_json(&c)?).await?;
+                    }
+                    _ => (),
+                }
+            }
+
+            debug!("Closing D-Bus interface");
+
+            Ok(())
+        });
+
+        self.clients.insert(
+            id,
+            ClientHandle {
+                id,
+                tx,
+                listen: Some(vec![Topic::State, Topic::ConfigChanges]),
+                raw_limit: RateLimit::new(),
+                last_seen: std::time::Instant::now(),
+                pinged: None,
+                auth: ClientAuth::Internal,
+                task: h,
+            },
+        );
+
+        // Enable state updates for signal emission
+        self.enable_update_task().await;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vmouse::{Aggregate, Axis, UsbDevice};
+
+    use super::*;
+
+    fn config() -> Config {
+        let mut c = Config::default();
+        let d = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+        let mut axes = c.default;
+        axes[Axis::X].scale = 2.0;
+        axes[Axis::Y].scale_neg = Some(0.5);
+        c.devices.insert(d.clone(), axes);
+        c.devices.insert(UsbDevice { vid: 0x046d, pid: 0xc626, name: None }, c.default);
+        c.aliases.insert("spacemouse".to_string(), d);
+        c.aggregate.insert("X".to_string(), Aggregate::Priority(vec!["spacemouse".to_string()]));
+        c.aggregate.insert("default".to_string(), Aggregate::Latest);
+
+        c
+    }
+
+    #[test]
+    fn config_json_round_trip() {
+        let c = config();
+        let s = config_to_json(&c).unwrap();
+
+        assert_eq!(config_from_json(&s).unwrap(), c);
+    }
+
+    #[test]
+    fn config_json_keys_devices() {
+        let v: serde_json::Value = serde_json::from_str(&config_to_json(&config()).unwrap()).unwrap();
+
+        assert!(v["devices"].get("256f:c635").is_some());
+        assert!(v["devices"].get("046d:c626").is_some());
+        assert_eq!(v["aliases"]["spacemouse"], "256f:c635");
+    }
+
+    #[test]
+    fn config_json_accepts_defaults() {
+        assert_eq!(config_from_json("{}").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn config_json_rejects_invalid_devices() {
+        assert!(config_from_json(r#"{"devices": {"zz:1": {}}}"#).is_err());
+        assert!(config_from_json(r#"{"aliases": {"a": "nope"}}"#).is_err());
+    }
+}
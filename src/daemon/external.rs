@@ -0,0 +1,92 @@
+//! External source ingest socket, see [`vmouse::ExternalPacket`] for the packet layout
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_std::os::unix::net::UnixDatagram;
+use async_std::task::JoinHandle;
+use evdev_rs::TimeVal;
+use futures::FutureExt;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use vmouse::{device_identity, external_device, ExternalPacket, EXTERNAL_PACKET_LEN};
+
+use crate::{DeviceEvent, DeviceHandle, RateLimit};
+
+/// Maximum packets per second accepted from external sources, excess packets are dropped
+pub const EXTERNAL_RATE_MAX: u32 = 500;
+
+/// Current time as an event timestamp, matching evdev device timestamps
+fn event_time() -> TimeVal {
+    let t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    TimeVal::new(t.as_secs() as _, t.subsec_micros() as _)
+}
+
+impl crate::Daemon {
+    /// Bind a datagram socket for external source packets, registered as the `external` device
+    pub(crate) async fn attach_external(&mut self, path: &str) -> anyhow::Result<()> {
+        // Replace stale sockets from previous runs, the instance lock prevents removing a live one
+        let _ = std::fs::remove_file(path);
+        let socket = UnixDatagram::bind(path).await?;
+
+        let dev = external_device();
+        let info = dev.clone();
+
+        info!(device = %dev.to_string(), path = %path, "External source socket bound");
+
+        let evt_tx = self.evt_tx.clone();
+        let device = path.to_string();
+        let span = info_span!("device", device = %dev.to_string(), path = %path);
+        let (cancel, cancel_rx) = async_std::channel::bounded::<()>(1);
+
+        let t: JoinHandle<Result<(), anyhow::Error>> = async_std::task::spawn(async move {
+            let mut limit = RateLimit::new();
+            let mut dropped = 0u64;
+
+            // Oversized buffer so long packets are detected rather than truncated to a valid length
+            let mut buff = [0u8; EXTERNAL_PACKET_LEN * 2];
+
+            let r = loop {
+                let n = futures::select!(
+                    r = socket.recv(&mut buff).fuse() => match r {
+                        Ok(n) => n,
+                        Err(e) => break Err(e.into()),
+                    },
+                    // Stop on shutdown
+                    _ = cancel_rx.recv().fuse() => break Ok(()),
+                );
+
+                if !limit.allow(EXTERNAL_RATE_MAX) {
+                    dropped += 1;
+                    if dropped % EXTERNAL_RATE_MAX as u64 == 1 {
+                        warn!("External source rate limited, {} packets dropped", dropped);
+                    }
+                    continue;
+                }
+
+                let p = match ExternalPacket::parse(&buff[..n]) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        debug!("Invalid external packet: {}", e);
+                        continue;
+                    }
+                };
+
+                // TODO: map buttons once device button events are handled
+                for evt in p.events(event_time()) {
+                    if evt_tx.send(DeviceEvent::Input(dev.clone(), evt)).await.is_err() {
+                        return Ok(());
+                    }
+                }
+            };
+
+            let _ = evt_tx.send(DeviceEvent::Removed(device, dev)).await;
+
+            r
+        }.instrument(span));
+
+        let identity = device_identity(&info, None, None);
+        self.devices.insert(path.to_string(), DeviceHandle { info, path: path.to_string(), identity, axes: true, task: t, cancel, leds: None, battery: None });
+
+        Ok(())
+    }
+}
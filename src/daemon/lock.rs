@@ -0,0 +1,147 @@
+//! Single-instance lock, held for the daemon lifetime
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+
+use tracing::debug;
+
+/// Exclusive `flock` on a lock file derived from the socket path,
+/// released when dropped or the process exits
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: String,
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Lock file path for a socket path, user and system sockets lock separately
+    pub fn path(socket: &str) -> String {
+        format!("{}.lock", socket)
+    }
+
+    /// Acquire the lock, failing if another instance holds it
+    pub fn acquire(path: &str) -> anyhow::Result<Self> {
+        // The holder unlinks the file on release, so a file opened before then may be
+        // locked after it is no longer at the path, retry with the current file
+        let mut f = loop {
+            let f = Self::try_lock(path)?;
+            if is_current(&f, path) {
+                break f;
+            }
+            debug!("Lock file '{}' replaced while locking, retrying", path);
+        };
+
+        // Record the holder pid
+        f.set_len(0)?;
+        f.seek(SeekFrom::Start(0))?;
+        writeln!(f, "{}", std::process::id())?;
+
+        debug!("Acquired instance lock: {}", path);
+
+        Ok(Self { path: path.to_string(), _file: f })
+    }
+
+    /// Open and lock the file at the path, failing if another instance holds it
+    fn try_lock(path: &str) -> anyhow::Result<File> {
+        let mut f = OpenOptions::new().read(true).write(true).create(true).open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open lock file '{}': {}", path, e))?;
+
+        if unsafe { libc::flock(f.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } < 0 {
+            let e = std::io::Error::last_os_error();
+            if e.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                return Err(anyhow::anyhow!("Failed to lock '{}': {}", path, e));
+            }
+
+            // Report the holder pid recorded in the lock file
+            let mut pid = String::new();
+            let _ = f.read_to_string(&mut pid);
+            let pid = match pid.trim() {
+                "" => "unknown".to_string(),
+                p => p.to_string(),
+            };
+
+            return Err(anyhow::anyhow!(
+                "another vmoused instance is running (pid {}), lock: '{}', use --force to start anyway",
+                pid,
+                path
+            ));
+        }
+
+        Ok(f)
+    }
+}
+
+/// Check an open file is still the file at the path, rather than an unlinked or replaced one
+fn is_current(f: &File, path: &str) -> bool {
+    match (f.metadata(), std::fs::metadata(path)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Remove the file while still holding the lock, instances that opened it
+        // beforehand find it unlinked once locked and retry with a new file
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    #[test]
+    fn second_instance_rejected() {
+        let path = InstanceLock::path(&test_dir("lock", "second").join("vmoused.sock").to_string_lossy());
+
+        let l = InstanceLock::acquire(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), std::process::id().to_string());
+
+        // Separate opens do not share the lock, so this fails as for another process
+        let e = InstanceLock::acquire(&path).unwrap_err().to_string();
+        assert!(e.contains("another vmoused instance is running"), "{}", e);
+        assert!(e.contains(&format!("pid {}", std::process::id())), "{}", e);
+
+        // Released on drop, with the lock file removed
+        drop(l);
+        assert!(!Path::new(&path).exists());
+
+        let l = InstanceLock::acquire(&path).unwrap();
+        drop(l);
+    }
+
+    #[test]
+    fn unlinked_lock_file_not_current() {
+        let path = InstanceLock::path(&test_dir("lock", "unlinked").join("vmoused.sock").to_string_lossy());
+
+        // Opened before the holder released and unlinked it
+        let l = InstanceLock::acquire(&path).unwrap();
+        let f = File::open(&path).unwrap();
+        assert!(is_current(&f, &path));
+
+        drop(l);
+        assert!(!is_current(&f, &path));
+
+        // Nor once another instance creates a new file at the path
+        let _l = InstanceLock::acquire(&path).unwrap();
+        assert!(!is_current(&f, &path));
+    }
+
+    #[test]
+    fn stale_lock_file_reused() {
+        let path = InstanceLock::path(&test_dir("lock", "stale").join("vmoused.sock").to_string_lossy());
+
+        // Lock files left by a killed instance are not locked
+        std::fs::write(&path, "12345678\n").unwrap();
+
+        let _l = InstanceLock::acquire(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), std::process::id().to_string());
+    }
+}
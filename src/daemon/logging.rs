@@ -0,0 +1,127 @@
+//! Daemon logging setup using `tracing`
+//!
+//! Library `log` records are forwarded to the active subscriber.
+
+use strum::{Display, EnumString};
+use tracing::level_filters::LevelFilter;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Log output formats
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable text
+    Text,
+    /// One JSON object per line
+    Json,
+    /// systemd journal (`systemd` feature)
+    Journald,
+}
+
+type InitError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Setup the global logger with the provided level and format
+pub fn init(level: LevelFilter, format: LogFormat) -> anyhow::Result<()> {
+    let fmt = tracing_subscriber::fmt().with_max_level(level);
+
+    let r = match format {
+        LogFormat::Text => fmt.try_init(),
+        LogFormat::Json => json(level, std::io::stdout).try_init().map_err(InitError::from),
+        LogFormat::Journald => journald(level),
+    };
+
+    r.map_err(|e| anyhow::anyhow!("Failed to setup {} logging: {}", format, e))
+}
+
+/// JSON subscriber, writing one object per event with the current span fields
+fn json<W>(level: LevelFilter, writer: W) -> impl Subscriber + Send + Sync
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(writer)
+        .json()
+        .flatten_event(true)
+        .with_current_span(true)
+        .finish()
+}
+
+#[cfg(feature = "systemd")]
+fn journald(level: LevelFilter) -> Result<(), InitError> {
+    use tracing_subscriber::prelude::*;
+
+    let j = tracing_journald::layer()?;
+    tracing_subscriber::registry().with(j.with_filter(level)).try_init()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "systemd"))]
+fn journald(_level: LevelFilter) -> Result<(), InitError> {
+    Err("journald output requires the `systemd` feature".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Shared in-memory log output
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_lines_parse() {
+        let b = Buffer::default();
+        let w = b.clone();
+
+        tracing::subscriber::with_default(json(LevelFilter::INFO, move || w.clone()), || {
+            tracing::info!(client_id = 3, "client connected");
+
+            let span = tracing::info_span!("device", device = "256f:c635", path = "/dev/input/event3");
+            span.in_scope(|| tracing::warn!(error = "disconnected", "device error"));
+
+            tracing::debug!("filtered");
+        });
+
+        let out = String::from_utf8(b.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = out.lines()
+            .map(|l| serde_json::from_str(l).unwrap_or_else(|e| panic!("{}: {}", e, l)))
+            .collect();
+
+        assert_eq!(lines.len(), 2, "{}", out);
+
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["message"], "client connected");
+        assert_eq!(lines[0]["client_id"], 3);
+
+        assert_eq!(lines[1]["level"], "WARN");
+        assert_eq!(lines[1]["error"], "disconnected");
+        assert_eq!(lines[1]["span"]["name"], "device");
+        assert_eq!(lines[1]["span"]["device"], "256f:c635");
+        assert_eq!(lines[1]["span"]["path"], "/dev/input/event3");
+    }
+
+    #[test]
+    fn format_names() {
+        for (f, s) in [(LogFormat::Text, "text"), (LogFormat::Json, "json"), (LogFormat::Journald, "journald")] {
+            assert_eq!(f.to_string(), s);
+            assert_eq!(s.parse::<LogFormat>().unwrap(), f);
+        }
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+}
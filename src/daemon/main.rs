@@ -1,12 +1,16 @@
 use std::collections::HashMap;
 
-use std::fs::{File, read_to_string};
+use std::ffi::CString;
+use std::fs::{File, OpenOptions, Permissions};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::prelude::FromRawFd;
 
 use std::io::{ErrorKind};
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use async_std::task::JoinHandle;
-use evdev_rs::{Device, DeviceWrapper, InputEvent, ReadFlag};
+use evdev_rs::{enums::{EventCode, EV_SYN}, Device, GrabMode, InputEvent, TimeVal};
 use futures::{stream::StreamExt as _, FutureExt};
 
 use async_std::channel::Sender;
@@ -15,60 +19,220 @@ use async_std::{io::ReadExt, io::WriteExt};
 
 use structopt::StructOpt;
 
-use log::{debug, warn, error, info, trace, LevelFilter};
-use simplelog::{Config as LogConfig, SimpleLogger};
-
-use vmouse::{AxisCollection, AxisValue, Command, Config, UsbDevice, ConfigFile, DeviceConfig};
+use tracing::{debug, warn, error, info, info_span, trace, Instrument};
+use tracing::level_filters::LevelFilter;
+
+mod activation;
+mod auth;
+mod check;
+mod notify;
+mod pointer;
+mod logging;
+mod coalesce;
+mod daemonize;
+mod lock;
+mod metrics;
+mod external;
+mod snap;
+mod autozero;
+mod aggregate;
+mod state;
+
+#[cfg(test)]
+#[path = "../testutil.rs"]
+mod testutil;
+
+use auth::{ClientAuth, PeerCred};
+use pointer::AbsPointer;
+use logging::LogFormat;
+use coalesce::Coalescer;
+use snap::Snap;
+use autozero::AutoZero;
+use aggregate::Aggregator;
+use state::{RuntimeState, StateFile};
+use metrics::Metrics;
+
+#[cfg(feature = "dbus")]
+mod dbus;
+
+#[cfg(feature = "watch")]
+mod watch;
+
+use vmouse::{Axis, AXIS, AxisCollection, AxisState, AxisValue, BindTarget, CalibrateAction, Command, Config, UsbDevice, ConfigFile, ConfigFormat, HidrawDevice, InputSource, LedPattern, Passthrough, SocketConfig, StatusInfo, DeviceStatus, Topic, Outputs, OutputDevice, Pipeline, Mapped, ErrorCode, KEEPALIVE_DEFAULT, RAW_RATE_DEFAULT, Decoder, Response, RawTiming, ExternalPacket, PROTOCOL_VERSION};
 
 #[derive(Clone, PartialEq, Debug, StructOpt)]
 pub struct Options {
     /// Socket for daemon connections
-    #[structopt(long, default_value = "/var/run/vmouse.sock")]
-    pub socket: String,
+    /// (defaults to /var/run/vmouse.sock, or $XDG_RUNTIME_DIR/vmouse.sock with --user)
+    #[structopt(long)]
+    pub socket: Option<String>,
 
     /// Configuration file
-    #[structopt(long, default_value = "/etc/vmouse/vmouse.toml")]
-    pub config: String,
+    /// (defaults to /etc/vmouse/vmouse.toml, or $XDG_CONFIG_HOME/vmouse/vmouse.toml with --user)
+    #[structopt(long)]
+    pub config: Option<String>,
+
+    /// Run as a user daemon, using XDG runtime and config paths
+    #[structopt(long)]
+    pub user: bool,
+
+    /// Socket file mode in octal (eg. 0660), overrides config
+    #[structopt(long, parse(try_from_str = parse_mode))]
+    pub socket_mode: Option<u32>,
+
+    /// Socket owning group name or gid (eg. input), overrides config
+    #[structopt(long)]
+    pub socket_group: Option<String>,
+
+    /// Allow world-writable socket modes
+    #[structopt(long)]
+    pub allow_insecure_socket: bool,
 
     /// Log verbosity
     #[structopt(long, default_value = "debug")]
     pub log_level: LevelFilter,
+
+    /// Log output format (text, json, or journald with the `systemd` feature)
+    #[structopt(long, default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Serve Prometheus metrics on a TCP address (eg. 127.0.0.1:9464)
+    #[cfg(feature = "metrics")]
+    #[structopt(long)]
+    pub metrics_addr: Option<String>,
+
+    /// Detach and run in the background (for non-systemd init systems),
+    /// use with `--log-format journald` or logs are discarded
+    #[structopt(long)]
+    pub daemonize: bool,
+
+    /// Run in the foreground (default)
+    #[structopt(long, conflicts_with = "daemonize")]
+    pub foreground: bool,
+
+    /// Pid file, refuses to start if the recorded process is running
+    #[structopt(long)]
+    pub pidfile: Option<String>,
+
+    /// Datagram socket for external source packets (eg. head trackers),
+    /// events are reported as device `0000:0000`
+    #[structopt(long)]
+    pub external_socket: Option<String>,
+
+    /// Start even if another instance holds the lock for this socket
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Export the org.vmouse.Daemon interface on the system D-Bus
+    #[cfg(feature = "dbus")]
+    #[structopt(long)]
+    pub dbus: bool,
+
+    /// Runtime state file for output enabled flags and active profiles
+    /// (defaults to /var/lib/vmouse/state.toml, or $XDG_STATE_HOME/vmouse/state.toml with --user)
+    #[structopt(long)]
+    pub state_file: Option<String>,
+
+    /// Validate the config, uinput access and socket directory then exit,
+    /// without creating devices or binding the socket (eg. for `ExecStartPre=`)
+    #[structopt(long, conflicts_with = "daemonize")]
+    pub check: bool,
 }
 
-#[async_std::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     // Parse command line arguments
-    let opts = Options::from_args();
+    let mut opts = Options::from_args();
+
+    // Check config and environment, exiting 0 on success or 1 on failure
+    if opts.check {
+        std::process::exit(check::run(&opts));
+    }
+
+    // Detach before starting the runtime so devices and sockets are created in the child
+    if opts.daemonize {
+        // Resolve relative paths as the daemon changes directory to `/`
+        let cwd = std::env::current_dir()?;
+        for p in [&mut opts.socket, &mut opts.config, &mut opts.pidfile, &mut opts.external_socket, &mut opts.state_file].into_iter().flatten() {
+            *p = cwd.join(&*p).to_string_lossy().to_string();
+        }
+
+        daemonize::daemonize()?;
+    }
 
     // Setup logging
-    let _ = SimpleLogger::init(opts.log_level, LogConfig::default());
+    logging::init(opts.log_level, opts.log_format)?;
+
+    // Hold the pid file until exit
+    let _pidfile = match &opts.pidfile {
+        Some(p) => match daemonize::PidFile::create(p) {
+            Ok(f) => Some(f),
+            Err(e) => {
+                error!("{}", e);
+                return Err(e);
+            }
+        },
+        None => None,
+    };
+
+    async_std::task::block_on(run(opts))
+}
 
+async fn run(opts: Options) -> anyhow::Result<()> {
     info!("Starting vmouse daemon");
 
-    debug!("Loading config: '{}'", opts.config);
+    // Resolve socket and config paths
+    let (socket, config_file) = match resolve_paths(&opts) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{}", e);
+            return Err(e);
+        }
+    };
+
+    // Enforce a single instance per socket before creating devices or binding
+    let lock_path = lock::InstanceLock::path(&socket);
+    let lock = match lock::InstanceLock::acquire(&lock_path) {
+        Ok(l) => Some(l),
+        Err(e) if opts.force => {
+            warn!("{}, continuing with --force", e);
+            None
+        }
+        Err(e) => {
+            error!("{}", e);
+            return Err(e);
+        }
+    };
+
+    debug!("Loading config: '{}'", config_file);
 
     let mut config = Config::default();
+    let mut socket_config = SocketConfig::default();
 
     // Load configuration file
-    match read_to_string(&opts.config).map(|s| toml::from_str::<ConfigFile>(&s) ) {
-        Ok(Ok(v)) => {
-            // Load devices from config
-            for e in v.devices {
-                let d = UsbDevice{ vid: e.vid, pid: e.pid, name: None }; 
-
-                // TODO: load axes
+    match ConfigFile::load(&config_file) {
+        Ok(v) => {
+            let c = Config::from(&v);
+
+            // Validate loaded config, rejecting hard errors
+            let errors = c.validate().err().unwrap_or_default();
+            for e in &errors {
+                match e.is_warning() {
+                    true => warn!("Config '{}': {}", config_file, e),
+                    false => error!("Invalid config '{}': {}", config_file, e),
+                }
+            }
 
-                config.devices.insert(d, Default::default());
+            if errors.iter().all(|e| e.is_warning()) {
+                config = c;
+            } else {
+                warn!("Invalid config file '{}', using defaults", config_file);
             }
 
+            socket_config = v.socket;
         },
-        // Read file, parsing failed
-        Ok(Err(e)) => {
-            warn!("Failed to parse config file '{}': {:?}, using defaults", opts.config, e);
-        },
-        // Read failed
+        // Read or parse failed
         Err(e) => {
-            warn!("Failed to read config file: '{}': {:?}, using defaults", opts.config, e);
+            warn!("{}, using defaults", e);
         },
     };
 
@@ -79,21 +243,138 @@ async fn main() -> anyhow::Result<()> {
     let (evt_tx, mut evt_rx) = async_std::channel::unbounded();
     let (tick_tx, mut tick_rx) = async_std::channel::unbounded::<()>();
 
-    debug!("Connecting to socket: {}", opts.socket);
+    debug!("Connecting to socket: {}", socket);
 
-    // Setup unix listener socket
-    let listener = UnixListener::bind(&opts.socket).await?;
+    // Setup unix listener socket, using a systemd activated socket if available
+    let activated = activation::listen_fd();
+    let listener = match activated {
+        Some(fd) => {
+            info!("Using systemd activated socket");
+            unsafe { UnixListener::from_raw_fd(fd) }
+        }
+        None => match UnixListener::bind(&socket).await {
+            Ok(l) => l,
+            Err(e) if e.kind() == ErrorKind::PermissionDenied && !opts.user => {
+                return Err(anyhow::anyhow!(
+                    "Permission denied binding socket '{}', run as root or use --user for a user daemon",
+                    socket
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        },
+    };
     let mut incoming = listener.incoming().fuse();
 
+    // Apply socket permissions, command line options override config
+    if let Some(m) = opts.socket_mode {
+        socket_config.mode = Some(m);
+    }
+    if let Some(g) = &opts.socket_group {
+        socket_config.group = Some(g.clone());
+    }
+    let socket_gid = match activated {
+        // Activated socket permissions are owned by systemd (`SocketMode=`, `SocketGroup=`)
+        Some(_) => std::fs::metadata(&socket).map(|m| m.gid()).unwrap_or(0),
+        None => match configure_socket(&socket, &socket_config, opts.allow_insecure_socket) {
+            Ok(gid) => gid,
+            Err(e) => {
+                error!("Failed to configure socket '{}': {:?}", socket, e);
+                drop(incoming);
+                drop(listener);
+                let _ = std::fs::remove_file(&socket);
+                return Err(e);
+            }
+        },
+    };
+
     debug!("Starting daemon");
 
-    let mut d = Daemon::new(config, opts.config.clone(), evt_tx, tick_tx);
+    let mut d = Daemon::new(config, config_file.clone(), socket_config, socket_gid, evt_tx, tick_tx);
+
+    // Restore output enabled flags and active profiles from the last run
+    match resolve_state_path(&opts) {
+        Some(p) => d.restore_state(StateFile::new(p)),
+        None => warn!("XDG_STATE_HOME and HOME not set, runtime state will not be persisted (specify --state-file)"),
+    }
+
+    // Setup D-Bus interface if enabled
+    #[cfg(feature = "dbus")]
+    if opts.dbus {
+        d.attach_dbus(ctl_tx.clone()).await?;
+    }
+
+    // Setup external source socket if enabled
+    if let Some(p) = &opts.external_socket {
+        d.attach_external(p).await?;
+    }
+
+    // Setup metrics endpoint if enabled
+    #[cfg(feature = "metrics")]
+    if let Some(a) = &opts.metrics_addr {
+        d.attach_metrics(a, ctl_tx.clone()).await?;
+    }
+
+    // Setup virtual output devices, released while idle if `idle_destroy` is set
+    let mut outputs = match Outputs::new(&d.config) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            error!("{}", e);
+            drop(incoming);
+            drop(listener);
+            if activated.is_none() {
+                let _ = std::fs::remove_file(&socket);
+            }
+            return Err(e.into());
+        }
+    };
+
+    d.output_devices = outputs.as_ref().map(|o| o.devices()).unwrap_or_default();
 
-    // Setup virtual device
-    let v = vmouse::virtual_device()?;
+    // Idle detection and the output watchdog run on the tick task
+    if d.ticks_required() {
+        d.enable_update_task().await;
+    }
 
     // TODO: scan for existing devices?
 
+    // Setup watchdog pings if configured
+    let mut watchdog = match notify::watchdog_interval() {
+        Some(i) => {
+            debug!("Enabling watchdog, interval: {:?}", i);
+            async_std::stream::interval(i).boxed().fuse()
+        }
+        None => futures::stream::pending::<()>().boxed().fuse(),
+    };
+
+    // Setup client keepalive checks if enabled
+    let mut keepalive = match d.keepalive_interval() {
+        Some(i) => {
+            debug!("Enabling client keepalive, interval: {:?}", i);
+            async_std::stream::interval(i).boxed().fuse()
+        }
+        None => futures::stream::pending::<()>().boxed().fuse(),
+    };
+
+    // Reload config on changes to the config file, unless disabled
+    let mut config_watch = watch_config(&config_file, d.config.watch_config.unwrap_or(true));
+
+    // Poll for devices awaited by pending binds
+    let mut bind_poll = async_std::stream::interval(BIND_POLL_INTERVAL).fuse();
+
+    // Setup coalesced output flushes if rate limited
+    let mut flush = match d.config.max_output_hz {
+        Some(hz) => {
+            debug!("Limiting output rate to {} Hz", hz);
+            d.coalesce = Some(Coalescer::default());
+            async_std::stream::interval(Duration::from_secs_f64(1.0 / hz as f64)).boxed().fuse()
+        }
+        None => futures::stream::pending::<()>().boxed().fuse(),
+    };
+
+    // Signal readiness now listener and virtual device are available
+    notify::status("Running, 0 devices bound");
+    notify::ready();
+
     // Run listen loop
     loop {
         futures::select!(
@@ -110,30 +391,131 @@ async fn main() -> anyhow::Result<()> {
             ctl = ctl_rx.next() => {
                 if let Some(h) = ctl {
                     debug!("Received command: {:?}", h.c);
+                    let active = d.config_file.clone();
+
                     if let Some(r) = d.handle_cmd(&h).await? {
-                        h.tx.send(r).await?;
+                        // Return latched joystick outputs to zero after a state reset
+                        if let (Command::ResetState, Command::Ok, Some(o)) = (&h.c, &r, outputs.as_ref()) {
+                            o.zero(vmouse::output_time())?;
+                        }
+                        h.respond(r).await;
+                    }
+
+                    // Follow the active config file after `SaveConfigAs` with `set_active`
+                    if d.config_file != active {
+                        config_watch = watch_config(&d.config_file, d.config.watch_config.unwrap_or(true));
+                    }
+
+                    // Output limits apply to existing devices on config changes
+                    if let Some(o) = outputs.as_mut() {
+                        o.limits = d.config.output_limits.unwrap_or_default();
                     }
                 }
             },
             // Handle input events
             evt = evt_rx.next() => {
-                if let Some(evt) = evt {
+                // Drop state for removed devices and notify listeners
+                if let Some(DeviceEvent::Removed(path, dev)) = &evt {
+                    info!(device = %dev.to_string(), path = %path, "Device unbound");
+
+                    d.devices.remove(path);
+                    d.device_state.remove(dev);
+                    d.metrics.remove(dev);
+                    d.snap.remove(dev);
+                    d.aggregate.remove(dev);
+                    notify::status(&format!("Running, {} devices bound", d.devices.len()));
+                    d.broadcast(Command::Removed(dev.clone()));
+                }
+
+                if let Some(DeviceEvent::Battery(path, level)) = &evt {
+                    d.update_battery(path, *level);
+                }
+
+                if let Some(DeviceEvent::Input(dev, ie)) = evt {
+                    let evt = (dev, ie);
                     trace!("Input event: {:?}", evt);
+                    d.metrics.input(&evt.0);
+                    d.wake();
+
+                    // Map input to output events
+                    let output = Pipeline::new(&d.config).map(&evt.0, &evt.1);
+                    let mut admitted = true;
+                    if let Some(Mapped { map, value: val, events }) = &output {
+                        let (map, val) = (*map, *val);
+
+                        // If output is enabled globally and for this device, write to virtual device
+                        if d.output_enabled(&evt.0) {
+                            // Recreate output devices released while idle
+                            if outputs.is_none() {
+                                match Outputs::new(&d.config) {
+                                    Ok(o) => {
+                                        d.output_devices = o.devices();
+                                        d.capabilities = vmouse::capabilities_for(&d.config);
+                                        info!("Recreated output devices: {}", o.devnodes().join(", "));
+                                        outputs = Some(o);
+                                    }
+                                    Err(e) => error!("Failed to recreate output devices: {}", e),
+                                }
+                            }
+                        }
+
+                        if let (true, Some(outputs)) = (d.output_enabled(&evt.0), outputs.as_ref()) {
+                            // Hold pointer outputs until the end of the frame when snapping
+                            let held = d.config.axis_snap_for(&evt.0).is_some()
+                                && d.snap.entry(evt.0.clone()).or_default().push(map, val);
+                            admitted = match held {
+                                true => d.would_admit(&evt.0, map, val),
+                                false => d.emit(&evt.0, outputs, map, val, events)?,
+                            };
+
+                            if map != vmouse::Map::None {
+                                d.metrics.events_mapped += 1;
+                                d.record_latency(&evt.1.time);
+                            }
+                        }
+                    }
 
-                    // Map input to output event
-                    // TODO: multi-device and reconfigurable mappings?
-                    if let Some((map, val)) = d.config.map(&evt.0, &evt.1) {
+                    // Write held pointer outputs at the end of each frame, snapped to the dominant axis
+                    if evt.1.event_code == EventCode::EV_SYN(EV_SYN::SYN_REPORT) {
+                        if let (Some(ratio), true, Some(outputs)) = (d.config.axis_snap_for(&evt.0), d.output_enabled(&evt.0), outputs.as_ref()) {
+                            let frame = d.snap.get_mut(&evt.0).map(|s| s.frame(ratio)).unwrap_or_default();
+                            let abs_range = d.config.abs_range.unwrap_or(vmouse::ABS_RANGE_DEFAULT);
 
-                        // If output is enabled, write to virtual device
-                        if d.enabled {
-                            map.event(&v, evt.1.time, val)?;
+                            for (m, v) in frame {
+                                d.emit(&evt.0, outputs, m, v, &m.output_events(v, abs_range))?;
+                            }
                         }
                     }
 
+                    // Record raw extremes while calibrating
+                    if let Some(c) = d.calibration.as_mut() {
+                        c.record(&evt.0, &evt.1);
+                    }
+
                     // Update internal state
-                    // Convert input event to axis value
-                    if let Ok(v) = AxisValue::try_from(evt.1) {
-                        d.state[v.a] = v.v;
+                    // Convert input event to axis value, normalised using the device calibration
+                    let v = AxisValue::from_event(&evt.1, &d.config.device_range(&evt.0))
+                        .map_err(|e| trace!("Skipping state update: {}", e));
+                    if let Ok(v) = v {
+                        // Outputs dropped under the aggregation policy are not written by this device
+                        let out = output.as_ref().filter(|_m| admitted).map(|m| m.value).unwrap_or_default();
+
+                        // Track nonzero outputs for the watchdog
+                        let map = output.as_ref().map(|m| m.map).unwrap_or(vmouse::Map::None);
+                        d.auto_zero.record(&evt.0, v.a, map, out, Instant::now());
+
+                        d.broadcast_raw(v, RawTiming { source_us: vmouse::time_us(&evt.1.time), processed_us: vmouse::now_us() });
+
+                        // Update aggregate state, unless the output is held by another device
+                        if admitted {
+                            d.state.raw[v.a] = v.v;
+                            d.state.output[v.a] = out;
+                        }
+
+                        // Update per-device state
+                        let s = d.device_state.entry(evt.0.clone()).or_default();
+                        s.raw[v.a] = v.v;
+                        s.output[v.a] = out;
                     };
                     d.changed = true;
 
@@ -142,21 +524,57 @@ async fn main() -> anyhow::Result<()> {
             },
             // Handle tick events
             _t = tick_rx.next() => {
+                // Release output devices once idle
+                if d.check_idle() && d.config.idle_destroy && outputs.take().is_some() {
+                    info!("Destroyed idle output devices");
+                    d.output_devices.clear();
+                }
+
+                // Zero outputs left nonzero without input
+                d.expire_outputs(outputs.as_ref())?;
+
+                // Write runtime state once changes settle
+                d.persist_state(false);
+
                 // Only send on changes
                 if !d.changed {
                     continue
                 }
 
-                // Periodically push state to connected listeners
-                for (_id, c) in d.clients.iter().filter(|(_id, c)| c.listen ) {
-                    let tx = c.tx.clone();
-
-                    let _ = async_std::task::spawn(async move {
-                        tx.send(Command::State(d.state)).await
-                    });
+                // Periodically push aggregate and per-device state to connected listeners
+                d.broadcast(Command::State{ device: None, state: d.state });
+                for (dev, s) in d.device_state.clone() {
+                    d.broadcast(Command::State{ device: Some(dev), state: s });
                 }
 
             }
+            // Write coalesced outputs
+            _f = flush.next() => {
+                if let (Some(c), Some(o)) = (d.coalesce.as_mut(), outputs.as_ref()) {
+                    let n = c.flush(o, vmouse::output_time())?;
+                    d.metrics.output(n);
+                    d.metrics.outputs_clamped += o.take_clamped();
+                }
+            },
+            // Check for devices awaited by pending binds
+            _b = bind_poll.next() => {
+                d.poll_binds().await;
+            },
+            // Reload the config file after changes on disk
+            _c = config_watch.next() => {
+                d.reload_config().await;
+                if let Some(o) = outputs.as_mut() {
+                    o.limits = d.config.output_limits.unwrap_or_default();
+                }
+            },
+            // Ping idle clients and reap unresponsive ones
+            _k = keepalive.next() => {
+                d.keepalive().await;
+            },
+            // Handle watchdog pings
+            _w = watchdog.next() => {
+                notify::watchdog();
+            },
             // Handle exit event
             _e = exit => {
                 debug!("Exiting daemon");
@@ -165,21 +583,235 @@ async fn main() -> anyhow::Result<()> {
         )
     }
 
-    // Close listener socket
+    info!("Shutting down");
+    notify::stopping();
+
+    // Stop accepting connections
     drop(incoming);
+
+    // Write pending outputs and release latched axes
+    if let Some(o) = &outputs {
+        let ts = vmouse::output_time();
+        if let Some(c) = d.coalesce.as_mut() {
+            let _ = c.flush(o, ts);
+        }
+        if let Err(e) = o.zero(ts) {
+            warn!("Failed to zero outputs: {}", e);
+        }
+    }
+
+    // Write pending runtime state
+    d.persist_state(true);
+
+    // Notify clients and stop device tasks
+    d.shutdown().await;
+
+    // Destroy virtual output devices
+    drop(outputs);
+
+    // Close listener socket, activated socket files are owned by systemd
     drop(listener);
-    let _ = std::fs::remove_file(&opts.socket);
+    if activated.is_none() {
+        let _ = std::fs::remove_file(&socket);
+    }
+    if let Some(p) = &opts.external_socket {
+        let _ = std::fs::remove_file(p);
+    }
+
+    // Release the instance lock, removing the lock file
+    drop(lock);
 
     Ok(())
 }
 
+/// Watch a config file for changes if enabled, pending otherwise
+fn watch_config(path: &str, enabled: bool) -> futures::stream::Fuse<futures::stream::BoxStream<'static, ()>> {
+    #[cfg(feature = "watch")]
+    let w = match enabled {
+        true => watch::watch(path)
+            .map_err(|e| warn!("Failed to watch config file '{}': {}", path, e))
+            .ok(),
+        false => None,
+    };
+    #[cfg(not(feature = "watch"))]
+    let w: Option<futures::stream::BoxStream<'static, ()>> = {
+        let _ = (path, enabled);
+        None
+    };
+
+    match w {
+        Some(w) => w.fuse(),
+        None => futures::stream::pending::<()>().boxed().fuse(),
+    }
+}
+
+/// Resolve socket and config paths from options, applying user mode defaults
+fn resolve_paths(opts: &Options) -> anyhow::Result<(String, String)> {
+    if !opts.user {
+        let socket = opts.socket.clone().unwrap_or_else(|| vmouse::SYSTEM_SOCKET.to_string());
+        let config = opts.config.clone().unwrap_or_else(|| vmouse::SYSTEM_CONFIG.to_string());
+        return Ok((socket, config));
+    }
+
+    let socket = match opts.socket.clone().or_else(vmouse::user_socket_path) {
+        Some(s) => s,
+        None => return Err(anyhow::anyhow!("XDG_RUNTIME_DIR not set, specify --socket for user mode")),
+    };
+
+    let config = match opts.config.clone().or_else(vmouse::user_config_path) {
+        Some(c) => c,
+        None => return Err(anyhow::anyhow!("XDG_CONFIG_HOME and HOME not set, specify --config for user mode")),
+    };
+
+    Ok((socket, config))
+}
+
+/// Resolve the runtime state path from options, applying user mode defaults
+fn resolve_state_path(opts: &Options) -> Option<String> {
+    match opts.user {
+        false => Some(opts.state_file.clone().unwrap_or_else(|| vmouse::SYSTEM_STATE.to_string())),
+        true => opts.state_file.clone().or_else(vmouse::user_state_path),
+    }
+}
+
+/// Parse an octal file mode (eg. `0660`)
+fn parse_mode(s: &str) -> Result<u32, std::num::ParseIntError> {
+    let s = s.trim_start_matches("0o");
+    u32::from_str_radix(s, 8)
+}
+
+/// Resolve a group name or numeric gid
+fn resolve_group(group: &str) -> anyhow::Result<u32> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Ok(gid);
+    }
+
+    let name = CString::new(group)?;
+    let g = unsafe { libc::getgrnam(name.as_ptr()) };
+    if g.is_null() {
+        return Err(anyhow::anyhow!("Unknown group '{}'", group));
+    }
+
+    Ok(unsafe { (*g).gr_gid })
+}
+
+/// Apply socket mode and group ownership, logging the resulting permissions
+///
+/// Returns the socket-owning gid
+fn configure_socket(path: &str, c: &SocketConfig, allow_insecure: bool) -> anyhow::Result<u32> {
+    if let Some(group) = &c.group {
+        let gid = resolve_group(group)?;
+
+        let p = CString::new(path)?;
+        if unsafe { libc::chown(p.as_ptr(), u32::MAX, gid) } < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+
+    if let Some(mode) = c.mode {
+        // Refuse world-writable sockets unless explicitly allowed
+        if mode & 0o002 != 0 && !allow_insecure {
+            return Err(anyhow::anyhow!(
+                "Refusing world-writable socket mode {:04o}, use --allow-insecure-socket to override",
+                mode
+            ));
+        }
+
+        std::fs::set_permissions(path, Permissions::from_mode(mode))?;
+    }
+
+    let m = std::fs::metadata(path)?;
+    info!(
+        "Socket '{}' mode: {:04o} uid: {} gid: {}",
+        path,
+        m.mode() & 0o7777,
+        m.uid(),
+        m.gid()
+    );
+
+    Ok(m.gid())
+}
+
+/// Load a config file, applying presets for bound devices as they are not part of the file
+fn load_config<'a>(path: &str, bound: impl Iterator<Item = &'a UsbDevice>) -> Result<Config, vmouse::Error> {
+    let mut c = Config::from(&ConfigFile::load(path)?);
+
+    for d in bound {
+        apply_preset(&mut c, d);
+    }
+
+    Ok(c)
+}
+
+/// Apply the built-in preset for a known device without its own config
+fn apply_preset(config: &mut Config, d: &UsbDevice) {
+    if config.has_device(d) {
+        return;
+    }
+
+    if let Some(p) = vmouse::preset_for(d) {
+        info!(device = %d.to_string(), "Applying preset '{}' ({})", p.name, p.model);
+        config.devices.insert(UsbDevice { name: None, ..d.clone() }, p.axes_for(config.version));
+    }
+}
+
+/// Timeout for writing a response frame to a client
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Interval for checking pending binds
+const BIND_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time allowed for each client and device task to close on shutdown
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Interval for polling device battery levels
+const BATTERY_POLL: Duration = Duration::from_secs(60);
+
+/// Battery level (%) below which listeners are notified
+const BATTERY_LOW: u8 = 15;
+
 pub struct Daemon {
     id: u32,
     config: Config,
     config_file: String,
-    state: AxisCollection<f32>,
-    evt_tx: Sender<(UsbDevice, InputEvent)>,
+    /// Directory of the initial config file, non-root clients may save configs here
+    config_dir: Option<PathBuf>,
+    socket_config: SocketConfig,
+    socket_gid: u32,
+    /// Bound devices and their reader tasks by path
+    devices: HashMap<String, DeviceHandle>,
+    /// Binds waiting for a device to appear
+    pending_binds: Vec<PendingBind>,
+    state: AxisState,
+    device_state: HashMap<UsbDevice, AxisState>,
+    evt_tx: Sender<DeviceEvent>,
     enabled: bool,
+    /// Per-device output enabled flags by `vid:pid`
+    device_enabled: HashMap<String, bool>,
+    /// Active axis calibration
+    calibration: Option<Calibration>,
+    /// Event codes enabled on the virtual device
+    capabilities: Vec<EventCode>,
+    /// Absolute pointer position state
+    abs_pointer: AbsPointer,
+    /// Virtual output devices, set once the devices are created
+    output_devices: Vec<OutputDevice>,
+    /// Event and latency metrics
+    metrics: Metrics,
+    /// Pending outputs when rate limited by `max_output_hz`
+    coalesce: Option<Coalescer>,
+    /// Dominant axis snap state by device
+    snap: HashMap<UsbDevice, Snap>,
+    /// Output watchdog, zeroing outputs without fresh input
+    auto_zero: AutoZero,
+    /// Per-output device values for aggregation policies
+    aggregate: Aggregator,
+    /// Runtime state file, if persisting state
+    persist: Option<StateFile>,
+    /// Last input event time, for idle detection
+    last_input: Instant,
+    /// Whether output is idle after `idle_timeout_s` without input
+    idle: bool,
 
     clients: HashMap<u32, ClientHandle>,
 
@@ -189,15 +821,38 @@ pub struct Daemon {
 }
 
 impl Daemon {
-    fn new(config: Config, config_file: String, evt_tx: Sender<(UsbDevice, InputEvent)>, tick_tx: Sender<()>) -> Self {
+    fn new(config: Config, config_file: String, socket_config: SocketConfig, socket_gid: u32, evt_tx: Sender<DeviceEvent>, tick_tx: Sender<()>) -> Self {
+        let capabilities = vmouse::capabilities_for(&config);
+        let abs_pointer = AbsPointer::new(config.abs_pointer.unwrap_or_default());
+        let config_dir = Path::new(&config_file).parent().map(Path::to_path_buf);
+
         Self {
             id: 0,
             config,
             config_file,
+            config_dir,
+            socket_config,
+            socket_gid,
+            devices: HashMap::new(),
+            pending_binds: vec![],
             enabled: true,
+            device_enabled: HashMap::new(),
+            calibration: None,
+            capabilities,
+            abs_pointer,
+            output_devices: vec![],
+            metrics: Metrics::new(),
+            coalesce: None,
+            snap: HashMap::new(),
+            auto_zero: AutoZero::default(),
+            aggregate: Aggregator::default(),
+            persist: None,
+            last_input: Instant::now(),
+            idle: false,
             evt_tx,
             tick_tx,
-            state: AxisCollection::with_axis(|_| Default::default()),
+            state: AxisState::default(),
+            device_state: HashMap::new(),
             clients: Default::default(),
             changed: false,
             update_task: None,
@@ -213,10 +868,26 @@ impl Daemon {
         let id = self.id;
         self.id = self.id.wrapping_add(1);
 
+        // Fetch peer credentials for authorisation
+        let auth = match PeerCred::from_socket(&stream) {
+            Ok(c) => {
+                info!(client_id = id, uid = c.uid, pid = c.pid, "Client connected");
+                ClientAuth::Peer(c)
+            }
+            Err(e) => {
+                warn!(client_id = id, "Client connected, failed to fetch peer credentials: {:?}", e);
+                ClientAuth::Unknown
+            }
+        };
+
         let (resp_tx, mut resp_rx) = async_std::channel::unbounded();
         let tx = resp_tx.clone();
 
         let mut buff = [0u8; 1024];
+        let mut decoder = Decoder::new();
+
+        // Responses are enveloped once the client sends an enveloped request
+        let mut enveloped = false;
 
         debug!("Spawning task for client: {}", id);
 
@@ -235,18 +906,38 @@ impl Daemon {
 
                         debug!("Received: {:02x?}", r);
 
-                        let c: Command = bincode::deserialize(r)?;
-
-                        ctl_tx.send(CommandHandle{id, c, tx: resp_tx.clone()}).await?;
+                        // Forward all complete frames
+                        decoder.push(r);
+                        while let Some(f) = decoder.decode_frame()? {
+                            enveloped |= f.id.is_some();
+                            let req = f.id.unwrap_or(vmouse::BROADCAST_ID);
+                            ctl_tx.send(CommandHandle{id, req, c: f.cmd, tx: resp_tx.clone(), cred: None}).await?;
+                        }
                     },
                     // Forward responses
-                    c = resp_rx.next() => {
-                        if let Some(c) = c {
-                            let enc: Vec<u8> = bincode::serialize(&c)?;
+                    r = resp_rx.next() => {
+                        if let Some(r) = r {
+                            let enc: Vec<u8> = match enveloped {
+                                true => vmouse::encode_response(&r)?,
+                                false => vmouse::encode(&r.cmd)?,
+                            };
 
                             trace!("Sending: {:02x?}", enc);
 
-                            stream.write(&enc).await?;
+                            // Write complete frames, disconnecting clients that stop reading
+                            match write_frame(&mut stream, &enc, CLIENT_WRITE_TIMEOUT).await {
+                                Ok(_) => (),
+                                Err(e) if e.kind() == ErrorKind::TimedOut => {
+                                    warn!(client_id = id, "Client write timed out");
+                                    break Err(e.into());
+                                },
+                                Err(e) => break Err(e.into()),
+                            }
+
+                            // Close connections with incompatible protocol versions or on shutdown
+                            if let Command::Error(ErrorCode::VersionMismatch { .. }) | Command::ShuttingDown = r.cmd {
+                                break Ok(());
+                            }
                         } else {
                             break Ok(());
                         }
@@ -255,25 +946,31 @@ impl Daemon {
                 )
             };
 
-            debug!("Disconnecting from client: {}", id);
+            info!(client_id = id, "Client disconnected");
 
             ctl_tx
                 .send(CommandHandle {
                     id,
+                    req: vmouse::BROADCAST_ID,
                     c: Command::Disconnect,
                     tx: resp_tx.clone(),
+                    cred: None,
                 })
                 .await?;
 
             res
-        });
+        }.instrument(info_span!("client", client_id = id)));
 
         // Create client handle
         let client = ClientHandle {
             id,
-            _h: h,
+            task: h,
             tx,
-            listen: false,
+            listen: None,
+            raw_limit: RateLimit::new(),
+            last_seen: Instant::now(),
+            pinged: None,
+            auth,
         };
 
         // Add client to tracking
@@ -299,159 +996,1853 @@ impl Daemon {
         }
     }
 
+    /// Remove a client, cancelling its task and stopping updates if no longer required
+    async fn remove_client(&mut self, id: u32) {
+        debug!("Removing client: {}", id);
+
+        self.pending_binds.retain(|p| p.client != id);
+
+        if let Some(c) = self.clients.remove(&id) {
+            // Internal (D-Bus) tasks are not tied to a socket
+            if !matches!(c.auth, ClientAuth::Internal) {
+                let _ = c.task.cancel().await;
+            }
+        }
+
+        self.disable_update_task().await;
+    }
+
+    /// Client keepalive interval, `None` if disabled
+    fn keepalive_interval(&self) -> Option<Duration> {
+        match self.socket_config.keepalive.unwrap_or(KEEPALIVE_DEFAULT) {
+            0 => None,
+            s => Some(Duration::from_secs(s)),
+        }
+    }
+
+    /// Ping idle listening clients, removing clients that did not respond to the previous ping
+    async fn keepalive(&mut self) {
+        let interval = match self.keepalive_interval() {
+            Some(i) => i,
+            None => return,
+        };
+
+        let mut dead = vec![];
+
+        for (id, c) in self.clients.iter_mut() {
+            if c.listen.is_none() || matches!(c.auth, ClientAuth::Internal) {
+                continue;
+            }
+
+            match c.pinged {
+                Some(p) if p.elapsed() >= interval => dead.push(*id),
+                Some(_) => (),
+                None if c.last_seen.elapsed() >= interval => {
+                    let _ = c.tx.try_send(Response::broadcast(Command::Ping));
+                    c.pinged = Some(Instant::now());
+                }
+                None => (),
+            }
+        }
+
+        for id in dead {
+            warn!("Client {} did not respond to keepalive, disconnecting", id);
+            self.remove_client(id).await;
+        }
+    }
+
+    /// Stop the state update task once no clients are subscribed to state
+    async fn disable_update_task(&mut self) {
+        // Ticks are also required for idle detection and the output watchdog
+        let listening = self.clients.values().any(|c| c.subscribed(Topic::State)) || self.ticks_required();
+
+        if listening {
+            return;
+        }
+        if let Some(t) = self.update_task.take() {
+            let _ = t.cancel().await;
+        }
+    }
+
     async fn attach_device(&mut self, device: String) -> anyhow::Result<()> {
-        // Connect to device
-        let f = File::open(&device)?;
-        let d = Device::new_from_file(f)?;
+        // Resolve symlinks (eg. `/dev/input/by-id/*-event-joystick`) to the device node
+        let device = std::fs::canonicalize(&device)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or(device);
+
+        // Reject duplicate binds, a second reader would double every event
+        if self.devices.contains_key(&device) {
+            return Err(anyhow::anyhow!("Device '{}' already bound", device));
+        }
+
+        // Connect to device using the appropriate backend
+        if vmouse::is_hidraw_path(&device) {
+            // LED output requires write access, fall back to read only
+            let d = match !self.config.feedback.is_empty() {
+                true => HidrawDevice::open_rw(&device).or_else(|_| HidrawDevice::open(&device))?,
+                false => HidrawDevice::open(&device)?,
+            };
+            if self.config.passthrough_for(&d.device()) {
+                warn!(path = %device, "Passthrough is not supported for hidraw devices");
+            }
+            if self.config.input_kind_for(&d.device()) == vmouse::InputKind::Mouse {
+                warn!(path = %device, "Mouse input is not supported for hidraw devices");
+            }
+            self.attach_source(device, d, None)
+        } else {
+            let f = match !self.config.feedback.is_empty() {
+                true => OpenOptions::new().read(true).write(true).open(&device).or_else(|_| File::open(&device))?,
+                false => File::open(&device)?,
+            };
+            let mut d = Device::new_from_file(f)?;
+
+            // Grab mouse inputs so the original pointer motion is suppressed,
+            // the grab is released when the device is closed on unbind
+            if self.config.input_kind_for(&d.device()) == vmouse::InputKind::Mouse {
+                d.grab(GrabMode::Grab)
+                    .map_err(|e| anyhow::anyhow!("Failed to grab mouse device '{}': {}", device, e))?;
+                debug!(path = %device, "Grabbed mouse device");
+            }
+
+            // Passthrough failures are not fatal, mouse emulation continues without the mirror
+            let p = match self.config.passthrough_for(&d.device()) && !vmouse::is_passthrough(&d.device()) {
+                true => Passthrough::new(&d)
+                    .map_err(|e| warn!(path = %device, "Failed to create passthrough device: {}", e))
+                    .ok(),
+                false => None,
+            };
+
+            self.attach_source(device, d, p)
+        }
+    }
 
+    fn attach_source<S>(&mut self, device: String, d: S, mut passthrough: Option<Passthrough>) -> anyhow::Result<()>
+    where
+        S: InputSource + Send + Sync + 'static,
+    {
         let evt_tx = self.evt_tx.clone();
 
-        // Log device info
-        if let Some(n) = d.name() {
-            info!(
-                "Connected to device: '{}' ({:04x}:{:04x})",
-                n,
-                d.vendor_id(),
-                d.product_id()
-            );
+        let h = d.device();
+        let info = h.clone();
+        let device_path = device.clone();
+
+        // Never bind our own passthrough devices, this would loop events back as input
+        if vmouse::is_passthrough(&h) {
+            return Err(anyhow::anyhow!("Device '{}' is a vmouse passthrough device", device));
         }
 
-        let h = UsbDevice{
-            name: d.name().map(|v| v.to_string() ),
-            vid: d.vendor_id(),
-            pid: d.product_id(),
-        };
+        // Reject other axis nodes of a bound device, button only nodes may be bound alongside
+        let identity = d.identity();
+        let axes = d.has_axes();
+        if let Some(o) = self.devices.values().find(|o| o.identity == identity && o.axes && axes) {
+            return Err(anyhow::anyhow!("Device '{}' is another node of bound device '{}' ({})", device, o.path, identity));
+        }
 
-        self.config.devices.insert(h.clone(), Default::default());
+        info!(device = %h.to_string(), path = %device, name = h.name.as_deref().unwrap_or(""), "Device bound");
+        if let Some(p) = &passthrough {
+            info!(device = %h.to_string(), "Passthrough device: {}", p.devnode().unwrap_or(""));
+        }
+
+        apply_preset(&mut self.config, &h);
+
+        let span = info_span!("device", device = %h.to_string(), path = %device);
 
         // Wrap device in async adapter
         let a = smol::Async::new(d)?;
 
-        // Setup event listening task
-        let _h: JoinHandle<Result<(), anyhow::Error>> = async_std::task::spawn(async move {
+        let (cancel, cancel_rx) = async_std::channel::bounded::<()>(1);
+        let (leds, leds_rx) = async_std::channel::bounded::<LedPattern>(4);
+
+        // Wireless devices report battery level via sysfs, polled by the reader task
+        let supply = vmouse::find_power_supply(&device);
+        let battery = supply.as_deref().and_then(vmouse::read_capacity);
+        if let Some(b) = battery {
+            info!(device = %h.to_string(), "Battery: {}%", b);
+        }
+        let mut poll = async_std::stream::interval(BATTERY_POLL).fuse();
+
+        // Set LEDs for the active profile
+        if let Some(p) = self.config.feedback_for(&h) {
+            let _ = leds.try_send(p);
+        }
+
+        // Setup event listening task, the passthrough device is owned by
+        // (and removed with) the task
+        let t: JoinHandle<Result<(), anyhow::Error>> = async_std::task::spawn(async move {
+            // LED failures are reported once per device
+            let mut leds_failed = false;
+
             let r = loop {
                 futures::select!(
                     // Read on incoming events
-                    r = a.read_with(|d| d.next_event(ReadFlag::NORMAL)).fuse() => {
+                    r = a.read_with(|d| d.read_events()).fuse() => {
                         match r {
-                            Ok((_status, evt)) => evt_tx.send((h.clone(), evt)).await?,
-                            Err(e) => break Err(e.into()),
+                            Ok(events) => {
+                                for evt in events {
+                                    // Raw events are mirrored regardless of the device enable flag,
+                                    // the mirror is dropped on the first write failure
+                                    if let Some(Err(e)) = passthrough.as_ref().map(|p| p.forward(&evt)) {
+                                        warn!(device = %h.to_string(), "Passthrough write failed, removing passthrough device: {}", e);
+                                        passthrough = None;
+                                    }
+                                    evt_tx.send(DeviceEvent::Input(h.clone(), evt)).await?;
+                                }
+                            },
+                            Err(e) => {
+                                error!(device = %h.to_string(), path = %device, "Device error: {}", e);
+                                break Err(e.into());
+                            },
+                        }
+                    },
+                    // Write LED feedback, failures are not fatal
+                    p = leds_rx.recv().fuse() => {
+                        // Handle dropped
+                        let p = match p {
+                            Ok(p) => p,
+                            Err(_) => break Ok(()),
+                        };
+
+                        match a.get_ref().set_leds(p) {
+                            Ok(_) => debug!(device = %h.to_string(), "Set LEDs: {:#04x}", p.0),
+                            Err(e) if !leds_failed => {
+                                warn!(device = %h.to_string(), path = %device, "Failed to set LEDs: {}", e);
+                                leds_failed = true;
+                            }
+                            Err(_) => (),
+                        }
+                    },
+                    // Poll battery level
+                    _ = poll.next() => {
+                        if let Some(s) = &supply {
+                            evt_tx.send(DeviceEvent::Battery(device.clone(), vmouse::read_capacity(s))).await?;
                         }
                     },
-                    // TODO: exit handler
+                    // Stop on unbind or shutdown
+                    _ = cancel_rx.recv().fuse() => break Ok(()),
                 )
             };
 
             debug!("Disconnecting from device: {}", device);
 
+            let _ = evt_tx.send(DeviceEvent::Removed(device, h)).await;
+
             r
-        });
+        }.instrument(span));
+
+        self.devices.insert(device_path.clone(), DeviceHandle { info, path: device_path, identity, axes, task: t, cancel, leds: Some(leds), battery });
 
         Ok(())
     }
 
-    async fn handle_cmd(&mut self, h: &CommandHandle) -> anyhow::Result<Option<Command>> {
-        let resp = match &h.c {
-            Command::Ping => Some(Command::Ok),
-            Command::Bind { event } => {
-                info!("Binding device: {}", event);
-                match self.attach_device(event.clone()).await {
-                    Ok(_) => {
-                        info!("Device {} attach OK!", event);
-                        Some(Command::Ok)
-                    }
-                    Err(e) => {
-                        error!("Device {} attach failed: {:?}", event, e);
-                        Some(Command::Failed)
-                    }
-                }
-            }
-            Command::Enable { enabled } => {
-                self.enabled = *enabled;
-                Some(Command::Ok)
-            }
-            Command::GetState => Some(Command::State(self.state)),
-            Command::GetConfig => Some(Command::SetConfig(self.config.clone())),
-            Command::SetConfig(c) => {
-                debug!("Updating config: {:?}", c);
+    /// Record a device battery level, notifying listeners when it falls below [`BATTERY_LOW`]
+    fn update_battery(&mut self, path: &str, level: Option<u8>) {
+        let h = match self.devices.get_mut(path) {
+            Some(h) => h,
+            None => return,
+        };
 
-                self.config = c.clone();
+        let prev = std::mem::replace(&mut h.battery, level);
+        let info = h.info.clone();
 
-                Some(Command::Ok)
-            },
-            Command::WriteConfig => {
-                info!("Writing updated config to: {}", self.config_file);
+        if let Some(l) = level.filter(|l| *l < BATTERY_LOW && prev.map(|p| p >= BATTERY_LOW).unwrap_or(true)) {
+            warn!(device = %info.to_string(), path = %path, "Battery low: {}%", l);
+            self.broadcast(Command::BatteryLow { device: self.config.aliased(&info), percent: l });
+        }
+    }
 
-                let c = ConfigFile{
-                    devices: self.config.devices.iter().map(|v| DeviceConfig{ vid: v.0.vid, pid: v.0.pid, axes: v.1.clone() }).collect()
-                };
+    /// Record a device output against the output aggregation policy, returning whether it is written
+    fn admit(&mut self, dev: &UsbDevice, map: vmouse::Map, val: f32) -> bool {
+        let config = &self.config;
+        match config.aggregate_for(map) {
+            Some(p) => self.aggregate.admit(p, aggregate::rank_for(config, p), dev, map, val, Instant::now()),
+            None => true,
+        }
+    }
 
-                let s = match toml::to_string_pretty(&c) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        error!("Failed to encode config: {:?}", e);
-                        return Ok(Some(Command::Failed))
+    /// Check whether a device output would be written under the output aggregation policy,
+    /// without recording it (eg. for outputs held until the end of the frame)
+    fn would_admit(&self, dev: &UsbDevice, map: vmouse::Map, val: f32) -> bool {
+        match self.config.aggregate_for(map) {
+            Some(p) => self.aggregate.peek(p, aggregate::rank_for(&self.config, p), dev, map, val, Instant::now()),
+            None => true,
+        }
+    }
+
+    /// Write (or buffer, when rate limited) a mapped device output value, returning whether it was
+    /// admitted under the output aggregation policy
+    fn emit(&mut self, dev: &UsbDevice, outputs: &Outputs, map: vmouse::Map, val: f32, events: &[vmouse::OutputEvent]) -> anyhow::Result<bool> {
+        // Drop outputs held by another device
+        if !self.admit(dev, map, val) {
+            return Ok(false);
+        }
+
+        // Integrate absolute pointer positions
+        let pos = self.abs_pointer.update(map, val, Instant::now());
+
+        match self.coalesce.as_mut() {
+            // Buffer outputs until the next flush when rate limited
+            Some(c) => {
+                c.push(map, val);
+                if let Some((code, pos)) = pos {
+                    c.push_pointer(code, pos);
+                }
+            }
+            None => {
+                // Stamp outputs at emission, source times may be stale
+                let ts = vmouse::output_time();
+                outputs.write(map, ts, events)?;
+                self.metrics.outputs_clamped += outputs.take_clamped();
+
+                if let Some((code, pos)) = pos {
+                    outputs.abs_pointer_event(ts, code, pos)?;
+                }
+                if map != vmouse::Map::None {
+                    self.metrics.output(1);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Send LED patterns for the active profiles to bound devices
+    fn update_feedback(&self) {
+        for h in self.devices.values() {
+            if let (Some(tx), Some(p)) = (&h.leds, self.config.feedback_for(&h.info)) {
+                let _ = tx.try_send(p);
+            }
+        }
+    }
+
+    /// Bind a device by path
+    async fn bind(&mut self, event: &str) -> Command {
+        info!("Binding device: {}", event);
+
+        match self.attach_device(event.to_string()).await {
+            Ok(_) => {
+                info!("Device {} attach OK!", event);
+                notify::status(&format!("Running, {} devices bound", self.devices.len()));
+                self.broadcast(Command::Devices(self.devices.values().map(|h| h.info.clone()).collect()));
+                Command::Ok
+            }
+            Err(e) => {
+                error!("Device {} attach failed: {:?}", event, e);
+                Command::Error(ErrorCode::BindFailed)
+            }
+        }
+    }
+
+    /// Find the path of an unbound device matching `vid:pid`
+    fn find_unbound(&self, id: &UsbDevice) -> Option<String> {
+        vmouse::find_devices(id).into_iter().find(|p| !self.devices.contains_key(p))
+    }
+
+    /// Resolve pending binds for devices that have appeared, failing those past their deadline
+    async fn poll_binds(&mut self) {
+        if self.pending_binds.is_empty() {
+            return;
+        }
+
+        let now = Instant::now();
+
+        for p in std::mem::take(&mut self.pending_binds) {
+            let r = match self.find_unbound(&p.device) {
+                Some(path) => self.bind(&path).await,
+                None if now >= p.deadline => {
+                    warn!(client_id = p.client, device = %p.device.to_string(), "Timed out waiting for device");
+                    Command::Error(ErrorCode::DeviceNotFound)
+                }
+                None => {
+                    self.pending_binds.push(p);
+                    continue;
+                }
+            };
+
+            let _ = p.tx.try_send(Response { id: p.req, cmd: r });
+        }
+    }
+
+    /// Notify clients of shutdown, waiting for pending writes and device tasks to finish
+    async fn shutdown(&mut self) {
+        for c in self.clients.values() {
+            let _ = c.tx.try_send(Response::broadcast(Command::ShuttingDown));
+        }
+
+        // Socket client tasks close after writing the notification,
+        // internal (D-Bus) tasks are not tied to a socket
+        for (id, c) in self.clients.drain() {
+            if matches!(c.auth, ClientAuth::Internal) {
+                continue;
+            }
+            if async_std::future::timeout(SHUTDOWN_TIMEOUT, c.task).await.is_err() {
+                warn!(client_id = id, "Client did not close before shutdown timeout");
+            }
+        }
+
+        for (_path, h) in self.devices.drain() {
+            let _ = h.cancel.try_send(());
+            if async_std::future::timeout(SHUTDOWN_TIMEOUT, h.task).await.is_err() {
+                warn!(path = %h.path, "Device task did not stop before shutdown timeout");
+            }
+        }
+    }
+
+    /// Send a message to all clients subscribed to its topic
+    fn broadcast(&mut self, cmd: Command) {
+        let topic = match Topic::for_command(&cmd) {
+            Some(t) => t,
+            None => return,
+        };
+
+        // Client channels are unbounded, sends only fail once a client has closed
+        for (_id, c) in self.clients.iter().filter(|(_id, c)| c.subscribed(topic) ) {
+            if c.tx.try_send(Response::broadcast(cmd.clone())).is_err() {
+                self.metrics.broadcasts_dropped += 1;
+            }
+        }
+    }
+
+    /// Send a raw value to subscribed clients, rate limited per client
+    fn broadcast_raw(&mut self, v: AxisValue, t: RawTiming) {
+        let max = self.socket_config.raw_rate.unwrap_or(RAW_RATE_DEFAULT);
+
+        for c in self.clients.values_mut().filter(|c| c.subscribed(Topic::RawValues)) {
+            if !c.raw_limit.allow(max) || c.tx.try_send(Response::broadcast(Command::RawValue(v, t))).is_err() {
+                self.metrics.broadcasts_dropped += 1;
+            }
+        }
+    }
+
+    /// Check whether a request may issue privileged commands, using the caller
+    /// credentials where provided, otherwise the client peer credentials
+    fn authorised(&self, h: &CommandHandle) -> bool {
+        match self.caller(h) {
+            Some(cred) => cred.is_admin(self.socket_gid, &self.socket_config),
+            None => false,
+        }
+    }
+
+    /// Fetch credentials for a request, per-request for internal clients (eg. D-Bus) or for the client socket
+    fn caller<'a>(&'a self, h: &'a CommandHandle) -> Option<&'a PeerCred> {
+        match (&h.cred, self.clients.get(&h.id).map(|c| &c.auth)) {
+            (Some(cred), _) => Some(cred),
+            (None, Some(ClientAuth::Peer(cred))) => Some(cred),
+            _ => None,
+        }
+    }
+
+    /// Check whether a client may save configs to a path
+    ///
+    /// Root may save anywhere, other admins only to the config file directory or `config_dirs`
+    fn save_permitted(&self, h: &CommandHandle, path: &str) -> bool {
+        if self.caller(h).map(|c| c.uid == 0).unwrap_or(false) {
+            return true;
+        }
+
+        let dirs = self.config_dir.iter()
+            .map(PathBuf::as_path)
+            .chain(self.socket_config.config_dirs.iter().map(Path::new));
+
+        config_path_within(path, dirs)
+    }
+
+    /// Check whether output is enabled for a device
+    fn output_enabled(&self, d: &UsbDevice) -> bool {
+        self.enabled && self.device_enabled.get(&d.to_string()).copied().unwrap_or(true)
+    }
+
+    /// Build a status summary for [`Command::Status`]
+    fn status(&self) -> StatusInfo {
+        StatusInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol: PROTOCOL_VERSION,
+            uptime: self.metrics.uptime(),
+            enabled: self.enabled,
+            device_enabled: self.device_enabled.clone(),
+            devices: self.devices.values()
+                .map(|h| DeviceStatus {
+                    device: self.config.aliased(&h.info),
+                    path: h.path.clone(),
+                    identity: h.identity.clone(),
+                    battery: h.battery,
+                })
+                .collect(),
+            clients: self.clients.len(),
+            listening: self.clients.values().filter(|c| c.listen.is_some()).count(),
+            devnodes: self.output_devices.iter().filter_map(|o| o.devnode.clone()).collect(),
+            events_in: self.metrics.events_in,
+            events_mapped: self.metrics.events_mapped,
+            events_out: self.metrics.events_out,
+            latency_us: self.metrics.latency_us,
+            latency_max_us: self.metrics.latency_max_us,
+            idle: self.idle,
+        }
+    }
+
+    /// Record input activity, leaving idle state
+    fn wake(&mut self) {
+        self.last_input = Instant::now();
+
+        if self.idle {
+            info!("Input resumed, output active");
+            self.idle = false;
+            notify::status(&format!("Running, {} devices bound", self.devices.len()));
+        }
+    }
+
+    /// Whether the tick task is required regardless of state listeners
+    fn ticks_required(&self) -> bool {
+        self.config.idle_timeout_s.is_some()
+            || self.config.auto_zero_window().is_some()
+            || self.persist.as_ref().map(|p| p.pending()).unwrap_or(false)
+    }
+
+    /// Restore runtime state, unknown profiles are skipped and missing state keeps config defaults
+    fn restore_state(&mut self, f: StateFile) {
+        if let Some(s) = RuntimeState::load(f.path()) {
+            info!("Restoring runtime state from '{}'", f.path());
+
+            self.enabled = s.enabled;
+            self.device_enabled = s.device_enabled.into_iter().collect();
+
+            for (device, profile) in &s.active {
+                if let Err(e) = self.config.activate(device, profile) {
+                    debug!("Skipping saved profile '{}' for device {}: {}", profile, device, e);
+                }
+            }
+        }
+
+        self.persist = Some(f);
+    }
+
+    /// Mark runtime state as changed, written on a later tick
+    async fn state_changed(&mut self) {
+        if let Some(p) = self.persist.as_mut() {
+            p.mark(Instant::now());
+            self.enable_update_task().await;
+        }
+    }
+
+    /// Write runtime state once the debounce has elapsed, or immediately with `force`
+    fn persist_state(&mut self, force: bool) {
+        let p = match self.persist.as_mut() {
+            Some(p) => p,
+            None => return,
+        };
+        if !p.due(Instant::now(), force) {
+            return;
+        }
+
+        let s = RuntimeState {
+            enabled: self.enabled,
+            device_enabled: self.device_enabled.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            active: self.config.active.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        };
+
+        match s.save(p.path()) {
+            Ok(_) => debug!("Wrote runtime state to '{}'", p.path()),
+            Err(e) => warn!("Failed to write runtime state '{}': {}", p.path(), e),
+        }
+    }
+
+    /// Zero outputs without input within the watchdog window
+    ///
+    /// Latched outputs (joystick axes, absolute pointer) return to rest, relative outputs
+    /// simply stop. Pending values and axis state are cleared in both cases.
+    fn expire_outputs(&mut self, outputs: Option<&Outputs>) -> anyhow::Result<()> {
+        let window = match self.config.auto_zero_window() {
+            Some(w) => w,
+            None => return Ok(()),
+        };
+
+        for (dev, axis, map) in self.auto_zero.expire(window, Instant::now()) {
+            debug!(device = %dev.to_string(), "No input on {} for {:?}, zeroing {} output", axis, window, map);
+
+            if let Some(c) = self.coalesce.as_mut() {
+                c.clear(map);
+            }
+
+            if let (true, Some(o)) = (self.output_enabled(&dev), outputs) {
+                match map {
+                    vmouse::Map::Abs(_) => self.emit(&dev, o, map, 0.0, &map.output_events(0.0, o.abs_range))?,
+                    vmouse::Map::AbsX | vmouse::Map::AbsY => self.emit(&dev, o, map, 0.0, &[])?,
+                    _ => false,
+                };
+            }
+
+            if let Some(s) = self.device_state.get_mut(&dev) {
+                s.raw[axis] = 0.0;
+                s.output[axis] = 0.0;
+            }
+            self.state.raw[axis] = 0.0;
+            self.state.output[axis] = 0.0;
+            self.changed = true;
+        }
+
+        Ok(())
+    }
+
+    /// Check for the idle timeout, returning whether output is idle
+    fn check_idle(&mut self) -> bool {
+        if let Some(t) = self.config.idle_timeout_s {
+            if !self.idle && self.last_input.elapsed() >= Duration::from_secs(t) {
+                info!("No input for {}s, output idle", t);
+                self.idle = true;
+                notify::status(&format!("Idle, {} devices bound", self.devices.len()));
+            }
+        }
+
+        self.idle
+    }
+
+    /// Update latency metrics for an output written from a source event
+    fn record_latency(&mut self, source: &TimeVal) {
+        if let Some(l) = vmouse::event_latency(source) {
+            self.metrics.latency(l);
+        }
+    }
+
+    /// Write the current config (and socket config) atomically, format detected by extension
+    fn save_config(&self, path: &str) -> Result<(), vmouse::Error> {
+        let c = ConfigFile::new(&self.config, self.socket_config.clone());
+        c.save(path, ConfigFormat::from_path(path))
+    }
+
+    /// Validate and apply a config, warning for changes that require a restart
+    async fn apply_config(&mut self, c: Config, id: u32) -> Command {
+        debug!("Updating config: {:?}", c);
+
+        // Reject configs with hard errors
+        let errors = c.errors();
+        if !errors.is_empty() {
+            for e in &errors {
+                warn!("Rejecting config from client {}: {}", id, e);
+            }
+            return Command::Error(ErrorCode::InvalidConfig);
+        }
+
+        self.replace_config(c).await;
+        info!(client_id = id, "Applied config");
+
+        self.broadcast(Command::SetConfig(self.config.clone()));
+
+        Command::Ok
+    }
+
+    /// Reload the config file after a change on disk, keeping the current config if invalid
+    async fn reload_config(&mut self) {
+        let path = self.config_file.clone();
+
+        let c = match load_config(&path, self.devices.values().map(|h| &h.info)) {
+            Ok(c) => c,
+            Err(e) => {
+                warn!("Failed to reload config '{}', keeping current config: {}", path, e);
+                self.broadcast(Command::ConfigInvalid { path, errors: vec![e.to_string()] });
+                return;
+            }
+        };
+
+        // Skip our own writes and saves without changes
+        if c == self.config {
+            debug!("Config '{}' unchanged", path);
+            return;
+        }
+
+        let errors: Vec<_> = c.errors().iter().map(|e| e.to_string()).collect();
+        if !errors.is_empty() {
+            for e in &errors {
+                warn!("Invalid config '{}': {}", path, e);
+            }
+            warn!("Keeping current config");
+            self.broadcast(Command::ConfigInvalid { path, errors });
+            return;
+        }
+
+        self.replace_config(c).await;
+        info!("Reloaded config '{}'", path);
+
+        self.broadcast(Command::SetConfig(self.config.clone()));
+    }
+
+    /// Replace the active config, warning for changes that require a restart
+    async fn replace_config(&mut self, c: Config) {
+        // Virtual device capabilities are fixed on creation
+        let missing: Vec<_> = vmouse::capabilities_for(&c).into_iter()
+            .filter(|e| !self.capabilities.contains(e))
+            .collect();
+        if !missing.is_empty() {
+            warn!("Config requires event codes not enabled on the virtual device ({:?}), restart vmoused to apply", missing);
+        }
+        if c.split_outputs != self.config.split_outputs
+            || c.abs_range != self.config.abs_range
+            || c.abs_pointer != self.config.abs_pointer
+            || c.max_output_hz != self.config.max_output_hz
+            || (vmouse::uses_abs_pointer(&c) && !vmouse::uses_abs_pointer(&self.config))
+            || (vmouse::uses_joystick(&c) && !vmouse::uses_joystick(&self.config))
+        {
+            warn!("Output devices changed, restart vmoused to apply");
+        }
+        if c.passthrough != self.config.passthrough {
+            warn!("Passthrough devices changed, rebind devices to apply");
+        }
+
+        if c.watch_config != self.config.watch_config {
+            warn!("Config watching changed, restart vmoused to apply");
+        }
+
+        if c.active != self.config.active {
+            self.state_changed().await;
+        }
+
+        self.config = c;
+
+        self.update_feedback();
+
+        // Start or stop ticks for idle detection and the output watchdog
+        match self.ticks_required() {
+            true => self.enable_update_task().await,
+            false => self.disable_update_task().await,
+        }
+    }
+
+    async fn handle_cmd(&mut self, h: &CommandHandle) -> anyhow::Result<Option<Command>> {
+        // Gate mutating commands on client credentials
+        if h.c.is_privileged() && !self.authorised(h) {
+            let uid = self.caller(h).map(|c| c.uid);
+            warn!("Client {} (uid: {:?}) not authorised for command: {:?}", h.id, uid, h.c);
+            return Ok(Some(Command::Error(ErrorCode::PermissionDenied)));
+        }
+
+        // Any message from a client counts as activity
+        if let Some(c) = self.clients.get_mut(&h.id) {
+            c.last_seen = Instant::now();
+            c.pinged = None;
+        }
+
+        let resp = match &h.c {
+            Command::Hello { version, features } => {
+                match vmouse::protocol_compatible(*version) {
+                    true => {
+                        debug!("Client {} protocol v{} (features: {:?})", h.id, vmouse::protocol_string(*version), features);
+                        Some(Command::Hello { version: PROTOCOL_VERSION, features: vmouse::protocol_features() })
                     }
+                    false => {
+                        warn!("Client {} protocol v{} incompatible with daemon v{}, disconnecting", h.id, vmouse::protocol_string(*version), vmouse::protocol_string(PROTOCOL_VERSION));
+                        Some(Command::Error(ErrorCode::VersionMismatch { daemon: PROTOCOL_VERSION, client: *version }))
+                    }
+                }
+            }
+            Command::Ping => Some(Command::Ok),
+            Command::Bind { target: BindTarget::Path(event), .. } => Some(self.bind(event).await),
+            Command::Bind { target: BindTarget::Id(id), wait } => match self.find_unbound(id) {
+                Some(path) => Some(self.bind(&path).await),
+                // Hold the response until the device appears or the wait expires
+                None if wait.unwrap_or(0) > 0 => {
+                    info!(client_id = h.id, device = %id.to_string(), "Waiting up to {}s for device", wait.unwrap_or(0));
+                    self.pending_binds.push(PendingBind {
+                        client: h.id,
+                        device: id.clone(),
+                        deadline: Instant::now() + Duration::from_secs(wait.unwrap_or(0)),
+                        tx: h.tx.clone(),
+                        req: h.req,
+                    });
+                    None
+                }
+                None => {
+                    warn!(client_id = h.id, device = %id.to_string(), "No unbound device found");
+                    Some(Command::Error(ErrorCode::DeviceNotFound))
+                }
+            },
+            Command::Enable { enabled, device: None } => {
+                self.enabled = *enabled;
+                self.state_changed().await;
+                self.broadcast(Command::Status(self.status()));
+                Some(Command::Ok)
+            }
+            Command::Enable { enabled, device: Some(device) } => {
+                // Resolve event paths to bound devices, otherwise normalise vid:pid or alias names
+                let name = match self.devices.get(device) {
+                    Some(h) => h.info.to_string(),
+                    None => match self.config.resolve(device).parse::<UsbDevice>() {
+                        Ok(d) => d.to_string(),
+                        Err(e) => {
+                            warn!("Invalid device '{}' for enable: {}", device, e);
+                            return Ok(Some(Command::Error(ErrorCode::InvalidDevice)));
+                        }
+                    },
                 };
 
-                if let Err(e) = std::fs::write(&self.config_file, s) {
+                info!("Output for device {}: {}", name, if *enabled { "enabled" } else { "disabled" });
+                self.device_enabled.insert(name, *enabled);
+                self.state_changed().await;
+
+                self.broadcast(Command::Status(self.status()));
+                Some(Command::Ok)
+            }
+            Command::GetStatus => Some(Command::Status(self.status())),
+            Command::GetMetrics => Some(Command::Metrics(self.metrics.snapshot(self.clients.len()))),
+            Command::GetDevnode => Some(Command::Devnodes(self.output_devices.clone())),
+            Command::Inject { axes, time_us } => {
+                if AXIS.iter().any(|a| !axes[*a].is_finite()) {
+                    warn!("Rejecting injected sample with non-finite values");
+                    return Ok(Some(Command::Error(ErrorCode::InvalidValue)));
+                }
+
+                // Processed as an external device sample so injected input follows the full pipeline
+                let mut p = ExternalPacket { axes: *axes, buttons: 0 };
+                for a in AXIS {
+                    p.axes[*a] = p.axes[*a].clamp(-1.0, 1.0);
+                }
+
+                for evt in p.events(vmouse::time_from_us(*time_us)) {
+                    self.evt_tx.send(DeviceEvent::Input(vmouse::external_device(), evt)).await?;
+                }
+
+                Some(Command::Ok)
+            }
+            Command::Calibrate { device, action: CalibrateAction::Start } => {
+                let device = self.config.resolve(device);
+                info!("Starting calibration for device: {}", device);
+                self.calibration = Some(Calibration::new(device));
+                Some(Command::Ok)
+            }
+            Command::Calibrate { device, action: CalibrateAction::Cancel } => {
+                let device = &self.config.resolve(device);
+                match self.calibration.take() {
+                    Some(c) if &c.device == device => {
+                        info!("Cancelled calibration for device: {}", device);
+                        Some(Command::Ok)
+                    }
+                    c => {
+                        self.calibration = c;
+                        Some(Command::Error(ErrorCode::NotCalibrating))
+                    }
+                }
+            }
+            Command::Calibrate { device, action: CalibrateAction::Finish } => {
+                let device = &self.config.resolve(device);
+                let c = match self.calibration.take() {
+                    Some(c) if &c.device == device => c,
+                    c => {
+                        warn!("No calibration in progress for device: {}", device);
+                        self.calibration = c;
+                        return Ok(Some(Command::Error(ErrorCode::NotCalibrating)));
+                    }
+                };
+
+                // Create device config from defaults if required
+                let mut config = self.config.clone();
+                if config.get(device).is_none() {
+                    match self.devices.values().map(|h| &h.info).find(|d| &d.to_string() == device) {
+                        Some(d) => {
+                            let axes = config.default;
+                            config.devices.insert(d.clone(), axes);
+                        }
+                        None => {
+                            warn!("Unknown calibration device: {}", device);
+                            return Ok(Some(Command::Error(ErrorCode::DeviceNotFound)));
+                        }
+                    }
+                }
+
+                // Store the measured extremes for axes that moved, axes moved in one
+                // direction keep the current range for the other
+                if let Some(axes) = config.get_mut(device) {
+                    for (a, &(min, max)) in &c.range {
+                        if min == 0 && max == 0 {
+                            continue;
+                        }
+
+                        let r = axes[a].axis_range();
+                        let min = if min < 0 { min } else { r.min };
+                        let max = if max > 0 { max } else { r.max };
+                        axes[a].range = Some((min, max));
+                    }
+                }
+
+                info!("Finished calibration for device {}: {:?}", device, c.range);
+
+                // Validated and broadcast as for client config updates
+                match self.apply_config(config, h.id).await {
+                    Command::Ok => Some(Command::Calibrated(c.range)),
+                    r => Some(r),
+                }
+            }
+            Command::SelectProfile { device, profile } => {
+                match self.config.activate(device, profile) {
+                    Ok(_) => {
+                        info!("Activated profile '{}' for device: {}", profile, device);
+                        self.state_changed().await;
+                        self.broadcast(Command::ActiveProfile { device: self.config.resolve(device), profile: Some(profile.clone()) });
+                        self.update_feedback();
+                        Some(Command::Ok)
+                    }
+                    Err(e) => {
+                        warn!("Failed to activate profile: {:?}", e);
+                        Some(Command::Error(ErrorCode::ProfileNotFound))
+                    }
+                }
+            }
+            Command::GetState { device: None } => Some(Command::State{ device: None, state: self.state }),
+            Command::GetState { device: Some(n) } => {
+                let n = self.config.resolve(n);
+                match self.device_state.iter().find(|(d, _s)| d.to_string() == n) {
+                    Some((d, s)) => Some(Command::State{ device: Some(d.clone()), state: *s }),
+                    None => Some(Command::Error(ErrorCode::DeviceNotFound)),
+                }
+            },
+            Command::GetConfig => Some(Command::SetConfig(self.config.clone())),
+            Command::ListDevices => Some(Command::Devices(self.devices.values().map(|h| self.config.aliased(&h.info)).collect())),
+            Command::SetConfig(c) => Some(self.apply_config(c.clone(), h.id).await),
+            Command::ResetState => {
+                info!(client_id = h.id, "Resetting axis state");
+
+                self.state = AxisState::default();
+                for s in self.device_state.values_mut() {
+                    *s = AxisState::default();
+                }
+
+                // Drop pending outputs and re-centre the absolute pointer
+                if let Some(c) = self.coalesce.as_mut() {
+                    *c = Coalescer::default();
+                }
+                self.auto_zero.clear();
+                self.aggregate.clear();
+                self.abs_pointer = AbsPointer::new(self.config.abs_pointer.unwrap_or_default());
+
+                self.broadcast(Command::State{ device: None, state: self.state });
+                for dev in self.device_state.keys().cloned().collect::<Vec<_>>() {
+                    self.broadcast(Command::State{ device: Some(dev), state: AxisState::default() });
+                }
+
+                Some(Command::Ok)
+            }
+            Command::ResetConfig { device } => {
+                let mut c = self.config.clone();
+
+                match device.as_deref() {
+                    None => c = Config::default(),
+                    Some("default") => c.default = AxisCollection::defaults_for(c.version),
+                    Some(n) => {
+                        // Resolve event paths to bound devices, otherwise parse vid:pid or alias names
+                        let d = match self.devices.get(n) {
+                            Some(h) => h.info.clone(),
+                            None => match self.config.resolve(n).parse::<UsbDevice>() {
+                                Ok(d) => d,
+                                Err(e) => {
+                                    warn!("Invalid device '{}' for config reset: {}", n, e);
+                                    return Ok(Some(Command::Error(ErrorCode::InvalidDevice)));
+                                }
+                            },
+                        };
+
+                        // Replace any existing entries for the device, keeping the configured name
+                        let existing: Vec<_> = c.devices.keys().filter(|k| k.vid == d.vid && k.pid == d.pid).cloned().collect();
+                        let key = existing.first().cloned().unwrap_or(d);
+                        for k in &existing {
+                            c.devices.remove(k);
+                        }
+                        c.devices.insert(key, AxisCollection::defaults_for(c.version));
+                    }
+                }
+
+                info!(client_id = h.id, "Resetting config for: {}", device.as_deref().unwrap_or("all devices"));
+
+                Some(self.apply_config(c, h.id).await)
+            }
+            Command::WriteConfig => {
+                info!("Writing updated config to: {}", self.config_file);
+
+                // Create config directory if required (eg. for user mode)
+                if let Some(p) = Path::new(&self.config_file).parent() {
+                    let _ = std::fs::create_dir_all(p);
+                }
+
+                if let Err(e) = self.save_config(&self.config_file) {
                     error!("Failed to write config file '{}': {:?}", self.config_file, e);
-                    return Ok(Some(Command::Failed))
+                    let code = match e.io_kind() {
+                        Some(ErrorKind::PermissionDenied) => ErrorCode::WriteDenied,
+                        _ => ErrorCode::WriteFailed,
+                    };
+                    return Ok(Some(Command::Error(code)));
                 }
 
                 info!("Config updated!");
 
                 Some(Command::Ok)
             }
-            Command::Listen => {
-                // Set client listen flag
+            Command::GetConfigPath => Some(Command::ConfigPath(self.config_file.clone())),
+            Command::SaveConfigAs { path, set_active } => {
+                if let Err(e) = validate_config_path(path) {
+                    warn!(client_id = h.id, "Rejected config path '{}': {}", path, e);
+                    return Ok(Some(Command::Error(ErrorCode::InvalidPath)));
+                }
+                if !self.save_permitted(h, path) {
+                    warn!(client_id = h.id, "Rejected config path '{}' outside config directories", path);
+                    return Ok(Some(Command::Error(ErrorCode::PermissionDenied)));
+                }
+
+                info!("Writing config to: {}", path);
+
+                match self.save_config(path) {
+                    Ok(_) => {
+                        if *set_active {
+                            info!("Active config path now: {}", path);
+                            self.config_file = path.clone();
+                        }
+                        Some(Command::Ok)
+                    }
+                    Err(e) => {
+                        error!("Failed to write config file '{}': {}", path, e);
+                        match e.io_kind() {
+                            Some(ErrorKind::PermissionDenied) => Some(Command::Error(ErrorCode::WriteDenied)),
+                            _ => Some(Command::Error(ErrorCode::WriteFailed)),
+                        }
+                    }
+                }
+            }
+            Command::Listen { topics } => {
+                // Set client subscription, empty topics subscribe to all
                 if let Some(c) = self.clients.get_mut(&h.id) {
-                    c.listen = true;
+                    c.listen = Some(topics.clone());
                 }
 
-                // Enable update task if required
-                self.enable_update_task().await;
+                // Enable update task if required for state updates
+                match self.clients.get(&h.id).map(|c| c.subscribed(Topic::State)) {
+                    Some(true) => self.enable_update_task().await,
+                    _ => self.disable_update_task().await,
+                }
 
                 // Signal listen success
                 Some(Command::Ok)
             }
-            Command::Disconnect => {
-                debug!("Removing client: {}", h.id);
-
-                // Remove client from listing
-                let _ = self.clients.remove(&h.id);
-
-                // Disable timer task if no longer required
-                let listening = self.clients.iter().filter(|(_id, c)| c.listen).count() > 0;
-                match (listening, self.update_task.take()) {
-                    (false, Some(t)) => {
-                        let _ = t.cancel().await;
-                    }
-                    (_, Some(t)) => self.update_task = Some(t),
-                    _ => (),
+            Command::Unlisten => {
+                if let Some(c) = self.clients.get_mut(&h.id) {
+                    c.listen = None;
                 }
 
+                self.disable_update_task().await;
+
+                Some(Command::Ok)
+            }
+            Command::Disconnect => {
+                self.remove_client(h.id).await;
+                None
+            }
+            // Keepalive acknowledgement, activity is recorded above
+            Command::Ok => {
+                trace!("Keepalive from client {}", h.id);
+                None
+            }
+            // Responses and notifications are not valid requests, listed explicitly
+            // so new request variants must be handled above
+            #[allow(deprecated)]
+            Command::Failed
+            | Command::Error(_)
+            | Command::RawValue(..)
+            | Command::State { .. }
+            | Command::Removed(_)
+            | Command::BatteryLow { .. }
+            | Command::ConfigInvalid { .. }
+            | Command::Devices(_)
+            | Command::Status(_)
+            | Command::Metrics(_)
+            | Command::Devnodes(_)
+            | Command::ConfigPath(_)
+            | Command::ShuttingDown
+            | Command::Calibrated(_)
+            | Command::ActiveProfile { .. } => {
+                debug!("Ignoring unsolicited {:?} from client {}", h.c, h.id);
                 None
             }
-            _ => None,
         };
 
         Ok(resp)
     }
 }
 
+/// Check a [`Command::SaveConfigAs`] path is absolute, has an existing parent, and is not a directory
+fn validate_config_path(path: &str) -> Result<(), &'static str> {
+    let p = Path::new(path);
+
+    if !p.is_absolute() {
+        return Err("path is not absolute");
+    }
+    if !p.parent().map(|p| p.is_dir()).unwrap_or(false) {
+        return Err("parent directory does not exist");
+    }
+    if p.is_dir() {
+        return Err("path is a directory");
+    }
+
+    Ok(())
+}
+
+/// Check a [`Command::SaveConfigAs`] path is a config file (`.toml` or `.json`) directly within
+/// one of the provided directories, symlinks and `..` components are resolved before matching
+fn config_path_within<'a>(path: &str, dirs: impl IntoIterator<Item = &'a Path>) -> bool {
+    let p = Path::new(path);
+
+    let config_ext = matches!(p.extension().and_then(|e| e.to_str()), Some(e) if e.eq_ignore_ascii_case("toml") || e.eq_ignore_ascii_case("json"));
+    if !config_ext {
+        return false;
+    }
+
+    let parent = match p.parent().and_then(|d| d.canonicalize().ok()) {
+        Some(d) => d,
+        None => return false,
+    };
+
+    dirs.into_iter().filter_map(|d| d.canonicalize().ok()).any(|d| d == parent)
+}
+
+/// A [`Command::Bind`] by id awaiting its device
+struct PendingBind {
+    client: u32,
+    device: UsbDevice,
+    deadline: Instant,
+    tx: Sender<Response>,
+    /// Request id for the response
+    req: u64,
+}
+
+/// Bound input device and its reader task
+struct DeviceHandle {
+    /// Device descriptor
+    info: UsbDevice,
+    /// Device node (or external source socket) path
+    path: String,
+    /// Physical device identity, shared by nodes of the same device
+    identity: String,
+    /// Whether the device reports motion axes
+    axes: bool,
+    task: JoinHandle<Result<(), anyhow::Error>>,
+    /// Stops the reader task
+    cancel: Sender<()>,
+    /// LED patterns written by the reader task, `None` for sources without LEDs
+    leds: Option<Sender<LedPattern>>,
+    /// Battery level (%), `None` for devices without a battery
+    battery: Option<u8>,
+}
+
 struct ClientHandle {
     id: u32,
-    tx: Sender<Command>,
-    listen: bool,
-    _h: JoinHandle<Result<(), anyhow::Error>>,
+    tx: Sender<Response>,
+    /// Subscribed topics, `None` if not listening, empty for all topics
+    listen: Option<Vec<Topic>>,
+    /// Raw value rate limit
+    raw_limit: RateLimit,
+    /// Time of the last message from the client
+    last_seen: Instant,
+    /// Time of an unacknowledged keepalive ping
+    pinged: Option<Instant>,
+    auth: ClientAuth,
+    task: JoinHandle<Result<(), anyhow::Error>>,
+}
+
+impl ClientHandle {
+    /// Check whether the client is subscribed to a topic
+    fn subscribed(&self, t: Topic) -> bool {
+        match &self.listen {
+            Some(topics) => topics.is_empty() || topics.contains(&t),
+            None => false,
+        }
+    }
+}
+
+/// Fixed window rate limiter, allows up to `max` events per second
+struct RateLimit {
+    window: Instant,
+    count: u32,
+}
+
+impl RateLimit {
+    fn new() -> Self {
+        Self { window: Instant::now(), count: 0 }
+    }
+
+    /// Check whether another event is allowed in the current window
+    fn allow(&mut self, max: u32) -> bool {
+        let now = Instant::now();
+        if now.duration_since(self.window) >= Duration::from_secs(1) {
+            self.window = now;
+            self.count = 0;
+        }
+
+        if self.count >= max {
+            return false;
+        }
+
+        self.count += 1;
+        true
+    }
+}
+
+/// In-progress axis calibration, records raw extremes for a device
+struct Calibration {
+    device: String,
+    range: AxisCollection<(i32, i32)>,
+}
+
+impl Calibration {
+    fn new(device: String) -> Self {
+        Self {
+            device,
+            range: AxisCollection::with_axis(|_| (0, 0)),
+        }
+    }
+
+    /// Record an input event if it belongs to the calibrating device
+    fn record(&mut self, dev: &UsbDevice, e: &InputEvent) {
+        if dev.to_string() != self.device {
+            return;
+        }
+
+        if let Ok(a) = Axis::try_from(e.event_code) {
+            let (min, max) = &mut self.range[a];
+            *min = (*min).min(e.value);
+            *max = (*max).max(e.value);
+        }
+    }
+}
+
+/// Events from device reader tasks
+pub enum DeviceEvent {
+    /// Input event from a device
+    Input(UsbDevice, InputEvent),
+    /// Device reader exited, contains device path and descriptor
+    Removed(String, UsbDevice),
+    /// Battery level update, contains device path and level (%)
+    Battery(String, Option<u8>),
 }
 
 struct CommandHandle {
     /// Client ID
     pub id: u32,
+    /// Request id, echoed in the response ([`vmouse::BROADCAST_ID`] for bare commands)
+    pub req: u64,
     /// Command
     pub c: Command,
     /// Response channel
-    pub tx: Sender<Command>,
+    pub tx: Sender<Response>,
+    /// Caller credentials for internal clients multiplexing callers (eg. D-Bus)
+    pub cred: Option<PeerCred>,
+}
+
+impl CommandHandle {
+    /// Send a response to the requesting client
+    ///
+    /// Clients may disconnect (or be dropped for not reading) before a response is sent,
+    /// which must not stop the daemon.
+    async fn respond(&self, cmd: Command) {
+        if let Err(e) = self.tx.send(Response { id: self.req, cmd }).await {
+            debug!(client_id = self.id, "Client disconnected, dropping response: {:?}", e.into_inner().cmd);
+        }
+    }
+}
+
+/// Write a complete frame, failing with [`ErrorKind::TimedOut`] if the peer stops reading
+async fn write_frame<W: async_std::io::Write + Unpin>(w: &mut W, enc: &[u8], timeout: Duration) -> std::io::Result<()> {
+    async_std::io::timeout(timeout, w.write_all(enc)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::channel::Receiver;
+    use evdev_rs::{enums::{EventCode, EV_REL}, TimeVal};
+    use vmouse::AxisConfig;
+
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    /// Config path within a fresh temporary directory
+    fn config_path(name: &str) -> String {
+        let p = test_dir("daemon", name).join("vmouse.toml");
+        p.to_string_lossy().to_string()
+    }
+
+    fn remove(path: &str) {
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(format!("{}{}", path, vmouse::BACKUP_SUFFIX));
+    }
+
+    /// Write a config as [`Daemon::save_config`] does
+    fn save(path: &str, c: &Config) {
+        ConfigFile::new(c, SocketConfig::default()).save(path, ConfigFormat::from_path(path)).unwrap();
+    }
+
+    /// Daemon with a temporary config file and no bound devices, event and tick receivers
+    /// are returned so sends from the daemon succeed
+    fn daemon(name: &str) -> (Daemon, Receiver<DeviceEvent>, Receiver<()>) {
+        let (evt_tx, evt_rx) = async_std::channel::unbounded();
+        let (tick_tx, tick_rx) = async_std::channel::unbounded();
+        let d = Daemon::new(Config::default(), config_path(name), SocketConfig::default(), 0, evt_tx, tick_tx);
+        (d, evt_rx, tick_rx)
+    }
+
+    /// Input source without events, for binding without device nodes
+    struct MockSource {
+        device: UsbDevice,
+        identity: String,
+        axes: bool,
+        sock: std::os::unix::net::UnixDatagram,
+    }
+
+    impl MockSource {
+        fn new(identity: &str, axes: bool) -> Self {
+            let (sock, _peer) = std::os::unix::net::UnixDatagram::pair().unwrap();
+            let device = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+            Self { device, identity: identity.to_string(), axes, sock }
+        }
+    }
+
+    impl std::os::unix::io::AsRawFd for MockSource {
+        fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+            self.sock.as_raw_fd()
+        }
+    }
+
+    impl InputSource for MockSource {
+        fn device(&self) -> UsbDevice {
+            self.device.clone()
+        }
+
+        fn read_events(&self) -> Result<Vec<InputEvent>, std::io::Error> {
+            Err(ErrorKind::WouldBlock.into())
+        }
+
+        fn identity(&self) -> String {
+            self.identity.clone()
+        }
+
+        fn has_axes(&self) -> bool {
+            self.axes
+        }
+    }
+
+    /// Read commands from a client connection until the daemon closes it
+    async fn read_to_close(mut s: UnixStream) -> Vec<Command> {
+        let mut decoder = Decoder::new();
+        let mut buff = [0u8; 1024];
+        let mut cmds = vec![];
+
+        loop {
+            while let Some(f) = decoder.decode_frame().unwrap() {
+                cmds.push(f.cmd);
+            }
+            match s.read(&mut buff).await.unwrap() {
+                0 => return cmds,
+                n => decoder.push(&buff[..n]),
+            }
+        }
+    }
+
+    /// Shrink a socket send buffer so large frames need several writes
+    fn small_sndbuf(s: &impl std::os::unix::io::AsRawFd) {
+        let size: libc::c_int = 4096;
+        let res = unsafe {
+            libc::setsockopt(
+                s.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_SNDBUF,
+                &size as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        assert_eq!(res, 0);
+    }
+
+    /// Config large enough to exceed the socket buffer
+    fn large_config() -> Config {
+        let mut c = Config::default();
+        for pid in 0..256 {
+            c.devices.insert(UsbDevice { vid: 0x256f, pid, name: None }, c.default);
+        }
+        c
+    }
+
+    /// Privileged request from a root caller
+    fn request(tx: &Sender<Response>, c: Command) -> CommandHandle {
+        request_as(tx, 0, c)
+    }
+
+    /// Request from a caller in the socket group (gid 0 in tests)
+    fn request_as(tx: &Sender<Response>, uid: u32, c: Command) -> CommandHandle {
+        CommandHandle { id: 1, req: 1, c, tx: tx.clone(), cred: Some(PeerCred { pid: 1, uid, gids: vec![0] }) }
+    }
+
+    #[test]
+    fn reload_after_write_is_unchanged() {
+        let path = config_path("reload");
+        let d = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+        // Runtime edits to the default axes are kept
+        for version in [0, vmouse::CONFIG_VERSION] {
+            let mut c = Config { version, ..Default::default() };
+            c.default[Axis::X].scale = 0.01;
+            c.default[Axis::RZ] = AxisConfig { map: vmouse::Map::H, scale: 0.002, ..AxisConfig::disabled() };
+            apply_preset(&mut c, &d);
+
+            save(&path, &c);
+            assert_eq!(load_config(&path, [&d].into_iter()).unwrap(), c, "version {}", version);
+        }
+
+        // Unchanged defaults are not written, so follow the config version
+        let c = Config::default();
+        save(&path, &c);
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("[default"));
+        assert_eq!(load_config(&path, std::iter::empty()).unwrap(), c);
+
+        remove(&path);
+    }
+
+    #[test]
+    fn requests_always_reply() {
+        let (mut d, _evt_rx, _tick_rx) = daemon("requests");
+        let (tx, _rx) = async_std::channel::unbounded();
+        let saved = config_path("requests-saved");
+        let id = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+        let mut nan = AxisCollection::<f32>::default();
+        nan[Axis::X] = f32::NAN;
+
+        let mut invalid = Config::default();
+        invalid.default[Axis::X].scale = f32::NAN;
+
+        // Successful and failing forms of each request variant
+        let requests = [
+            Command::Hello { version: PROTOCOL_VERSION, features: vec![] },
+            Command::Hello { version: 2, features: vec![] },
+            Command::Ping,
+            Command::Bind { target: BindTarget::Path("/dev/input/vmouse-missing".to_string()), wait: None },
+            Command::Bind { target: BindTarget::Id(id.clone()), wait: None },
+            Command::Listen { topics: vec![] },
+            Command::Unlisten,
+            Command::GetState { device: None },
+            Command::GetState { device: Some(id.to_string()) },
+            Command::GetConfig,
+            Command::ListDevices,
+            Command::GetStatus,
+            Command::GetDevnode,
+            Command::ResetState,
+            Command::ResetConfig { device: None },
+            Command::ResetConfig { device: Some("default".to_string()) },
+            Command::ResetConfig { device: Some(id.to_string()) },
+            Command::ResetConfig { device: Some("not-a-device".to_string()) },
+            Command::GetMetrics,
+            Command::Inject { axes: AxisCollection::default(), time_us: 0 },
+            Command::Inject { axes: nan, time_us: 0 },
+            Command::Calibrate { device: id.to_string(), action: CalibrateAction::Cancel },
+            Command::Calibrate { device: id.to_string(), action: CalibrateAction::Start },
+            Command::Calibrate { device: id.to_string(), action: CalibrateAction::Cancel },
+            Command::Calibrate { device: id.to_string(), action: CalibrateAction::Start },
+            Command::Calibrate { device: id.to_string(), action: CalibrateAction::Finish },
+            Command::Calibrate { device: id.to_string(), action: CalibrateAction::Finish },
+            Command::SelectProfile { device: "default".to_string(), profile: "missing".to_string() },
+            Command::Enable { enabled: false, device: None },
+            Command::Enable { enabled: true, device: Some(id.to_string()) },
+            Command::Enable { enabled: true, device: Some("not-a-device".to_string()) },
+            Command::SetConfig(Config::default()),
+            Command::SetConfig(invalid),
+            Command::WriteConfig,
+            Command::GetConfigPath,
+            Command::SaveConfigAs { path: saved.clone(), set_active: false },
+            Command::SaveConfigAs { path: "relative.toml".to_string(), set_active: false },
+        ];
+
+        for c in requests {
+            let r = async_std::task::block_on(d.handle_cmd(&request(&tx, c.clone()))).unwrap();
+            assert!(r.is_some(), "no response to {:?}", c);
+        }
+
+        remove(&d.config_file);
+        remove(&saved);
+    }
+
+    #[test]
+    fn calibration_stores_ranges() {
+        let (mut d, _evt_rx, _tick_rx) = daemon("calibrate");
+        let (tx, _rx) = async_std::channel::unbounded();
+        let dev = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+        let mut axes = d.config.default;
+        axes[Axis::RX].range = Some((-100, 100));
+        d.config.devices.insert(dev.clone(), axes);
+
+        let calibrate = |d: &mut Daemon, action| {
+            let h = request(&tx, Command::Calibrate { device: dev.to_string(), action });
+            async_std::task::block_on(d.handle_cmd(&h)).unwrap().unwrap()
+        };
+        assert_eq!(calibrate(&mut d, CalibrateAction::Start), Command::Ok);
+
+        let moves = [(EV_REL::REL_X, -300), (EV_REL::REL_X, 320), (EV_REL::REL_Y, 200), (EV_REL::REL_RX, -250)];
+        for (code, value) in moves {
+            let e = InputEvent { time: TimeVal::new(0, 0), event_code: EventCode::EV_REL(code), value };
+            d.calibration.as_mut().unwrap().record(&dev, &e);
+        }
+
+        let mut range = AxisCollection::with_axis(|_| (0, 0));
+        range[Axis::X] = (-300, 320);
+        range[Axis::Y] = (0, 200);
+        range[Axis::RX] = (-250, 0);
+        assert_eq!(calibrate(&mut d, CalibrateAction::Finish), Command::Calibrated(range));
+
+        // Axes moved one way keep the current range for the other, unmoved axes are unchanged
+        let axes = d.config.devices[&dev];
+        assert_eq!(axes[Axis::X].range, Some((-300, 320)));
+        assert_eq!(axes[Axis::Y].range, Some((vmouse::AXIS_MIN, 200)));
+        assert_eq!(axes[Axis::RX].range, Some((-250, 100)));
+        assert_eq!(axes[Axis::Z].range, d.config.default[Axis::Z].range);
+
+        // Finishing again has no calibration to apply
+        assert_eq!(calibrate(&mut d, CalibrateAction::Finish), Command::Error(ErrorCode::NotCalibrating));
+
+        remove(&d.config_file);
+    }
+
+    #[test]
+    fn save_config_as_restricted() {
+        let (mut d, _evt_rx, _tick_rx) = daemon("save-as");
+        let (tx, _rx) = async_std::channel::unbounded();
+
+        // Config directory holds the daemon config, with another directory and a link to it
+        let outside = test_dir("daemon", "save-as-outside");
+        let link = test_dir("daemon", "save-as-link").join("link");
+        std::os::unix::fs::symlink(&outside, &link).unwrap();
+
+        let inside = Path::new(&d.config_file).with_file_name("inside.toml").to_string_lossy().to_string();
+        let outside_path = outside.join("vmouse.toml").to_string_lossy().to_string();
+        let linked_path = link.join("vmouse.toml").to_string_lossy().to_string();
+        let not_config = d.config_file.replace(".toml", ".conf");
+
+        let save = |d: &mut Daemon, uid, path: &str| {
+            let h = request_as(&tx, uid, Command::SaveConfigAs { path: path.to_string(), set_active: false });
+            async_std::task::block_on(d.handle_cmd(&h)).unwrap().unwrap()
+        };
+
+        // Admins may only save configs within the config directory
+        let denied = Command::Error(ErrorCode::PermissionDenied);
+        assert_eq!(save(&mut d, 1000, &inside), Command::Ok);
+        assert_eq!(save(&mut d, 1000, &outside_path), denied);
+        assert_eq!(save(&mut d, 1000, &linked_path), denied);
+        assert_eq!(save(&mut d, 1000, &not_config), denied);
+
+        // Root may save anywhere
+        assert_eq!(save(&mut d, 0, &outside_path), Command::Ok);
+        assert_eq!(save(&mut d, 0, &not_config), Command::Ok);
+
+        // Configured directories are permitted for admins
+        d.socket_config.config_dirs.push(outside.to_string_lossy().to_string());
+        assert_eq!(save(&mut d, 1000, &linked_path), Command::Ok);
+
+        for p in [&inside, &outside_path, &not_config] {
+            remove(p);
+        }
+    }
+
+    #[test]
+    fn frames_intact_with_short_writes() {
+        async_std::task::block_on(async {
+            let (mut tx, mut rx) = UnixStream::pair().unwrap();
+            small_sndbuf(&tx);
+
+            let cmd = Command::SetConfig(large_config());
+            let enc = vmouse::encode(&cmd).unwrap();
+            assert!(enc.len() > 4 * 4096);
+
+            let w = async_std::task::spawn(async move { write_frame(&mut tx, &enc, CLIENT_WRITE_TIMEOUT).await });
+
+            // Read slowly in small chunks so the writer sees a full buffer
+            let mut decoder = Decoder::new();
+            let mut buff = [0u8; 256];
+            let f = loop {
+                let n = rx.read(&mut buff).await.unwrap();
+                assert_ne!(n, 0, "stream closed before a complete frame");
+
+                decoder.push(&buff[..n]);
+                if let Some(f) = decoder.decode_frame().unwrap() {
+                    break f;
+                }
+                async_std::task::yield_now().await;
+            };
+
+            w.await.unwrap();
+            assert_eq!(f.cmd, cmd);
+            assert_eq!(decoder.decode_frame().unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn broadcasts_filtered_by_topic() {
+        async_std::task::block_on(async {
+            let (mut d, _evt_rx, _tick_rx) = daemon("topics");
+            let (ctl_tx, ctl_rx) = async_std::channel::unbounded();
+            let dev = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+            // Clients subscribed to state, config and device events, everything, and nothing
+            let subscriptions = [
+                Some(vec![Topic::State]),
+                Some(vec![Topic::ConfigChanges, Topic::DeviceEvents]),
+                Some(vec![]),
+                None,
+            ];
+
+            let mut clients = vec![];
+            for topics in &subscriptions {
+                let (server, mut client) = UnixStream::pair().unwrap();
+                d.attach_client(server, ctl_tx.clone()).await.unwrap();
+
+                if let Some(topics) = topics {
+                    client.write_all(&vmouse::encode(&Command::Listen { topics: topics.clone() }).unwrap()).await.unwrap();
+                    let listen = ctl_rx.recv().await.unwrap();
+                    assert_eq!(d.handle_cmd(&listen).await.unwrap(), Some(Command::Ok));
+                }
+
+                clients.push(client);
+            }
+
+            let state = Command::State { device: None, state: AxisState::default() };
+            let config = Command::SetConfig(Config::default());
+            let removed = Command::Removed(dev);
+            for c in [state.clone(), config.clone(), removed.clone(), Command::Ping] {
+                d.broadcast(c);
+            }
+
+            d.shutdown().await;
+
+            let mut received = vec![];
+            for c in clients {
+                received.push(read_to_close(c).await);
+            }
+
+            assert_eq!(received, vec![
+                vec![state.clone(), Command::ShuttingDown],
+                vec![config.clone(), removed.clone(), Command::ShuttingDown],
+                vec![state, config, removed, Command::ShuttingDown],
+                vec![Command::ShuttingDown],
+            ]);
+
+            remove(&d.config_file);
+        });
+    }
+
+    #[test]
+    fn last_listener_stops_ticks() {
+        async_std::task::block_on(async {
+            let (mut d, _evt_rx, _tick_rx) = daemon("listeners");
+            let (ctl_tx, ctl_rx) = async_std::channel::unbounded();
+
+            // Ticks are otherwise kept for the output watchdog
+            d.config.auto_zero_ms = Some(0);
+
+            let mut clients = vec![];
+            for _i in 0..2 {
+                let (server, mut client) = UnixStream::pair().unwrap();
+                d.attach_client(server, ctl_tx.clone()).await.unwrap();
+
+                client.write_all(&vmouse::encode(&Command::Listen { topics: vec![] }).unwrap()).await.unwrap();
+                let listen = ctl_rx.recv().await.unwrap();
+                assert_eq!(d.handle_cmd(&listen).await.unwrap(), Some(Command::Ok));
+
+                clients.push(client);
+            }
+            assert!(d.update_task.is_some());
+
+            // Closed connections are reported as disconnects, ticks stop with the last listener
+            for (i, c) in clients.into_iter().enumerate() {
+                drop(c);
+
+                let disconnect = ctl_rx.recv().await.unwrap();
+                assert_eq!(disconnect.c, Command::Disconnect);
+                assert_eq!(d.handle_cmd(&disconnect).await.unwrap(), None);
+
+                assert_eq!(d.clients.len(), 1 - i);
+                assert_eq!(d.update_task.is_some(), i == 0);
+            }
+
+            remove(&d.config_file);
+        });
+    }
+
+    #[test]
+    fn stalled_client_dropped() {
+        async_std::task::block_on(async {
+            let (mut d, _evt_rx, _tick_rx) = daemon("stalled");
+            let (ctl_tx, ctl_rx) = async_std::channel::unbounded();
+
+            // Client subscribes to config changes then stops reading
+            let (server, mut client) = UnixStream::pair().unwrap();
+            small_sndbuf(&server);
+            d.attach_client(server, ctl_tx).await.unwrap();
+
+            client.write_all(&vmouse::encode(&Command::Listen { topics: vec![Topic::ConfigChanges] }).unwrap()).await.unwrap();
+            let listen = ctl_rx.recv().await.unwrap();
+            let r = d.handle_cmd(&listen).await.unwrap().unwrap();
+            listen.respond(r).await;
+
+            // Broadcasts fill the socket buffer until the write times out and the client task exits
+            let disconnect = loop {
+                d.broadcast(Command::SetConfig(large_config()));
+
+                match async_std::future::timeout(Duration::from_millis(100), ctl_rx.recv()).await {
+                    Ok(h) => break h.unwrap(),
+                    Err(_) => continue,
+                }
+            };
+            assert_eq!(disconnect.c, Command::Disconnect);
+            assert_eq!(d.handle_cmd(&disconnect).await.unwrap(), None);
+            assert!(d.clients.is_empty());
+
+            // Late responses to the dropped client are discarded
+            listen.respond(Command::Ok).await;
+            d.broadcast(Command::SetConfig(Config::default()));
+
+            let (tx, rx) = async_std::channel::unbounded();
+            let ping = request(&tx, Command::Ping);
+            let r = d.handle_cmd(&ping).await.unwrap().unwrap();
+            ping.respond(r).await;
+            assert_eq!(rx.recv().await.unwrap().cmd, Command::Ok);
+
+            drop(client);
+            remove(&d.config_file);
+        });
+    }
+
+    #[test]
+    fn duplicate_bind_rejected() {
+        async_std::task::block_on(async {
+            let (mut d, _evt_rx, _tick_rx) = daemon("duplicate-bind");
+            let (event0, event1, event2) = ("/dev/input/vmouse-event0", "/dev/input/vmouse-event1", "/dev/input/vmouse-event2");
+
+            d.attach_source(event0.to_string(), MockSource::new("usb-0000:00:14.0-1", true), None).unwrap();
+
+            // Binding the same path again would double every event
+            assert_eq!(d.bind(event0).await, Command::Error(ErrorCode::BindFailed));
+
+            // As would a second axis node of the same device, button only nodes are permitted
+            assert!(d.attach_source(event1.to_string(), MockSource::new("usb-0000:00:14.0-1", true), None).is_err());
+            d.attach_source(event2.to_string(), MockSource::new("usb-0000:00:14.0-1", false), None).unwrap();
+
+            let mut paths: Vec<_> = d.devices.keys().cloned().collect();
+            paths.sort();
+            assert_eq!(paths, vec![event0.to_string(), event2.to_string()]);
+
+            d.shutdown().await;
+            remove(&d.config_file);
+        });
+    }
+
+    #[test]
+    fn passthrough_devices_not_bound() {
+        async_std::task::block_on(async {
+            let (mut d, _evt_rx, _tick_rx) = daemon("passthrough-bind");
+
+            // Passthrough mirrors share the source vid:pid, binding one would loop events back as input
+            let mut s = MockSource::new("usb-0000:00:14.0-1", true);
+            s.device.name = Some(format!("{} SpaceMouse Compact", vmouse::PASSTHROUGH_PREFIX));
+            assert!(d.attach_source("/dev/input/vmouse-event0".to_string(), s, None).is_err());
+            assert!(d.devices.is_empty());
+
+            d.shutdown().await;
+            remove(&d.config_file);
+        });
+    }
+
+    #[test]
+    fn battery_low_notified() {
+        async_std::task::block_on(async {
+            let (mut d, _evt_rx, _tick_rx) = daemon("battery-low");
+            let (ctl_tx, ctl_rx) = async_std::channel::unbounded();
+            let path = "/dev/input/vmouse-event0";
+
+            d.attach_source(path.to_string(), MockSource::new("usb-0000:00:14.0-1", true), None).unwrap();
+            let dev = d.devices[path].info.clone();
+
+            let (server, mut client) = UnixStream::pair().unwrap();
+            d.attach_client(server, ctl_tx).await.unwrap();
+            client.write_all(&vmouse::encode(&Command::Listen { topics: vec![Topic::DeviceEvents] }).unwrap()).await.unwrap();
+            let listen = ctl_rx.recv().await.unwrap();
+            assert_eq!(d.handle_cmd(&listen).await.unwrap(), Some(Command::Ok));
+
+            // Notified once on falling below the threshold, and again after recovering
+            for level in [Some(50), Some(BATTERY_LOW), Some(10), Some(5), None, Some(60), Some(3)] {
+                d.update_battery(path, level);
+            }
+            assert_eq!(d.status().devices.iter().map(|s| s.battery).collect::<Vec<_>>(), vec![Some(3)]);
+
+            // Unknown devices are ignored
+            d.update_battery("/dev/input/vmouse-event1", Some(1));
+
+            d.shutdown().await;
+
+            assert_eq!(read_to_close(client).await, vec![
+                Command::BatteryLow { device: dev.clone(), percent: 10 },
+                Command::BatteryLow { device: dev, percent: 3 },
+                Command::ShuttingDown,
+            ]);
+
+            remove(&d.config_file);
+        });
+    }
+
+    #[test]
+    fn pending_bind_cancelled() {
+        async_std::task::block_on(async {
+            let (mut d, _evt_rx, _tick_rx) = daemon("pending-bind");
+            let (tx, rx) = async_std::channel::unbounded();
+
+            // Device that is never present
+            let missing = UsbDevice { vid: 0xffff, pid: 0xfffe, name: None };
+            let bind = Command::Bind { target: BindTarget::Id(missing), wait: Some(60) };
+
+            // Waiting binds are held without a response
+            let h = CommandHandle { id: 7, ..request(&tx, bind.clone()) };
+            assert_eq!(d.handle_cmd(&h).await.unwrap(), None);
+            assert_eq!(d.pending_binds.len(), 1);
+
+            // Disconnecting cancels the pending bind, no late response is sent
+            d.remove_client(7).await;
+            assert!(d.pending_binds.is_empty());
+
+            d.poll_binds().await;
+            assert!(rx.try_recv().is_err());
+
+            // Other clients time out with an error once the wait expires
+            let h = CommandHandle { id: 8, ..request(&tx, bind) };
+            assert_eq!(d.handle_cmd(&h).await.unwrap(), None);
+            d.pending_binds[0].deadline = Instant::now();
+
+            d.poll_binds().await;
+            assert!(d.pending_binds.is_empty());
+            assert_eq!(rx.try_recv().unwrap().cmd, Command::Error(ErrorCode::DeviceNotFound));
+
+            remove(&d.config_file);
+        });
+    }
+
+    #[test]
+    fn clients_notified_on_shutdown() {
+        async_std::task::block_on(async {
+            let (mut d, _evt_rx, _tick_rx) = daemon("shutdown");
+            let (ctl_tx, _ctl_rx) = async_std::channel::unbounded();
+
+            let mut clients = vec![];
+            for _i in 0..2 {
+                let (server, client) = UnixStream::pair().unwrap();
+                d.attach_client(server, ctl_tx.clone()).await.unwrap();
+                clients.push(client);
+            }
+
+            async_std::future::timeout(SHUTDOWN_TIMEOUT, d.shutdown()).await.unwrap();
+            assert!(d.clients.is_empty());
+
+            // Each client receives the notification before the connection closes
+            for c in clients {
+                assert_eq!(read_to_close(c).await, vec![Command::ShuttingDown]);
+            }
+
+            remove(&d.config_file);
+        });
+    }
 }
@@ -0,0 +1,363 @@
+//! Daemon metrics registry, updated from the event loop
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use vmouse::{DeviceMetrics, Histogram, MetricsSnapshot, UsbDevice, LATENCY_BUCKETS_US};
+
+/// Rate measurement window
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Windowed event rate
+#[derive(Clone, Debug)]
+pub struct Rate {
+    start: Instant,
+    count: u64,
+    per_sec: f32,
+}
+
+impl Rate {
+    fn new() -> Self {
+        Self { start: Instant::now(), count: 0, per_sec: 0.0 }
+    }
+
+    /// Count events, updating the rate at the end of each window
+    fn add(&mut self, n: u64, now: Instant) {
+        self.count += n;
+
+        let elapsed = now.duration_since(self.start);
+        if elapsed >= RATE_WINDOW {
+            self.per_sec = self.count as f32 / elapsed.as_secs_f32();
+            self.start = now;
+            self.count = 0;
+        }
+    }
+
+    /// Fetch the current rate, decaying to zero once events stop
+    fn get(&self, now: Instant) -> f32 {
+        match now.duration_since(self.start) < RATE_WINDOW * 2 {
+            true => self.per_sec,
+            false => 0.0,
+        }
+    }
+}
+
+/// Daemon event and latency metrics
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    started: Instant,
+    /// Input events processed
+    pub events_in: u64,
+    rate_in: Rate,
+    /// Mapped output events, before coalescing
+    pub events_mapped: u64,
+    /// Output events written
+    pub events_out: u64,
+    rate_out: Rate,
+    /// Client messages dropped
+    pub broadcasts_dropped: u64,
+    /// Output values saturated to the output limits
+    pub outputs_clamped: u64,
+    devices: HashMap<String, (u64, Rate)>,
+    latency: Histogram,
+    /// Smoothed source to output latency (us)
+    pub latency_us: u64,
+    /// Maximum source to output latency (us)
+    pub latency_max_us: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            events_in: 0,
+            rate_in: Rate::new(),
+            events_mapped: 0,
+            events_out: 0,
+            rate_out: Rate::new(),
+            broadcasts_dropped: 0,
+            outputs_clamped: 0,
+            devices: HashMap::new(),
+            latency: Histogram::new(LATENCY_BUCKETS_US),
+            latency_us: 0,
+            latency_max_us: 0,
+        }
+    }
+
+    /// Seconds since daemon start
+    pub fn uptime(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// Record an input event from a device
+    pub fn input(&mut self, d: &UsbDevice) {
+        let now = Instant::now();
+
+        self.events_in += 1;
+        self.rate_in.add(1, now);
+
+        let (n, r) = self.devices.entry(d.to_string()).or_insert_with(|| (0, Rate::new()));
+        *n += 1;
+        r.add(1, now);
+    }
+
+    /// Record written output events
+    pub fn output(&mut self, n: u64) {
+        self.events_out += n;
+        self.rate_out.add(n, Instant::now());
+    }
+
+    /// Record source to output latency
+    pub fn latency(&mut self, l: Duration) {
+        let l = l.as_micros() as u64;
+
+        self.latency.record(l);
+
+        // Exponential moving average over ~16 events
+        self.latency_us = match self.latency_us {
+            0 => l,
+            a => a - a / 16 + l / 16,
+        };
+        self.latency_max_us = self.latency_max_us.max(l);
+    }
+
+    /// Drop metrics for a removed device
+    pub fn remove(&mut self, d: &UsbDevice) {
+        self.devices.remove(&d.to_string());
+    }
+
+    /// Build a snapshot for [`vmouse::Command::Metrics`]
+    pub fn snapshot(&self, clients: usize) -> MetricsSnapshot {
+        let now = Instant::now();
+
+        MetricsSnapshot {
+            uptime: self.uptime(),
+            clients,
+            events_in: self.events_in,
+            rate_in: self.rate_in.get(now),
+            events_mapped: self.events_mapped,
+            events_out: self.events_out,
+            rate_out: self.rate_out.get(now),
+            broadcasts_dropped: self.broadcasts_dropped,
+            outputs_clamped: self.outputs_clamped,
+            devices: self.devices.iter()
+                .map(|(k, (n, r))| (k.clone(), DeviceMetrics { events_in: *n, rate_in: r.get(now) }))
+                .collect(),
+            latency_us: self.latency.clone(),
+        }
+    }
+}
+
+/// Render a snapshot in the Prometheus text exposition format
+#[cfg(feature = "metrics")]
+pub fn prometheus(m: &MetricsSnapshot) -> String {
+    use std::fmt::Write;
+
+    let mut s = String::new();
+
+    let _ = writeln!(s, "# TYPE vmouse_uptime_seconds gauge\nvmouse_uptime_seconds {}", m.uptime);
+    let _ = writeln!(s, "# TYPE vmouse_clients gauge\nvmouse_clients {}", m.clients);
+    let _ = writeln!(s, "# TYPE vmouse_events_in_total counter\nvmouse_events_in_total {}", m.events_in);
+    let _ = writeln!(s, "# TYPE vmouse_events_mapped_total counter\nvmouse_events_mapped_total {}", m.events_mapped);
+    let _ = writeln!(s, "# TYPE vmouse_events_out_total counter\nvmouse_events_out_total {}", m.events_out);
+    let _ = writeln!(s, "# TYPE vmouse_broadcasts_dropped_total counter\nvmouse_broadcasts_dropped_total {}", m.broadcasts_dropped);
+    let _ = writeln!(s, "# TYPE vmouse_outputs_clamped_total counter\nvmouse_outputs_clamped_total {}", m.outputs_clamped);
+
+    let _ = writeln!(s, "# TYPE vmouse_device_events_in_total counter");
+    for (d, v) in &m.devices {
+        let _ = writeln!(s, "vmouse_device_events_in_total{{device=\"{}\"}} {}", d, v.events_in);
+    }
+
+    // Histogram buckets are cumulative in the exposition format
+    let h = &m.latency_us;
+    let _ = writeln!(s, "# TYPE vmouse_latency_us histogram");
+    let mut n = 0;
+    for (b, c) in h.bounds.iter().zip(&h.counts) {
+        n += c;
+        let _ = writeln!(s, "vmouse_latency_us_bucket{{le=\"{}\"}} {}", b, n);
+    }
+    let _ = writeln!(s, "vmouse_latency_us_bucket{{le=\"+Inf\"}} {}", h.count);
+    let _ = writeln!(s, "vmouse_latency_us_sum {}", h.sum);
+    let _ = writeln!(s, "vmouse_latency_us_count {}", h.count);
+
+    s
+}
+
+#[cfg(feature = "metrics")]
+impl crate::Daemon {
+    /// Serve metrics in Prometheus text format on a TCP address, requests are
+    /// forwarded through the control channel as an internal client
+    pub(crate) async fn attach_metrics(&mut self, addr: &str, ctl_tx: async_std::channel::Sender<crate::CommandHandle>) -> anyhow::Result<()> {
+        use async_std::io::{ReadExt, WriteExt};
+        use async_std::net::TcpListener;
+        use async_std::task::JoinHandle;
+        use futures::stream::StreamExt as _;
+        use tracing::{debug, info, warn};
+        use vmouse::Command;
+
+        use crate::{auth::ClientAuth, ClientHandle, CommandHandle, RateLimit};
+
+        let id = self.id;
+        self.id = self.id.wrapping_add(1);
+
+        let listener = TcpListener::bind(addr).await?;
+        let local = listener.local_addr()?;
+        if !local.ip().is_loopback() {
+            warn!("Metrics endpoint {} is not bound to localhost", local);
+        }
+
+        info!("Serving metrics on http://{}/metrics", local);
+
+        let (tx, _rx) = async_std::channel::unbounded();
+
+        let h: JoinHandle<Result<(), anyhow::Error>> = async_std::task::spawn(async move {
+            let mut incoming = listener.incoming();
+
+            while let Some(s) = incoming.next().await {
+                let mut s = match s {
+                    Ok(s) => s,
+                    Err(e) => {
+                        debug!("Metrics connection failed: {:?}", e);
+                        continue;
+                    }
+                };
+
+                // Every request is answered with the current snapshot, the request is read
+                // but not parsed so the connection is not reset on close
+                let mut buff = [0u8; 1024];
+                let _ = s.read(&mut buff).await;
+
+                let (resp_tx, resp_rx) = async_std::channel::bounded(1);
+                ctl_tx.send(CommandHandle { id, req: vmouse::BROADCAST_ID, c: Command::GetMetrics, tx: resp_tx, cred: None }).await?;
+
+                let body = match resp_rx.recv().await?.cmd {
+                    Command::Metrics(m) => prometheus(&m),
+                    r => {
+                        warn!("Unexpected metrics response: {:?}", r);
+                        continue;
+                    }
+                };
+
+                let resp = format!(
+                    "HTTP/1.0 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                if let Err(e) = s.write_all(resp.as_bytes()).await {
+                    debug!("Metrics write failed: {:?}", e);
+                }
+            }
+
+            Ok(())
+        });
+
+        self.clients.insert(
+            id,
+            ClientHandle {
+                id,
+                tx,
+                listen: None,
+                raw_limit: RateLimit::new(),
+                last_seen: Instant::now(),
+                pinged: None,
+                auth: ClientAuth::Internal,
+                task: h,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE: UsbDevice = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+    #[test]
+    fn rate_windows() {
+        let mut r = Rate::new();
+        let start = r.start;
+
+        // Rates update at the end of each window
+        r.add(50, start + Duration::from_millis(500));
+        assert_eq!(r.get(start + Duration::from_millis(500)), 0.0);
+        r.add(50, start + Duration::from_secs(1));
+        assert_eq!(r.get(start + Duration::from_secs(1)), 100.0);
+
+        r.add(20, start + Duration::from_secs(3));
+        assert_eq!(r.get(start + Duration::from_secs(3)), 10.0);
+
+        // And decay to zero once events stop
+        assert_eq!(r.get(start + Duration::from_millis(4500)), 10.0);
+        assert_eq!(r.get(start + Duration::from_secs(5)), 0.0);
+    }
+
+    #[test]
+    fn latency_averaged() {
+        let mut m = Metrics::new();
+
+        m.latency(Duration::from_micros(1600));
+        assert_eq!((m.latency_us, m.latency_max_us), (1600, 1600));
+
+        m.latency(Duration::from_micros(3200));
+        assert_eq!((m.latency_us, m.latency_max_us), (1700, 3200));
+
+        m.latency(Duration::from_micros(200));
+        assert_eq!((m.latency_us, m.latency_max_us), (1606, 3200));
+
+        assert_eq!(m.snapshot(0).latency_us.count, 3);
+    }
+
+    #[test]
+    fn device_counts() {
+        let mut m = Metrics::new();
+        let other = UsbDevice { vid: 0x046d, pid: 0xc626, name: None };
+
+        for _ in 0..3 {
+            m.input(&DEVICE);
+        }
+        m.input(&other);
+        m.output(5);
+
+        let s = m.snapshot(2);
+        assert_eq!((s.clients, s.events_in, s.events_out), (2, 4, 5));
+        assert_eq!(s.devices[&DEVICE.to_string()].events_in, 3);
+        assert_eq!(s.devices[&other.to_string()].events_in, 1);
+
+        // Removed devices are dropped from snapshots
+        m.remove(&other);
+        assert_eq!(m.snapshot(2).devices.keys().collect::<Vec<_>>(), vec![&DEVICE.to_string()]);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn prometheus_text() {
+        let mut m = Metrics::new();
+        m.input(&DEVICE);
+        m.output(2);
+        for l in [50, 50, 300, 200_000] {
+            m.latency(Duration::from_micros(l));
+        }
+
+        let s = prometheus(&m.snapshot(1));
+        let lines: Vec<_> = s.lines().collect();
+
+        for l in ["vmouse_clients 1", "vmouse_events_in_total 1", "vmouse_events_out_total 2", "vmouse_device_events_in_total{device=\"256f:c635\"} 1"] {
+            assert!(lines.contains(&l), "{}: {}", l, s);
+        }
+
+        // Buckets are cumulative, with the overflow counted in +Inf
+        for l in ["vmouse_latency_us_bucket{le=\"100\"} 2", "vmouse_latency_us_bucket{le=\"500\"} 3", "vmouse_latency_us_bucket{le=\"100000\"} 3", "vmouse_latency_us_bucket{le=\"+Inf\"} 4", "vmouse_latency_us_sum 200400", "vmouse_latency_us_count 4"] {
+            assert!(lines.contains(&l), "{}: {}", l, s);
+        }
+
+        // Every sample follows a type declaration
+        for l in lines.iter().filter(|l| !l.starts_with('#')) {
+            let name = l.split(['{', ' ']).next().unwrap();
+            let family = name.trim_end_matches("_bucket").trim_end_matches("_sum").trim_end_matches("_count");
+            assert!(s.contains(&format!("# TYPE {} ", family)), "{}", l);
+        }
+    }
+}
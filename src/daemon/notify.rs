@@ -0,0 +1,142 @@
+//! systemd service notifications (`systemd` feature)
+//!
+//! Implements the `sd_notify` datagram protocol directly so no libsystemd
+//! dependency is required, without the feature all calls are no-ops.
+
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+use tracing::{debug, trace};
+
+/// Signal service readiness
+pub fn ready() {
+    send("READY=1");
+}
+
+/// Signal service shutdown
+pub fn stopping() {
+    send("STOPPING=1");
+}
+
+/// Update service status string
+pub fn status(s: &str) {
+    send(&format_status(s));
+}
+
+/// Ping the service watchdog
+pub fn watchdog() {
+    send("WATCHDOG=1");
+}
+
+/// Format a `STATUS=` message, newlines would terminate the assignment
+pub fn format_status(s: &str) -> String {
+    format!("STATUS={}", s.replace('\n', " "))
+}
+
+/// Fetch the watchdog ping interval (half the configured `WatchdogSec`)
+pub fn watchdog_interval() -> Option<Duration> {
+    if !cfg!(feature = "systemd") {
+        return None;
+    }
+
+    // Ignore watchdog settings intended for another process
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    if usec == 0 {
+        return None;
+    }
+
+    Some(Duration::from_micros(usec / 2))
+}
+
+/// Send a notification message to `NOTIFY_SOCKET` if set
+fn send(msg: &str) {
+    if !cfg!(feature = "systemd") {
+        return;
+    }
+
+    let path = match std::env::var("NOTIFY_SOCKET") {
+        Ok(p) if !p.is_empty() => p,
+        _ => return,
+    };
+
+    trace!("sd_notify: {}", msg);
+
+    if let Err(e) = send_to(&path, msg) {
+        debug!("Failed to send notification to '{}': {:?}", path, e);
+    }
+}
+
+/// Send a notification message to the provided socket path
+pub fn send_to(path: &str, msg: &str) -> Result<(), std::io::Error> {
+    let s = UnixDatagram::unbound()?;
+
+    match path.strip_prefix('@') {
+        // Abstract namespace socket
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+            s.send_to_addr(msg.as_bytes(), &addr)?;
+        }
+        // Filesystem socket
+        None => {
+            s.send_to(msg.as_bytes(), path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::SocketAddr;
+
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    /// Receive a single datagram as a string
+    fn recv(s: &UnixDatagram) -> String {
+        let mut buff = [0u8; 256];
+        let n = s.recv(&mut buff).unwrap();
+        String::from_utf8(buff[..n].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn status_format() {
+        assert_eq!(format_status("running"), "STATUS=running");
+        assert_eq!(format_status("2 devices\nbound"), "STATUS=2 devices bound");
+    }
+
+    #[test]
+    fn send_to_path() {
+        let p = test_dir("notify", "path").join("notify.sock");
+        let s = UnixDatagram::bind(&p).unwrap();
+
+        for m in ["READY=1", &format_status("listening"), "WATCHDOG=1", "STOPPING=1"] {
+            send_to(p.to_str().unwrap(), m).unwrap();
+            assert_eq!(recv(&s), m);
+        }
+    }
+
+    #[test]
+    fn send_to_abstract() {
+        use std::os::linux::net::SocketAddrExt;
+
+        let name = format!("vmouse-notify-{}", std::process::id());
+        let s = UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(name.as_bytes()).unwrap()).unwrap();
+
+        send_to(&format!("@{}", name), "READY=1").unwrap();
+        assert_eq!(recv(&s), "READY=1");
+    }
+
+    #[test]
+    fn send_to_missing() {
+        assert!(send_to("/nonexistent/notify.sock", "READY=1").is_err());
+    }
+}
@@ -0,0 +1,142 @@
+//! Absolute pointer position integration for `Map::AbsX` / `Map::AbsY` outputs
+
+use std::time::{Duration, Instant};
+
+use evdev_rs::enums::EV_ABS;
+
+use vmouse::{AbsPointerConfig, AbsPointerMode, Map};
+
+/// Maximum integration step, avoids jumps after pauses in input events
+const MAX_STEP: Duration = Duration::from_millis(100);
+
+/// Absolute pointer state, positions in virtual resolution units
+#[derive(Clone, Debug)]
+pub struct AbsPointer {
+    config: AbsPointerConfig,
+    pos: [f32; 2],
+    last: [Option<Instant>; 2],
+}
+
+impl AbsPointer {
+    /// Create a new pointer, starting at the centre of the virtual area
+    pub fn new(config: AbsPointerConfig) -> Self {
+        let mut s = Self { config, pos: [0.0; 2], last: [None; 2] };
+        s.pos = [s.centre(0), s.centre(1)];
+        s
+    }
+
+    /// Update the pointer with a normalised (-1.0 to 1.0) output value,
+    /// returning the axis code and new position if it changed
+    pub fn update(&mut self, map: Map, val: f32, now: Instant) -> Option<(EV_ABS, i32)> {
+        let (i, code) = match map {
+            Map::AbsX => (0, EV_ABS::ABS_X),
+            Map::AbsY => (1, EV_ABS::ABS_Y),
+            _ => return None,
+        };
+
+        let dt = self.last[i].map(|l| now.duration_since(l).min(MAX_STEP)).unwrap_or_default();
+        self.last[i] = Some(now);
+
+        let prev = self.pos[i];
+        let max = self.extent(i);
+
+        self.pos[i] = match (val == 0.0, self.config.recenter, self.config.mode) {
+            // At rest, snap back or hold
+            (true, true, _) => self.centre(i),
+            (true, false, _) => prev,
+            (false, _, AbsPointerMode::Direct) => self.centre(i) + val.clamp(-1.0, 1.0) * max / 2.0,
+            (false, _, AbsPointerMode::Velocity) => prev + val * self.config.speed * max * dt.as_secs_f32(),
+        }
+        .clamp(0.0, max);
+
+        match self.pos[i] as i32 == prev as i32 {
+            true => None,
+            false => Some((code, self.pos[i] as i32)),
+        }
+    }
+
+    /// Maximum position for an axis
+    fn extent(&self, i: usize) -> f32 {
+        match i {
+            0 => (self.config.width - 1) as f32,
+            _ => (self.config.height - 1) as f32,
+        }
+    }
+
+    fn centre(&self, i: usize) -> f32 {
+        self.extent(i) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pointer with a 0..=100 x 0..=50 area
+    fn pointer(mode: AbsPointerMode, recenter: bool) -> AbsPointer {
+        AbsPointer::new(AbsPointerConfig { width: 101, height: 51, mode, speed: 2.0, recenter })
+    }
+
+    #[test]
+    fn direct_positions() {
+        let mut p = pointer(AbsPointerMode::Direct, true);
+        let now = Instant::now();
+
+        assert_eq!(p.update(Map::AbsX, 0.5, now), Some((EV_ABS::ABS_X, 75)));
+        assert_eq!(p.update(Map::AbsY, -1.0, now), Some((EV_ABS::ABS_Y, 0)));
+
+        // Saturated to the virtual area, unchanged positions are not reported
+        assert_eq!(p.update(Map::AbsX, 4.0, now), Some((EV_ABS::ABS_X, 100)));
+        assert_eq!(p.update(Map::AbsX, 1.0, now), None);
+
+        // Other mappings are ignored
+        assert_eq!(p.update(Map::X, 1.0, now), None);
+    }
+
+    #[test]
+    fn recenter_or_hold() {
+        let now = Instant::now();
+
+        let mut p = pointer(AbsPointerMode::Direct, true);
+        p.update(Map::AbsX, 1.0, now);
+        assert_eq!(p.update(Map::AbsX, 0.0, now), Some((EV_ABS::ABS_X, 50)));
+
+        let mut p = pointer(AbsPointerMode::Direct, false);
+        p.update(Map::AbsX, 1.0, now);
+        assert_eq!(p.update(Map::AbsX, 0.0, now), None);
+        assert_eq!(p.pos[0], 100.0);
+    }
+
+    #[test]
+    fn velocity_integrates() {
+        let mut p = pointer(AbsPointerMode::Velocity, false);
+        let start = Instant::now();
+
+        // The first event has no interval to integrate over
+        assert_eq!(p.update(Map::AbsX, 0.5, start), None);
+
+        // 0.5 * 2.0 widths/s * 100 * 50ms
+        assert_eq!(p.update(Map::AbsX, 0.5, start + Duration::from_millis(50)), Some((EV_ABS::ABS_X, 55)));
+        assert_eq!(p.update(Map::AbsX, -0.5, start + Duration::from_millis(100)), Some((EV_ABS::ABS_X, 50)));
+
+        // Steps after a pause are limited, then saturate at the bounds
+        assert_eq!(p.update(Map::AbsX, 1.0, start + Duration::from_secs(10)), Some((EV_ABS::ABS_X, 70)));
+        assert_eq!(p.update(Map::AbsX, 1.0, start + Duration::from_secs(11)), Some((EV_ABS::ABS_X, 90)));
+        assert_eq!(p.update(Map::AbsX, 1.0, start + Duration::from_secs(12)), Some((EV_ABS::ABS_X, 100)));
+        assert_eq!(p.update(Map::AbsX, 1.0, start + Duration::from_secs(13)), None);
+
+        // Axes are integrated independently
+        assert_eq!(p.update(Map::AbsY, 1.0, start + Duration::from_secs(14)), None);
+        assert_eq!(p.update(Map::AbsY, 1.0, start + Duration::from_secs(15)), Some((EV_ABS::ABS_Y, 35)));
+    }
+
+    #[test]
+    fn velocity_recenters_at_rest() {
+        let mut p = pointer(AbsPointerMode::Velocity, true);
+        let start = Instant::now();
+
+        p.update(Map::AbsY, -1.0, start);
+        assert_eq!(p.update(Map::AbsY, -1.0, start + Duration::from_millis(100)), Some((EV_ABS::ABS_Y, 15)));
+        assert_eq!(p.update(Map::AbsY, 0.0, start + Duration::from_millis(200)), Some((EV_ABS::ABS_Y, 25)));
+    }
+}
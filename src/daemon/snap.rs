@@ -0,0 +1,145 @@
+//! Dominant axis snapping for pointer outputs, constraining motion to a single
+//! direction once X or Y clearly dominates within an input frame
+
+use vmouse::Map;
+
+/// Fraction of the snap ratio below which a snapped axis is released
+const RELEASE: f32 = 0.5;
+
+/// Per-device snap state, pointer outputs are held until the end of each frame
+#[derive(Clone, Debug, Default)]
+pub struct Snap {
+    /// Pending X / Y outputs for the current frame
+    x: Option<f32>,
+    y: Option<f32>,
+    /// Currently dominant axis, if snapped
+    snapped: Option<Map>,
+}
+
+impl Snap {
+    /// Hold a pointer output until the end of the frame, returns `false` for
+    /// other mappings which are written immediately
+    pub fn push(&mut self, map: Map, val: f32) -> bool {
+        match map {
+            Map::X => self.x = Some(val),
+            Map::Y => self.y = Some(val),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Complete a frame, returning held outputs with the secondary axis zeroed while snapped
+    ///
+    /// Snapping starts once `|primary| > ratio * |secondary|` and holds until the ratio
+    /// falls below `ratio * RELEASE` (at least 1.0), or both axes return to rest.
+    pub fn frame(&mut self, ratio: f32) -> Vec<(Map, f32)> {
+        let (x, y) = (self.x.take(), self.y.take());
+        if x.is_none() && y.is_none() {
+            return vec![];
+        }
+
+        let (ax, ay) = (x.unwrap_or(0.0).abs(), y.unwrap_or(0.0).abs());
+        let release = (ratio * RELEASE).max(1.0);
+
+        self.snapped = match self.snapped {
+            _ if ax == 0.0 && ay == 0.0 => None,
+            Some(Map::X) if ax > release * ay => Some(Map::X),
+            Some(Map::Y) if ay > release * ax => Some(Map::Y),
+            _ if ax > ratio * ay => Some(Map::X),
+            _ if ay > ratio * ax => Some(Map::Y),
+            _ => None,
+        };
+
+        let mut out = vec![];
+        if let Some(v) = x {
+            out.push((Map::X, if self.snapped == Some(Map::Y) { 0.0 } else { v }));
+        }
+        if let Some(v) = y {
+            out.push((Map::Y, if self.snapped == Some(Map::X) { 0.0 } else { v }));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATIO: f32 = 4.0;
+
+    fn frame(s: &mut Snap, x: f32, y: f32) -> Vec<(Map, f32)> {
+        s.push(Map::X, x);
+        s.push(Map::Y, y);
+        s.frame(RATIO)
+    }
+
+    #[test]
+    fn passes_other_mappings() {
+        let mut s = Snap::default();
+        assert!(!s.push(Map::V, 1.0));
+        assert!(s.frame(RATIO).is_empty());
+    }
+
+    #[test]
+    fn enter_and_leave_band() {
+        let mut s = Snap::default();
+
+        // Below the snap ratio, both axes pass
+        assert_eq!(frame(&mut s, 10.0, 3.0), vec![(Map::X, 10.0), (Map::Y, 3.0)]);
+
+        // Above the snap ratio, secondary axis is zeroed
+        assert_eq!(frame(&mut s, 10.0, 2.0), vec![(Map::X, 10.0), (Map::Y, 0.0)]);
+
+        // Within the hysteresis band the snap holds
+        assert_eq!(frame(&mut s, 10.0, 3.0), vec![(Map::X, 10.0), (Map::Y, 0.0)]);
+        assert_eq!(frame(&mut s, 10.0, 4.5), vec![(Map::X, 10.0), (Map::Y, 0.0)]);
+
+        // Below the release ratio the snap is dropped
+        assert_eq!(frame(&mut s, 10.0, 6.0), vec![(Map::X, 10.0), (Map::Y, 6.0)]);
+        assert_eq!(frame(&mut s, 10.0, 3.0), vec![(Map::X, 10.0), (Map::Y, 3.0)]);
+    }
+
+    #[test]
+    fn oscillating_at_threshold() {
+        let mut s = Snap::default();
+
+        assert_eq!(frame(&mut s, 0.5, 10.0), vec![(Map::X, 0.0), (Map::Y, 10.0)]);
+
+        // Crossing the snap threshold either way does not release
+        for i in 0..20 {
+            let x = if i % 2 == 0 { 2.6 } else { 2.4 };
+            assert_eq!(frame(&mut s, x, 10.0), vec![(Map::X, 0.0), (Map::Y, 10.0)], "frame {}", i);
+        }
+
+        // Nor does oscillating at the release threshold once released
+        assert_eq!(frame(&mut s, 5.5, 10.0), vec![(Map::X, 5.5), (Map::Y, 10.0)]);
+        for i in 0..20 {
+            let x = if i % 2 == 0 { 4.9 } else { 5.1 };
+            assert_eq!(frame(&mut s, x, 10.0), vec![(Map::X, x), (Map::Y, 10.0)], "frame {}", i);
+        }
+    }
+
+    #[test]
+    fn rest_releases_snap() {
+        let mut s = Snap::default();
+
+        assert_eq!(frame(&mut s, 10.0, 0.0), vec![(Map::X, 10.0), (Map::Y, 0.0)]);
+        assert_eq!(frame(&mut s, 0.0, 0.0), vec![(Map::X, 0.0), (Map::Y, 0.0)]);
+
+        // Snap does not carry over once both axes rest
+        assert_eq!(frame(&mut s, 10.0, 4.5), vec![(Map::X, 10.0), (Map::Y, 4.5)]);
+    }
+
+    #[test]
+    fn single_axis_frame() {
+        let mut s = Snap::default();
+
+        s.push(Map::Y, -8.0);
+        assert_eq!(s.frame(RATIO), vec![(Map::Y, -8.0)]);
+
+        // Snapped to Y, a lone small X update is held at zero
+        s.push(Map::X, 1.0);
+        s.push(Map::Y, -8.0);
+        assert_eq!(s.frame(RATIO), vec![(Map::X, 0.0), (Map::Y, -8.0)]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Runtime state persisted across daemon restarts (output enabled flags and active profiles)
+//!
+//! State is kept separate from the config file so toggling output or switching profiles
+//! does not rewrite user config. Missing or invalid state files fall back to config defaults.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Delay between a state change and writing the state file, so bursts of changes are written once
+pub const STATE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Persisted runtime state
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeState {
+    /// Global output enabled flag
+    pub enabled: bool,
+    /// Per-device output enabled flags by `vid:pid`
+    pub device_enabled: BTreeMap<String, bool>,
+    /// Active profiles by device (`default` or `vid:pid`)
+    pub active: BTreeMap<String, String>,
+}
+
+impl Default for RuntimeState {
+    fn default() -> Self {
+        Self { enabled: true, device_enabled: BTreeMap::new(), active: BTreeMap::new() }
+    }
+}
+
+impl RuntimeState {
+    /// Load state, returning `None` if missing or invalid
+    pub fn load(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref();
+
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| debug!("No runtime state loaded from '{}': {}", path.display(), e))
+            .ok()?;
+
+        toml::from_str(&s)
+            .map_err(|e| debug!("Ignoring invalid runtime state '{}': {}", path.display(), e))
+            .ok()
+    }
+
+    /// Save state atomically, creating the state directory if required
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+
+        if let Some(d) = path.parent() {
+            std::fs::create_dir_all(d)?;
+        }
+
+        vmouse::write_atomic(path, toml::to_string_pretty(self)?.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// State file with debounced writes
+#[derive(Clone, Debug)]
+pub struct StateFile {
+    path: String,
+    /// Time of the first unsaved change
+    dirty: Option<Instant>,
+}
+
+impl StateFile {
+    pub fn new(path: String) -> Self {
+        Self { path, dirty: None }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Mark state as changed, the write is delayed from the first unsaved change
+    pub fn mark(&mut self, now: Instant) {
+        self.dirty.get_or_insert(now);
+    }
+
+    /// Whether a write is pending
+    pub fn pending(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    /// Take a pending write once the debounce has elapsed, or immediately when `force` is set
+    pub fn due(&mut self, now: Instant, force: bool) -> bool {
+        match self.dirty {
+            Some(t) if force || now.duration_since(t) >= STATE_DEBOUNCE => {
+                self.dirty = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::testutil::test_dir;
+
+    use super::*;
+
+    /// Unique state path within a fresh temporary directory
+    fn state_path(name: &str) -> PathBuf {
+        test_dir("state", name).join("state").join("state.toml")
+    }
+
+    #[test]
+    fn load_missing() {
+        assert_eq!(RuntimeState::load(state_path("missing")), None);
+    }
+
+    #[test]
+    fn load_corrupt() {
+        let p = state_path("corrupt");
+        std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+
+        for s in ["enabled = \"yes\"", "not toml [", "device_enabled = 1"] {
+            std::fs::write(&p, s).unwrap();
+            assert_eq!(RuntimeState::load(&p), None, "{}", s);
+        }
+
+        // Missing fields use defaults
+        std::fs::write(&p, "").unwrap();
+        assert_eq!(RuntimeState::load(&p), Some(RuntimeState::default()));
+
+        let _ = std::fs::remove_dir_all(p.parent().unwrap().parent().unwrap());
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let p = state_path("round-trip");
+
+        let mut s = RuntimeState { enabled: false, ..Default::default() };
+        s.device_enabled.insert("256f:c635".to_string(), false);
+        s.device_enabled.insert("046d:c626".to_string(), true);
+        s.active.insert("default".to_string(), "cad".to_string());
+
+        // State directory is created as required
+        s.save(&p).unwrap();
+        assert_eq!(RuntimeState::load(&p), Some(s.clone()));
+
+        s.active.clear();
+        s.save(&p).unwrap();
+        assert_eq!(RuntimeState::load(&p), Some(s));
+
+        let _ = std::fs::remove_dir_all(p.parent().unwrap().parent().unwrap());
+    }
+
+    #[test]
+    fn state_file_debounce() {
+        let t0 = Instant::now();
+        let mut f = StateFile::new("state.toml".to_string());
+
+        assert!(!f.pending());
+        assert!(!f.due(t0, true));
+
+        // Writes are delayed from the first unsaved change
+        f.mark(t0);
+        f.mark(t0 + STATE_DEBOUNCE / 2);
+        assert!(f.pending());
+        assert!(!f.due(t0 + STATE_DEBOUNCE / 2, false));
+        assert!(f.due(t0 + STATE_DEBOUNCE, false));
+
+        // Taken writes are not repeated
+        assert!(!f.pending());
+        assert!(!f.due(t0 + STATE_DEBOUNCE * 2, false));
+
+        // Forced writes ignore the debounce
+        f.mark(t0);
+        assert!(f.due(t0, true));
+        assert!(!f.pending());
+    }
+}
@@ -0,0 +1,120 @@
+//! Config file watching, notifying the daemon when the file changes on disk
+//!
+//! The parent directory is watched alongside the file so editors saving by
+//! writing a temporary file and renaming it over the original are detected.
+
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read};
+use std::os::unix::prelude::{AsRawFd, FromRawFd, OsStrExt, RawFd};
+use std::path::Path;
+use std::time::Duration;
+
+use futures::stream::{BoxStream, StreamExt};
+use tracing::{debug, warn};
+
+/// Delay after a change before notifying, changes within this window are merged
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// inotify watch on a file and its parent directory
+struct Watcher {
+    file: File,
+    /// Watched file name within the directory
+    name: OsString,
+    dir_wd: i32,
+    file_wd: Option<i32>,
+}
+
+impl Watcher {
+    fn new(path: &Path) -> Result<Self, Error> {
+        let name = path.file_name().ok_or_else(|| Error::from(ErrorKind::InvalidInput))?.to_owned();
+        let dir = match path.parent() {
+            Some(d) if !d.as_os_str().is_empty() => d,
+            _ => Path::new("."),
+        };
+
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let file = unsafe { File::from_raw_fd(fd) };
+
+        let dir_wd = add_watch(fd, dir, libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO)?;
+        // The file may not exist yet, directory events cover its creation
+        let file_wd = add_watch(fd, path, libc::IN_CLOSE_WRITE).ok();
+
+        Ok(Self { file, name, dir_wd, file_wd })
+    }
+
+    /// Read pending events, returns whether any refer to the watched file
+    ///
+    /// Returns `ErrorKind::WouldBlock` where no events are pending
+    fn read(&self) -> Result<bool, Error> {
+        let mut buff = [0u8; 4096];
+        let n = (&self.file).read(&mut buff)?;
+
+        let header = std::mem::size_of::<libc::inotify_event>();
+        let mut changed = false;
+        let mut i = 0;
+
+        while i + header <= n {
+            let e: libc::inotify_event = unsafe { std::ptr::read_unaligned(buff[i..].as_ptr() as *const _) };
+            let end = (i + header + e.len as usize).min(n);
+
+            // Names are NUL padded
+            let name = &buff[i + header..end];
+            let name = &name[..name.iter().position(|c| *c == 0).unwrap_or(name.len())];
+
+            if Some(e.wd) == self.file_wd || (e.wd == self.dir_wd && OsStr::from_bytes(name) == self.name) {
+                changed = true;
+            }
+
+            i = end;
+        }
+
+        Ok(changed)
+    }
+}
+
+impl AsRawFd for Watcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// Add an inotify watch for a path, returning the watch descriptor
+fn add_watch(fd: RawFd, path: &Path, mask: u32) -> Result<i32, Error> {
+    let p = CString::new(path.as_os_str().as_bytes()).map_err(|_| Error::from(ErrorKind::InvalidInput))?;
+
+    match unsafe { libc::inotify_add_watch(fd, p.as_ptr(), mask) } {
+        wd if wd < 0 => Err(Error::last_os_error()),
+        wd => Ok(wd),
+    }
+}
+
+/// Watch a config file, yielding once for each (debounced) change
+pub fn watch(path: &str) -> Result<BoxStream<'static, ()>, Error> {
+    let w = smol::Async::new(Watcher::new(Path::new(path))?)?;
+    debug!("Watching config file: {}", path);
+
+    let s = futures::stream::unfold(w, |w| async move {
+        loop {
+            match w.read_with(|w| w.read()).await {
+                Ok(true) => break,
+                Ok(false) => continue,
+                Err(e) => {
+                    warn!("Config file watch failed: {}", e);
+                    return None;
+                }
+            }
+        }
+
+        // Merge successive events (eg. an editor's write then rename) into one change
+        async_std::task::sleep(DEBOUNCE).await;
+        while w.get_ref().read().is_ok() {}
+
+        Some(((), w))
+    });
+
+    Ok(s.boxed())
+}
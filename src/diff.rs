@@ -0,0 +1,186 @@
+//! Config differences, for `vmousectl diff-config` and unsaved change indicators
+
+use serde_json::{Map as JsonMap, Value};
+
+use crate::Config;
+
+/// A single changed config field
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConfigDelta {
+    /// Dotted field path (eg. `devices.256f:c635.x.scale`)
+    pub path: String,
+    /// Value in the original config, `None` if added
+    pub old: Option<String>,
+    /// Value in the updated config, `None` if removed
+    pub new: Option<String>,
+}
+
+impl std::fmt::Display for ConfigDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.old, &self.new) {
+            (Some(o), Some(n)) => write!(f, "~ {}: {} -> {}", self.path, o, n),
+            (None, Some(n)) => write!(f, "+ {}: {}", self.path, n),
+            (Some(o), None) => write!(f, "- {}: {}", self.path, o),
+            (None, None) => write!(f, "  {}", self.path),
+        }
+    }
+}
+
+impl Config {
+    /// Compute per-field differences from this config to `other`, sorted by path
+    pub fn diff(&self, other: &Config) -> Vec<ConfigDelta> {
+        let mut deltas = vec![];
+        diff_value("", &diff_tree(self), &diff_tree(other), &mut deltas);
+        deltas
+    }
+}
+
+/// Convert a config to a JSON tree for comparison, with profiles keyed by `device/name`
+/// so reordering does not show as a change
+fn diff_tree(c: &Config) -> Value {
+    let mut v = serde_json::to_value(c).unwrap_or(Value::Null);
+
+    if let Some(o) = v.as_object_mut() {
+        let profiles: JsonMap<String, Value> = c.profiles.iter()
+            .map(|p| (format!("{}/{}", p.device, p.name), serde_json::to_value(p.axes).unwrap_or(Value::Null)))
+            .collect();
+        o.insert("profiles".to_string(), Value::Object(profiles));
+    }
+
+    v
+}
+
+/// Recursively compare JSON values, recording leaf differences
+fn diff_value(path: &str, a: &Value, b: &Value, deltas: &mut Vec<ConfigDelta>) {
+    if a == b {
+        return;
+    }
+
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut keys: Vec<_> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            for k in keys {
+                let p = match path {
+                    "" => k.to_string(),
+                    _ => format!("{}.{}", path, k),
+                };
+
+                // Added or removed tables are expanded to their fields
+                let empty = Value::Object(JsonMap::new());
+                match (a.get(k), b.get(k)) {
+                    (Some(a), Some(b)) => diff_value(&p, a, b, deltas),
+                    (Some(a @ Value::Object(_)), None) => diff_value(&p, a, &empty, deltas),
+                    (None, Some(b @ Value::Object(_))) => diff_value(&p, &empty, b, deltas),
+                    (a, b) => deltas.push(ConfigDelta { path: p, old: a.map(format_value), new: b.map(format_value) }),
+                }
+            }
+        }
+        _ => deltas.push(ConfigDelta { path: path.to_string(), old: Some(format_value(a)), new: Some(format_value(b)) }),
+    }
+}
+
+/// Format a JSON value for display, with floats shown at `f32` precision
+fn format_value(v: &Value) -> String {
+    match v {
+        Value::Number(n) if n.is_f64() => format!("{}", n.as_f64().unwrap_or_default() as f32),
+        Value::String(s) => s.clone(),
+        Value::Null => "none".to_string(),
+        Value::Array(a) => format!("[{}]", a.iter().map(format_value).collect::<Vec<_>>().join(", ")),
+        _ => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Axis, AxisConfig, Map, Profile, UsbDevice};
+
+    use super::*;
+
+    fn device() -> UsbDevice {
+        UsbDevice { vid: 0x256f, pid: 0xc635, name: None }
+    }
+
+    fn profile(name: &str, scale: f32) -> Profile {
+        let mut axes = Config::default().default;
+        axes[Axis::X].scale = scale;
+        Profile { device: "default".to_string(), name: name.to_string(), axes }
+    }
+
+    #[test]
+    fn identical_configs() {
+        assert_eq!(Config::default().diff(&Config::default()), vec![]);
+
+        let mut a = Config::default();
+        a.devices.insert(device(), a.default);
+        a.profiles = vec![profile("slow", 0.5), profile("fast", 2.0)];
+
+        // Profile order is not significant
+        let mut b = a.clone();
+        b.profiles.reverse();
+
+        assert_eq!(a.diff(&a.clone()), vec![]);
+        assert_eq!(a.diff(&b), vec![]);
+    }
+
+    #[test]
+    fn nested_differences() {
+        let mut a = Config::default();
+        a.devices.insert(device(), a.default);
+        a.profiles = vec![profile("slow", 0.5)];
+
+        let mut b = a.clone();
+        b.devices.get_mut(&device()).unwrap()[Axis::X].scale = 0.25;
+        b.default[Axis::RZ] = AxisConfig { map: Map::H, ..a.default[Axis::RZ] };
+        b.profiles[0].axes[Axis::X].scale = 0.75;
+
+        let delta = |path: &str, old: &str, new: &str| ConfigDelta {
+            path: path.to_string(),
+            old: Some(old.to_string()),
+            new: Some(new.to_string()),
+        };
+
+        let scale = a.default[Axis::X].scale.to_string();
+        assert_eq!(a.diff(&b), vec![
+            delta("default.rz.map", "None", "H"),
+            delta("devices.256f:c635.x.scale", &scale, "0.25"),
+            delta("profiles.default/slow.x.scale", "0.5", "0.75"),
+        ]);
+    }
+
+    #[test]
+    fn added_and_removed_tables() {
+        let a = Config::default();
+        let mut b = a.clone();
+        b.devices.insert(device(), a.default);
+
+        // Added tables expand to one delta per axis field
+        let axes = serde_json::to_value(a.default).unwrap();
+        let fields: usize = axes.as_object().unwrap().values()
+            .map(|v| v.as_object().unwrap().len())
+            .sum();
+
+        let added = a.diff(&b);
+        assert_eq!(added.len(), fields);
+        for d in &added {
+            assert!(d.path.starts_with("devices.256f:c635."), "{}", d.path);
+            assert!(d.old.is_none() && d.new.is_some(), "{}", d);
+        }
+
+        let removed = b.diff(&a);
+        assert_eq!(removed.len(), added.len());
+        for d in &removed {
+            assert!(d.old.is_some() && d.new.is_none(), "{}", d);
+        }
+    }
+
+    #[test]
+    fn delta_display() {
+        let d = ConfigDelta { path: "split_outputs".to_string(), old: Some("false".to_string()), new: Some("true".to_string()) };
+        assert_eq!(d.to_string(), "~ split_outputs: false -> true");
+        assert_eq!(ConfigDelta { old: None, ..d.clone() }.to_string(), "+ split_outputs: true");
+        assert_eq!(ConfigDelta { new: None, ..d }.to_string(), "- split_outputs: false");
+    }
+}
@@ -0,0 +1,157 @@
+//! Library error type
+
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+use crate::Command;
+
+/// vmouse library errors
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to create or write a uinput output device
+    Uinput(io::Error),
+    /// Failed to open or scan input devices
+    DeviceIo(io::Error),
+    /// Failed to encode a command frame
+    Encode(String),
+    /// Failed to decode a command frame, the connection framing may be lost
+    Decode(String),
+    /// Config or profile is invalid, or could not be parsed / encoded
+    Config(String),
+    /// Failed to read or write a config file
+    ConfigIo(PathBuf, io::Error),
+    /// Daemon socket connection failed, timed out, or was closed
+    SocketIo(io::Error),
+    /// Daemon protocol version is incompatible with this client
+    VersionMismatch {
+        daemon: u32,
+        client: u32,
+    },
+    /// Daemon sent an unexpected response
+    UnexpectedResponse(Box<Command>),
+    /// Shared profile file format is newer than supported
+    ProfileVersion {
+        found: u32,
+        supported: u32,
+        version: String,
+    },
+}
+
+impl Error {
+    /// Underlying IO error kind, if any
+    pub fn io_kind(&self) -> Option<ErrorKind> {
+        match self {
+            Error::Uinput(e) | Error::DeviceIo(e) | Error::ConfigIo(_, e) | Error::SocketIo(e) => Some(e.kind()),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Uinput(e) if e.kind() == ErrorKind::PermissionDenied => write!(
+                f,
+                "Permission denied opening /dev/uinput, add this user to the group owning \
+                /dev/uinput (see 99-vmouse.rules) or run vmoused as root"
+            ),
+            Error::Uinput(e) if e.kind() == ErrorKind::NotFound => write!(
+                f,
+                "/dev/uinput not found, check the uinput kernel module is loaded (`modprobe uinput`)"
+            ),
+            Error::Uinput(e) => write!(f, "uinput error: {}", e),
+            Error::DeviceIo(e) => write!(f, "Input device error: {}", e),
+            Error::Encode(e) => write!(f, "Failed to encode command: {}", e),
+            Error::Decode(e) => write!(f, "Failed to decode command: {}", e),
+            Error::Config(e) => write!(f, "Invalid config: {}", e),
+            Error::ConfigIo(p, e) => write!(f, "Failed to access config '{}': {}", p.display(), e),
+            Error::SocketIo(e) => write!(f, "Socket error: {}", e),
+            Error::VersionMismatch { daemon, client } => write!(
+                f,
+                "Daemon protocol v{} is incompatible with client protocol v{}, please upgrade vmoused or this client",
+                crate::protocol_string(*daemon), crate::protocol_string(*client)
+            ),
+            Error::UnexpectedResponse(c) => write!(f, "Unexpected response: {:?}", c),
+            Error::ProfileVersion { found, supported, version } => write!(
+                f,
+                "Profile format v{} (exported by vmouse {}) is newer than supported format v{}, please upgrade vmouse to import it",
+                found, version, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Uinput(e) | Error::DeviceIo(e) | Error::ConfigIo(_, e) | Error::SocketIo(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use crate::testutil::test_dir;
+    use crate::{ConfigFile, ConfigFormat};
+
+    use super::*;
+
+    #[test]
+    fn uinput_messages() {
+        let e = Error::Uinput(io::Error::from(ErrorKind::PermissionDenied));
+        assert!(e.to_string().contains("99-vmouse.rules"), "{}", e);
+
+        let e = Error::Uinput(io::Error::from(ErrorKind::NotFound));
+        assert!(e.to_string().contains("modprobe uinput"), "{}", e);
+
+        let e = Error::Uinput(io::Error::from(ErrorKind::BrokenPipe));
+        assert!(e.to_string().starts_with("uinput error: "), "{}", e);
+    }
+
+    #[test]
+    fn io_kinds() {
+        let io = |k| io::Error::from(k);
+
+        for e in [
+            Error::Uinput(io(ErrorKind::PermissionDenied)),
+            Error::DeviceIo(io(ErrorKind::PermissionDenied)),
+            Error::ConfigIo(PathBuf::from("vmouse.toml"), io(ErrorKind::PermissionDenied)),
+            Error::SocketIo(io(ErrorKind::PermissionDenied)),
+        ] {
+            assert_eq!(e.io_kind(), Some(ErrorKind::PermissionDenied), "{:?}", e);
+            assert!(e.source().is_some(), "{:?}", e);
+        }
+
+        for e in [Error::Config("invalid".to_string()), Error::Decode("invalid".to_string()), Error::VersionMismatch { daemon: 1, client: 2 }] {
+            assert_eq!(e.io_kind(), None, "{:?}", e);
+            assert!(e.source().is_none(), "{:?}", e);
+        }
+    }
+
+    #[test]
+    fn config_errors() {
+        let d = test_dir("error", "config");
+
+        // Missing files are IO errors with the path
+        let missing = d.join("missing.toml");
+        match ConfigFile::load(&missing) {
+            Err(Error::ConfigIo(p, e)) => assert_eq!((p, e.kind()), (missing, ErrorKind::NotFound)),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        // Unparseable files are config errors
+        let invalid = d.join("invalid.toml");
+        std::fs::write(&invalid, "devices = 4").unwrap();
+        match ConfigFile::load(&invalid) {
+            Err(Error::Config(m)) => assert!(m.contains("invalid.toml"), "{}", m),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        assert!(matches!(ConfigFile::parse("{", ConfigFormat::Json), Err(Error::Config(_))));
+
+        let _ = std::fs::remove_dir_all(&d);
+    }
+}
@@ -0,0 +1,286 @@
+//! Input event constants and virtual device capabilities
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use evdev_rs::enums::{EventCode, EventType, EV_ABS, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::TimeVal;
+use serde::{Deserialize, Serialize};
+
+use crate::{Config, Map, AXIS};
+
+/// Maximum axis value
+pub const AXIS_MAX: i32 = 350;
+/// Minimum axis value
+pub const AXIS_MIN: i32 = -350;
+
+/// Raw input axis range, used to normalise values to -1.0 to 1.0
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct AxisRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+/// Default (uncalibrated) axis range
+pub const AXIS_RANGE: AxisRange = AxisRange { min: AXIS_MIN, max: AXIS_MAX };
+
+impl AxisRange {
+    /// Normalise a raw value to -1.0 to 1.0, values on the wrong side of zero map to 0.0
+    pub fn normalise(&self, v: i32) -> f32 {
+        let r = match v {
+            v if v > 0 && self.max > 0 => v as f32 / self.max as f32,
+            v if v < 0 && self.min < 0 => v as f32 / -(self.min as f32),
+            _ => 0.0,
+        };
+
+        r.clamp(-1.0, 1.0)
+    }
+}
+
+impl From<(i32, i32)> for AxisRange {
+    fn from((min, max): (i32, i32)) -> Self {
+        Self { min, max }
+    }
+}
+
+/// Enabled event types
+pub const EVENT_TYPES: &[EventType] = &[EventType::EV_KEY, EventType::EV_REL];
+
+/// Event codes always enabled on the virtual device
+pub const BASE_EVENT_CODES: &[EventCode] = &[
+    EventCode::EV_KEY(EV_KEY::BTN_LEFT),
+    EventCode::EV_KEY(EV_KEY::BTN_RIGHT),
+    EventCode::EV_REL(EV_REL::REL_X),
+    EventCode::EV_REL(EV_REL::REL_Y),
+    EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+];
+
+/// Vertical scroll event codes, enabled for [`Map::V`]
+pub const WHEEL_EVENT_CODES: &[EventCode] = &[
+    EventCode::EV_REL(EV_REL::REL_WHEEL),
+    EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES),
+];
+
+/// Horizontal scroll event codes, enabled for [`Map::H`]
+pub const HWHEEL_EVENT_CODES: &[EventCode] = &[
+    EventCode::EV_REL(EV_REL::REL_HWHEEL),
+    EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES),
+];
+
+/// Zoom modifier event codes, enabled for [`Map::Zoom`] with [`WHEEL_EVENT_CODES`]
+pub const ZOOM_EVENT_CODES: &[EventCode] = &[
+    EventCode::EV_KEY(EV_KEY::KEY_LEFTCTRL),
+];
+
+/// Default absolute joystick axis range (±)
+pub const ABS_RANGE_DEFAULT: i32 = 32767;
+
+/// Joystick event types
+pub const JOYSTICK_EVENT_TYPES: &[EventType] = &[EventType::EV_KEY, EventType::EV_ABS];
+
+/// Joystick event codes, a trigger button is required for joystick classification
+pub const JOYSTICK_EVENT_CODES: &[EventCode] = &[
+    EventCode::EV_KEY(EV_KEY::BTN_TRIGGER),
+    EventCode::EV_ABS(EV_ABS::ABS_X),
+    EventCode::EV_ABS(EV_ABS::ABS_Y),
+    EventCode::EV_ABS(EV_ABS::ABS_Z),
+    EventCode::EV_ABS(EV_ABS::ABS_RX),
+    EventCode::EV_ABS(EV_ABS::ABS_RY),
+    EventCode::EV_ABS(EV_ABS::ABS_RZ),
+    EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+];
+
+/// Absolute pointer event types
+pub const ABS_POINTER_EVENT_TYPES: &[EventType] = &[EventType::EV_KEY, EventType::EV_ABS];
+
+/// Absolute pointer event codes, buttons are required for pointer classification
+pub const ABS_POINTER_EVENT_CODES: &[EventCode] = &[
+    EventCode::EV_KEY(EV_KEY::BTN_LEFT),
+    EventCode::EV_KEY(EV_KEY::BTN_RIGHT),
+    EventCode::EV_ABS(EV_ABS::ABS_X),
+    EventCode::EV_ABS(EV_ABS::ABS_Y),
+    EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+];
+
+/// Collect output mappings from the default, device, and saved profile configs
+pub fn config_maps(config: &Config) -> Vec<Map> {
+    config.iter().map(|(_, a)| a)
+        .chain(config.profiles.iter().map(|p| &p.axes))
+        .flat_map(|a| AXIS.iter().map(move |x| a[*x].map))
+        .collect()
+}
+
+/// Check whether a config maps any axis to an absolute joystick output
+pub fn uses_joystick(config: &Config) -> bool {
+    config_maps(config).iter().any(|m| matches!(m, Map::Abs(_)))
+}
+
+/// Check whether a config maps any axis to an absolute pointer output
+pub fn uses_abs_pointer(config: &Config) -> bool {
+    config_maps(config).iter().any(|m| matches!(m, Map::AbsX | Map::AbsY))
+}
+
+/// Derive virtual device event codes from the axis mappings in a config
+///
+/// Includes default, device, and saved profile mappings so profiles can be activated at runtime.
+pub fn capabilities_for(config: &Config) -> Vec<EventCode> {
+    let maps = config_maps(config);
+
+    let mut codes = BASE_EVENT_CODES.to_vec();
+
+    if maps.contains(&Map::V) || maps.contains(&Map::Zoom) {
+        codes.extend_from_slice(WHEEL_EVENT_CODES);
+    }
+    if maps.contains(&Map::Zoom) {
+        codes.extend_from_slice(ZOOM_EVENT_CODES);
+    }
+    if maps.contains(&Map::H) {
+        codes.extend_from_slice(HWHEEL_EVENT_CODES);
+    }
+
+    codes
+}
+
+/// Fetch the current `CLOCK_MONOTONIC` time for output event timestamps
+///
+/// Output events are stamped at emission so replayed or delayed inputs never carry stale times.
+pub fn output_time() -> TimeVal {
+    let mut t = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut t) };
+
+    TimeVal::new(t.tv_sec as _, (t.tv_nsec / 1000) as _)
+}
+
+/// Convert an event time to microseconds since the unix epoch
+pub fn time_us(t: &TimeVal) -> u64 {
+    (t.tv_sec as u64).saturating_mul(1_000_000).saturating_add(t.tv_usec as u64)
+}
+
+/// Convert microseconds since the unix epoch to an event time
+pub fn time_from_us(us: u64) -> TimeVal {
+    TimeVal::new((us / 1_000_000) as _, (us % 1_000_000) as _)
+}
+
+/// Fetch the current `CLOCK_REALTIME` time in microseconds, matching source event times
+pub fn now_us() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_micros() as u64).unwrap_or_default()
+}
+
+/// Compute the time between a source event and now
+///
+/// Source events use the evdev default `CLOCK_REALTIME`, returns `None` if the source time is in the future.
+pub fn event_latency(source: &TimeVal) -> Option<Duration> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    let src = Duration::new(source.tv_sec as u64, source.tv_usec as u32 * 1000);
+
+    now.checked_sub(src)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AbsAxis, Axis, Profile, UsbDevice};
+
+    use super::*;
+
+    /// Config with every axis unmapped
+    fn unmapped() -> Config {
+        let mut c = Config::default();
+        for a in AXIS {
+            c.default[*a].map = Map::None;
+        }
+        c
+    }
+
+    fn with_map(map: Map) -> Config {
+        let mut c = unmapped();
+        c.default[Axis::Z].map = map;
+        c
+    }
+
+    fn has(codes: &[EventCode], set: &[EventCode]) -> bool {
+        set.iter().all(|c| codes.contains(c))
+    }
+
+    fn has_any(codes: &[EventCode], set: &[EventCode]) -> bool {
+        set.iter().any(|c| codes.contains(c))
+    }
+
+    #[test]
+    fn base_capabilities() {
+        for m in [Map::None, Map::X, Map::Y] {
+            assert_eq!(capabilities_for(&with_map(m)), BASE_EVENT_CODES.to_vec(), "{:?}", m);
+        }
+    }
+
+    #[test]
+    fn wheel_capabilities() {
+        // Wheel codes only with a scroll mapping
+        let v = capabilities_for(&with_map(Map::V));
+        assert!(has(&v, WHEEL_EVENT_CODES));
+        assert!(!has_any(&v, HWHEEL_EVENT_CODES) && !has_any(&v, ZOOM_EVENT_CODES));
+
+        let h = capabilities_for(&with_map(Map::H));
+        assert!(has(&h, HWHEEL_EVENT_CODES));
+        assert!(!has_any(&h, WHEEL_EVENT_CODES) && !has_any(&h, ZOOM_EVENT_CODES));
+
+        // Zoom scrolls the vertical wheel with a modifier
+        let z = capabilities_for(&with_map(Map::Zoom));
+        assert!(has(&z, WHEEL_EVENT_CODES) && has(&z, ZOOM_EVENT_CODES));
+        assert!(!has_any(&z, HWHEEL_EVENT_CODES));
+
+        for c in [v, h, z] {
+            assert!(has(&c, BASE_EVENT_CODES));
+        }
+    }
+
+    #[test]
+    fn device_and_profile_capabilities() {
+        let mut c = unmapped();
+        let mut device = unmapped().default;
+        device[Axis::X].map = Map::V;
+        c.devices.insert(UsbDevice { vid: 0x256f, pid: 0xc635, name: None }, device);
+
+        let mut axes = unmapped().default;
+        axes[Axis::Y].map = Map::H;
+        c.profiles.push(Profile { device: "default".to_string(), name: "scroll".to_string(), axes });
+
+        let codes = capabilities_for(&c);
+        assert!(has(&codes, WHEEL_EVENT_CODES) && has(&codes, HWHEEL_EVENT_CODES));
+    }
+
+    #[test]
+    fn output_times_monotonic() {
+        let mut last = output_time();
+        for _ in 0..1000 {
+            let t = output_time();
+            assert!((t.tv_sec, t.tv_usec) >= (last.tv_sec, last.tv_usec), "{:?} < {:?}", t, last);
+            assert!((0..1_000_000).contains(&t.tv_usec));
+            last = t;
+        }
+    }
+
+    #[test]
+    fn source_latency() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let at = |d: Duration| TimeVal::new(d.as_secs() as _, d.subsec_micros() as _);
+
+        let l = event_latency(&at(now - Duration::from_millis(5))).unwrap();
+        assert!(l >= Duration::from_millis(5) && l < Duration::from_secs(5), "{:?}", l);
+
+        // Replayed events report their age, events from the future have no latency
+        assert!(event_latency(&TimeVal::new(0, 0)).unwrap() >= now);
+        assert_eq!(event_latency(&at(now + Duration::from_secs(60))), None);
+    }
+
+    #[test]
+    fn output_device_selection() {
+        assert!(!uses_joystick(&unmapped()) && !uses_abs_pointer(&unmapped()));
+
+        assert!(uses_joystick(&with_map(Map::Abs(AbsAxis::Z))));
+        assert!(!uses_abs_pointer(&with_map(Map::Abs(AbsAxis::Z))));
+
+        for m in [Map::AbsX, Map::AbsY] {
+            assert!(uses_abs_pointer(&with_map(m)));
+            assert!(!uses_joystick(&with_map(m)));
+        }
+    }
+}
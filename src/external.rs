@@ -0,0 +1,213 @@
+//! External source packets, for head trackers and other synthetic inputs
+//!
+//! Packets are sent as single datagrams to the daemon `--external-socket`:
+//!
+//! | Offset | Size | Field                                  |
+//! |--------|------|----------------------------------------|
+//! | 0      | 4    | x, `f32` little-endian (-1.0 to 1.0)   |
+//! | 4      | 4    | y                                      |
+//! | 8      | 4    | z                                      |
+//! | 12     | 4    | rx                                     |
+//! | 16     | 4    | ry                                     |
+//! | 20     | 4    | rz                                     |
+//! | 24     | 4    | button bitmask, `u32` little-endian    |
+//!
+//! Values outside -1.0 to 1.0 are clamped, packets of any other length or containing
+//! non-finite values are rejected.
+
+use evdev_rs::enums::{EventCode, EV_REL, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+
+use crate::{Axis, AxisCollection, UsbDevice, AXIS, AXIS_MAX};
+
+/// External packet length in bytes
+pub const EXTERNAL_PACKET_LEN: usize = 28;
+
+/// Vendor id for the external pseudo-device, configured as `0000:0000`
+pub const EXTERNAL_VID: u16 = 0x0000;
+
+/// Product id for the external pseudo-device
+pub const EXTERNAL_PID: u16 = 0x0000;
+
+/// Pseudo-device for external source events
+pub fn external_device() -> UsbDevice {
+    UsbDevice { vid: EXTERNAL_VID, pid: EXTERNAL_PID, name: Some("external".to_string()) }
+}
+
+/// External source packet errors
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PacketError {
+    /// Packet is not [`EXTERNAL_PACKET_LEN`] bytes
+    Length(usize),
+    /// Axis value is NaN or infinite
+    NonFinite(Axis),
+}
+
+impl std::fmt::Display for PacketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketError::Length(n) => write!(f, "invalid packet length {} (expected {})", n, EXTERNAL_PACKET_LEN),
+            PacketError::NonFinite(a) => write!(f, "non-finite value for axis {}", a),
+        }
+    }
+}
+
+impl std::error::Error for PacketError {}
+
+/// Decoded external source packet
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ExternalPacket {
+    /// Normalised (-1.0 to 1.0) axis values
+    pub axes: AxisCollection<f32>,
+    /// Button bitmask
+    pub buttons: u32,
+}
+
+impl ExternalPacket {
+    /// Parse and validate a packet
+    pub fn parse(b: &[u8]) -> Result<Self, PacketError> {
+        if b.len() != EXTERNAL_PACKET_LEN {
+            return Err(PacketError::Length(b.len()));
+        }
+
+        let word = |i: usize| [b[i * 4], b[i * 4 + 1], b[i * 4 + 2], b[i * 4 + 3]];
+
+        let mut axes = AxisCollection::<f32>::default();
+        for (i, a) in AXIS.iter().enumerate() {
+            let v = f32::from_le_bytes(word(i));
+            if !v.is_finite() {
+                return Err(PacketError::NonFinite(*a));
+            }
+            axes[*a] = v.clamp(-1.0, 1.0);
+        }
+
+        let buttons = u32::from_le_bytes(word(6));
+
+        Ok(Self { axes, buttons })
+    }
+
+    /// Encode a packet, for senders and testing
+    pub fn encode(&self) -> [u8; EXTERNAL_PACKET_LEN] {
+        let mut b = [0u8; EXTERNAL_PACKET_LEN];
+
+        for (i, a) in AXIS.iter().enumerate() {
+            b[i * 4..][..4].copy_from_slice(&self.axes[*a].to_le_bytes());
+        }
+        b[24..].copy_from_slice(&self.buttons.to_le_bytes());
+
+        b
+    }
+
+    /// Convert axis values to relative input events followed by a sync, matching evdev device events
+    pub fn events(&self, time: TimeVal) -> Vec<InputEvent> {
+        let codes = [EV_REL::REL_X, EV_REL::REL_Y, EV_REL::REL_Z, EV_REL::REL_RX, EV_REL::REL_RY, EV_REL::REL_RZ];
+
+        let mut events: Vec<_> = AXIS.iter().zip(codes)
+            .map(|(a, c)| InputEvent {
+                time,
+                event_code: EventCode::EV_REL(c),
+                value: (self.axes[*a] * AXIS_MAX as f32).round() as i32,
+            })
+            .collect();
+
+        events.push(InputEvent { time, event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT), value: 0 });
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic xorshift generator for fuzz-ish inputs
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn bytes(&mut self, n: usize) -> Vec<u8> {
+            (0..n).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    fn packet() -> ExternalPacket {
+        let mut axes = AxisCollection::<f32>::default();
+        axes[Axis::X] = 0.5;
+        axes[Axis::RZ] = -1.0;
+        ExternalPacket { axes, buttons: 0b101 }
+    }
+
+    #[test]
+    fn round_trip() {
+        let p = packet();
+        assert_eq!(ExternalPacket::parse(&p.encode()), Ok(p));
+    }
+
+    #[test]
+    fn layout() {
+        let b = packet().encode();
+
+        assert_eq!(&b[0..4], &0.5f32.to_le_bytes());
+        assert_eq!(&b[20..24], &(-1.0f32).to_le_bytes());
+        assert_eq!(&b[24..28], &[0b101, 0, 0, 0]);
+    }
+
+    #[test]
+    fn invalid_length() {
+        for n in [0, 1, EXTERNAL_PACKET_LEN - 1, EXTERNAL_PACKET_LEN + 1, 4096] {
+            assert_eq!(ExternalPacket::parse(&vec![0; n]), Err(PacketError::Length(n)));
+        }
+    }
+
+    #[test]
+    fn non_finite_values() {
+        for (i, a) in AXIS.iter().enumerate() {
+            for v in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+                let mut b = packet().encode();
+                b[i * 4..][..4].copy_from_slice(&v.to_le_bytes());
+
+                assert_eq!(ExternalPacket::parse(&b), Err(PacketError::NonFinite(*a)));
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_parse() {
+        let mut rng = Rng(0x1234_5678);
+
+        for _ in 0..10_000 {
+            // Mostly valid lengths with arbitrary contents
+            let n = match rng.next() % 4 {
+                0 => rng.next() as usize % (EXTERNAL_PACKET_LEN * 2),
+                _ => EXTERNAL_PACKET_LEN,
+            };
+            let b = rng.bytes(n);
+
+            match ExternalPacket::parse(&b) {
+                Ok(p) => {
+                    // Accepted packets are normalised and produce in-range events
+                    assert!(AXIS.iter().all(|a| p.axes[*a].is_finite() && p.axes[*a].abs() <= 1.0), "{:?}", p);
+                    assert_eq!(p.buttons, u32::from_le_bytes([b[24], b[25], b[26], b[27]]));
+
+                    let events = p.events(TimeVal { tv_sec: 0, tv_usec: 0 });
+                    assert_eq!(events.len(), AXIS.len() + 1);
+                    assert!(events.iter().all(|e| e.value.abs() <= AXIS_MAX));
+
+                    // And survive re-encoding
+                    assert_eq!(ExternalPacket::parse(&p.encode()), Ok(p));
+                }
+                Err(PacketError::Length(l)) => assert!(l == n && n != EXTERNAL_PACKET_LEN),
+                Err(PacketError::NonFinite(a)) => {
+                    let i = AXIS.iter().position(|x| *x == a).unwrap();
+                    assert!(!f32::from_le_bytes([b[i * 4], b[i * 4 + 1], b[i * 4 + 2], b[i * 4 + 3]]).is_finite());
+                }
+            }
+        }
+    }
+}
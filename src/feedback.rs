@@ -0,0 +1,100 @@
+//! Device LED feedback, indicating the active profile on the source device
+
+use evdev_rs::enums::{EventCode, EV_LED, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// LED output report ID for 3Dconnexion hidraw devices
+pub const HID_REPORT_LED: u8 = 4;
+
+/// LED codes by pattern bit
+const LED_CODES: &[EV_LED] = &[
+    EV_LED::LED_NUML,
+    EV_LED::LED_CAPSL,
+    EV_LED::LED_SCROLLL,
+    EV_LED::LED_COMPOSE,
+    EV_LED::LED_KANA,
+    EV_LED::LED_SLEEP,
+    EV_LED::LED_SUSPEND,
+    EV_LED::LED_MUTE,
+];
+
+/// LED pattern, bit `n` sets the state of LED `n`
+///
+/// For evdev devices LED `n` is `EV_LED` code `n` (`LED_NUML` = 0), for hidraw
+/// devices the pattern is written as the LED report value.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct LedPattern(pub u8);
+
+impl LedPattern {
+    /// Check whether LED `n` is set
+    pub fn is_set(&self, n: usize) -> bool {
+        n < 8 && self.0 & (1 << n) != 0
+    }
+
+    /// Encode as `EV_LED` events for every LED, followed by a sync
+    pub fn events(&self, time: TimeVal) -> Vec<InputEvent> {
+        let mut events: Vec<_> = LED_CODES.iter().enumerate()
+            .map(|(i, c)| InputEvent { time, event_code: EventCode::EV_LED(*c), value: self.is_set(i) as i32 })
+            .collect();
+
+        events.push(InputEvent { time, event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT), value: 0 });
+
+        events
+    }
+
+    /// Encode as a hidraw LED output report
+    pub fn report(&self) -> [u8; 2] {
+        [HID_REPORT_LED, self.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_bits() {
+        let p = LedPattern(0b1000_0101);
+
+        let set: Vec<_> = (0..10).filter(|n| p.is_set(*n)).collect();
+        assert_eq!(set, vec![0, 2, 7]);
+
+        assert!((0..8).all(|n| LedPattern(0xff).is_set(n)));
+        assert!(!LedPattern(0xff).is_set(8));
+        assert!((0..8).all(|n| !LedPattern::default().is_set(n)));
+    }
+
+    #[test]
+    fn pattern_events() {
+        let time = TimeVal::new(1, 2);
+        let events = LedPattern(0b0000_0110).events(time);
+
+        // Every LED is written so previous patterns are cleared, then synced
+        assert_eq!(events.len(), LED_CODES.len() + 1);
+        for (i, e) in events[..LED_CODES.len()].iter().enumerate() {
+            assert_eq!(e.event_code, EventCode::EV_LED(LED_CODES[i]));
+            assert_eq!(e.value, (i == 1 || i == 2) as i32, "LED {}", i);
+        }
+        assert_eq!(events[1].event_code, EventCode::EV_LED(EV_LED::LED_CAPSL));
+
+        let sync = events.last().unwrap();
+        assert_eq!(sync.event_code, EventCode::EV_SYN(EV_SYN::SYN_REPORT));
+        assert!(events.iter().all(|e| e.time == time));
+    }
+
+    #[test]
+    fn pattern_report() {
+        assert_eq!(LedPattern(0).report(), [HID_REPORT_LED, 0]);
+        assert_eq!(LedPattern(0b101).report(), [HID_REPORT_LED, 0b101]);
+    }
+
+    #[test]
+    fn pattern_serialised_as_integer() {
+        assert_eq!(serde_json::to_string(&LedPattern(5)).unwrap(), "5");
+        assert_eq!(serde_json::from_str::<LedPattern>("255").unwrap(), LedPattern(255));
+        assert!(serde_json::from_str::<LedPattern>("256").is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! hidraw input backend for devices without a usable evdev node
+//!
+//! Parses the 3Dconnexion 6-DOF HID report format, report ID 1 contains
+//! translation (and rotation on newer devices), report ID 2 rotation,
+//! and report ID 3 the button bitmask.
+
+use std::fs::{read_dir, File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::prelude::{AsRawFd, RawFd};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::{InputEvent, TimeVal};
+use tracing::{debug, trace};
+
+use crate::{device_identity, Error, InputSource, LedPattern, UsbDevice};
+
+/// Translation report ID
+pub const HID_REPORT_TRANSLATION: u8 = 1;
+/// Rotation report ID
+pub const HID_REPORT_ROTATION: u8 = 2;
+/// Button report ID
+pub const HID_REPORT_BUTTONS: u8 = 3;
+
+/// Known 3Dconnexion vendor IDs (Logitech for older devices, 3Dconnexion for newer)
+pub const HID_VIDS: &[u16] = &[0x046d, 0x256f];
+
+/// Button codes used for reported buttons (by bit index)
+pub const HID_BUTTONS: &[EV_KEY] = &[
+    EV_KEY::BTN_0,
+    EV_KEY::BTN_1,
+    EV_KEY::BTN_2,
+    EV_KEY::BTN_3,
+    EV_KEY::BTN_4,
+    EV_KEY::BTN_5,
+    EV_KEY::BTN_6,
+    EV_KEY::BTN_7,
+    EV_KEY::BTN_8,
+    EV_KEY::BTN_9,
+];
+
+const TRANSLATION_CODES: &[EV_REL] = &[EV_REL::REL_X, EV_REL::REL_Y, EV_REL::REL_Z];
+const ROTATION_CODES: &[EV_REL] = &[EV_REL::REL_RX, EV_REL::REL_RY, EV_REL::REL_RZ];
+
+// `HIDIOCGRAWINFO`, `_IOR('H', 0x03, struct hidraw_devinfo)`
+const HIDIOCGRAWINFO: u32 = 0x8008_4803;
+// `HIDIOCGRAWNAME(len)`, `_IOC(_IOC_READ, 'H', 0x04, len)`
+const fn hidiocgrawname(len: usize) -> u32 {
+    0x8000_0000 | ((len as u32) << 16) | (0x48 << 8) | 0x04
+}
+// `HIDIOCGRAWPHYS(len)`, `_IOC(_IOC_READ, 'H', 0x05, len)`
+const fn hidiocgrawphys(len: usize) -> u32 {
+    0x8000_0000 | ((len as u32) << 16) | (0x48 << 8) | 0x05
+}
+
+/// Matches `struct hidraw_devinfo` from `linux/hidraw.h`
+#[repr(C)]
+#[derive(Default)]
+struct HidrawDevinfo {
+    bustype: u32,
+    vendor: i16,
+    product: i16,
+}
+
+/// hidraw device handle
+#[derive(Debug)]
+pub struct HidrawDevice {
+    file: File,
+    device: UsbDevice,
+    /// Physical (bus) location, shared with the evdev nodes of the device
+    phys: Option<String>,
+}
+
+impl HidrawDevice {
+    /// Open a hidraw device by path (eg. `/dev/hidraw0`)
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let file = File::open(path).map_err(Error::DeviceIo)?;
+        Self::from_file(path, file)
+    }
+
+    /// Open a hidraw device for reading and writing, required for LED output
+    pub fn open_rw(path: &str) -> Result<Self, Error> {
+        let file = OpenOptions::new().read(true).write(true).open(path).map_err(Error::DeviceIo)?;
+        Self::from_file(path, file)
+    }
+
+    fn from_file(path: &str, file: File) -> Result<Self, Error> {
+        let device = Self::device_info(&file).map_err(Error::DeviceIo)?;
+        let phys = Self::phys(&file);
+
+        debug!("Opened hidraw device: {} ({})", path, device.to_string());
+
+        Ok(Self { file, device, phys })
+    }
+
+    /// List hidraw devices, filtered by vendor ID if provided
+    pub fn scan(vids: &[u16]) -> Result<Vec<(String, UsbDevice)>, Error> {
+        let mut devices = vec![];
+
+        for e in read_dir("/dev").map_err(Error::DeviceIo)? {
+            let e = e.map_err(Error::DeviceIo)?;
+            let name = e.file_name().to_string_lossy().to_string();
+            if !name.starts_with("hidraw") {
+                continue;
+            }
+
+            let path = format!("/dev/{}", name);
+
+            // Skip devices we can't access
+            let d = match File::open(&path).and_then(|f| Self::device_info(&f)) {
+                Ok(d) => d,
+                Err(e) => {
+                    trace!("Skipping hidraw device {}: {:?}", path, e);
+                    continue;
+                }
+            };
+
+            if vids.is_empty() || vids.contains(&d.vid) {
+                devices.push((path, d));
+            }
+        }
+
+        devices.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(devices)
+    }
+
+    /// Fetch the physical location using the hidraw phys ioctl
+    fn phys(file: &File) -> Option<String> {
+        let mut phys = [0u8; 256];
+        let res = unsafe { libc::ioctl(file.as_raw_fd(), hidiocgrawphys(phys.len()) as _, phys.as_mut_ptr()) };
+        match res {
+            n if n > 0 => {
+                let n = phys.iter().position(|c| *c == 0).unwrap_or(n as usize);
+                Some(String::from_utf8_lossy(&phys[..n]).to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Fetch device information using hidraw ioctls
+    fn device_info(file: &File) -> Result<UsbDevice, std::io::Error> {
+        let fd = file.as_raw_fd();
+
+        let mut info = HidrawDevinfo::default();
+        let res = unsafe { libc::ioctl(fd, HIDIOCGRAWINFO as _, &mut info as *mut HidrawDevinfo) };
+        if res < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let mut name = [0u8; 256];
+        let res = unsafe { libc::ioctl(fd, hidiocgrawname(name.len()) as _, name.as_mut_ptr()) };
+        let name = match res {
+            n if n > 0 => {
+                let n = name.iter().position(|c| *c == 0).unwrap_or(n as usize);
+                Some(String::from_utf8_lossy(&name[..n]).to_string())
+            }
+            _ => None,
+        };
+
+        Ok(UsbDevice {
+            vid: info.vendor as u16,
+            pid: info.product as u16,
+            name,
+        })
+    }
+}
+
+impl AsRawFd for HidrawDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl InputSource for HidrawDevice {
+    fn device(&self) -> UsbDevice {
+        self.device.clone()
+    }
+
+    fn identity(&self) -> String {
+        device_identity(&self.device, None, self.phys.as_deref())
+    }
+
+    fn read_events(&self) -> Result<Vec<InputEvent>, std::io::Error> {
+        let mut buff = [0u8; 64];
+        let n = (&self.file).read(&mut buff)?;
+
+        trace!("hidraw report: {:02x?}", &buff[..n]);
+
+        Ok(parse_report(&buff[..n], timestamp()))
+    }
+
+    fn set_leds(&self, pattern: LedPattern) -> Result<(), std::io::Error> {
+        (&self.file).write_all(&pattern.report())
+    }
+}
+
+/// Parse a 3Dconnexion HID report into input events
+///
+/// Unrecognised or truncated reports produce no events
+pub fn parse_report(buff: &[u8], time: TimeVal) -> Vec<InputEvent> {
+    let mut events = vec![];
+
+    let (id, data) = match buff.split_first() {
+        Some(v) => v,
+        None => return events,
+    };
+
+    match *id {
+        HID_REPORT_TRANSLATION if data.len() >= 12 => {
+            // Newer devices report translation and rotation together
+            let codes = TRANSLATION_CODES.iter().chain(ROTATION_CODES.iter());
+            push_axes(&mut events, time, codes, data);
+        }
+        HID_REPORT_TRANSLATION if data.len() >= 6 => {
+            push_axes(&mut events, time, TRANSLATION_CODES.iter(), data);
+        }
+        HID_REPORT_ROTATION if data.len() >= 6 => {
+            push_axes(&mut events, time, ROTATION_CODES.iter(), data);
+        }
+        HID_REPORT_BUTTONS if !data.is_empty() => {
+            for (i, b) in HID_BUTTONS.iter().enumerate() {
+                let v = match data.get(i / 8) {
+                    Some(v) => (v >> (i % 8)) & 0x01,
+                    None => break,
+                };
+
+                events.push(InputEvent {
+                    time,
+                    event_code: EventCode::EV_KEY(*b),
+                    value: v as i32,
+                });
+            }
+        }
+        _ => return events,
+    }
+
+    // Terminate with sync event to match evdev streams
+    events.push(InputEvent {
+        time,
+        event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+        value: 0,
+    });
+
+    events
+}
+
+/// Push little-endian i16 axis values as relative events
+fn push_axes<'a>(
+    events: &mut Vec<InputEvent>,
+    time: TimeVal,
+    codes: impl Iterator<Item = &'a EV_REL>,
+    data: &[u8],
+) {
+    for (c, v) in codes.zip(data.chunks_exact(2)) {
+        events.push(InputEvent {
+            time,
+            event_code: EventCode::EV_REL(*c),
+            value: i16::from_le_bytes([v[0], v[1]]) as i32,
+        });
+    }
+}
+
+/// Fetch the current time as an evdev timestamp
+fn timestamp() -> TimeVal {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    TimeVal {
+        tv_sec: now.as_secs() as _,
+        tv_usec: now.subsec_micros() as _,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME: TimeVal = TimeVal { tv_sec: 1, tv_usec: 2 };
+
+    /// Parse a report to (code, value) pairs
+    fn parse(report: &[u8]) -> Vec<(EventCode, i32)> {
+        parse_report(report, TIME).iter().map(|e| (e.event_code, e.value)).collect()
+    }
+
+    fn rel(c: EV_REL, v: i32) -> (EventCode, i32) {
+        (EventCode::EV_REL(c), v)
+    }
+
+    fn key(b: EV_KEY, v: i32) -> (EventCode, i32) {
+        (EventCode::EV_KEY(b), v)
+    }
+
+    fn syn() -> (EventCode, i32) {
+        (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)
+    }
+
+    #[test]
+    fn translation_report() {
+        // SpaceNavigator (046d:c626) translation, x=10 y=-10 z=350
+        let r = parse(&[0x01, 0x0a, 0x00, 0xf6, 0xff, 0x5e, 0x01]);
+
+        assert_eq!(r, vec![rel(EV_REL::REL_X, 10), rel(EV_REL::REL_Y, -10), rel(EV_REL::REL_Z, 350), syn()]);
+    }
+
+    #[test]
+    fn rotation_report() {
+        // SpaceNavigator rotation, rx=-350 ry=0 rz=1
+        let r = parse(&[0x02, 0xa2, 0xfe, 0x00, 0x00, 0x01, 0x00]);
+
+        assert_eq!(r, vec![rel(EV_REL::REL_RX, -350), rel(EV_REL::REL_RY, 0), rel(EV_REL::REL_RZ, 1), syn()]);
+    }
+
+    #[test]
+    fn combined_report() {
+        // SpaceMouse Wireless (256f:c62e) translation and rotation in a single report
+        let r = parse(&[0x01, 0x05, 0x00, 0x00, 0x00, 0xfb, 0xff, 0x80, 0x00, 0x00, 0x00, 0x81, 0xff]);
+
+        assert_eq!(r, vec![
+            rel(EV_REL::REL_X, 5),
+            rel(EV_REL::REL_Y, 0),
+            rel(EV_REL::REL_Z, -5),
+            rel(EV_REL::REL_RX, 128),
+            rel(EV_REL::REL_RY, 0),
+            rel(EV_REL::REL_RZ, -127),
+            syn(),
+        ]);
+    }
+
+    #[test]
+    fn button_report() {
+        // Left button pressed, then both buttons with a second bitmask byte
+        let r = parse(&[0x03, 0x01]);
+        let mut expected: Vec<_> = HID_BUTTONS[..8].iter().map(|b| key(*b, (*b == EV_KEY::BTN_0) as i32)).collect();
+        expected.push(syn());
+        assert_eq!(r, expected);
+
+        let r = parse(&[0x03, 0x02, 0x02]);
+        assert_eq!(r.len(), HID_BUTTONS.len() + 1);
+        assert_eq!(r[1], key(EV_KEY::BTN_1, 1));
+        assert_eq!(r[9], key(EV_KEY::BTN_9, 1));
+        assert_eq!(r.iter().filter(|(_c, v)| *v == 1).count(), 2);
+    }
+
+    #[test]
+    fn events_use_report_time() {
+        assert!(parse_report(&[0x01, 0, 0, 0, 0, 0, 0], TIME).iter().all(|e| e.time == TIME));
+    }
+
+    #[test]
+    fn invalid_reports_ignored() {
+        let reports: [&[u8]; 7] = [&[], &[0x01], &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00], &[0x02, 0x00], &[0x03], &[0x04, 0x01, 0x02], &[0xff; 16]];
+
+        // Empty, truncated, and unknown reports
+        for r in reports {
+            assert!(parse(r).is_empty(), "{:02x?}", r);
+        }
+    }
+}
@@ -1,23 +1,67 @@
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::str::FromStr;
 
 
 use serde::{Deserialize, Serialize};
-use evdev_rs::enums::{BusType, EventCode, EV_REL};
-use evdev_rs::{DeviceWrapper, InputEvent, UInputDevice, UninitDevice};
-use log::{debug, trace};
+use evdev_rs::enums::{BusType, EventCode, EventType, EV_ABS, EV_REL, EV_SYN};
+use evdev_rs::{AbsInfo, DeviceWrapper, EnableCodeData, InputEvent, TimeVal, UInputDevice, UninitDevice};
+use tracing::{debug, trace};
 
 
+mod error;
+pub use error::*;
 mod command;
 pub use command::*;
 mod axis;
 pub use axis::*;
 mod client;
 pub use client::*;
+mod wire;
+pub use wire::*;
+mod reconnect;
+pub use reconnect::*;
+mod blocking;
+pub use blocking::*;
+mod events;
+pub use events::*;
 mod map;
 pub use map::*;
 mod config;
 pub use config::*;
+mod curve;
+pub use curve::*;
+mod source;
+pub use source::*;
+mod hidraw;
+pub use hidraw::*;
+mod paths;
+pub use paths::*;
+mod atomic;
+pub use atomic::*;
+mod metrics;
+pub use metrics::*;
+mod diff;
+pub use diff::*;
+mod schema;
+pub use schema::*;
+mod presets;
+pub use presets::*;
+mod share;
+pub use share::*;
+mod external;
+pub use external::*;
+mod pipeline;
+pub use pipeline::*;
+mod passthrough;
+pub use passthrough::*;
+mod feedback;
+pub use feedback::*;
+mod battery;
+pub use battery::*;
+
+#[cfg(test)]
+mod testutil;
 
 /// Device descriptor object
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
@@ -33,28 +77,57 @@ impl ToString for UsbDevice {
     }
 }
 
+/// Errors parsing a [`UsbDevice`] from a `vid:pid[:name]` string
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UsbDeviceParseError {
+    /// Missing `vid` or `pid` segment
+    Segments(String),
+    /// Segment is not valid hex
+    InvalidHex(String),
+    /// Segment exceeds 16 bits
+    OutOfRange(String),
+}
+
+impl std::fmt::Display for UsbDeviceParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UsbDeviceParseError::Segments(s) => write!(f, "invalid device '{}', expected `vid:pid` or `vid:pid:name`", s),
+            UsbDeviceParseError::InvalidHex(s) => write!(f, "invalid hex id '{}'", s),
+            UsbDeviceParseError::OutOfRange(s) => write!(f, "id '{}' out of range (max ffff)", s),
+        }
+    }
+}
+
+impl std::error::Error for UsbDeviceParseError {}
+
+/// Parse a 16-bit hex id, with optional `0x` prefix
+fn parse_usb_id(s: &str) -> Result<u16, UsbDeviceParseError> {
+    let h = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+
+    u16::from_str_radix(h, 16).map_err(|e| match e.kind() {
+        std::num::IntErrorKind::PosOverflow => UsbDeviceParseError::OutOfRange(s.to_string()),
+        _ => UsbDeviceParseError::InvalidHex(s.to_string()),
+    })
+}
+
 impl FromStr for UsbDevice {
-    type Err = ();
+    type Err = UsbDeviceParseError;
 
+    /// Parse a device from `vid:pid` or `vid:pid:name`
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut s = s.split(':');
-        
-        let (vid, pid) = match (s.next(), s.next()) {
-            (Some(vid), Some(pid)) => (vid, pid),
-            _ => return Err(()),
-        };
+        let mut p = s.splitn(3, ':');
 
-        let vid = match u16::from_str_radix(vid, 16) {
-            Ok(v) => v,
-            Err(_) => return Err(()),
+        let (vid, pid) = match (p.next(), p.next()) {
+            (Some(vid), Some(pid)) if !vid.is_empty() && !pid.is_empty() => (vid, pid),
+            _ => return Err(UsbDeviceParseError::Segments(s.to_string())),
         };
 
-        let pid = match u16::from_str_radix(pid, 16) {
-            Ok(v) => v,
-            Err(_) => return Err(()),
-        };
+        let vid = parse_usb_id(vid.trim())?;
+        let pid = parse_usb_id(pid.trim())?;
+
+        let name = p.next().map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
 
-        Ok(Self{vid, pid, name: None})
+        Ok(Self{vid, pid, name})
     }
 }
 
@@ -62,17 +135,33 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             devices: HashMap::new(),
-            default: Default::default(),
+            aliases: HashMap::new(),
+            default: AxisCollection::spacemouse_defaults(),
+            profiles: Vec::new(),
+            active: HashMap::new(),
+            split_outputs: false,
+            abs_range: None,
+            abs_pointer: None,
+            idle_timeout_s: None,
+            idle_destroy: false,
+            max_output_hz: None,
+            passthrough: Vec::new(),
+            watch_config: None,
+            output_limits: None,
+            axis_snap: HashMap::new(),
+            input_kind: HashMap::new(),
+            mouse_divisor: None,
+            aggregate: HashMap::new(),
+            auto_zero_ms: None,
+            feedback: HashMap::new(),
+            version: CONFIG_VERSION,
         }
     }
 }
 
 impl Config {
     pub fn map(&self, d: &UsbDevice, e: &InputEvent) -> Option<(Map, f32)> {
-        let axes = match self.devices.get(d) {
-            Some(d) => d,
-            None => &self.default,
-        };
+        let axes = self.device(d);
 
         // Match event codes to configuration
         let m = match e.event_code {
@@ -85,8 +174,8 @@ impl Config {
             _ => return None,
         };
 
-        // Normalise input value (AXIS_MIN -> AXIS_MAX to -1.0 -> 1.0)
-        let r = e.value as f32 / AXIS_MAX as f32;
+        // Normalise input value (calibrated or AXIS_MIN -> AXIS_MAX to -1.0 -> 1.0, or mouse velocity)
+        let r = self.normalise(d, &m, e.value);
 
         // Apply axis value transformation
         let v = m.transform(r);
@@ -99,28 +188,291 @@ impl Config {
 }
 
 
-pub fn virtual_device() -> Result<UInputDevice, anyhow::Error> {
+/// Virtual output device roles
+#[derive(Copy, Clone, PartialEq, Eq, Debug, strum::Display, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum OutputRole {
+    /// Pointer (and scroll, when not split)
+    Pointer,
+    /// Scroll, when split
+    Scroll,
+    /// Absolute joystick
+    Joystick,
+    /// Absolute pointer
+    AbsPointer,
+}
+
+/// Virtual output device node info, for [`Command::Devnodes`]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct OutputDevice {
+    pub role: OutputRole,
+    /// Device node (eg. `/dev/input/eventN`)
+    pub devnode: Option<String>,
+    /// Sysfs path, if available
+    pub syspath: Option<String>,
+}
+
+/// Virtual output devices, scroll events are routed to a separate device when split
+pub struct Outputs {
+    /// Pointer (and scroll, when not split) output device
+    pub pointer: UInputDevice,
+    /// Scroll output device, if split
+    pub scroll: Option<UInputDevice>,
+    /// Absolute joystick output device, if any axis uses [`Map::Abs`]
+    pub joystick: Option<UInputDevice>,
+    /// Absolute pointer output device, if any axis uses [`Map::AbsX`] or [`Map::AbsY`]
+    pub abs_pointer: Option<UInputDevice>,
+    /// Joystick axis range (±)
+    pub abs_range: i32,
+    /// Relative output value limits
+    pub limits: OutputLimits,
+    /// Output values clamped since the last [`Outputs::take_clamped`]
+    clamped: Cell<u64>,
+}
+
+impl Outputs {
+    /// Create output devices for the provided config
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let abs_range = config.abs_range.unwrap_or(ABS_RANGE_DEFAULT);
+        let limits = config.output_limits.unwrap_or_default();
+
+        let joystick = match uses_joystick(config) {
+            true => {
+                let abs = AbsInfo { value: 0, minimum: -abs_range, maximum: abs_range, fuzz: 0, flat: 0, resolution: 0 };
+                Some(create_virtual_device("vmouse joystick", 0xeff1, JOYSTICK_EVENT_TYPES, JOYSTICK_EVENT_CODES, |_| Some(abs))?)
+            }
+            false => None,
+        };
+
+        let abs_pointer = match uses_abs_pointer(config) {
+            true => {
+                let p = config.abs_pointer.unwrap_or_default();
+                let info = |max: i32| AbsInfo { value: max / 2, minimum: 0, maximum: max, fuzz: 0, flat: 0, resolution: 0 };
+                let abs = |c: &EV_ABS| match c {
+                    EV_ABS::ABS_X => Some(info(p.width - 1)),
+                    EV_ABS::ABS_Y => Some(info(p.height - 1)),
+                    _ => None,
+                };
+                Some(create_virtual_device("vmouse absolute pointer", 0xeff2, ABS_POINTER_EVENT_TYPES, ABS_POINTER_EVENT_CODES, abs)?)
+            }
+            false => None,
+        };
+
+        if !config.split_outputs {
+            return Ok(Self { pointer: virtual_device(config)?, scroll: None, joystick, abs_pointer, abs_range, limits, clamped: Cell::new(0) });
+        }
+
+        let scroll_codes: Vec<_> = WHEEL_EVENT_CODES.iter().chain(HWHEEL_EVENT_CODES).chain(ZOOM_EVENT_CODES).cloned().collect();
+
+        let mut pointer_codes = capabilities_for(config);
+        pointer_codes.retain(|c| !scroll_codes.contains(c));
+
+        let mut scroll_codes = scroll_codes;
+        scroll_codes.push(EventCode::EV_SYN(EV_SYN::SYN_REPORT));
+
+        Ok(Self {
+            pointer: create_virtual_device("vmouse pointer", 0xefef, EVENT_TYPES, &pointer_codes, |_| None)?,
+            scroll: Some(create_virtual_device("vmouse scroll", 0xeff0, EVENT_TYPES, &scroll_codes, |_| None)?),
+            joystick,
+            abs_pointer,
+            abs_range,
+            limits,
+            clamped: Cell::new(0),
+        })
+    }
+
+    /// Fetch the relative output device for a mapping
+    pub fn for_map(&self, m: Map) -> &UInputDevice {
+        match (m, &self.scroll) {
+            (Map::H | Map::V | Map::Zoom, Some(s)) => s,
+            _ => &self.pointer,
+        }
+    }
+
+    /// Write output events for a mapping followed by a sync, joystick events are dropped
+    /// if no joystick device exists
+    pub fn write(&self, m: Map, ts: TimeVal, events: &[OutputEvent]) -> Result<(), Error> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let d = match m {
+            Map::Abs(_) => match &self.joystick {
+                Some(j) => j,
+                None => return Ok(()),
+            },
+            _ => self.for_map(m),
+        };
+
+        for e in events {
+            // Saturate out of range values, a misconfigured scale should not fling the pointer
+            let (value, clamped) = self.limits.clamp(&e.code, e.value);
+            if clamped {
+                trace!("Clamped output {:?} {} to {}", e.code, e.value, value);
+                self.clamped.set(self.clamped.get() + 1);
+            }
+
+            d.write_event(&InputEvent { time: ts, event_code: e.code.clone(), value }).map_err(Error::Uinput)?;
+        }
+        d.write_event(&InputEvent { time: ts, event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT), value: 0 }).map_err(Error::Uinput)?;
+
+        Ok(())
+    }
+
+    /// Fetch and reset the number of output values clamped to [`Outputs::limits`]
+    pub fn take_clamped(&self) -> u64 {
+        self.clamped.replace(0)
+    }
+
+    /// Write an absolute pointer position
+    pub fn abs_pointer_event(&self, ts: TimeVal, code: EV_ABS, value: i32) -> Result<(), Error> {
+        let p = match &self.abs_pointer {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        p.write_event(&InputEvent { time: ts, event_code: EventCode::EV_ABS(code), value }).map_err(Error::Uinput)?;
+        p.write_event(&InputEvent { time: ts, event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT), value: 0 }).map_err(Error::Uinput)?;
+
+        Ok(())
+    }
+
+    /// Return latching joystick axes to zero, relative outputs do not latch
+    ///
+    /// Axes already at zero are filtered by the kernel so only nonzero axes produce events.
+    pub fn zero(&self, ts: TimeVal) -> Result<(), Error> {
+        let j = match &self.joystick {
+            Some(j) => j,
+            None => return Ok(()),
+        };
+
+        for c in JOYSTICK_EVENT_CODES.iter().filter(|c| matches!(c, EventCode::EV_ABS(_))) {
+            j.write_event(&InputEvent { time: ts, event_code: c.clone(), value: 0 }).map_err(Error::Uinput)?;
+        }
+        j.write_event(&InputEvent { time: ts, event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT), value: 0 }).map_err(Error::Uinput)?;
+
+        Ok(())
+    }
+
+    /// Fetch device nodes for all output devices
+    pub fn devnodes(&self) -> Vec<String> {
+        self.devices().into_iter().filter_map(|d| d.devnode).collect()
+    }
+
+    /// Fetch role, device node, and sysfs path for all output devices, pointer first
+    pub fn devices(&self) -> Vec<OutputDevice> {
+        let devices = [
+            (OutputRole::Pointer, Some(&self.pointer)),
+            (OutputRole::Scroll, self.scroll.as_ref()),
+            (OutputRole::Joystick, self.joystick.as_ref()),
+            (OutputRole::AbsPointer, self.abs_pointer.as_ref()),
+        ];
+
+        devices.iter()
+            .filter_map(|(role, d)| d.map(|d| OutputDevice {
+                role: *role,
+                devnode: d.devnode().map(String::from),
+                syspath: d.syspath().map(String::from),
+            }))
+            .collect()
+    }
+}
+
+/// Create a uinput virtual device with capabilities for the provided config
+pub fn virtual_device(config: &Config) -> Result<UInputDevice, Error> {
+    create_virtual_device("Virtual SpaceMouse", 0xefef, EVENT_TYPES, &capabilities_for(config), |_| None)
+}
+
+/// Create a uinput virtual device with the provided event types and codes,
+/// absolute axes are configured with info from `abs`
+fn create_virtual_device(name: &str, product_id: u16, types: &[EventType], codes: &[EventCode], abs: impl Fn(&EV_ABS) -> Option<AbsInfo>) -> Result<UInputDevice, Error> {
     let u = UninitDevice::new().unwrap();
 
-    u.set_name("Virtual SpaceMouse");
+    u.set_name(name);
     u.set_bustype(BusType::BUS_USB as u16);
     u.set_vendor_id(0xabcd);
-    u.set_product_id(0xefef);
+    u.set_product_id(product_id);
 
     // https://stackoverflow.com/a/64559658/6074942
-    for t in EVENT_TYPES {
-        u.enable_event_type(t)?;
+    for t in types {
+        u.enable_event_type(t).map_err(Error::Uinput)?;
     }
 
-    for c in EVENT_CODES {
-        u.enable_event_code(c, None)?;
+    for c in codes {
+        let data = match c {
+            EventCode::EV_ABS(a) => abs(a).map(EnableCodeData::AbsInfo),
+            _ => None,
+        };
+        u.enable_event_code(c, data).map_err(Error::Uinput)?;
     }
 
     // Attach virtual device to uinput file
     //let v = v.set_file(f)?;
 
-    let v = UInputDevice::create_from_device(&u)?;
+    // Permission and missing module hints are provided by the `Error::Uinput` display
+    let v = UInputDevice::create_from_device(&u).map_err(Error::Uinput)?;
     debug!("Created virtual device: {}", v.devnode().unwrap());
 
     Ok(v)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dev(vid: u16, pid: u16, name: Option<&str>) -> UsbDevice {
+        UsbDevice { vid, pid, name: name.map(|n| n.to_string()) }
+    }
+
+    #[test]
+    fn usb_device_parse() {
+        let cases = [
+            ("256f:c635", dev(0x256f, 0xc635, None)),
+            ("256F:C635", dev(0x256f, 0xc635, None)),
+            ("0x256f:0xc635", dev(0x256f, 0xc635, None)),
+            ("0X256F:0Xc635", dev(0x256f, 0xc635, None)),
+            ("46d:c626", dev(0x046d, 0xc626, None)),
+            ("0:ffff", dev(0x0000, 0xffff, None)),
+            (" 256f : c635 ", dev(0x256f, 0xc635, None)),
+            ("256f:c635:SpaceMouse Pro", dev(0x256f, 0xc635, Some("SpaceMouse Pro"))),
+            ("256f:c635: 3Dconnexion: Wireless ", dev(0x256f, 0xc635, Some("3Dconnexion: Wireless"))),
+            ("256f:c635:", dev(0x256f, 0xc635, None)),
+        ];
+
+        for (s, d) in cases {
+            assert_eq!(s.parse::<UsbDevice>(), Ok(d), "{}", s);
+        }
+    }
+
+    #[test]
+    fn usb_device_parse_errors() {
+        let cases = [
+            ("", UsbDeviceParseError::Segments("".to_string())),
+            ("256f", UsbDeviceParseError::Segments("256f".to_string())),
+            ("256f:", UsbDeviceParseError::Segments("256f:".to_string())),
+            (":c635", UsbDeviceParseError::Segments(":c635".to_string())),
+            ("zz:c635", UsbDeviceParseError::InvalidHex("zz".to_string())),
+            ("256f:c63g", UsbDeviceParseError::InvalidHex("c63g".to_string())),
+            ("0x:c635", UsbDeviceParseError::InvalidHex("0x".to_string())),
+            ("256f:-1", UsbDeviceParseError::InvalidHex("-1".to_string())),
+            ("10000:c635", UsbDeviceParseError::OutOfRange("10000".to_string())),
+            ("256f:0x1ffff", UsbDeviceParseError::OutOfRange("0x1ffff".to_string())),
+        ];
+
+        for (s, e) in cases {
+            assert_eq!(s.parse::<UsbDevice>(), Err(e), "{}", s);
+        }
+    }
+
+    #[test]
+    fn usb_device_display_round_trip() {
+        for d in [dev(0x256f, 0xc635, None), dev(0x046d, 0xc626, None), dev(0, 1, None)] {
+            let s = d.to_string();
+            assert_eq!(s.len(), 9, "{}", s);
+            assert_eq!(s.parse::<UsbDevice>(), Ok(d));
+        }
+
+        // Names are not included in the display form
+        assert_eq!(dev(0x256f, 0xc635, Some("SpaceMouse")).to_string(), "256f:c635");
+    }
+}
@@ -1,41 +1,26 @@
-use evdev_rs::{enums::{EventType, EventCode, EV_KEY, EV_REL, EV_SYN}, UInputDevice, TimeVal, InputEvent};
+use std::str::FromStr;
+
+use evdev_rs::{enums::EV_ABS, TimeVal};
 use strum::{Display, EnumString, EnumVariantNames};
-use serde::{Serialize, Deserialize};
-
-/// Maximum axis value
-pub const AXIS_MAX: i32 = 350;
-/// Minimum axis value
-pub const AXIS_MIN: i32 = -350;
-
-/// Enabled event types
-pub const EVENT_TYPES: &[EventType] = &[EventType::EV_KEY, EventType::EV_REL];
-
-/// Enabled event codes
-pub const EVENT_CODES: &[EventCode] = &[
-    EventCode::EV_KEY(EV_KEY::BTN_LEFT),
-    EventCode::EV_KEY(EV_KEY::BTN_RIGHT),
-    EventCode::EV_REL(EV_REL::REL_X),
-    EventCode::EV_REL(EV_REL::REL_Y),
-    EventCode::EV_REL(EV_REL::REL_WHEEL),
-    EventCode::EV_REL(EV_REL::REL_HWHEEL),
-    EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES),
-    EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES),
-    EventCode::EV_SYN(EV_SYN::SYN_REPORT),
-];
+use serde::ser::SerializeMap;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use schemars::JsonSchema;
 
+use crate::{Error, Outputs};
 
 /// Output axis function
+///
+/// Absolute joystick axes are serialized as a single entry map (eg. `{ Abs = "X" }`)
+/// in human readable formats, as TOML does not support enum newtype variants.
 #[derive(
     Copy,
     Clone,
     PartialEq,
     Eq,
+    Hash,
     Debug,
-    Display,
-    EnumString,
-    EnumVariantNames,
-    Serialize,
     Deserialize,
+    JsonSchema,
 )]
 pub enum Map {
     /// Unmapped
@@ -48,68 +33,202 @@ pub enum Map {
     H,
     /// V axis (vertical scroll)
     V,
+    /// Zoom, written as vertical scroll with Ctrl held, positive values zoom in
+    Zoom,
+    /// Absolute joystick axis
+    Abs(AbsAxis),
+    /// Absolute pointer horizontal position
+    AbsX,
+    /// Absolute pointer vertical position
+    AbsY,
 }
 
-pub const MAPPINGS: &[Map] = &[Map::None, Map::X, Map::Y, Map::H, Map::V];
+pub const MAPPINGS: &[Map] = &[
+    Map::None, Map::X, Map::Y, Map::H, Map::V, Map::Zoom,
+    Map::Abs(AbsAxis::X), Map::Abs(AbsAxis::Y), Map::Abs(AbsAxis::Z),
+    Map::Abs(AbsAxis::Rx), Map::Abs(AbsAxis::Ry), Map::Abs(AbsAxis::Rz),
+    Map::AbsX, Map::AbsY,
+];
 
-impl Map {
-    pub fn event(&self, v: &UInputDevice, ts: TimeVal, val: f32) -> anyhow::Result<()> {
-        // De-normalise value
-        let val_i32 = (val * AXIS_MAX as f32) as i32;
+/// Absolute joystick output axes
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Display,
+    EnumString,
+    EnumVariantNames,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub enum AbsAxis {
+    X,
+    Y,
+    Z,
+    Rx,
+    Ry,
+    Rz,
+}
 
-        // Write events based on map type
+pub const ABS_AXES: &[AbsAxis] = &[AbsAxis::X, AbsAxis::Y, AbsAxis::Z, AbsAxis::Rx, AbsAxis::Ry, AbsAxis::Rz];
+
+impl AbsAxis {
+    /// Fetch the evdev code for this axis
+    pub fn code(&self) -> EV_ABS {
         match self {
-            Map::None => return Ok(()),
-            Map::X => {
-                v.write_event(&InputEvent {
-                    time: ts,
-                    event_code: EventCode::EV_REL(EV_REL::REL_X),
-                    value: val_i32,
-                })?;
-            }
-            Map::Y => {
-                v.write_event(&InputEvent {
-                    time: ts,
-                    event_code: EventCode::EV_REL(EV_REL::REL_Y),
-                    value: val_i32,
-                })?;
-            }
-            Map::H => {
-                v.write_event(&InputEvent {
-                    time: ts,
-                    event_code: EventCode::EV_REL(EV_REL::REL_HWHEEL),
-                    value: val_i32,
-                })?;
-
-                v.write_event(&InputEvent {
-                    time: ts,
-                    event_code: EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES),
-                    value: (val * AXIS_MAX as f32 * 120.00) as i32,
-                })?;
-            }
-            Map::V => {
-                v.write_event(&InputEvent {
-                    time: ts,
-                    event_code: EventCode::EV_REL(EV_REL::REL_WHEEL),
-                    value: -val_i32,
-                })?;
-
-                v.write_event(&InputEvent {
-                    time: ts,
-                    event_code: EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES),
-                    value: -(val * AXIS_MAX as f32 * 120.0) as i32,
-                })?;
+            AbsAxis::X => EV_ABS::ABS_X,
+            AbsAxis::Y => EV_ABS::ABS_Y,
+            AbsAxis::Z => EV_ABS::ABS_Z,
+            AbsAxis::Rx => EV_ABS::ABS_RX,
+            AbsAxis::Ry => EV_ABS::ABS_RY,
+            AbsAxis::Rz => EV_ABS::ABS_RZ,
+        }
+    }
+}
+
+impl Serialize for Map {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let (index, name) = match self {
+            Map::None => (0, "None"),
+            Map::X => (1, "X"),
+            Map::Y => (2, "Y"),
+            Map::H => (3, "H"),
+            Map::V => (4, "V"),
+            Map::Zoom => (5, "Zoom"),
+            Map::Abs(_) => (6, "Abs"),
+            Map::AbsX => (7, "AbsX"),
+            Map::AbsY => (8, "AbsY"),
+        };
+
+        match self {
+            Map::Abs(a) if s.is_human_readable() => {
+                let mut m = s.serialize_map(Some(1))?;
+                m.serialize_entry(name, a)?;
+                m.end()
             }
+            Map::Abs(a) => s.serialize_newtype_variant("Map", index, name, a),
+            _ => s.serialize_unit_variant("Map", index, name),
+        }
+    }
+}
+
+impl std::fmt::Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Map::None => write!(f, "None"),
+            Map::X => write!(f, "X"),
+            Map::Y => write!(f, "Y"),
+            Map::H => write!(f, "H"),
+            Map::V => write!(f, "V"),
+            Map::Zoom => write!(f, "Zoom"),
+            Map::Abs(a) => write!(f, "Abs({})", a),
+            Map::AbsX => write!(f, "AbsX"),
+            Map::AbsY => write!(f, "AbsY"),
         }
+    }
+}
 
-        // Write sync event to commit
-        v.write_event(&InputEvent {
-            time: ts,
-            event_code: EventCode::EV_SYN(EV_SYN::SYN_REPORT),
-            value: 0,
-        })?;
+impl FromStr for Map {
+    type Err = strum::ParseError;
 
-        Ok(())
+    /// Parse a mapping, absolute axes are written as `Abs(X)`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(a) = s.strip_prefix("Abs(").and_then(|s| s.strip_suffix(')')) {
+            return AbsAxis::from_str(a).map(Map::Abs);
+        }
+
+        match s {
+            "None" => Ok(Map::None),
+            "X" => Ok(Map::X),
+            "Y" => Ok(Map::Y),
+            "H" => Ok(Map::H),
+            "V" => Ok(Map::V),
+            "Zoom" => Ok(Map::Zoom),
+            "AbsX" => Ok(Map::AbsX),
+            "AbsY" => Ok(Map::AbsY),
+            _ => Err(strum::ParseError::VariantNotFound),
+        }
     }
 }
 
+impl Map {
+    /// Deserialize a mapping, accepting absolute axes written as tables (`[x.map]`)
+    /// or strings (`map = "Abs(X)"`) in human readable formats
+    pub fn deserialize_compat<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Compat {
+            Map(Map),
+            Name(String),
+        }
+
+        // Untagged enums are not supported by non-self-describing formats (eg. bincode)
+        if !d.is_human_readable() {
+            return Map::deserialize(d);
+        }
+
+        match Compat::deserialize(d)? {
+            Compat::Map(m) => Ok(m),
+            Compat::Name(s) => s.parse().map_err(|_| D::Error::custom(format!("unknown mapping '{}'", s))),
+        }
+    }
+
+    /// Write output events for a mapped value to the appropriate output device
+    pub fn event(&self, o: &Outputs, ts: TimeVal, val: f32) -> Result<(), Error> {
+        o.write(*self, ts, &self.output_events(val, o.abs_range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Axis, Config, ConfigFile, ConfigFormat};
+
+    use super::*;
+
+    #[test]
+    fn map_names() {
+        for m in MAPPINGS {
+            assert_eq!(m.to_string().parse::<Map>().unwrap(), *m);
+        }
+
+        assert_eq!("Abs(Rz)".parse::<Map>().unwrap(), Map::Abs(AbsAxis::Rz));
+        assert_eq!(Map::Abs(AbsAxis::Ry).to_string(), "Abs(Ry)");
+
+        for s in ["", "x", "Abs", "Abs()", "Abs(X", "Abs(W)", "AbsZ"] {
+            assert!(s.parse::<Map>().is_err(), "'{}'", s);
+        }
+    }
+
+    #[test]
+    fn abs_axis_codes() {
+        let codes: Vec<_> = ABS_AXES.iter().map(|a| a.code()).collect();
+        assert_eq!(codes, vec![EV_ABS::ABS_X, EV_ABS::ABS_Y, EV_ABS::ABS_Z, EV_ABS::ABS_RX, EV_ABS::ABS_RY, EV_ABS::ABS_RZ]);
+    }
+
+    #[test]
+    fn abs_mappings_saved() {
+        let mut config = Config::default();
+        config.default[Axis::X].map = Map::Abs(AbsAxis::X);
+        config.default[Axis::RZ].map = Map::Abs(AbsAxis::Rz);
+        config.abs_range = Some(1000);
+
+        let s = ConfigFile::new(&config, Default::default()).encode(ConfigFormat::Toml).unwrap();
+        let loaded = Config::from(&ConfigFile::parse(&s, ConfigFormat::Toml).unwrap());
+
+        assert_eq!(loaded.default[Axis::X].map, Map::Abs(AbsAxis::X));
+        assert_eq!(loaded.default[Axis::RZ].map, Map::Abs(AbsAxis::Rz));
+        assert_eq!(loaded.abs_range, Some(1000));
+
+        // Absolute axes can also be written by name
+        let s = s.replace("[default.x.map]\nAbs = 'X'", "").replace("[default.x]\n", "[default.x]\nmap = \"Abs(Ry)\"\n");
+        let loaded = Config::from(&ConfigFile::parse(&s, ConfigFormat::Toml).unwrap());
+        assert_eq!(loaded.default[Axis::X].map, Map::Abs(AbsAxis::Ry));
+
+        let s = s.replace("Abs(Ry)", "Abs(W)");
+        assert!(ConfigFile::parse(&s, ConfigFormat::Toml).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Daemon metrics snapshot types for [`Command::GetMetrics`](crate::Command::GetMetrics)
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Latency histogram bucket upper bounds (us)
+pub const LATENCY_BUCKETS_US: &[u64] = &[100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000];
+
+/// Fixed-bucket histogram, values above the last bound are counted in a trailing overflow bucket
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Histogram {
+    /// Bucket upper bounds (inclusive), ascending
+    pub bounds: Vec<u64>,
+    /// Counts per bucket, one longer than `bounds`
+    pub counts: Vec<u64>,
+    /// Sum of recorded values
+    pub sum: u64,
+    /// Number of recorded values
+    pub count: u64,
+}
+
+impl Histogram {
+    /// Create an empty histogram with the provided bucket bounds
+    pub fn new(bounds: &[u64]) -> Self {
+        Self { bounds: bounds.to_vec(), counts: vec![0; bounds.len() + 1], sum: 0, count: 0 }
+    }
+
+    /// Record a value
+    pub fn record(&mut self, v: u64) {
+        let i = self.bounds.iter().position(|b| v <= *b).unwrap_or(self.bounds.len());
+
+        self.counts[i] += 1;
+        self.sum = self.sum.saturating_add(v);
+        self.count += 1;
+    }
+
+    /// Estimate a percentile (0.0 to 1.0) as the upper bound of the bucket containing it,
+    /// `None` if empty or the percentile falls in the overflow bucket
+    pub fn percentile(&self, p: f32) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let target = ((self.count as f32 * p.clamp(0.0, 1.0)).ceil() as u64).max(1);
+        let mut n = 0;
+
+        for (i, c) in self.counts.iter().enumerate() {
+            n += c;
+            if n >= target {
+                return self.bounds.get(i).copied();
+            }
+        }
+
+        None
+    }
+
+    /// Mean of recorded values
+    pub fn mean(&self) -> Option<u64> {
+        self.sum.checked_div(self.count)
+    }
+}
+
+/// Per-device input metrics
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct DeviceMetrics {
+    /// Input events since bind
+    pub events_in: u64,
+    /// Input events per second
+    pub rate_in: f32,
+}
+
+/// Daemon metrics snapshot
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// Seconds since daemon start
+    pub uptime: u64,
+    /// Connected clients
+    pub clients: usize,
+    /// Input events since start
+    pub events_in: u64,
+    /// Input events per second
+    pub rate_in: f32,
+    /// Mapped output events since start, before coalescing
+    pub events_mapped: u64,
+    /// Output events written since start
+    pub events_out: u64,
+    /// Output events per second
+    pub rate_out: f32,
+    /// Client messages dropped by rate limits or closed channels
+    pub broadcasts_dropped: u64,
+    /// Output values saturated to the configured output limits
+    pub outputs_clamped: u64,
+    /// Per-device input metrics by `vid:pid`
+    pub devices: BTreeMap<String, DeviceMetrics>,
+    /// Source event to output latency (us)
+    pub latency_us: Histogram,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram(values: &[u64]) -> Histogram {
+        let mut h = Histogram::new(&[10, 100, 1000]);
+        for v in values {
+            h.record(*v);
+        }
+        h
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        // Bounds are inclusive, larger values overflow
+        let h = histogram(&[0, 10, 11, 100, 1000, 1001, 50_000]);
+        assert_eq!(h.counts, vec![2, 2, 1, 2]);
+        assert_eq!(h.count, 7);
+        assert_eq!(h.sum, 52_122);
+        assert_eq!(h.mean(), Some(7446));
+    }
+
+    #[test]
+    fn histogram_percentiles() {
+        assert_eq!(histogram(&[]).percentile(0.5), None);
+        assert_eq!(histogram(&[]).mean(), None);
+
+        let h = histogram(&[5, 5, 5, 5, 50, 50, 50, 500, 500, 5000]);
+        assert_eq!(h.percentile(0.0), Some(10));
+        assert_eq!(h.percentile(0.4), Some(10));
+        assert_eq!(h.percentile(0.5), Some(100));
+        assert_eq!(h.percentile(0.9), Some(1000));
+
+        // Percentiles in the overflow bucket have no bound
+        assert_eq!(h.percentile(0.99), None);
+        assert_eq!(h.percentile(2.0), None);
+    }
+}
@@ -0,0 +1,123 @@
+//! Raw passthrough devices, mirroring a source's 6-DOF events for applications
+//! that read the device directly (eg. Blender)
+
+use evdev_rs::enums::{EventCode, EV_ABS, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::{Device, DeviceWrapper, EnableCodeData, InputEvent, UInputDevice, UninitDevice};
+use tracing::debug;
+
+use crate::{Error, UsbDevice};
+
+/// Name prefix for passthrough devices, these are never bound as input sources
+pub const PASSTHROUGH_PREFIX: &str = "vmouse passthrough";
+
+/// Event codes mirrored from the source device, where supported
+const PASSTHROUGH_CODES: &[EventCode] = &[
+    EventCode::EV_REL(EV_REL::REL_X),
+    EventCode::EV_REL(EV_REL::REL_Y),
+    EventCode::EV_REL(EV_REL::REL_Z),
+    EventCode::EV_REL(EV_REL::REL_RX),
+    EventCode::EV_REL(EV_REL::REL_RY),
+    EventCode::EV_REL(EV_REL::REL_RZ),
+    EventCode::EV_ABS(EV_ABS::ABS_X),
+    EventCode::EV_ABS(EV_ABS::ABS_Y),
+    EventCode::EV_ABS(EV_ABS::ABS_Z),
+    EventCode::EV_ABS(EV_ABS::ABS_RX),
+    EventCode::EV_ABS(EV_ABS::ABS_RY),
+    EventCode::EV_ABS(EV_ABS::ABS_RZ),
+    EventCode::EV_KEY(EV_KEY::BTN_0),
+    EventCode::EV_KEY(EV_KEY::BTN_1),
+    EventCode::EV_KEY(EV_KEY::BTN_2),
+    EventCode::EV_KEY(EV_KEY::BTN_3),
+    EventCode::EV_KEY(EV_KEY::BTN_4),
+    EventCode::EV_KEY(EV_KEY::BTN_5),
+    EventCode::EV_KEY(EV_KEY::BTN_6),
+    EventCode::EV_KEY(EV_KEY::BTN_7),
+    EventCode::EV_KEY(EV_KEY::BTN_8),
+    EventCode::EV_KEY(EV_KEY::BTN_9),
+];
+
+/// Check whether a device is a passthrough device created by vmoused
+pub fn is_passthrough(d: &UsbDevice) -> bool {
+    d.name.as_deref().map(|n| n.starts_with(PASSTHROUGH_PREFIX)).unwrap_or(false)
+}
+
+/// Check whether an event code is forwarded to passthrough devices
+fn forwarded(c: &EventCode) -> bool {
+    PASSTHROUGH_CODES.contains(c) || *c == EventCode::EV_SYN(EV_SYN::SYN_REPORT)
+}
+
+/// Virtual device mirroring the 6-DOF capabilities of a source device,
+/// removed when dropped
+pub struct Passthrough {
+    device: UInputDevice,
+}
+
+impl Passthrough {
+    /// Create a passthrough device for an evdev source, keeping the source ids
+    /// so applications matching on `vid:pid` detect it
+    pub fn new(source: &Device) -> Result<Self, Error> {
+        let u = UninitDevice::new().unwrap();
+
+        u.set_name(format!("{} {}", PASSTHROUGH_PREFIX, source.name().unwrap_or("")).trim_end());
+        u.set_bustype(source.bustype());
+        u.set_vendor_id(source.vendor_id());
+        u.set_product_id(source.product_id());
+
+        for c in PASSTHROUGH_CODES.iter().filter(|c| source.has_event_code(c)) {
+            let data = match c {
+                EventCode::EV_ABS(_) => source.abs_info(c).map(EnableCodeData::AbsInfo),
+                _ => None,
+            };
+            u.enable_event_code(c, data).map_err(Error::Uinput)?;
+        }
+        u.enable_event_code(&EventCode::EV_SYN(EV_SYN::SYN_REPORT), None).map_err(Error::Uinput)?;
+
+        let device = UInputDevice::create_from_device(&u).map_err(Error::Uinput)?;
+        debug!("Created passthrough device: {}", device.devnode().unwrap_or(""));
+
+        Ok(Self { device })
+    }
+
+    /// Passthrough device node, if available
+    pub fn devnode(&self) -> Option<&str> {
+        self.device.devnode()
+    }
+
+    /// Forward a raw source event, unsupported codes are dropped
+    pub fn forward(&self, e: &InputEvent) -> Result<(), Error> {
+        if !forwarded(&e.event_code) {
+            return Ok(());
+        }
+
+        self.device.write_event(e).map_err(Error::Uinput)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passthrough_names() {
+        let named = |n: Option<&str>| UsbDevice { vid: 0x256f, pid: 0xc635, name: n.map(|n| n.to_string()) };
+
+        assert!(is_passthrough(&named(Some("vmouse passthrough"))));
+        assert!(is_passthrough(&named(Some("vmouse passthrough 3Dconnexion SpaceMouse Compact"))));
+
+        for n in [None, Some("3Dconnexion SpaceMouse Compact"), Some("vmouse"), Some("a vmouse passthrough")] {
+            assert!(!is_passthrough(&named(n)), "{:?}", n);
+        }
+    }
+
+    #[test]
+    fn forwarded_codes() {
+        for c in [EventCode::EV_REL(EV_REL::REL_RZ), EventCode::EV_ABS(EV_ABS::ABS_X), EventCode::EV_KEY(EV_KEY::BTN_9), EventCode::EV_SYN(EV_SYN::SYN_REPORT)] {
+            assert!(forwarded(&c), "{:?}", c);
+        }
+
+        // Non 6-DOF codes are dropped
+        for c in [EventCode::EV_REL(EV_REL::REL_WHEEL), EventCode::EV_KEY(EV_KEY::BTN_LEFT), EventCode::EV_KEY(EV_KEY::KEY_LEFTCTRL), EventCode::EV_SYN(EV_SYN::SYN_DROPPED)] {
+            assert!(!forwarded(&c), "{:?}", c);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Default socket and configuration paths
+//!
+//! System mode uses `/var/run` and `/etc`, user mode uses the XDG runtime
+//! and config directories. Daemon runtime state uses `/var/lib` or the XDG state
+//! directory, as does client state (eg. the GUI).
+
+use std::path::Path;
+
+/// System daemon socket path
+pub const SYSTEM_SOCKET: &str = "/var/run/vmouse.sock";
+
+/// System daemon configuration file
+pub const SYSTEM_CONFIG: &str = "/etc/vmouse/vmouse.toml";
+
+/// System daemon runtime state file
+pub const SYSTEM_STATE: &str = "/var/lib/vmouse/state.toml";
+
+/// Socket file name for user mode
+const SOCKET_NAME: &str = "vmouse.sock";
+
+/// Resolve the user mode socket path from `XDG_RUNTIME_DIR`
+pub fn user_socket_path() -> Option<String> {
+    resolve_user_socket(std::env::var("XDG_RUNTIME_DIR").ok().as_deref())
+}
+
+/// Resolve the user mode config path from `XDG_CONFIG_HOME`, falling back to `$HOME/.config`
+pub fn user_config_path() -> Option<String> {
+    resolve_user_config(
+        std::env::var("XDG_CONFIG_HOME").ok().as_deref(),
+        std::env::var("HOME").ok().as_deref(),
+    )
+}
+
+/// Resolve the user mode daemon state path from `XDG_STATE_HOME`, falling back to `$HOME/.local/state`
+pub fn user_state_path() -> Option<String> {
+    resolve_user_state(
+        std::env::var("XDG_STATE_HOME").ok().as_deref(),
+        std::env::var("HOME").ok().as_deref(),
+    )
+}
+
+/// Resolve the GUI state file path from `XDG_STATE_HOME`, falling back to `$HOME/.local/state`
+pub fn ui_state_path() -> Option<String> {
+    resolve_ui_state(
+        std::env::var("XDG_STATE_HOME").ok().as_deref(),
+        std::env::var("HOME").ok().as_deref(),
+    )
+}
+
+/// Resolve a user mode socket path for the provided runtime directory
+pub fn resolve_user_socket(runtime_dir: Option<&str>) -> Option<String> {
+    match runtime_dir {
+        Some(d) if !d.is_empty() => Some(format!("{}/{}", d.trim_end_matches('/'), SOCKET_NAME)),
+        _ => None,
+    }
+}
+
+/// Resolve a user mode config path for the provided config and home directories
+pub fn resolve_user_config(config_home: Option<&str>, home: Option<&str>) -> Option<String> {
+    let base = match (config_home, home) {
+        (Some(c), _) if !c.is_empty() => c.trim_end_matches('/').to_string(),
+        (_, Some(h)) if !h.is_empty() => format!("{}/.config", h.trim_end_matches('/')),
+        _ => return None,
+    };
+
+    Some(format!("{}/vmouse/vmouse.toml", base))
+}
+
+/// Resolve a GUI state file path for the provided state and home directories
+pub fn resolve_ui_state(state_home: Option<&str>, home: Option<&str>) -> Option<String> {
+    resolve_state_file(state_home, home, "ui.toml")
+}
+
+/// Resolve a user mode daemon state path for the provided state and home directories
+pub fn resolve_user_state(state_home: Option<&str>, home: Option<&str>) -> Option<String> {
+    resolve_state_file(state_home, home, "state.toml")
+}
+
+/// Resolve a file in the vmouse XDG state directory
+fn resolve_state_file(state_home: Option<&str>, home: Option<&str>, name: &str) -> Option<String> {
+    let base = match (state_home, home) {
+        (Some(s), _) if !s.is_empty() => s.trim_end_matches('/').to_string(),
+        (_, Some(h)) if !h.is_empty() => format!("{}/.local/state", h.trim_end_matches('/')),
+        _ => return None,
+    };
+
+    Some(format!("{}/vmouse/{}", base, name))
+}
+
+/// Default socket path for clients, prefers a running user mode daemon
+/// where available, otherwise the system daemon
+pub fn default_socket_path() -> String {
+    match user_socket_path() {
+        Some(p) if Path::new(&p).exists() => p,
+        _ => SYSTEM_SOCKET.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_socket_from_runtime_dir() {
+        assert_eq!(resolve_user_socket(Some("/run/user/1000")), Some("/run/user/1000/vmouse.sock".to_string()));
+        assert_eq!(resolve_user_socket(Some("/run/user/1000/")), Some("/run/user/1000/vmouse.sock".to_string()));
+    }
+
+    #[test]
+    fn user_socket_without_runtime_dir() {
+        assert_eq!(resolve_user_socket(None), None);
+        assert_eq!(resolve_user_socket(Some("")), None);
+    }
+
+    #[test]
+    fn user_config_prefers_config_home() {
+        assert_eq!(resolve_user_config(Some("/cfg"), Some("/home/u")), Some("/cfg/vmouse/vmouse.toml".to_string()));
+        assert_eq!(resolve_user_config(Some("/cfg/"), None), Some("/cfg/vmouse/vmouse.toml".to_string()));
+    }
+
+    #[test]
+    fn user_config_falls_back_to_home() {
+        assert_eq!(resolve_user_config(None, Some("/home/u")), Some("/home/u/.config/vmouse/vmouse.toml".to_string()));
+        assert_eq!(resolve_user_config(Some(""), Some("/home/u/")), Some("/home/u/.config/vmouse/vmouse.toml".to_string()));
+        assert_eq!(resolve_user_config(None, None), None);
+        assert_eq!(resolve_user_config(Some(""), Some("")), None);
+    }
+
+    #[test]
+    fn state_files_prefer_state_home() {
+        assert_eq!(resolve_ui_state(Some("/state"), Some("/home/u")), Some("/state/vmouse/ui.toml".to_string()));
+        assert_eq!(resolve_user_state(Some("/state/"), None), Some("/state/vmouse/state.toml".to_string()));
+    }
+
+    #[test]
+    fn state_files_fall_back_to_home() {
+        assert_eq!(resolve_ui_state(None, Some("/home/u")), Some("/home/u/.local/state/vmouse/ui.toml".to_string()));
+        assert_eq!(resolve_user_state(Some(""), Some("/home/u")), Some("/home/u/.local/state/vmouse/state.toml".to_string()));
+        assert_eq!(resolve_ui_state(None, None), None);
+        assert_eq!(resolve_user_state(None, Some("")), None);
+    }
+}
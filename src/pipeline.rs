@@ -0,0 +1,285 @@
+//! Input to output mapping pipeline, independent of uinput devices
+
+use evdev_rs::enums::{EventCode, EV_KEY, EV_REL, EV_SYN};
+use evdev_rs::InputEvent;
+
+use crate::{Config, Map, UsbDevice, ABS_RANGE_DEFAULT, AXIS_MAX};
+
+/// Output event data, written to the output device for the associated [`Map`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OutputEvent {
+    pub code: EventCode,
+    pub value: i32,
+}
+
+/// Result of mapping an input event
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mapped {
+    /// Output mapping for the input axis
+    pub map: Map,
+    /// Transformed (deadzone / curve / scale) output value
+    pub value: f32,
+    /// Output events, empty for unmapped axes and absolute pointer positions
+    /// (integrated by the daemon)
+    pub events: Vec<OutputEvent>,
+}
+
+/// Mapping pipeline: normalise raw input, apply the axis config, and produce output events
+pub struct Pipeline<'a> {
+    config: &'a Config,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self { config }
+    }
+
+    /// Map a device input event, `None` for events that are not axis inputs
+    pub fn map(&self, d: &UsbDevice, e: &InputEvent) -> Option<Mapped> {
+        let (map, value) = self.config.map(d, e)?;
+        let abs_range = self.config.abs_range.unwrap_or(ABS_RANGE_DEFAULT);
+
+        Some(Mapped { map, value, events: map.output_events(value, abs_range) })
+    }
+}
+
+impl Map {
+    /// Compute output events for a mapped (normalised) value, without the trailing sync
+    ///
+    /// Outputs spanning several frames (eg. [`Map::Zoom`]) include intermediate syncs.
+    pub fn output_events(&self, val: f32, abs_range: i32) -> Vec<OutputEvent> {
+        // De-normalise value, float to int casts saturate so extreme values cannot wrap
+        let val_i32 = (val * AXIS_MAX as f32) as i32;
+        let rel = |c, value| OutputEvent { code: EventCode::EV_REL(c), value };
+
+        match self {
+            // Absolute pointer positions are integrated by the daemon
+            Map::None | Map::AbsX | Map::AbsY => vec![],
+            Map::Abs(a) => vec![OutputEvent {
+                code: EventCode::EV_ABS(a.code()),
+                value: (val.clamp(-1.0, 1.0) * abs_range as f32) as i32,
+            }],
+            Map::X => vec![rel(EV_REL::REL_X, val_i32)],
+            Map::Y => vec![rel(EV_REL::REL_Y, val_i32)],
+            Map::H => vec![
+                rel(EV_REL::REL_HWHEEL, val_i32),
+                rel(EV_REL::REL_HWHEEL_HI_RES, (val * AXIS_MAX as f32 * 120.00) as i32),
+            ],
+            Map::V => vec![
+                rel(EV_REL::REL_WHEEL, val_i32.saturating_neg()),
+                rel(EV_REL::REL_WHEEL_HI_RES, -(val * AXIS_MAX as f32 * 120.0) as i32),
+            ],
+            // Ctrl is pressed and released in separate frames so the scroll is seen with the modifier held
+            Map::Zoom => {
+                let hi_res = (val * AXIS_MAX as f32 * 120.0) as i32;
+                if hi_res == 0 {
+                    return vec![];
+                }
+
+                let ctrl = |value| OutputEvent { code: EventCode::EV_KEY(EV_KEY::KEY_LEFTCTRL), value };
+                let sync = OutputEvent { code: EventCode::EV_SYN(EV_SYN::SYN_REPORT), value: 0 };
+
+                vec![
+                    ctrl(1),
+                    sync.clone(),
+                    rel(EV_REL::REL_WHEEL, val_i32),
+                    rel(EV_REL::REL_WHEEL_HI_RES, hi_res),
+                    sync,
+                    ctrl(0),
+                ]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev_rs::enums::EV_ABS;
+    use evdev_rs::TimeVal;
+
+    use crate::{capabilities_for, preset_for, AbsAxis, Axis, AxisCollection, AxisConfig, ConfigFile, ConfigFormat, CurveKind, InputKind, OutputLimits, AXIS};
+
+    use super::*;
+
+    const DEVICE: UsbDevice = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+    fn z(value: i32) -> InputEvent {
+        InputEvent { time: TimeVal::new(0, 0), event_code: EventCode::EV_REL(EV_REL::REL_Z), value }
+    }
+
+    fn codes(events: &[OutputEvent]) -> Vec<(EventCode, i32)> {
+        events.iter().map(|e| (e.code.clone(), e.value.signum())).collect()
+    }
+
+    #[test]
+    fn fresh_config_zooms_on_z() {
+        let config = Config::default();
+        let p = Pipeline::new(&config);
+
+        let ctrl = EventCode::EV_KEY(EV_KEY::KEY_LEFTCTRL);
+        let sync = EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+        let wheel = EventCode::EV_REL(EV_REL::REL_WHEEL);
+        let hi_res = EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES);
+
+        // Ctrl is held around the scroll, positive deflection scrolls up
+        let m = p.map(&DEVICE, &z(350)).unwrap();
+        assert_eq!(m.map, Map::Zoom);
+        assert_eq!(codes(&m.events), vec![
+            (ctrl.clone(), 1), (sync.clone(), 0), (wheel.clone(), 1), (hi_res.clone(), 1), (sync.clone(), 0), (ctrl.clone(), 0),
+        ]);
+
+        let m = p.map(&DEVICE, &z(-350)).unwrap();
+        assert_eq!(codes(&m.events), vec![
+            (ctrl.clone(), 1), (sync.clone(), 0), (wheel, -1), (hi_res, -1), (sync, 0), (ctrl, 0),
+        ]);
+
+        // No Ctrl taps within the deadzone
+        assert!(p.map(&DEVICE, &z(10)).unwrap().events.is_empty());
+
+        assert!(capabilities_for(&config).contains(&EventCode::EV_KEY(EV_KEY::KEY_LEFTCTRL)));
+        assert!(capabilities_for(&config).contains(&EventCode::EV_REL(EV_REL::REL_WHEEL)));
+    }
+
+    #[test]
+    fn legacy_config_keeps_z_unmapped() {
+        // Config files without a version predate the zoom defaults
+        let legacy = Config::from(&ConfigFile::parse("[devices]\n", ConfigFormat::Toml).unwrap());
+        assert_eq!(legacy.version, 0);
+        assert_eq!(legacy.default[Axis::Z].map, Map::None);
+        assert!(Pipeline::new(&legacy).map(&DEVICE, &z(350)).unwrap().events.is_empty());
+        assert!(!capabilities_for(&legacy).contains(&EventCode::EV_KEY(EV_KEY::KEY_LEFTCTRL)));
+
+        let current = Config::from(&ConfigFile::parse("version = 1\n[devices]\n", ConfigFormat::Toml).unwrap());
+        assert_eq!(current.default, Config::default().default);
+
+        // Presets follow the config version
+        let preset = preset_for(&DEVICE).unwrap();
+        assert_eq!(preset.axes_for(0)[Axis::Z].map, Map::None);
+        assert_eq!(preset.axes_for(crate::CONFIG_VERSION)[Axis::Z].map, Map::Zoom);
+        assert_eq!(preset.axes_for(0)[Axis::RZ], preset.axes_for(crate::CONFIG_VERSION)[Axis::RZ]);
+    }
+
+    /// Map a raw X axis value and apply the default output limits
+    fn golden(p: &Pipeline, value: i32) -> Vec<(EventCode, i32)> {
+        let e = InputEvent { time: TimeVal::new(0, 0), event_code: EventCode::EV_REL(EV_REL::REL_X), value };
+        let limits = OutputLimits::default();
+
+        p.map(&DEVICE, &e).unwrap().events.iter()
+            .map(|e| (e.code.clone(), limits.clamp(&e.code, e.value).0))
+            .collect()
+    }
+
+    #[test]
+    fn golden_pointer() {
+        let mut config = Config::default();
+        let mut axes = config.default;
+        axes[Axis::X] = AxisConfig { map: Map::X, curve: CurveKind::CubicBlend(0.5), scale: 2.0, deadzone: 0.1, ..AxisConfig::disabled() };
+        config.devices.insert(DEVICE, axes);
+
+        let p = Pipeline::new(&config);
+        let x = |v| vec![(EventCode::EV_REL(EV_REL::REL_X), v)];
+
+        // Deadzone
+        assert_eq!(golden(&p, 0), x(0));
+        assert_eq!(golden(&p, 35), x(0));
+        assert_eq!(golden(&p, -35), x(0));
+
+        // Deadzone rescaled, curve, then scale: -0.2 -> -0.111 -> -0.0562 -> -0.1125
+        assert_eq!(golden(&p, -70), x(-39));
+        // 0.3 -> 0.222 -> 0.1166 -> 0.2332
+        assert_eq!(golden(&p, 105), x(81));
+
+        // Saturated to the pointer limit
+        assert_eq!(golden(&p, 175), x(127));
+        assert_eq!(golden(&p, 350), x(127));
+        assert_eq!(golden(&p, -350), x(-127));
+    }
+
+    #[test]
+    fn golden_wheel() {
+        let mut config = Config::default();
+        let mut axes = config.default;
+        axes[Axis::X] = AxisConfig { map: Map::V, curve: CurveKind::Power(2.0), scale: 0.05, deadzone: 0.0, ..AxisConfig::disabled() };
+        config.devices.insert(DEVICE, axes);
+
+        let p = Pipeline::new(&config);
+        let wheel = |v, hi_res| vec![(EventCode::EV_REL(EV_REL::REL_WHEEL), v), (EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES), hi_res)];
+
+        // 0.5 -> 0.25 -> 0.0125, positive deflection scrolls down
+        assert_eq!(golden(&p, 175), wheel(-4, -525));
+        assert_eq!(golden(&p, -175), wheel(4, 525));
+
+        // Full deflection, saturated to 8 detents
+        assert_eq!(golden(&p, 350), wheel(-8, -960));
+        assert_eq!(golden(&p, -350), wheel(8, 960));
+    }
+
+    #[test]
+    fn joystick_positions() {
+        let mut config = Config::default();
+        let mut axes = config.default;
+        axes[Axis::X] = AxisConfig { map: Map::Abs(AbsAxis::Rx), curve: CurveKind::CubicBlend(0.0), scale: 1.0, deadzone: 0.0, ..AxisConfig::disabled() };
+        config.devices.insert(DEVICE, axes);
+
+        let x = |p: &Pipeline, value| {
+            let e = InputEvent { time: TimeVal::new(0, 0), event_code: EventCode::EV_REL(EV_REL::REL_X), value };
+            p.map(&DEVICE, &e).unwrap().events
+        };
+        let rx = |value| vec![OutputEvent { code: EventCode::EV_ABS(EV_ABS::ABS_RX), value }];
+
+        // Positions span the default range, saturating at full deflection
+        let p = Pipeline::new(&config);
+        assert_eq!(x(&p, 0), rx(0));
+        assert_eq!(x(&p, 175), rx(ABS_RANGE_DEFAULT / 2));
+        assert_eq!(x(&p, -350), rx(-ABS_RANGE_DEFAULT));
+
+        config.devices.get_mut(&DEVICE).unwrap()[Axis::X].scale = 2.0;
+        let p = Pipeline::new(&config);
+        assert_eq!(x(&p, 350), rx(ABS_RANGE_DEFAULT));
+        assert_eq!(x(&p, -350), rx(-ABS_RANGE_DEFAULT));
+
+        // Or the configured range
+        config.abs_range = Some(1000);
+        let p = Pipeline::new(&config);
+        assert_eq!(x(&p, 87), rx(497));
+        assert_eq!(x(&p, -350), rx(-1000));
+    }
+
+    #[test]
+    fn mouse_scrolls_only() {
+        // A mouse with X mapped to the vertical wheel and Y unused
+        let mut config = Config::default();
+        let mut axes = AxisCollection::<AxisConfig>::default();
+        for a in AXIS {
+            axes[*a] = AxisConfig::disabled();
+        }
+        axes[Axis::X] = AxisConfig { map: Map::V, scale: 1.0, ..AxisConfig::disabled() };
+        config.devices.insert(DEVICE, axes);
+        config.input_kind.insert(DEVICE.to_string(), InputKind::Mouse);
+
+        let p = Pipeline::new(&config);
+        let rel = |c, value| p.map(&DEVICE, &InputEvent { time: TimeVal::new(0, 0), event_code: EventCode::EV_REL(c), value }).unwrap();
+        let wheel = EventCode::EV_REL(EV_REL::REL_WHEEL);
+        let hi_res = EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES);
+
+        // Deltas are velocities, scaled by the divisor without clamping
+        let m = rel(EV_REL::REL_X, 16);
+        assert_eq!(m.value, 0.5);
+        assert_eq!(m.map, Map::V);
+        assert_eq!(codes(&m.events), vec![(wheel.clone(), -1), (hi_res.clone(), -1)]);
+        assert_eq!(rel(EV_REL::REL_X, -64).value, -2.0);
+        assert_eq!(codes(&rel(EV_REL::REL_X, -64).events), vec![(wheel, 1), (hi_res, 1)]);
+
+        // Unmapped motion produces no events
+        assert!(rel(EV_REL::REL_Y, 16).events.is_empty());
+    }
+
+    #[test]
+    fn saved_configs_keep_version() {
+        let s = ConfigFile::new(&Config::default(), Default::default()).encode(ConfigFormat::Toml).unwrap();
+        assert!(s.starts_with("version = 1\n"), "{}", s);
+
+        assert_eq!("Zoom".parse::<Map>().unwrap(), Map::Zoom);
+        assert_eq!(Map::Zoom.to_string(), "Zoom");
+    }
+}
@@ -0,0 +1,166 @@
+//! Built-in axis presets for known devices, applied when a device has no config
+
+use crate::{AxisCollection, AxisConfig, CurveKind, Map, UsbDevice};
+
+/// Built-in device preset
+#[derive(Clone, PartialEq, Debug)]
+pub struct Preset {
+    /// Preset name, used with `vmousectl apply-preset`
+    pub name: &'static str,
+    /// Device model
+    pub model: &'static str,
+    /// Matching device ids (vid, pid)
+    pub ids: &'static [(u16, u16)],
+    pub axes: AxisCollection<AxisConfig>,
+}
+
+impl Preset {
+    /// Check whether a preset applies to a device, matched by vid:pid
+    pub fn matches(&self, d: &UsbDevice) -> bool {
+        self.ids.iter().any(|(vid, pid)| *vid == d.vid && *pid == d.pid)
+    }
+
+    /// Fetch preset axes for a config version, Z is unmapped for configs predating
+    /// [`CONFIG_VERSION`](crate::CONFIG_VERSION) 1
+    pub fn axes_for(&self, version: u32) -> AxisCollection<AxisConfig> {
+        match version {
+            0 => AxisCollection { z: NONE, ..self.axes },
+            _ => self.axes,
+        }
+    }
+}
+
+/// Axis config helper for preset definitions
+const fn axis(map: Map, scale: f32, curve: f32, deadzone: f32) -> AxisConfig {
+    AxisConfig {
+        map,
+        curve: CurveKind::CubicBlend(curve),
+        scale,
+        deadzone,
+        scale_neg: None,
+        deadzone_neg: None,
+        range: Some((-350, 350)),
+    }
+}
+
+/// Unmapped axis
+const NONE: AxisConfig = AxisConfig::disabled();
+
+/// Built-in presets
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "spacemouse-compact",
+        model: "3Dconnexion SpaceMouse Compact",
+        ids: &[(0x256f, 0xc635)],
+        axes: AxisCollection {
+            x: axis(Map::H, 0.005, 0.5, 0.05),
+            y: axis(Map::V, 0.005, 0.5, 0.05),
+            z: axis(Map::Zoom, 0.003, 0.5, 0.1),
+            rx: axis(Map::Y, 0.2, 1.0, 0.05),
+            ry: axis(Map::X, -0.2, 1.0, 0.05),
+            rz: NONE,
+        },
+    },
+    Preset {
+        name: "spacenavigator",
+        model: "3Dconnexion SpaceNavigator",
+        ids: &[(0x046d, 0xc626)],
+        axes: AxisCollection {
+            x: axis(Map::H, 0.004, 0.5, 0.1),
+            y: axis(Map::V, 0.004, 0.5, 0.1),
+            z: axis(Map::Zoom, 0.003, 0.5, 0.15),
+            rx: axis(Map::Y, 0.15, 1.0, 0.1),
+            ry: axis(Map::X, -0.15, 1.0, 0.1),
+            rz: NONE,
+        },
+    },
+    Preset {
+        name: "spacemouse-pro",
+        model: "3Dconnexion SpaceMouse Pro",
+        ids: &[(0x046d, 0xc62b), (0x256f, 0xc62b)],
+        axes: AxisCollection {
+            x: axis(Map::H, 0.005, 0.5, 0.05),
+            y: axis(Map::V, 0.005, 0.5, 0.05),
+            z: axis(Map::Zoom, 0.003, 0.5, 0.1),
+            rx: axis(Map::Y, 0.25, 1.0, 0.05),
+            ry: axis(Map::X, -0.25, 1.0, 0.05),
+            rz: NONE,
+        },
+    },
+    Preset {
+        name: "spacemouse-wireless",
+        model: "3Dconnexion SpaceMouse Wireless",
+        ids: &[(0x256f, 0xc62e), (0x256f, 0xc62f), (0x256f, 0xc652)],
+        axes: AxisCollection {
+            x: axis(Map::H, 0.005, 0.5, 0.05),
+            y: axis(Map::V, 0.005, 0.5, 0.05),
+            z: axis(Map::Zoom, 0.003, 0.5, 0.1),
+            rx: axis(Map::Y, 0.2, 1.0, 0.05),
+            ry: axis(Map::X, -0.2, 1.0, 0.05),
+            rz: NONE,
+        },
+    },
+];
+
+/// Find the built-in preset for a device, `None` if the device is unknown
+pub fn preset_for(d: &UsbDevice) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.matches(d))
+}
+
+/// Find a built-in preset by name
+pub fn preset(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preset_for_device() {
+        let d = |vid, pid| UsbDevice { vid, pid, name: None };
+
+        assert_eq!(preset_for(&d(0x256f, 0xc635)).map(|p| p.name), Some("spacemouse-compact"));
+        assert_eq!(preset_for(&d(0x046d, 0xc626)).map(|p| p.name), Some("spacenavigator"));
+
+        // Presets match every listed id
+        assert_eq!(preset_for(&d(0x046d, 0xc62b)).map(|p| p.name), Some("spacemouse-pro"));
+        assert_eq!(preset_for(&d(0x256f, 0xc62b)).map(|p| p.name), Some("spacemouse-pro"));
+        assert_eq!(preset_for(&d(0x256f, 0xc652)).map(|p| p.name), Some("spacemouse-wireless"));
+
+        assert_eq!(preset_for(&d(0x256f, 0x0000)), None);
+        assert_eq!(preset_for(&d(0x0000, 0xc635)), None);
+    }
+
+    #[test]
+    fn preset_by_name() {
+        for p in PRESETS {
+            assert_eq!(preset(p.name), Some(p));
+        }
+        assert_eq!(preset("spacemouse"), None);
+        assert_eq!(preset(""), None);
+    }
+
+    #[test]
+    fn presets_unique() {
+        let mut ids: Vec<_> = PRESETS.iter().flat_map(|p| p.ids.iter()).collect();
+        let n = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), n, "device ids listed by more than one preset");
+
+        let mut names: Vec<_> = PRESETS.iter().map(|p| p.name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), PRESETS.len());
+    }
+
+    #[test]
+    fn legacy_axes_unmap_z() {
+        let p = preset("spacemouse-compact").unwrap();
+
+        assert_eq!(p.axes_for(crate::CONFIG_VERSION), p.axes);
+        assert_eq!(p.axes_for(0).z.map, Map::None);
+        assert_eq!(AxisCollection { z: p.axes.z, ..p.axes_for(0) }, p.axes);
+    }
+}
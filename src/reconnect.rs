@@ -0,0 +1,233 @@
+//! Reconnecting client wrapper with exponential backoff
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::sync::Mutex;
+use futures::stream::{BoxStream, StreamExt};
+use tracing::{debug, warn};
+
+use crate::{Client, Command, Error};
+
+/// Initial reconnection delay
+const BACKOFF_MIN: Duration = Duration::from_millis(250);
+
+/// Maximum reconnection delay
+const BACKOFF_MAX: Duration = Duration::from_secs(10);
+
+/// Connection state and message events from a [`ReconnectingClient`]
+#[derive(Clone, Debug)]
+pub enum ClientEvent {
+    /// Connected (or reconnected) to the daemon
+    Connected,
+    /// Connection lost or failed, retrying after the provided delay, with the failure reason
+    Reconnecting(Duration, String),
+    /// Message received from the daemon
+    Message(Command),
+}
+
+/// Client wrapper that re-dials the daemon socket on disconnection,
+/// replaying [`Command::Listen`] if enabled
+#[derive(Clone, Debug)]
+pub struct ReconnectingClient {
+    path: String,
+    listen: bool,
+    current: Arc<Mutex<Option<Client>>>,
+    closed: Arc<AtomicBool>,
+}
+
+struct EventState {
+    client: ReconnectingClient,
+    reader: Option<Client>,
+    delay: Option<Duration>,
+}
+
+impl ReconnectingClient {
+    /// Create a new reconnecting client, connection is established by polling [`ReconnectingClient::events`]
+    pub fn new(path: String, listen: bool) -> Self {
+        Self {
+            path,
+            listen,
+            current: Arc::new(Mutex::new(None)),
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Fetch the socket path for this client
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Send a command using the current connection
+    pub async fn send(&self, cmd: Command) -> Result<(), Error> {
+        self.connection().await?.send(cmd).await
+    }
+
+    /// Send a command, awaiting the matching response where the daemon supports request ids
+    ///
+    /// Responses are read by the [`ReconnectingClient::events`] stream, which must be polled.
+    /// Returns `None` for daemons predating request ids, with the response delivered as a
+    /// [`ClientEvent::Message`].
+    pub async fn request(&self, cmd: Command) -> Result<Option<Command>, Error> {
+        let mut c = self.connection().await?;
+
+        if !c.supports_request_ids() {
+            c.send(cmd).await?;
+            return Ok(None);
+        }
+
+        let rx = c.send_request(cmd).await?;
+
+        rx.await.map(Some).map_err(|_| Error::SocketIo(std::io::Error::new(
+            std::io::ErrorKind::ConnectionAborted,
+            format!("Connection to '{}' closed awaiting response", self.path),
+        )))
+    }
+
+    /// Fetch the current connection
+    async fn connection(&self) -> Result<Client, Error> {
+        match self.current.lock().await.clone() {
+            Some(c) => Ok(c),
+            None => Err(Error::SocketIo(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                format!("Not connected to '{}'", self.path),
+            ))),
+        }
+    }
+
+    /// Close the client, deliberate closes do not trigger reconnection
+    pub async fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+
+        let c = self.current.lock().await.take();
+        if let Some(mut c) = c {
+            if let Err(e) = c.close().await {
+                debug!("Failed to close connection to '{}': {:?}", self.path, e);
+                let _ = c.shutdown();
+            }
+        }
+    }
+
+    /// Stream of connection and message events, (re)connecting as required
+    ///
+    /// The stream ends once the client is closed.
+    pub fn events(&self) -> BoxStream<'static, ClientEvent> {
+        let state = EventState {
+            client: self.clone(),
+            reader: None,
+            delay: None,
+        };
+
+        futures::stream::unfold(state, |mut s| async move {
+            loop {
+                if s.client.closed.load(Ordering::SeqCst) {
+                    return None;
+                }
+
+                // Read from the current connection
+                if let Some(r) = s.reader.as_mut() {
+                    match r.next().await {
+                        Some(Ok(c)) => return Some((ClientEvent::Message(c), s)),
+                        Some(Err(e)) => warn!("Connection to '{}' failed: {:?}", s.client.path, e),
+                        None => debug!("Connection to '{}' closed", s.client.path),
+                    }
+
+                    s.reader = None;
+                    s.client.current.lock().await.take();
+                    continue;
+                }
+
+                // Wait before reconnecting, doubling the delay each attempt
+                if let Some(d) = s.delay {
+                    async_std::task::sleep(d).await;
+                    if s.client.closed.load(Ordering::SeqCst) {
+                        return None;
+                    }
+                }
+
+                match s.client.connect().await {
+                    Ok(c) => {
+                        *s.client.current.lock().await = Some(c.clone());
+                        s.reader = Some(c);
+                        s.delay = Some(BACKOFF_MIN);
+
+                        return Some((ClientEvent::Connected, s));
+                    }
+                    Err(e) => {
+                        let d = s.delay.map(|d| (d * 2).min(BACKOFF_MAX)).unwrap_or(BACKOFF_MIN);
+                        s.delay = Some(d);
+
+                        debug!("Failed to connect to '{}': {:?}, retrying in {:?}", s.client.path, e, d);
+
+                        return Some((ClientEvent::Reconnecting(d, e.to_string()), s));
+                    }
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// Connect to the daemon, subscribing to events if enabled
+    async fn connect(&self) -> Result<Client, Error> {
+        let mut c = Client::connect(self.path.clone()).await?;
+
+        if self.listen {
+            c.send(Command::Listen { topics: vec![] }).await?;
+        }
+
+        Ok(c)
+    }
+}
+
+impl std::hash::Hash for ReconnectingClient {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+        (Arc::as_ptr(&self.closed) as usize).hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::tests::{broadcast, response, socket_path, spawn_peer};
+
+    use super::*;
+
+    #[test]
+    fn concurrent_requests() {
+        let path = socket_path("reconnect");
+        let peer = spawn_peer(&path, 2);
+
+        async_std::task::block_on(async {
+            let rc = ReconnectingClient::new(path.clone(), false);
+            let mut events = rc.events();
+            assert!(matches!(events.next().await, Some(ClientEvent::Connected)));
+
+            // Responses are read by the event stream
+            let (tx, mut rx) = futures::channel::mpsc::unbounded();
+            let reader = async_std::task::spawn(async move {
+                while let Some(e) = events.next().await {
+                    if tx.unbounded_send(e).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let (a, b) = futures::join!(rc.request(Command::GetConfig), rc.request(Command::GetStatus));
+            assert_eq!(a.unwrap(), Some(response(&Command::GetConfig)));
+            assert_eq!(b.unwrap(), Some(response(&Command::GetStatus)));
+
+            // Broadcasts are delivered as messages
+            match rx.next().await {
+                Some(ClientEvent::Message(c)) => assert_eq!(c, broadcast()),
+                e => panic!("unexpected event: {:?}", e),
+            }
+
+            rc.close().await;
+            reader.cancel().await;
+        });
+
+        peer.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+}
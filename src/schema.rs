@@ -0,0 +1,88 @@
+//! JSON Schema for config files, for editor tooling (see `vmousectl schema`)
+
+use std::ops::RangeInclusive;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{RootSchema, Schema};
+use schemars::JsonSchema;
+
+use crate::{Config, CURVE_RANGE, DEADZONE_RANGE, POWER_RANGE, SCALE_RANGE};
+
+/// Generate a JSON Schema describing [`Config`]
+///
+/// Numeric ranges are taken from the constants used by [`Config::validate`].
+pub fn config_schema() -> RootSchema {
+    schemars::schema_for!(Config)
+}
+
+/// Schema for a value of type `T` limited to a range
+fn ranged<T: JsonSchema>(gen: &mut SchemaGenerator, r: RangeInclusive<f32>) -> Schema {
+    let mut s = gen.subschema_for::<T>().into_object();
+
+    let n = s.number();
+    n.minimum = Some(*r.start() as f64);
+    n.maximum = Some(*r.end() as f64);
+
+    s.into()
+}
+
+pub(crate) fn deadzone(gen: &mut SchemaGenerator) -> Schema {
+    ranged::<f32>(gen, DEADZONE_RANGE)
+}
+
+pub(crate) fn deadzone_opt(gen: &mut SchemaGenerator) -> Schema {
+    ranged::<Option<f32>>(gen, DEADZONE_RANGE)
+}
+
+pub(crate) fn scale(gen: &mut SchemaGenerator) -> Schema {
+    ranged::<f32>(gen, SCALE_RANGE)
+}
+
+pub(crate) fn scale_opt(gen: &mut SchemaGenerator) -> Schema {
+    ranged::<Option<f32>>(gen, SCALE_RANGE)
+}
+
+pub(crate) fn cubic_blend(gen: &mut SchemaGenerator) -> Schema {
+    ranged::<f32>(gen, CURVE_RANGE)
+}
+
+pub(crate) fn power(gen: &mut SchemaGenerator) -> Schema {
+    ranged::<f32>(gen, POWER_RANGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonschema::JSONSchema;
+
+    use super::*;
+
+    fn validator() -> JSONSchema {
+        let schema = serde_json::to_value(config_schema()).unwrap();
+        JSONSchema::compile(&schema).unwrap()
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        let c = serde_json::to_value(Config::default()).unwrap();
+        assert!(validator().is_valid(&c));
+    }
+
+    #[test]
+    fn out_of_range_scale_is_invalid() {
+        for (k, v) in [("scale", 10.5), ("scale", -11.0), ("scale_neg", 20.0)] {
+            let mut c = serde_json::to_value(Config::default()).unwrap();
+            c["default"]["y"][k] = serde_json::json!(v);
+
+            assert!(!validator().is_valid(&c), "{} = {}", k, v);
+        }
+    }
+
+    #[test]
+    fn out_of_range_deadzone_is_invalid() {
+        let mut c = serde_json::to_value(Config::default()).unwrap();
+        c["default"]["x"]["deadzone"] = serde_json::json!(2.0);
+
+        assert!(!validator().is_valid(&c));
+        assert!(Config::validate(&serde_json::from_value(c).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Shareable device profile files, for `vmousectl export-profile` and `import-profile`
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AxisCollection, AxisConfig, Error, AXIS};
+
+/// Profile file format version, bumped on incompatible changes
+pub const PROFILE_FORMAT: u32 = 1;
+
+/// Conventional profile file extension
+pub const PROFILE_EXTENSION: &str = "vmprofile";
+
+/// Shared profile metadata
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ProfileMeta {
+    /// Profile file format version, see [`PROFILE_FORMAT`]
+    pub format: u32,
+    /// vmouse version that exported the profile
+    pub version: String,
+    /// Device the profile was exported from (`default` or `vid:pid`)
+    pub device: String,
+    /// Optional description (eg. application or use)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Shareable device axis configuration, written as TOML
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ProfileFile {
+    pub meta: ProfileMeta,
+    pub axes: AxisCollection<AxisConfig>,
+}
+
+impl ProfileFile {
+    /// Create a profile file for a device's axis configuration, by device name (`default` or `vid:pid`)
+    pub fn new(device: &str, axes: AxisCollection<AxisConfig>, description: Option<String>) -> Self {
+        Self {
+            meta: ProfileMeta {
+                format: PROFILE_FORMAT,
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                device: device.to_string(),
+                description,
+            },
+            axes,
+        }
+    }
+
+    /// Encode a profile file as TOML
+    pub fn encode(&self) -> Result<String, Error> {
+        // Encoded via `toml::Value` so tables (eg. curves) are emitted after plain values
+        toml::Value::try_from(self)
+            .and_then(|v| toml::to_string_pretty(&v))
+            .map_err(|e| Error::Config(e.to_string()))
+    }
+
+    /// Parse and validate a profile file
+    ///
+    /// The format version is checked before the axes are parsed, so profiles from newer
+    /// versions fail with [`Error::ProfileVersion`] rather than falling back to defaults.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let v: toml::Value = toml::from_str(s).map_err(|e| Error::Config(e.to_string()))?;
+
+        let meta = v.get("meta").ok_or_else(|| Error::Config("missing [meta] table".to_string()))?;
+        let format = meta.get("format").and_then(|f| f.as_integer())
+            .ok_or_else(|| Error::Config("missing profile format".to_string()))?;
+
+        if format > PROFILE_FORMAT as i64 {
+            return Err(Error::ProfileVersion {
+                found: format.try_into().unwrap_or(u32::MAX),
+                supported: PROFILE_FORMAT,
+                version: meta.get("version").and_then(|v| v.as_str()).unwrap_or("unknown").to_string(),
+            });
+        }
+
+        let p: Self = v.try_into().map_err(|e: toml::de::Error| Error::Config(e.to_string()))?;
+
+        for a in AXIS {
+            p.axes[*a].validate().map_err(|e| Error::Config(format!("axis {}: {}", a, e)))?;
+        }
+
+        Ok(p)
+    }
+
+    /// Load and validate a profile file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| Error::ConfigIo(path.to_owned(), e))?;
+
+        Self::parse(&s).map_err(|e| match e {
+            Error::Config(m) => Error::Config(format!("failed to parse '{}': {}", path.display(), m)),
+            e => e,
+        })
+    }
+
+    /// Save a profile file atomically
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let s = self.encode()?;
+        crate::write_atomic(path, s.as_bytes())
+            .map_err(|e| Error::ConfigIo(path.to_owned(), e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Axis, Config};
+
+    use super::*;
+
+    fn profile() -> ProfileFile {
+        let mut axes = Config::default().default;
+        axes[Axis::X].scale = 2.5;
+        ProfileFile::new("256f:c635", axes, Some("blender".to_string()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let p = profile();
+        let s = p.encode().unwrap();
+
+        assert_eq!(ProfileFile::parse(&s).unwrap(), p);
+    }
+
+    #[test]
+    fn newer_format_rejected() {
+        let s = profile().encode().unwrap()
+            .replace(&format!("format = {}", PROFILE_FORMAT), &format!("format = {}", PROFILE_FORMAT + 1));
+
+        match ProfileFile::parse(&s) {
+            Err(Error::ProfileVersion { found, supported, .. }) => {
+                assert_eq!(found, PROFILE_FORMAT + 1);
+                assert_eq!(supported, PROFILE_FORMAT);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn invalid_profiles_rejected() {
+        let mut p = profile();
+        p.axes[Axis::Y].scale = 100.0;
+        assert!(matches!(ProfileFile::parse(&p.encode().unwrap()), Err(Error::Config(_))));
+
+        // Metadata is required to check the format version
+        assert!(matches!(ProfileFile::parse("[axes]\n"), Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,193 @@
+//! Input source abstraction, allows device backends other than evdev
+
+use std::fs::{read_dir, File};
+use std::io::ErrorKind;
+use std::os::unix::prelude::AsRawFd;
+
+use evdev_rs::enums::{EventCode, EventType, EV_REL};
+use evdev_rs::{Device, DeviceWrapper, InputEvent, ReadFlag, TimeVal};
+
+use crate::{is_passthrough, Error, HidrawDevice, LedPattern, UsbDevice};
+
+/// Input source trait, implemented by each device backend (evdev, hidraw)
+pub trait InputSource: AsRawFd {
+    /// Fetch the device descriptor for this source
+    fn device(&self) -> UsbDevice;
+
+    /// Read available events from the source
+    ///
+    /// Returns `ErrorKind::WouldBlock` where no events are pending
+    fn read_events(&self) -> Result<Vec<InputEvent>, std::io::Error>;
+
+    /// Physical device identity, shared by all nodes of the same device (see [`device_identity`])
+    fn identity(&self) -> String {
+        device_identity(&self.device(), None, None)
+    }
+
+    /// Whether the source reports motion axes, sources without axes (eg. button only nodes)
+    /// may be bound alongside an axis source for the same device
+    fn has_axes(&self) -> bool {
+        true
+    }
+
+    /// Set device LEDs, returns `ErrorKind::Unsupported` for sources without LED output
+    fn set_leds(&self, _pattern: LedPattern) -> Result<(), std::io::Error> {
+        Err(ErrorKind::Unsupported.into())
+    }
+}
+
+/// Relative axis codes mapped by [`crate::Config::map`]
+const AXIS_CODES: &[EV_REL] = &[EV_REL::REL_X, EV_REL::REL_Y, EV_REL::REL_Z, EV_REL::REL_RX, EV_REL::REL_RY, EV_REL::REL_RZ];
+
+/// Build a physical device identity from the `uniq` (serial) or `phys` (bus location)
+/// strings reported by the kernel, falling back to `vid:pid`
+///
+/// Nodes for different interfaces of a device share a `phys` prefix, differing only
+/// in the trailing `/inputN`, which is removed.
+pub fn device_identity(dev: &UsbDevice, uniq: Option<&str>, phys: Option<&str>) -> String {
+    let id = format!("{:04x}:{:04x}", dev.vid, dev.pid);
+
+    if let Some(u) = uniq.map(str::trim).filter(|u| !u.is_empty()) {
+        return format!("{}/{}", id, u);
+    }
+
+    if let Some(p) = phys.map(str::trim).filter(|p| !p.is_empty()) {
+        let p = match p.rsplit_once('/') {
+            Some((b, i)) if i.starts_with("input") => b,
+            _ => p,
+        };
+        return format!("{}@{}", id, p);
+    }
+
+    id
+}
+
+impl InputSource for Device {
+    fn device(&self) -> UsbDevice {
+        UsbDevice {
+            name: self.name().map(|v| v.to_string()),
+            vid: self.vendor_id(),
+            pid: self.product_id(),
+        }
+    }
+
+    fn read_events(&self) -> Result<Vec<InputEvent>, std::io::Error> {
+        let (_status, evt) = self.next_event(ReadFlag::NORMAL)?;
+        Ok(vec![evt])
+    }
+
+    fn identity(&self) -> String {
+        device_identity(&self.device(), self.uniq(), self.phys())
+    }
+
+    fn has_axes(&self) -> bool {
+        AXIS_CODES.iter().any(|c| self.has_event_code(&EventCode::EV_REL(*c)))
+    }
+
+    /// Write `EV_LED` events to the device, which must be opened for writing
+    fn set_leds(&self, pattern: LedPattern) -> Result<(), std::io::Error> {
+        if !self.has_event_type(&EventType::EV_LED) {
+            return Err(ErrorKind::Unsupported.into());
+        }
+
+        let raw: Vec<_> = pattern.events(TimeVal { tv_sec: 0, tv_usec: 0 }).iter().map(InputEvent::as_raw).collect();
+        let len = std::mem::size_of_val(raw.as_slice());
+
+        let n = unsafe { libc::write(self.as_raw_fd(), raw.as_ptr() as *const libc::c_void, len) };
+        match n {
+            n if n < 0 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Check whether a device path refers to a hidraw node
+pub fn is_hidraw_path(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .map(|n| n.starts_with("hidraw"))
+        .unwrap_or(false)
+}
+
+/// List accessible evdev input devices (`/dev/input/eventN`) with their descriptors
+pub fn scan_event_devices() -> Result<Vec<(String, UsbDevice)>, Error> {
+    let mut devices = vec![];
+
+    for e in read_dir("/dev/input").map_err(Error::DeviceIo)? {
+        let name = e.map_err(Error::DeviceIo)?.file_name().to_string_lossy().to_string();
+        if !name.starts_with("event") {
+            continue;
+        }
+
+        let path = format!("/dev/input/{}", name);
+
+        // Skip devices we can't access
+        if let Ok(d) = File::open(&path).and_then(Device::new_from_file) {
+            devices.push((path, d.device()));
+        }
+    }
+
+    devices.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(devices)
+}
+
+/// Find input device paths matching a `vid:pid`, evdev nodes first, then hidraw
+///
+/// Passthrough devices share the source `vid:pid` and are excluded.
+pub fn find_devices(id: &UsbDevice) -> Vec<String> {
+    let events = scan_event_devices().unwrap_or_default();
+    let hidraw = HidrawDevice::scan(&[id.vid]).unwrap_or_default();
+
+    events.into_iter().chain(hidraw)
+        .filter(|(_p, d)| d.vid == id.vid && d.pid == id.pid && !is_passthrough(d))
+        .map(|(p, _d)| p)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device() -> UsbDevice {
+        UsbDevice { vid: 0x256f, pid: 0xc635, name: None }
+    }
+
+    #[test]
+    fn identity_from_serial() {
+        let d = device();
+        assert_eq!(device_identity(&d, Some("SN1234"), None), "256f:c635/SN1234");
+
+        // Serials take precedence over bus location
+        assert_eq!(device_identity(&d, Some(" SN1234\n"), Some("usb-0000:00:14.0-1/input0")), "256f:c635/SN1234");
+    }
+
+    #[test]
+    fn identity_from_phys() {
+        let d = device();
+
+        // Interface nodes of one device share an identity
+        let a = device_identity(&d, None, Some("usb-0000:00:14.0-1/input0"));
+        let b = device_identity(&d, Some(""), Some("usb-0000:00:14.0-1/input2"));
+        assert_eq!(a, "256f:c635@usb-0000:00:14.0-1");
+        assert_eq!(a, b);
+
+        // Devices on other ports do not
+        assert_ne!(a, device_identity(&d, None, Some("usb-0000:00:14.0-2/input0")));
+
+        // Only a trailing input suffix is removed
+        assert_eq!(device_identity(&d, None, Some("usb-0000:00:14.0-1")), "256f:c635@usb-0000:00:14.0-1");
+        assert_eq!(device_identity(&d, None, Some("bluetooth/aa:bb")), "256f:c635@bluetooth/aa:bb");
+    }
+
+    #[test]
+    fn identity_fallback() {
+        let d = device();
+        assert_eq!(device_identity(&d, None, None), "256f:c635");
+        assert_eq!(device_identity(&d, Some("  "), Some("")), "256f:c635");
+
+        // Names do not contribute
+        let named = UsbDevice { name: Some("SpaceMouse".to_string()), ..device() };
+        assert_eq!(device_identity(&named, None, None), "256f:c635");
+    }
+}
@@ -0,0 +1,11 @@
+//! Test helpers shared by the library and binaries
+
+use std::path::PathBuf;
+
+/// Fresh temporary directory, unique per process, module and test name
+pub fn test_dir(module: &str, name: &str) -> PathBuf {
+    let d = std::env::temp_dir().join(format!("vmouse-{}-{}-{}", module, std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&d);
+    std::fs::create_dir_all(&d).unwrap();
+    d
+}
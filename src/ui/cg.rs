@@ -2,11 +2,12 @@ use std::sync::{Arc, Mutex};
 
 use iced::{
     Color, Element, Length, Point, Rectangle, Size, Vector,
-    widget::canvas::{Cache, Cursor, Geometry, LineCap, Path, Program, Stroke, Text}, Theme,
+    keyboard, mouse,
+    widget::canvas::{event, Cache, Cursor, Event, Frame, Geometry, LineCap, Path, Program, Stroke, Text}, Theme,
 };
 use iced_native::{layout, renderer, Renderer, Widget, widget::Tree};
 
-use vmouse::{Axis, AxisConfig};
+use vmouse::{Axis, AxisConfig, DEADZONE_RANGE};
 
 use crate::message::Message;
 
@@ -21,7 +22,11 @@ pub struct CurveGraph {
 struct CurveGraphInner {
     config: AxisConfig,
     value: f32,
+    output: Option<f32>,
+    /// Static layer (box, axes, curves), cleared on config or selection changes
     cache: Cache,
+    /// Value marker layer, cleared on value changes
+    marker: Cache,
     selected: bool,
 }
 
@@ -34,7 +39,9 @@ impl CurveGraph {
             i: Arc::new(Mutex::new(CurveGraphInner {
                 config,
                 value,
+                output: None,
                 cache: Cache::new(),
+                marker: Cache::new(),
                 selected: false,
             })),
         }
@@ -43,9 +50,10 @@ impl CurveGraph {
     pub fn set_config(&self, c: AxisConfig) {
         let mut i = self.i.lock().unwrap();
 
-        // Clear cache on change
+        // Clear caches on change, the marker position depends on the config
         if i.config != c {
             i.cache.clear();
+            i.marker.clear();
         }
 
         // Update config
@@ -55,11 +63,29 @@ impl CurveGraph {
     pub fn set_value(&self, v: f32) {
         let mut i = self.i.lock().unwrap();
         if i.value != v {
-            i.cache.clear();
+            i.marker.clear();
         }
         i.value = v;
     }
 
+    /// Set the transformed output value reported by the daemon
+    pub fn set_output(&self, o: f32) {
+        let mut i = self.i.lock().unwrap();
+        if i.output != Some(o) {
+            i.marker.clear();
+        }
+        i.output = Some(o);
+    }
+
+    /// Clear the daemon output value, the marker then follows the local config
+    pub fn clear_output(&self) {
+        let mut i = self.i.lock().unwrap();
+        if i.output.is_some() {
+            i.marker.clear();
+        }
+        i.output = None;
+    }
+
     pub fn set_selected(&self, selected: bool) {
         let mut i = self.i.lock().unwrap();
         i.selected = selected;
@@ -69,14 +95,180 @@ impl CurveGraph {
 
 const BOUNDS: f32 = 10.0;
 
+/// Sample the transform for an axis config at `2n + 1` evenly spaced inputs from -1.0 to 1.0,
+/// clamping outputs to the graph bounds
+pub fn sample(config: &AxisConfig, n: isize) -> impl Iterator<Item = (f32, f32)> + '_ {
+    (-n..n + 1).map(move |i| {
+        let x = i as f32 / n as f32;
+        (x, config.transform(x).clamp(-1.0, 1.0))
+    })
+}
+
+/// Build a path through sampled points, scaled to graph coordinates about the origin
+fn curve_path(points: impl Iterator<Item = (f32, f32)>, bx: f32, by: f32) -> Path {
+    Path::new(|b| {
+        for (i, (x, y)) in points.enumerate() {
+            let p = Point { x: x * bx, y: y * -by };
+            match i {
+                0 => b.move_to(p),
+                _ => b.line_to(p),
+            }
+        }
+    })
+}
+
+/// Convert a position within the graph to normalised (-1.0 to 1.0) axis coordinates
+pub fn to_normalised(size: Size, p: Point) -> (f32, f32) {
+    let bx = size.width / 2.0 - BOUNDS;
+    let by = size.height / 2.0 - BOUNDS;
+
+    let x = (p.x - size.width / 2.0) / bx;
+    let y = -(p.y - size.height / 2.0) / by;
+
+    (x, y)
+}
+
+/// Drag sensitivity, parameter change per normalised cursor movement
+const DRAG_GAIN: f32 = 0.5;
+
+/// Fine adjustment multiplier (shift held)
+const DRAG_FINE: f32 = 0.1;
+
+/// Distance from the deadzone edge within which a press drags the deadzone
+const DEADZONE_GRAB: f32 = 0.1;
+
+/// Parameter being edited by dragging
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DragTarget {
+    Curve,
+    Deadzone,
+    DeadzoneNeg,
+}
+
+/// Active drag, tracks the last cursor position and current parameter value
+#[derive(Clone, Copy, Debug)]
+struct Drag {
+    target: DragTarget,
+    last: (f32, f32),
+    value: f32,
+    /// Parameter (min, max)
+    range: (f32, f32),
+}
+
+/// Interaction state for a [`CurveGraph`]
+#[derive(Debug, Default)]
+pub struct CurveGraphState {
+    /// Hovered x position (normalised)
+    hover: Option<f32>,
+    /// Active drag
+    drag: Option<Drag>,
+    /// Fine adjustment (shift) enabled
+    fine: bool,
+}
+
 impl Program<Message> for Arc<CurveGraph> {
-    type State = ();
+    type State = CurveGraphState;
 
-    fn draw(&self, _state: &Self::State, _theme: &Theme, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let p = cursor.position_in(&bounds);
+
+        match (event, p) {
+            (Event::Keyboard(keyboard::Event::ModifiersChanged(m)), _) => {
+                state.fine = m.shift();
+                (event::Status::Ignored, None)
+            }
+            // Select axis and start dragging on click
+            (Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)), Some(p)) => {
+                let (x, y) = to_normalised(bounds.size(), p);
+                let config = self.i.lock().unwrap().config;
+
+                // Presses near the deadzone edge drag the deadzone (negative side if asymmetric), otherwise the curve
+                let deadzone = config.deadzone_for(x);
+                let dz_range = (*DEADZONE_RANGE.start(), *DEADZONE_RANGE.end());
+                let curve_range = config.curve.range();
+                state.drag = match ((x.abs() - deadzone).abs() < DEADZONE_GRAB, config.curve.param()) {
+                    (true, _) if x < 0.0 && config.deadzone_neg.is_some() => {
+                        Some(Drag { target: DragTarget::DeadzoneNeg, last: (x, y), value: deadzone, range: dz_range })
+                    }
+                    (true, _) => Some(Drag { target: DragTarget::Deadzone, last: (x, y), value: deadzone, range: dz_range }),
+                    // Curve tables have no parameter to drag
+                    (false, Some(c)) => Some(Drag {
+                        target: DragTarget::Curve,
+                        last: (x, y),
+                        value: c,
+                        range: (*curve_range.start(), *curve_range.end()),
+                    }),
+                    (false, None) => None,
+                };
+
+                (event::Status::Captured, Some(Message::SelectAxis(self.axis)))
+            }
+            (Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)), _) if state.drag.is_some() => {
+                state.drag = None;
+                (event::Status::Captured, None)
+            }
+            // Update dragged parameter, cursor may leave the bounds while dragging
+            (Event::Mouse(mouse::Event::CursorMoved { position }), _) if state.drag.is_some() => {
+                let (x, y) = to_normalised(bounds.size(), Point::new(position.x - bounds.x, position.y - bounds.y));
+                let gain = if state.fine { DRAG_GAIN * DRAG_FINE } else { DRAG_GAIN };
+
+                let d = state.drag.as_mut().unwrap();
+                let (dx, dy) = (x - d.last.0, y - d.last.1);
+                d.last = (x, y);
+
+                state.hover = p.map(|_| x.clamp(-1.0, 1.0));
+
+                // Deadzone follows horizontal movement away from the center, curve follows vertical movement
+                let m = match d.target {
+                    DragTarget::Deadzone => {
+                        d.value = (d.value + dx * x.signum() * gain * 2.0).clamp(d.range.0, d.range.1);
+                        Message::DeadzoneChanged(self.axis, d.value)
+                    }
+                    DragTarget::DeadzoneNeg => {
+                        d.value = (d.value - dx * gain * 2.0).clamp(d.range.0, d.range.1);
+                        Message::DeadzoneNegChanged(self.axis, d.value)
+                    }
+                    DragTarget::Curve => {
+                        d.value = (d.value - dy * gain * (d.range.1 - d.range.0)).clamp(d.range.0, d.range.1);
+                        Message::CurveChanged(self.axis, d.value)
+                    }
+                };
+
+                // Redraw live while dragging
+                let i = self.i.lock().unwrap();
+                i.cache.clear();
+                i.marker.clear();
+
+                (event::Status::Captured, Some(m))
+            }
+            // Track hover position for value display
+            (Event::Mouse(mouse::Event::CursorMoved { .. }), Some(p)) => {
+                let (x, _y) = to_normalised(bounds.size(), p);
+                state.hover = Some(x.clamp(-1.0, 1.0));
+                (event::Status::Ignored, None)
+            }
+            (Event::Mouse(mouse::Event::CursorMoved { .. }), None) => {
+                state.hover = None;
+                (event::Status::Ignored, None)
+            }
+            _ => (event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(&self, state: &Self::State, _theme: &Theme, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
         let inner = self.i.lock().unwrap();
 
+        // Curve shape is drawn unscaled, the scaled output is overlaid and clipped to the box
         let mut config = inner.config;
         config.scale = 1.0;
+        config.scale_neg = None;
+        let scaled = inner.config;
 
         let g = inner.cache.draw(bounds.size(), |f| {
             let center = f.center();
@@ -112,6 +304,19 @@ impl Program<Message> for Arc<CurveGraph> {
 
             f.fill_text(t);
 
+            // Deadzone band, may be asymmetric
+            let (dz_neg, dz_pos) = (config.deadzone_for(-1.0), config.deadzone_for(1.0));
+            if dz_neg > 0.0 || dz_pos > 0.0 {
+                let p = Path::rectangle(
+                    Point::new(-dz_neg * bx, -by),
+                    Size::new((dz_neg + dz_pos) * bx, 2.0 * by),
+                );
+                f.with_save(|f| {
+                    f.translate(Vector::new(center.x, center.y));
+                    f.fill(&p, Color::from_rgb8(0xF0, 0xF0, 0xF0));
+                });
+            }
+
             // Axes
 
             let thin_stroke = thin_stroke.with_color(Color::from_rgb8(0xDC, 0xDC, 0xDC));
@@ -127,36 +332,50 @@ impl Program<Message> for Arc<CurveGraph> {
                 f.stroke(&p, thin_stroke.clone());
             });
 
-            let thin_stroke = thin_stroke.with_color(Color::BLACK);
-
-            let p = Path::new(|b| {
-                let mut last = Point { x: -bx, y: -by };
-
-                for i in -N..N + 1 {
-                    let x = i as f32 / N as f32;
-                    let y = config.transform(x);
-
-                    let p = Point {
-                        x: x * bx,
-                        y: y * -by,
-                    };
-
-                    b.quadratic_curve_to(last, p);
+            // Y axis label, full scale output
+            f.fill_text(Text {
+                content: match scaled.scale_neg {
+                    Some(n) => format!("{:+.1} / {:+.1}", scaled.scale, n),
+                    None => format!("{:+.1}", scaled.scale),
+                },
+                position: Point::new(center.x + 5.0, BOUNDS),
+                size: 14.0,
+                color: Color::from_rgb8(0x80, 0x80, 0x80),
+                ..Default::default()
+            });
 
-                    //println!("x: {:?} y: {:?}", x, y);
-                    //println!("prev: {:?} next: {:?}", last, next);
+            // Scaled output curve, clipped to the box
+            if scaled.scale != 1.0 || scaled.scale_neg.is_some() {
+                let p = curve_path(sample(&scaled, N), bx, by);
+                f.with_save(|f| {
+                    f.translate(Vector::new(center.x, center.y));
+                    f.stroke(&p, thin_stroke.clone().with_color(Color::from_rgb8(0xA0, 0xC8, 0xF0)));
+                });
+            }
 
-                    last = p;
-                }
-            });
+            let thin_stroke = thin_stroke.with_color(Color::BLACK);
 
+            // Curve shape, including deadzone
+            let p = curve_path(sample(&config, N), bx, by);
             f.with_save(|f| {
                 f.translate(Vector::new(center.x, center.y));
                 f.stroke(&p, thin_stroke.clone());
             });
+        });
+
+        // Value marker, redrawn separately so value updates do not rebuild the curves
+        let m = inner.marker.draw(bounds.size(), |f| {
+            let center = f.center();
 
-            // Center marker
-            let y = config.transform(inner.value);
+            let bx = bounds.size().width / 2.0 - BOUNDS;
+            let by = bounds.size().height / 2.0 - BOUNDS;
+
+            // Center marker, using the daemon output value (normalised by scale) where available
+            let scale = inner.config.scale_for(inner.value);
+            let y = match inner.output {
+                Some(o) if scale != 0.0 => o / scale,
+                _ => config.transform(inner.value),
+            };
             let p = Point {
                 x: inner.value * bx,
                 y: y * -by,
@@ -169,8 +388,23 @@ impl Program<Message> for Arc<CurveGraph> {
             });
         });
 
+        // Hover value overlay, drawn outside the caches
+        let mut geometry = vec![g, m];
+        if let Some(x) = state.hover {
+            let mut f = Frame::new(bounds.size());
+
+            f.fill_text(Text {
+                content: format!("{:+.2} -> {:+.3}", x, inner.config.transform(x)),
+                position: Point::new(10.0, bounds.height - 25.0),
+                size: 16.0,
+                ..Default::default()
+            });
+
+            geometry.push(f.into_geometry());
+        }
+
         //  Return geometry
-        vec![g]
+        geometry
     }
 }
 
@@ -217,3 +451,72 @@ impl<'a, M> Into<Element<'a, M>> for CurveGraph {
         Element::new(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use vmouse::Config;
+
+    use super::*;
+
+    /// Graph with a 100 x 50 plot area either side of the centre
+    const SIZE: Size = Size { width: 220.0, height: 120.0 };
+
+    fn config(scale: f32) -> AxisConfig {
+        AxisConfig { scale, scale_neg: None, ..Config::default().default[Axis::X] }
+    }
+
+    #[test]
+    fn normalised_centre() {
+        assert_eq!(to_normalised(SIZE, Point::new(110.0, 60.0)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn normalised_corners() {
+        // Plot corners are inset by the graph bounds, with y increasing upwards
+        assert_eq!(to_normalised(SIZE, Point::new(10.0, 10.0)), (-1.0, 1.0));
+        assert_eq!(to_normalised(SIZE, Point::new(210.0, 10.0)), (1.0, 1.0));
+        assert_eq!(to_normalised(SIZE, Point::new(10.0, 110.0)), (-1.0, -1.0));
+        assert_eq!(to_normalised(SIZE, Point::new(210.0, 110.0)), (1.0, -1.0));
+
+        // Positions outside the plot area extend past the axis range
+        assert_eq!(to_normalised(SIZE, Point::new(0.0, 120.0)), (-1.1, -1.2));
+    }
+
+    #[test]
+    fn normalised_aspect() {
+        // Each axis is scaled to its own extent
+        assert_eq!(to_normalised(SIZE, Point::new(160.0, 60.0)), (0.5, 0.0));
+        assert_eq!(to_normalised(SIZE, Point::new(110.0, 35.0)), (0.0, 0.5));
+
+        // So equal cursor offsets differ in normalised units on non-square graphs
+        let (x, _) = to_normalised(SIZE, Point::new(135.0, 60.0));
+        let (_, y) = to_normalised(SIZE, Point::new(110.0, 35.0));
+        assert_eq!((x, y), (0.25, 0.5));
+    }
+
+    #[test]
+    fn sample_points() {
+        let c = config(1.0);
+        let points: Vec<_> = sample(&c, 4).collect();
+
+        // 2n + 1 evenly spaced inputs, including both ends and zero
+        let xs: Vec<_> = points.iter().map(|(x, _y)| *x).collect();
+        assert_eq!(xs, vec![-1.0, -0.75, -0.5, -0.25, 0.0, 0.25, 0.5, 0.75, 1.0]);
+
+        for (x, y) in points {
+            assert_eq!(y, c.transform(x), "x: {}", x);
+        }
+    }
+
+    #[test]
+    fn sample_clamped() {
+        // Outputs beyond the graph are held at the bounds
+        let c = config(100.0);
+        let points: Vec<_> = sample(&c, 50).collect();
+
+        assert_eq!(points.first(), Some(&(-1.0, -1.0)));
+        assert_eq!(points.last(), Some(&(1.0, 1.0)));
+        assert!(points.iter().all(|(_x, y)| (-1.0..=1.0).contains(y)));
+        assert!(points.iter().any(|(x, y)| *x < 1.0 && *y == 1.0));
+    }
+}
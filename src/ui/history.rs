@@ -0,0 +1,137 @@
+//! Per-axis raw value history, drawn as a scrolling sparkline
+
+use std::collections::VecDeque;
+
+use iced::{
+    mouse, Color, Point, Rectangle, Size, Theme,
+    widget::canvas::{event, Cursor, Event, Frame, Geometry, LineCap, Path, Program, Stroke, Text},
+};
+
+use vmouse::AxisConfig;
+
+use crate::message::Message;
+
+/// Number of samples retained per axis, ~5 s of coalesced state updates
+const HISTORY_LEN: usize = 300;
+
+/// Ring buffer of raw axis values
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    values: VecDeque<f32>,
+}
+
+impl History {
+    /// Record a raw value
+    pub fn push(&mut self, v: f32) {
+        if self.values.len() >= HISTORY_LEN {
+            self.values.pop_front();
+        }
+        self.values.push_back(v);
+    }
+
+    /// Clear recorded values
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// Sparkline of an axis [`History`] with the current deadzone band
+#[derive(Debug)]
+pub struct Sparkline<'a> {
+    history: &'a History,
+    /// Deadzone (negative, positive)
+    deadzone: (f32, f32),
+}
+
+impl<'a> Sparkline<'a> {
+    pub fn new(history: &'a History, config: &AxisConfig) -> Self {
+        Self {
+            history,
+            deadzone: (config.deadzone_for(-1.0), config.deadzone_for(1.0)),
+        }
+    }
+}
+
+/// Interaction state for a [`Sparkline`]
+#[derive(Debug, Default)]
+pub struct SparklineState {
+    /// Snapshot of values displayed while hovered (paused)
+    frozen: Option<Vec<f32>>,
+}
+
+impl<'a> Program<Message> for Sparkline<'a> {
+    type State = SparklineState;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (event::Status, Option<Message>) {
+        // Pause while hovered so recent noise can be inspected
+        if let Event::Mouse(mouse::Event::CursorMoved { .. }) = event {
+            match (cursor.is_over(&bounds), state.frozen.is_some()) {
+                (true, false) => state.frozen = Some(self.history.values.iter().copied().collect()),
+                (false, true) => state.frozen = None,
+                _ => (),
+            }
+        }
+
+        (event::Status::Ignored, None)
+    }
+
+    fn draw(&self, state: &Self::State, _theme: &Theme, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        let mut f = Frame::new(bounds.size());
+
+        let w = bounds.width;
+        let h = bounds.height / 2.0 - 2.0;
+        let y = |v: f32| bounds.height / 2.0 - v.clamp(-1.0, 1.0) * h;
+
+        // Deadzone band
+        let (dz_neg, dz_pos) = self.deadzone;
+        if dz_neg > 0.0 || dz_pos > 0.0 {
+            let p = Path::rectangle(Point::new(0.0, y(dz_pos)), Size::new(w, y(-dz_neg) - y(dz_pos)));
+            f.fill(&p, Color::from_rgb8(0xF0, 0xF0, 0xF0));
+        }
+
+        // Zero line
+        let stroke = Stroke {
+            width: 1.0,
+            line_cap: LineCap::Round,
+            ..Stroke::default()
+        };
+        f.stroke(&Path::line(Point::new(0.0, y(0.0)), Point::new(w, y(0.0))), stroke.clone().with_color(Color::from_rgb8(0xDC, 0xDC, 0xDC)));
+
+        // Values, newest at the right edge
+        let values: Vec<f32> = match &state.frozen {
+            Some(v) => v.clone(),
+            None => self.history.values.iter().copied().collect(),
+        };
+        let dx = w / (HISTORY_LEN - 1) as f32;
+        let offset = HISTORY_LEN - values.len();
+
+        let p = Path::new(|b| {
+            for (i, v) in values.iter().enumerate() {
+                let p = Point::new((offset + i) as f32 * dx, y(*v));
+                match i {
+                    0 => b.move_to(p),
+                    _ => b.line_to(p),
+                }
+            }
+        });
+        f.stroke(&p, stroke.with_color(Color::from_rgb8(0x12, 0x93, 0xD8)));
+
+        if state.frozen.is_some() {
+            f.fill_text(Text {
+                content: "paused".to_string(),
+                position: Point::new(4.0, 2.0),
+                size: 12.0,
+                color: Color::from_rgb8(0x80, 0x80, 0x80),
+                ..Default::default()
+            });
+        }
+
+        vec![f.into_geometry()]
+    }
+}
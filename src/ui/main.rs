@@ -1,6 +1,8 @@
 use std::{
+    collections::HashMap,
     hash::Hash,
-    sync::{Arc, Mutex},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use futures::{stream::BoxStream, StreamExt};
@@ -8,6 +10,7 @@ use futures::{stream::BoxStream, StreamExt};
 use iced::{
     alignment::{self, Horizontal, Alignment},
     Application,
+    Color,
     widget::Canvas,
     Length,
     Command,
@@ -21,10 +24,10 @@ use iced_native::{
 };
 
 use structopt::StructOpt;
-use log::{debug, error, info, LevelFilter};
+use log::{debug, error, info, warn, LevelFilter};
 use simplelog::SimpleLogger;
 
-use vmouse::{Axis, AxisCollection, Client, Config, AXIS, AXIS_LIN, AXIS_ROT, MAPPINGS};
+use vmouse::{Axis, AxisCollection, AxisConfig, ClientEvent, Config, CurveKind, CurveType, Profile, ReconnectingClient, UsbDevice, CURVE_TYPES, DEADZONE_RANGE, ScaleValue, AXIS, AXIS_LIN, AXIS_ROT, MAPPINGS};
 
 mod cg;
 use cg::CurveGraph;
@@ -32,12 +35,53 @@ use cg::CurveGraph;
 mod message;
 use message::Message;
 
+mod state;
+use state::UiState;
+
+mod throttle;
+
+mod rate;
+use rate::RateCounter;
+
+mod history;
+use history::{History, Sparkline};
+
+/// Time status messages are displayed
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time after which axis values are displayed as stale
+const STALE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Suffix for connected devices without a device-specific config
+const UNCONFIGURED: &str = " (unconfigured)";
+
+/// Axis snap slider range, 1.0 disables snapping
+const AXIS_SNAP_RANGE: std::ops::RangeInclusive<f32> = 1.0..=10.0;
+
+/// Hint shown for scale values outside [`vmouse::SCALE_RANGE`]
+const SCALE_HINT: &str = "(valid range -10.0 - 10.0)";
+
 #[derive(Clone, PartialEq, Debug, StructOpt)]
 pub struct Options {
+    /// Daemon socket, defaults to the last used socket or the user / system socket
+    #[structopt(long)]
+    pub socket: Option<String>,
+
+    /// Initial device to select (`vid:pid`)
+    #[structopt(long)]
+    pub device: Option<String>,
+
     #[structopt(long, default_value = "debug")]
     pub log_level: LevelFilter,
 }
 
+/// Startup flags for the GUI application
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Flags {
+    socket: String,
+    device: Option<String>,
+}
+
 #[async_std::main]
 async fn main() -> anyhow::Result<()> {
     // Parse command line arguments
@@ -51,37 +95,106 @@ async fn main() -> anyhow::Result<()> {
 
     let _ = SimpleLogger::init(opts.log_level, log_config);
 
-    App::run(Settings::default())?;
+    // Resolve socket from flags or saved state
+    let state = UiState::load();
+    let flags = Flags {
+        socket: state::resolve_socket(opts.socket, state.socket, vmouse::default_socket_path),
+        device: opts.device,
+    };
+
+    App::run(Settings::with_flags(flags))?;
 
     Ok(())
 }
 
 struct App {
     values: AxisCollection<f32>,
+    /// Transformed output values reported by the daemon
+    outputs: AxisCollection<f32>,
+    /// Per-axis update rates
+    rates: AxisCollection<RateCounter>,
+    /// Per-axis raw value history
+    history: AxisCollection<History>,
     scale_text: String,
+    scale_invalid: bool,
+    /// Negative scale text for asymmetric axes
+    scale_neg_text: String,
+    scale_neg_invalid: bool,
+    curve_text: String,
+    curve_invalid: bool,
+    deadzone_text: String,
+    deadzone_invalid: bool,
 
     cgs: AxisCollection<Arc<CurveGraph>>,
 
     config: Config,
-
+    /// Last config applied to or received from the daemon
+    committed: Config,
+    /// Config sent to the daemon, committed once the apply request succeeds
+    applying: Option<Config>,
+    /// Action awaiting a second click to confirm discarding changes
+    confirm: Option<Confirm>,
+
+    /// Status bar message and time shown
+    status: Option<(Status, Instant)>,
+    /// Description of the last request awaiting an Ok / Error response
+    pending: Option<&'static str>,
+
+    /// Selected device, `default` or `vid:pid` (aliases are resolved)
     device: String,
+    /// Alias for the selected device
+    alias_text: String,
+    /// Inline alias error, cleared on edit
+    alias_error: Option<String>,
+    /// Device requested on the command line, selected once config is received
+    initial_device: Option<String>,
+    devices: Vec<UsbDevice>,
     axis: Axis,
 
+    /// Target for copying the selected axis config
+    copy_target: Option<CopyTarget>,
+    /// Source for duplicating an entire device config
+    copy_source: Option<String>,
+
+    /// Profile panel expanded
+    show_profiles: bool,
+    /// Selected profile for the current device
+    profile: Option<String>,
+    /// Profile name for create / rename
+    profile_name: String,
+
     socket: String,
 
-    attached: bool,
+    /// Daemon config file path, written by `write`
+    config_path: Option<String>,
+    /// Path for save as
+    save_as_path: String,
+
+    /// Simulated axis values enabled while disconnected
+    simulate: bool,
 
-    client: Option<Client>,
+    attached: bool,
+    /// Per-device output enabled flags by `vid:pid`
+    device_enabled: HashMap<String, bool>,
+    /// Requested enabled state while an attach / detach request is in flight
+    toggle: Option<bool>,
+
+    client: Option<ReconnectingClient>,
+    connected: bool,
+    /// Daemon package and protocol versions, from the status response
+    daemon: Option<(String, u32)>,
+    /// Last connection failure, cleared on connection
+    connect_error: Option<String>,
 }
 
 impl Application for App {
     type Executor = iced::executor::Default;
     type Message = Message;
     type Theme = Theme;
-    type Flags = ();
+    type Flags = Flags;
 
-    fn new(_flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
-        let socket = "/var/run/vmouse.sock".to_string();
+    fn new(flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
+        let socket = flags.socket;
 
         let config = Config::default();
 
@@ -89,91 +202,245 @@ impl Application for App {
         (
             Self {
                 values: AxisCollection::with_axis(|_| Default::default()),
+                outputs: AxisCollection::with_axis(|_| Default::default()),
+                rates: AxisCollection::with_axis(|_| Default::default()),
+                history: AxisCollection::with_axis(|_| Default::default()),
 
                 scale_text: Default::default(),
+                scale_invalid: false,
+                scale_neg_text: Default::default(),
+                scale_neg_invalid: false,
+                curve_text: Default::default(),
+                curve_invalid: false,
+                deadzone_text: Default::default(),
+                deadzone_invalid: false,
 
                 config: Config::default(),
+                committed: Config::default(),
+                applying: None,
+                confirm: None,
+                status: None,
+                pending: None,
 
                 cgs: AxisCollection::with_axis(|a| {
                     Arc::new(CurveGraph::new(a, config.default[a], 0.0))
                 }),
 
                 device: "default".to_string(),
+                alias_text: String::new(),
+                alias_error: None,
+                initial_device: flags.device,
+                devices: vec![],
                 axis: Axis::X,
 
+                copy_target: None,
+                copy_source: None,
+
+                show_profiles: false,
+                profile: None,
+                profile_name: String::new(),
+
                 socket: socket.clone(),
 
-                attached: true,
+                config_path: None,
+                save_as_path: String::new(),
+
+                simulate: false,
+
+                attached: false,
+                device_enabled: HashMap::new(),
+                toggle: None,
 
-                client: None,
+                client: Some(ReconnectingClient::new(socket, true)),
+                connected: false,
+                daemon: None,
+                connect_error: None,
             },
-            iced::Command::batch(vec![Self::connect(socket)]),
+            iced::Command::none(),
         )
     }
 
     fn title(&self) -> String {
-        "VMouse GUI".to_string()
+        match self.dirty() {
+            true => "VMouse GUI (unsaved changes)".to_string(),
+            false => "VMouse GUI".to_string(),
+        }
     }
 
     // Handle events
     fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
+        // Require confirmation for destructive actions while there are unsaved changes
+        let confirm = match &message {
+            Message::RevertConfig => Some(Confirm::Revert),
+            Message::Disconnect => Some(Confirm::Disconnect),
+            _ => None,
+        };
+        match confirm {
+            Some(c) if self.dirty() && self.confirm != Some(c) => {
+                self.confirm = Some(c);
+                return Command::none();
+            }
+            Some(_) => self.confirm = None,
+            None if !matches!(message, Message::Tick | Message::Command(_)) => self.confirm = None,
+            None => (),
+        }
+
         match (message, self.client.clone()) {
             (Message::Connect, None) => {
-                return Self::connect(self.socket.clone());
+                debug!("Connecting to socket: {}", self.socket);
+                self.client = Some(ReconnectingClient::new(self.socket.clone(), true));
             }
-            (Message::Connected(client), _) => {
-                debug!("Received client, unpacking");
-                let c = client.lock().unwrap().take();
-                self.client = c.clone();
+            (Message::Retry, c) => {
+                // Replace the client to retry immediately, skipping the reconnect backoff
+                debug!("Retrying connection to socket: {}", self.socket);
+                self.client = Some(ReconnectingClient::new(self.socket.clone(), true));
+                self.connected = false;
+                self.toggle = None;
+                self.applying = None;
 
                 if let Some(c) = c {
-                    return Self::command(c, vmouse::Command::GetConfig);
+                    return Command::perform(async move { c.close().await }, |_| Message::Tick);
+                }
+            }
+            (Message::Connected, Some(c)) => {
+                info!("Connected to socket: {}", c.path());
+                self.connected = true;
+                self.connect_error = None;
+
+                // Remember the last used socket
+                let s = UiState { socket: Some(c.path().to_string()) };
+                if let Err(e) = s.save() {
+                    warn!("Failed to save GUI state: {:?}", e);
                 }
+
+                return Command::batch(vec![
+                    Self::command(c.clone(), vmouse::Command::GetConfig),
+                    Self::command(c.clone(), vmouse::Command::ListDevices),
+                    Self::command(c.clone(), vmouse::Command::GetConfigPath),
+                    Self::command(c, vmouse::Command::GetStatus),
+                ]);
             }
-            (Message::Disconnect, Some(_)) => {
+            (Message::Reconnecting(d, e), Some(c)) => {
+                debug!("Reconnecting to socket: {} in {:?} ({})", c.path(), d, e);
+                self.connected = false;
+                self.daemon = None;
+                self.toggle = None;
+                self.applying = None;
+                self.set_status(Status::Error(format!("Connection to '{}' failed, retrying in {:.1}s", c.path(), d.as_secs_f32())));
+                self.connect_error = Some(e);
+            }
+            (Message::Disconnect, Some(c)) => {
+                // Close deliberately so the client does not reconnect
                 let _ = self.client.take();
+                self.connected = false;
+                self.daemon = None;
+                self.connect_error = None;
+                self.toggle = None;
+                self.applying = None;
+
+                return Command::perform(async move { c.close().await }, |_| Message::Tick);
             }
             (Message::ApplyConfig, Some(c)) => {
+                // Check for errors before sending, the daemon rejects invalid configs
+                if let Some(e) = self.config.errors().first() {
+                    self.set_status(Status::Error(format!("Invalid config: {}", e)));
+                    return Command::none();
+                }
+
+                self.applying = Some(self.config.clone());
+                self.pending = Some("Apply config");
                 return Self::command(c, vmouse::Command::SetConfig(self.config.clone()));
             }
             (Message::RevertConfig, Some(c)) => {
                 return Self::command(c, vmouse::Command::GetConfig);
             }
             (Message::WriteConfig, Some(c)) => {
+                self.pending = Some("Write config");
                 return Self::command(c, vmouse::Command::WriteConfig);
             }
+            (Message::SaveAsPathChanged(p), _) => {
+                self.save_as_path = p;
+            }
+            (Message::SaveConfigAs, Some(c)) => {
+                self.pending = Some("Save config");
+                let path = self.save_as_path.clone();
+                return Self::command(c, vmouse::Command::SaveConfigAs { path, set_active: false });
+            }
             (Message::Attach, Some(c)) => {
-                self.attached = true;
-                return Self::command(c, vmouse::Command::Enable { enabled: true });
+                self.toggle = Some(true);
+                self.pending = Some("Attach");
+                return Self::command(c, vmouse::Command::Enable { enabled: true, device: self.enable_target() });
             }
             (Message::Detach, Some(c)) => {
-                self.attached = false;
-                return Self::command(c, vmouse::Command::Enable { enabled: false });
+                self.toggle = Some(false);
+                self.pending = Some("Detach");
+                return Self::command(c, vmouse::Command::Enable { enabled: false, device: self.enable_target() });
             }
             (Message::ScaleChanged(_a, s), _) => {
-                // Update scale string
+                // Update scale string, flagging invalid values
+                self.scale_invalid = s.parse::<ScaleValue>().is_err();
                 self.scale_text = s;
             }
             (Message::ApplyScale, _) => {
                 // Update scale if value is valid
-                let v = match self.scale_text.parse::<f32>() {
-                    Ok(v) => v,
-                    Err(_e) => {
-                        error!("Non-numeric scale value: {}", self.scale_text);
-                        return iced::Command::none();
+                match self.scale_text.parse::<ScaleValue>() {
+                    Ok(v) => {
+                        info!("Applying scale {:0.4} for axis: {}", v.value(), self.axis);
+
+                        if let Some(config) = self.config.get_mut(&self.device) {
+                            config[self.axis].scale = v.value();
+                            self.cgs[self.axis].set_config(config[self.axis]);
+                        }
                     }
-                };
-
-                if v > -10.0 && v < 10.0 {
-                    info!("Applying scale {:0.4} for axis: {}", v, self.axis);
-
-                    if let Some(config) = self.config.get_mut(&self.device) {
-                        config[self.axis].scale = v;
-                        self.cgs[self.axis].set_config(config[self.axis]);
+                    Err(e) => {
+                        error!("Invalid scale value: {}", e);
+                        self.scale_invalid = true;
+                        self.set_status(Status::Error(format!("Invalid {}", e)));
                     }
-                    
-                } else {
-                    error!("Scale value: {:0.4} exceeds maximum range", v);
+                }
+            }
+            (Message::ToggleLinked(a), _) => {
+                if let Some(config) = self.config.get_mut(&self.device) {
+                    // Split from the current symmetric values, or re-link discarding overrides
+                    let c = &mut config[a];
+                    match c.is_asymmetric() {
+                        true => {
+                            c.scale_neg = None;
+                            c.deadzone_neg = None;
+                        }
+                        false => {
+                            c.scale_neg = Some(c.scale);
+                            c.deadzone_neg = Some(c.deadzone);
+                        }
+                    }
+                    self.cgs[a].set_config(config[a]);
+                }
+                self.refresh_text();
+            }
+            (Message::ScaleNegChanged(_a, s), _) => {
+                self.scale_neg_invalid = s.parse::<ScaleValue>().is_err();
+                self.scale_neg_text = s;
+            }
+            (Message::ApplyScaleNeg, _) => {
+                match self.scale_neg_text.parse::<ScaleValue>() {
+                    Ok(v) => {
+                        info!("Applying negative scale {:0.4} for axis: {}", v.value(), self.axis);
+
+                        if let Some(config) = self.config.get_mut(&self.device) {
+                            config[self.axis].scale_neg = Some(v.value());
+                            self.cgs[self.axis].set_config(config[self.axis]);
+                        }
+                    }
+                    Err(e) => {
+                        self.scale_neg_invalid = true;
+                        self.set_status(Status::Error(format!("Invalid {}", e)));
+                    }
+                }
+            }
+            (Message::DeadzoneNegChanged(a, d), _) => {
+                if let Some(config) = self.config.get_mut(&self.device) {
+                    config[a].deadzone_neg = Some(d);
+                    self.cgs[a].set_config(config[a]);
                 }
             }
             (Message::MappingChanged(m), _) => {
@@ -181,29 +448,149 @@ impl Application for App {
                     config[self.axis].map = m;
                 }
             }
+            (Message::CurveTypeChanged(a, t), _) => {
+                if let Some(config) = self.config.get_mut(&self.device) {
+                    if config[a].curve.curve_type() != t {
+                        config[a].curve = t.default_kind();
+                        self.cgs[a].set_config(config[a]);
+                    }
+                }
+                self.refresh_text();
+            }
             (Message::CurveChanged(a, c), _) => {
                 if let Some(config) = self.config.get_mut(&self.device) {
-                    config[a].curve = c;
+                    config[a].curve.set_param(c);
                     self.cgs[a].set_config(config[a]);
                 }
+                self.curve_text = format!("{:0.2}", c);
+                self.curve_invalid = false;
+            }
+            (Message::CurveTextChanged(a, t), _) => {
+                // Apply valid values, otherwise keep the last good value
+                if let Some(config) = self.config.get_mut(&self.device) {
+                    match t.parse::<f32>() {
+                        Ok(c) if config[a].curve.range().contains(&c) => {
+                            config[a].curve.set_param(c);
+                            self.cgs[a].set_config(config[a]);
+                            self.curve_invalid = false;
+                        }
+                        _ => self.curve_invalid = true,
+                    }
+                }
+                self.curve_text = t;
             }
             (Message::DeadzoneChanged(a, d), _) => {
                 if let Some(config) = self.config.get_mut(&self.device) {
                     config[a].deadzone = d;
                     self.cgs[a].set_config(config[a]);
                 }
+                self.deadzone_text = format!("{:0.2}", d);
+                self.deadzone_invalid = false;
+            }
+            (Message::AxisSnapChanged(v), _) => {
+                // Ratios of 1.0 or less disable snapping for the device
+                let device = self.config.resolve(&self.device);
+                let keys: Vec<_> = self.config.axis_snap.keys()
+                    .filter(|k| self.config.resolve(k) == device)
+                    .cloned()
+                    .collect();
+                for k in keys {
+                    self.config.axis_snap.remove(&k);
+                }
+                if v > 1.0 {
+                    self.config.axis_snap.insert(self.device.clone(), v);
+                }
+            }
+            (Message::DeadzoneTextChanged(a, t), _) => {
+                // Apply valid values, otherwise keep the last good value
+                match t.parse::<f32>() {
+                    Ok(d) if DEADZONE_RANGE.contains(&d) => {
+                        if let Some(config) = self.config.get_mut(&self.device) {
+                            config[a].deadzone = d;
+                            self.cgs[a].set_config(config[a]);
+                        }
+                        self.deadzone_invalid = false;
+                    }
+                    _ => self.deadzone_invalid = true,
+                }
+                self.deadzone_text = t;
             }
             (Message::ValueChanged(a, v), _) => {
                 self.values[a] = v;
                 self.cgs[a].set_value(v);
+
+                // Simulated values are transformed locally, never sent to the daemon
+                if self.simulating() {
+                    let config = self.config.get(&self.device).unwrap_or(&self.config.default);
+                    self.outputs[a] = config[a].transform(v);
+                    self.rates[a].push(Instant::now());
+                    self.history[a].push(v);
+                    self.cgs[a].clear_output();
+                }
+            }
+            (Message::ToggleSimulate, _) => {
+                self.simulate = !self.simulate;
+
+                // Reset simulated values
+                self.values = AxisCollection::with_axis(|_| Default::default());
+                self.outputs = AxisCollection::with_axis(|_| Default::default());
+                for a in AXIS {
+                    self.rates[*a].clear();
+                    self.history[*a].clear();
+                    self.cgs[*a].set_value(0.0);
+                    self.cgs[*a].clear_output();
+                }
             }
             (Message::SelectDevice(d), _) => {
+                // Create device config from defaults when selecting an unconfigured device
+                // Options are labelled by alias where one exists
+                let d = match d.strip_suffix(UNCONFIGURED) {
+                    Some(n) => {
+                        let n = self.config.resolve(n);
+                        if let Some(dev) = self.devices.iter().find(|v| v.to_string() == n) {
+                            info!("Creating config for device: {}", n);
+                            self.config.devices.insert(UsbDevice { name: None, ..dev.clone() }, self.config.default);
+                        }
+                        n
+                    }
+                    None => self.config.resolve(&d),
+                };
+
                 self.device = d;
+                self.profile = None;
+                self.refresh_alias();
 
-                let config = self.config.get(&self.device).unwrap_or(&self.config.default);
-                // Update curve graphs
+                // Clear values until state for the new device is received
+                self.values = AxisCollection::with_axis(|_| Default::default());
+                self.outputs = AxisCollection::with_axis(|_| Default::default());
                 for a in AXIS {
-                    self.cgs[*a].set_config(config[*a]);
+                    self.rates[*a].clear();
+                    self.history[*a].clear();
+                    self.cgs[*a].set_value(0.0);
+                }
+
+                // Update curve graphs and scale text
+                self.refresh_config();
+            }
+            (Message::AliasTextChanged(s), _) => {
+                self.alias_text = s;
+                self.alias_error = None;
+            }
+            (Message::SetAlias, _) => {
+                if let Err(e) = self.set_alias() {
+                    self.alias_error = Some(e);
+                }
+            }
+            (Message::RemoveDeviceConfig, _) => {
+                if self.device != "default" {
+                    info!("Removing config for device: {}", self.device);
+
+                    let device = self.device.clone();
+                    self.config.devices.retain(|d, _c| d.to_string() != device);
+
+                    // Revert to default config
+                    self.device = "default".to_string();
+                    self.refresh_config();
                 }
             }
             (Message::SelectAxis(a), _) => {
@@ -216,42 +603,265 @@ impl Application for App {
                 // Set new axis selected state
                 self.cgs[self.axis].set_selected(true);
 
-                // Update scale text for new axis
-                let config = self.config.get(&self.device).unwrap_or(&self.config.default);
-                self.scale_text = format!("{:0.4}", config[a].scale);
+                // Update numeric fields for new axis
+                self.refresh_text();
+            }
+            (Message::SelectCopyTarget(t), _) => {
+                self.copy_target = Some(t);
+            }
+            (Message::CopyAxis, _) => {
+                let (target, config) = match (self.copy_target, self.config.get_mut(&self.device)) {
+                    (Some(t), Some(c)) => (t, c),
+                    _ => return Command::none(),
+                };
+
+                info!("Copying {} config to {}", self.axis, target);
+
+                // AxisConfig is Copy, each target receives an independent value
+                let src = config[self.axis];
+                for a in target.axes() {
+                    config[*a] = src;
+                }
+
+                self.refresh_config();
+            }
+            (Message::SelectCopySource(s), _) => {
+                self.copy_source = Some(s);
+            }
+            (Message::CopyDevice, _) => {
+                let src = match self.copy_source.as_ref().and_then(|s| self.config.get(s)) {
+                    Some(c) => *c,
+                    None => return Command::none(),
+                };
+
+                if let Some(config) = self.config.get_mut(&self.device) {
+                    info!("Copying {} config to {}", self.copy_source.as_deref().unwrap_or_default(), self.device);
+                    *config = src;
+                }
+
+                self.refresh_config();
+            }
+            (Message::ToggleProfiles, _) => {
+                self.show_profiles = !self.show_profiles;
+            }
+            (Message::SelectProfile(p), _) => {
+                self.profile_name = p.clone();
+                self.profile = Some(p);
+            }
+            (Message::ProfileNameChanged(n), _) => {
+                self.profile_name = n;
+            }
+            (Message::CreateProfile, _) => {
+                let name = self.profile_name.trim().to_string();
+                let axes = match self.config.get(&self.device) {
+                    Some(c) if !name.is_empty() => *c,
+                    _ => return Command::none(),
+                };
+
+                // Snapshot the current device config, updating any existing profile
+                let device = self.device.clone();
+                match self.config.profiles.iter_mut().find(|p| p.device == device && p.name == name) {
+                    Some(p) => p.axes = axes,
+                    None => self.config.profiles.push(Profile { device, name: name.clone(), axes }),
+                }
+
+                info!("Created profile '{}' for device: {}", name, self.device);
+                self.profile = Some(name);
+            }
+            (Message::RenameProfile, _) => {
+                let name = self.profile_name.trim().to_string();
+                let old = match self.profile.clone() {
+                    Some(p) if !name.is_empty() && p != name => p,
+                    _ => return Command::none(),
+                };
+
+                if self.config.profile(&self.device, &name).is_some() {
+                    self.set_status(Status::Error(format!("Profile '{}' already exists", name)));
+                    return Command::none();
+                }
+
+                let device = self.device.clone();
+                if let Some(p) = self.config.profiles.iter_mut().find(|p| p.device == device && p.name == old) {
+                    p.name = name.clone();
+                }
+                if self.config.active.get(&device) == Some(&old) {
+                    self.config.active.insert(device, name.clone());
+                }
+
+                info!("Renamed profile '{}' to '{}'", old, name);
+                self.profile = Some(name);
+            }
+            (Message::DeleteProfile, _) => {
+                let name = match self.profile.take() {
+                    Some(p) => p,
+                    None => return Command::none(),
+                };
+
+                info!("Deleting profile '{}' for device: {}", name, self.device);
+
+                // Deleting the active profile falls back to the default (no profile)
+                if self.config.remove_profile(&self.device, &name) {
+                    self.set_status(Status::Error(format!("Deleted active profile '{}', falling back to default", name)));
+                }
+            }
+            (Message::ActivateProfile, Some(c)) => {
+                let profile = match self.profile.clone() {
+                    Some(p) => p,
+                    None => return Command::none(),
+                };
+
+                self.pending = Some("Activate profile");
+                return Self::command(c, vmouse::Command::SelectProfile { device: self.device.clone(), profile });
+            }
+            (Message::Command(vmouse::Command::ActiveProfile { device, profile }), _) => {
+                info!("Active profile for {}: {:?}", device, profile);
+
+                // Mirror the daemon activation on both local and committed configs
+                match &profile {
+                    Some(p) => {
+                        for c in [&mut self.config, &mut self.committed] {
+                            if let Err(e) = c.activate(&device, p) {
+                                warn!("Failed to apply active profile: {:?}", e);
+                            }
+                        }
+                    }
+                    None => {
+                        self.config.active.remove(&device);
+                        self.committed.active.remove(&device);
+                    }
+                }
+
+                if device == self.device {
+                    self.refresh_config();
+                }
             }
             (Message::SocketChanged(socket), _) => {
                 self.socket = socket;
             }
 
+            // Config file edited with errors, the daemon keeps its current config
+            (Message::Command(vmouse::Command::ConfigInvalid { path, errors }), _) => {
+                warn!("Config file '{}' has errors: {:?}", path, errors);
+                self.set_status(Status::Error(format!("Config file has errors: {}", errors.join(", "))));
+            }
+
             (Message::Command(vmouse::Command::SetConfig(c)), _) => {
                 debug!("Received config: {:?}", c);
 
-                self.config = c;
-
-                let config = self.config.get(&self.device).unwrap_or(&self.config.default);
+                self.config = c.clone();
+                self.committed = c;
 
-                // Update curve graphs
-                for a in AXIS {
-                    self.cgs[*a].set_config(config[*a]);
+                // Select the device requested on the command line
+                if let Some(d) = self.initial_device.take() {
+                    match self.config.get(&d) {
+                        Some(_) => self.device = self.config.resolve(&d),
+                        None => self.set_status(Status::Error(format!("No config for device '{}'", d))),
+                    }
                 }
 
-                self.cgs[self.axis].set_selected(true);
+                // Fall back to default if the selected device config no longer exists
+                if self.config.get(&self.device).is_none() {
+                    self.device = "default".to_string();
+                }
+                self.refresh_alias();
 
-                self.scale_text = format!("{:0.4}", self.config.default[self.axis].scale);
+                // Update curve graphs and scale text
+                self.refresh_config();
+            }
+            (Message::Command(vmouse::Command::ConfigPath(p)), _) => {
+                debug!("Received config path: {}", p);
+                self.config_path = Some(p);
+            }
+            (Message::Command(vmouse::Command::Devices(d)), _) => {
+                debug!("Received devices: {:?}", d);
+                self.devices = d;
             }
-            (Message::Command(vmouse::Command::State(s)), _) => {
+            (Message::Command(vmouse::Command::State { device, state: s }), _) => {
+                // Only display state for the selected device (aggregate for default)
+                let selected = match &device {
+                    Some(d) => d.to_string() == self.device,
+                    None => self.device == "default",
+                };
+                if !selected {
+                    return Command::none();
+                }
+
+                // Record updates for axes with changed values
+                let now = Instant::now();
+                for a in AXIS {
+                    if s.raw[*a] != self.values[*a] {
+                        self.rates[*a].push(now);
+                    }
+                    self.history[*a].push(s.raw[*a]);
+                }
+
                 // Update state map
-                self.values = s;
+                self.values = s.raw;
+                self.outputs = s.output;
 
                 // Update curve graphs
-                for a in AXIS {
-                    self.cgs[*a].set_value(s[*a]);
+                for (a, (raw, out)) in s.raw.zip(&s.output) {
+                    self.cgs[a].set_value(*raw);
+                    self.cgs[a].set_output(*out);
                 }
             }
+            (Message::Command(vmouse::Command::Removed(d)), _) => {
+                info!("Device removed: {}", d.to_string());
+
+                // Clear values for removed device
+                if d.to_string() == self.device {
+                    self.values = AxisCollection::with_axis(|_| Default::default());
+                    self.outputs = AxisCollection::with_axis(|_| Default::default());
+                    for a in AXIS {
+                        self.rates[*a].clear();
+                        self.history[*a].clear();
+                        self.cgs[*a].set_value(0.0);
+                    }
+                }
+            }
+            (Message::Command(vmouse::Command::Status(s)), _) => {
+                debug!("Received status, enabled: {} devices: {:?}", s.enabled, s.device_enabled);
+                self.daemon = Some((s.version.clone(), s.protocol));
+                self.attached = s.enabled;
+                self.device_enabled = s.device_enabled;
+            }
+            (Message::Command(vmouse::Command::Ok), _) => {
+                if let Some(e) = self.toggle.take() {
+                    match self.enable_target() {
+                        Some(d) => { self.device_enabled.insert(d, e); },
+                        None => self.attached = e,
+                    }
+                }
+                if let Some(c) = self.applying.take() {
+                    self.committed = c;
+                }
+                if let Some(p) = self.pending.take() {
+                    self.set_status(Status::Info(format!("{} OK", p)));
+                }
+            }
+            (Message::Command(vmouse::Command::Error(e)), _) => {
+                self.toggle = None;
+                self.applying = None;
+                let p = self.pending.take().unwrap_or("Request");
+                self.set_status(Status::Error(format!("{} failed: {}", p, e)));
+            }
             (Message::Command(cmd), _) => {
                 debug!("Received command: {:?}", cmd);
             }
+            (Message::Error(e), _) => {
+                self.set_status(Status::Error(e));
+            }
+            (Message::Info(i), _) => {
+                self.set_status(Status::Info(i));
+            }
+            (Message::Tick, _) => {
+                // Clear expired status messages
+                if let Some((_s, t)) = &self.status {
+                    if t.elapsed() > STATUS_TIMEOUT {
+                        self.status = None;
+                    }
+                }
+            }
             _ => (),
         }
 
@@ -259,10 +869,15 @@ impl Application for App {
     }
 
     fn subscription(&self) -> iced::Subscription<Self::Message> {
+        let tick = iced::time::every(Duration::from_secs(1)).map(|_| Message::Tick);
+
         if let Some(c) = self.client.clone() {
-            iced::Subscription::from_recipe(Idk { client: c })
+            iced::Subscription::batch(vec![
+                iced::Subscription::from_recipe(ClientRecipe { client: c }),
+                tick,
+            ])
         } else {
-            iced::Subscription::none()
+            tick
         }
     }
 
@@ -272,17 +887,7 @@ impl Application for App {
             .height(Length::Fill)
             .width(Length::FillPortion(2));
         for a in AXIS_LIN {
-            let cg = self.cgs[*a].clone();
-            let g = Canvas::new(cg)
-                .width(Length::FillPortion(2))
-                .height(Length::FillPortion(2));
-
-            let row = Row::new()
-                .padding(10)
-                .height(Length::FillPortion(2))
-                .push(g);
-
-            column_lin = column_lin.push(row);
+            column_lin = column_lin.push(self.graph(*a));
         }
 
         let mut column_rot = Column::new()
@@ -290,17 +895,7 @@ impl Application for App {
             .height(Length::Fill)
             .width(Length::FillPortion(2));
         for a in AXIS_ROT {
-            let cg = self.cgs[*a].clone();
-            let g = Canvas::new(cg)
-                .width(Length::FillPortion(2))
-                .height(Length::FillPortion(2));
-
-            let row = Row::new()
-                .padding(10)
-                .height(Length::FillPortion(2))
-                .push(g);
-
-            column_rot = column_rot.push(row);
+            column_rot = column_rot.push(self.graph(*a));
         }
 
         let axis = self.axis;
@@ -313,7 +908,15 @@ impl Application for App {
             )
             .width(Length::FillPortion(2)),
         );
-        if self.client.is_none() {
+        if self.client.is_some() && !self.connected {
+            connect_ctl = connect_ctl.push(
+                Button::new(
+                    Text::new("reconnecting…").horizontal_alignment(Horizontal::Center),
+                )
+                .on_press(Message::Disconnect)
+                .width(Length::FillPortion(1)),
+            )
+        } else if self.client.is_none() {
             connect_ctl = connect_ctl.push(
                 Button::new(
                     Text::new("connect").horizontal_alignment(Horizontal::Center),
@@ -324,7 +927,10 @@ impl Application for App {
         } else {
             connect_ctl = connect_ctl.push(
                 Button::new(
-                    Text::new("disconnect").horizontal_alignment(Horizontal::Center),
+                    Text::new(match self.confirm {
+                        Some(Confirm::Disconnect) => "confirm disconnect",
+                        _ => "disconnect",
+                    }).horizontal_alignment(Horizontal::Center),
                 )
                 .on_press(Message::Disconnect)
                 .width(Length::FillPortion(1)),
@@ -343,7 +949,10 @@ impl Application for App {
             )
             .push(
                 Button::new(
-                    Text::new("revert").horizontal_alignment(Horizontal::Center),
+                    Text::new(match self.confirm {
+                        Some(Confirm::Revert) => "confirm revert",
+                        _ => "revert",
+                    }).horizontal_alignment(Horizontal::Center),
                 )
                 .on_press(Message::RevertConfig)
                 .width(Length::FillPortion(1)),
@@ -355,23 +964,19 @@ impl Application for App {
                 .on_press(Message::WriteConfig)
                 .width(Length::FillPortion(1)),
             );
-        if !self.attached {
-            config_ctl = config_ctl.push(
-                Button::new(
-                    Text::new("attach").horizontal_alignment(Horizontal::Center),
-                )
-                .on_press(Message::Attach)
-                .width(Length::FillPortion(1)),
-            )
-        } else {
-            config_ctl = config_ctl.push(
-                Button::new(
-                    Text::new("detach").horizontal_alignment(Horizontal::Center),
-                )
-                .on_press(Message::Detach)
-                .width(Length::FillPortion(1)),
-            )
+        // Attach / detach, disabled while a request is in flight
+        let (label, msg) = match self.output_enabled() {
+            false => ("attach", Message::Attach),
+            true => ("detach", Message::Detach),
+        };
+        let mut toggle = Button::new(
+            Text::new(label).horizontal_alignment(Horizontal::Center),
+        )
+        .width(Length::FillPortion(1));
+        if self.toggle.is_none() && self.connected {
+            toggle = toggle.on_press(msg);
         }
+        config_ctl = config_ctl.push(toggle);
 
         let column_ctrl = Column::new()
             .padding(10)
@@ -381,11 +986,57 @@ impl Application for App {
             // Device selection
             .push(Text::new("Device:").vertical_alignment(alignment::Vertical::Center))
             .push(
-                PickList::new(
-                    self.config.iter().map(|(n, _c)| n ).collect::<Vec<_>>(),
-                    Some(self.device.clone()),
-                    Message::SelectDevice,
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        PickList::new(
+                            self.device_options(),
+                            Some(self.device_label(&self.device)),
+                            Message::SelectDevice,
+                        )
+                        .width(Length::Fill),
+                    )
+                    .push(
+                        Button::new(Text::new("remove"))
+                            .on_press(Message::RemoveDeviceConfig),
+                    ),
+            )
+            // Device alias
+            .push(self.alias_controls())
+            // Duplicate device config
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        PickList::new(
+                            self.config.iter().map(|(n, _c)| n).filter(|n| n != &self.device).collect::<Vec<_>>(),
+                            self.copy_source.clone(),
+                            Message::SelectCopySource,
+                        )
+                        .placeholder("duplicate config from…")
+                        .width(Length::Fill),
+                    )
+                    .push({
+                        let b = Button::new(Text::new("copy"));
+                        match &self.copy_source {
+                            Some(s) if s != &self.device => b.on_press(Message::CopyDevice),
+                            _ => b,
+                        }
+                    }),
+            )
+            // Profile management
+            .push(self.profile_panel())
+            // Dominant axis snapping
+            .push(Text::new("Axis snap:").vertical_alignment(alignment::Vertical::Center))
+            .push(
+                Slider::new(
+                    AXIS_SNAP_RANGE,
+                    self.axis_snap(),
+                    Message::AxisSnapChanged,
                 )
+                .step(0.1)
                 .width(Length::Fill),
             )
             // Axis selection
@@ -398,9 +1049,54 @@ impl Application for App {
                 )
                 .width(Length::Fill),
             )
-            // Current value display
-            .push(Text::new("Value:").vertical_alignment(alignment::Vertical::Center))
-            .push(ProgressBar::new(-1.0..=1.0, self.values[axis]))
+            // Copy axis config
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        PickList::new(
+                            CopyTarget::options(self.axis),
+                            self.copy_target,
+                            Message::SelectCopyTarget,
+                        )
+                        .placeholder("copy axis to…")
+                        .width(Length::Fill),
+                    )
+                    .push({
+                        let b = Button::new(Text::new("copy"));
+                        match self.copy_target {
+                            Some(_) => b.on_press(Message::CopyAxis),
+                            None => b,
+                        }
+                    }),
+            )
+            // Current value display, or simulated value input while disconnected
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(match self.simulating() {
+                        true => Self::label("Value:", Some("(simulated)")).width(Length::Fill),
+                        false => Self::label("Value:", None).width(Length::Fill),
+                    })
+                    .push({
+                        let b = Button::new(Text::new(match self.simulate {
+                            true => "simulate: on",
+                            false => "simulate: off",
+                        }));
+                        match self.connected {
+                            false => b.on_press(Message::ToggleSimulate),
+                            true => b,
+                        }
+                    }),
+            )
+            .push::<iced::Element<'_, Message>>(match self.simulating() {
+                true => Slider::new(-1.0..=1.0, self.values[axis], move |v| Message::ValueChanged(axis, v))
+                    .step(0.01)
+                    .into(),
+                false => ProgressBar::new(-1.0..=1.0, self.values[axis]).into(),
+            })
             .push(Row::new().height(Length::Fixed(10.0)))
             // Mapping configuration
             .push(Text::new("Mapping:").vertical_alignment(alignment::Vertical::Center))
@@ -413,7 +1109,7 @@ impl Application for App {
                 .width(Length::Fill),
             )
             // Scale configuration
-            .push(Text::new("Scale:").vertical_alignment(alignment::Vertical::Center))
+            .push(Self::label("Scale:", self.scale_invalid.then(|| SCALE_HINT)))
             .push(
                 Row::new()
                     .spacing(10)
@@ -429,87 +1125,609 @@ impl Application for App {
                     ),
             )
             // Curve configuration
-            .push(Text::new("Curve:").vertical_alignment(alignment::Vertical::Center))
-            .push(
-                Slider::new(
-                    0.0..=1.0,
-                    self.config.get(&self.device).map(|c| c[self.axis].curve ).unwrap_or_default(),
-                    move |x| Message::CurveChanged(axis, x),
-                )
-                .step(0.01),
-            )
+            .push(self.curve_controls())
             // Deadzone configuration
-            .push(Text::new("Deadzone:").vertical_alignment(alignment::Vertical::Center))
+            .push(Self::label("Deadzone:", self.deadzone_invalid.then(|| "(valid range 0.0 - 0.95)")))
             .push(
-                Slider::new(
-                    0.0..=1.0,
-                    self.config.get(&self.device).map(|c| c[self.axis].deadzone ).unwrap_or_default(),
-                    move |d| Message::DeadzoneChanged(axis, d),
-                )
-                .step(0.01),
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Slider::new(
+                            DEADZONE_RANGE,
+                            self.config.get(&self.device).unwrap_or(&self.config.default)[self.axis].deadzone,
+                            move |d| Message::DeadzoneChanged(axis, d),
+                        )
+                        .step(0.01)
+                        .width(Length::FillPortion(3)),
+                    )
+                    .push(
+                        TextInput::new(
+                            "deadzone",
+                            &self.deadzone_text,
+                            move |t| Message::DeadzoneTextChanged(axis, t),
+                        )
+                        .width(Length::FillPortion(1)),
+                    ),
             )
+            // Negative direction overrides
+            .push(self.asymmetric_controls())
             .push(Row::new().height(Length::Fill))
-            .push(Text::new("Control:").vertical_alignment(alignment::Vertical::Center))
+            .push(match self.dirty() {
+                true => Self::label("Control:", Some("(unsaved changes)")),
+                false => Self::label("Control:", None),
+            })
             .push(config_ctl)
+            .push(self.save_controls())
             // Daemon connection
             .push(Text::new("Socket:").vertical_alignment(alignment::Vertical::Center))
             .push(connect_ctl);
-        Row::new()
-            .padding(10)
-            .push(column_lin)
-            .push(column_rot)
-            .push(column_ctrl)
+        // Status bar
+        let status = match &self.status {
+            Some((Status::Error(e), _)) => Text::new(e.clone()).style(Color::from_rgb8(0xD0, 0x20, 0x20)),
+            Some((Status::Info(i), _)) => Text::new(i.clone()).style(Color::from_rgb8(0x20, 0x80, 0x20)),
+            None => Text::new(""),
+        };
+
+        Column::new()
+            .push(
+                Row::new()
+                    .padding(10)
+                    .height(Length::Fill)
+                    .push(column_lin)
+                    .push(column_rot)
+                    .push(column_ctrl),
+            )
+            .push(
+                Row::new()
+                    .padding([0, 20, 10, 20])
+                    .spacing(20)
+                    .align_items(Alignment::Center)
+                    .push(self.connection_chip())
+                    .push(status),
+            )
             .into()
     }
 }
 
 impl App {
-    fn connect(socket: String) -> Command<Message> {
-        Command::perform(
-            async move {
-                debug!("Connecting to socket: {}", socket);
-                let mut c = Client::connect(socket).await?;
+    /// Show a status bar message
+    fn set_status(&mut self, s: Status) {
+        self.status = Some((s, Instant::now()));
+    }
 
-                debug!("Subscribing to socket events");
-                c.send(vmouse::Command::Listen).await?;
+    /// Connection status chip, pressing it retries when not connected
+    fn connection_chip(&self) -> Button<'_, Message, iced::Renderer> {
+        let (text, color, press) = match (&self.client, self.connected, &self.daemon) {
+            (None, _, _) => ("disconnected".to_string(), Color::from_rgb8(0x80, 0x80, 0x80), Some(Message::Connect)),
+            (Some(_), false, _) => match &self.connect_error {
+                Some(e) => (format!("connecting… ({})", e), Color::from_rgb8(0xD0, 0x20, 0x20), Some(Message::Retry)),
+                None => ("connecting…".to_string(), Color::from_rgb8(0x80, 0x80, 0x80), Some(Message::Retry)),
+            },
+            (Some(c), true, Some((v, p))) => (
+                format!("connected to vmoused {} (protocol v{}) @ {}", v, vmouse::protocol_string(*p), c.path()),
+                Color::from_rgb8(0x20, 0x80, 0x20),
+                None,
+            ),
+            (Some(c), true, None) => (format!("connected @ {}", c.path()), Color::from_rgb8(0x20, 0x80, 0x20), None),
+        };
+
+        let mut b = Button::new(Text::new(text).size(14).style(color));
+        if let Some(m) = press {
+            b = b.on_press(m);
+        }
+        b
+    }
 
-                debug!("Subscribe ok!");
+    /// Curve graph with numeric raw / output values and update rate
+    fn graph(&self, a: Axis) -> Row<'_, Message, iced::Renderer> {
+        let g = Canvas::new(self.cgs[a].clone())
+            .width(Length::Fill)
+            .height(Length::Fill);
+
+        // Grey out values without a recent update
+        let now = Instant::now();
+        let stale = self.rates[a].last().map(|t| now.duration_since(t) > STALE_TIMEOUT).unwrap_or(true);
+        let color = match stale {
+            true => Color::from_rgb8(0xA0, 0xA0, 0xA0),
+            false => Color::BLACK,
+        };
+
+        let values = Text::new(format!(
+            "raw: {:+.3}  out: {:+.3}  {:.0} Hz",
+            self.values[a],
+            self.outputs[a],
+            self.rates[a].rate(now),
+        ))
+        .size(14)
+        .style(color);
+
+        // Recent raw values against the deadzone
+        let config = self.config.get(&self.device).unwrap_or(&self.config.default);
+        let history = Canvas::new(Sparkline::new(&self.history[a], &config[a]))
+            .width(Length::Fill)
+            .height(Length::Fixed(40.0));
 
-                Ok(c)
-            },
-            |r: Result<Client, anyhow::Error>| match r {
-                Ok(c) => Message::Connected(Arc::new(Mutex::new(Some(c)))),
-                Err(e) => {
-                    error!("Connection failed: {:?}", e);
-                    Message::Tick
-                }
-            },
-        )
+        Row::new()
+            .padding(10)
+            .height(Length::FillPortion(2))
+            .push(Column::new().spacing(5).push(g).push(history).push(values))
+    }
+
+    /// Attach / detach target, the selected device or `None` for global output
+    fn enable_target(&self) -> Option<String> {
+        match self.device.as_str() {
+            "default" => None,
+            d => Some(d.to_string()),
+        }
+    }
+
+    /// Check whether output is enabled for the attach / detach target
+    fn output_enabled(&self) -> bool {
+        match self.enable_target() {
+            Some(d) => self.device_enabled.get(&d).copied().unwrap_or(true),
+            None => self.attached,
+        }
+    }
+
+    /// Check whether simulated values are in use (enabled and not connected)
+    fn simulating(&self) -> bool {
+        self.simulate && !self.connected
     }
 
-    fn command(mut client: Client, cmd: vmouse::Command) -> Command<Message> {
+    /// Check whether the local config differs from the daemon config
+    fn dirty(&self) -> bool {
+        !config_approx_eq(&self.config, &self.committed)
+    }
+
+    /// Refresh curve graphs and scale text from the selected device config
+    fn refresh_config(&mut self) {
+        let config = self.config.get(&self.device).unwrap_or(&self.config.default);
+
+        for (a, c) in config {
+            self.cgs[a].set_config(*c);
+        }
+
+        self.cgs[self.axis].set_selected(true);
+
+        self.refresh_text();
+    }
+
+    /// Refresh numeric fields from the selected device and axis config
+    fn refresh_text(&mut self) {
+        let config = self.config.get(&self.device).unwrap_or(&self.config.default);
+
+        self.scale_text = format!("{:0.4}", config[self.axis].scale);
+        self.scale_invalid = false;
+        self.scale_neg_text = format!("{:0.4}", config[self.axis].scale_for(-1.0));
+        self.scale_neg_invalid = false;
+        self.curve_text = config[self.axis].curve.param().map(|c| format!("{:0.2}", c)).unwrap_or_default();
+        self.curve_invalid = false;
+        self.deadzone_text = format!("{:0.2}", config[self.axis].deadzone);
+        self.deadzone_invalid = false;
+    }
+
+    /// Curve type selection and parameter controls for the selected axis
+    fn curve_controls(&self) -> Column<'_, Message, iced::Renderer> {
+        let axis = self.axis;
+        let curve = self.config.get(&self.device).unwrap_or(&self.config.default)[axis].curve;
+
+        let hint = match curve.curve_type() {
+            CurveType::Power => "(valid range 1.0 - 5.0)",
+            _ => "(valid range 0.0 - 1.0)",
+        };
+
+        let mut row = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(
+                PickList::new(CURVE_TYPES, Some(curve.curve_type()), move |t| Message::CurveTypeChanged(axis, t))
+                    .width(Length::FillPortion(2)),
+            );
+
+        row = match curve.param() {
+            Some(c) => row
+                .push(
+                    Slider::new(curve.range(), c, move |x| Message::CurveChanged(axis, x))
+                        .step(0.01)
+                        .width(Length::FillPortion(3)),
+                )
+                .push(
+                    TextInput::new(
+                        "curve",
+                        &self.curve_text,
+                        move |t| Message::CurveTextChanged(axis, t),
+                    )
+                    .width(Length::FillPortion(1)),
+                ),
+            // Table editing is not yet supported in the GUI
+            None => row.push(
+                Text::new(format!("{} breakpoints (edit in config file)", match curve {
+                    CurveKind::Table(t) => t.points().len(),
+                    _ => 0,
+                }))
+                .width(Length::FillPortion(4)),
+            ),
+        };
+
+        Column::new()
+            .spacing(10)
+            .push(Self::label("Curve:", self.curve_invalid.then(|| hint)))
+            .push(row)
+    }
+
+    /// Link toggle and negative direction scale / deadzone controls for the selected axis
+    fn asymmetric_controls(&self) -> Column<'_, Message, iced::Renderer> {
+        let axis = self.axis;
+        let config = self.config.get(&self.device).unwrap_or(&self.config.default)[axis];
+
+        let mut c = Column::new().spacing(10).push(
+            Button::new(Text::new(match config.is_asymmetric() {
+                true => "directions split (click to link)",
+                false => "directions linked (click to split)",
+            }))
+            .on_press(Message::ToggleLinked(axis)),
+        );
+
+        if !config.is_asymmetric() {
+            return c;
+        }
+
+        c = c
+            .push(Self::label("Scale (−):", self.scale_neg_invalid.then(|| SCALE_HINT)))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(TextInput::new(
+                        "scale (−)",
+                        &self.scale_neg_text,
+                        move |s| Message::ScaleNegChanged(axis, s),
+                    ))
+                    .push(Button::new(Text::new("apply")).on_press(Message::ApplyScaleNeg)),
+            )
+            .push(Text::new(format!("Deadzone (−): {:0.2}", config.deadzone_for(-1.0))))
+            .push(
+                Slider::new(
+                    DEADZONE_RANGE,
+                    config.deadzone_for(-1.0),
+                    move |d| Message::DeadzoneNegChanged(axis, d),
+                )
+                .step(0.01),
+            );
+
+        c
+    }
+
+    /// Collapsible profile management panel for the selected device
+    /// Axis snap ratio for the selected device, 1.0 where disabled
+    fn axis_snap(&self) -> f32 {
+        let device = self.config.resolve(&self.device);
+        self.config.axis_snap.iter()
+            .find(|(k, _v)| self.config.resolve(k) == device)
+            .map(|(_k, v)| *v)
+            .unwrap_or(1.0)
+    }
+
+    fn profile_panel(&self) -> Column<'_, Message, iced::Renderer> {
+        let active = self.config.active.get(&self.device);
+
+        let header = format!(
+            "{} Profiles (active: {})",
+            if self.show_profiles { "▾" } else { "▸" },
+            active.map(|a| a.as_str()).unwrap_or("default"),
+        );
+        let mut c = Column::new()
+            .spacing(10)
+            .push(Button::new(Text::new(header)).on_press(Message::ToggleProfiles));
+
+        if !self.show_profiles {
+            return c;
+        }
+
+        let profiles: Vec<_> = self.config.device_profiles(&self.device).map(|p| p.name.clone()).collect();
+
+        // Disable actions without a selected profile
+        let selected = self.profile.as_ref().filter(|p| profiles.contains(p));
+        let action = |label: &str, m: Message, enabled: bool| {
+            let b = Button::new(Text::new(label.to_string()));
+            if enabled { b.on_press(m) } else { b }
+        };
+
+        c = c
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        PickList::new(profiles.clone(), selected.cloned(), Message::SelectProfile)
+                            .placeholder("profile")
+                            .width(Length::Fill),
+                    )
+                    .push(action("activate", Message::ActivateProfile, selected.is_some() && self.connected))
+                    .push(action("delete", Message::DeleteProfile, selected.is_some())),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        TextInput::new("profile name", &self.profile_name, Message::ProfileNameChanged)
+                            .width(Length::Fill),
+                    )
+                    .push(action("save", Message::CreateProfile, !self.profile_name.trim().is_empty()))
+                    .push(action("rename", Message::RenameProfile, selected.is_some() && !self.profile_name.trim().is_empty())),
+            );
+
+        c
+    }
+
+    /// Config write target and save as controls
+    fn save_controls(&self) -> Column<'_, Message, iced::Renderer> {
+        let target = match &self.config_path {
+            Some(p) => format!("write to: {}", p),
+            None => "write to: (unknown)".to_string(),
+        };
+
+        let mut save = Button::new(Text::new("save as"));
+        if self.connected && !self.save_as_path.trim().is_empty() {
+            save = save.on_press(Message::SaveConfigAs);
+        }
+
+        Column::new()
+            .spacing(10)
+            .push(Text::new(target).size(14))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        TextInput::new("/absolute/path/vmouse.toml", &self.save_as_path, Message::SaveAsPathChanged)
+                            .width(Length::Fill),
+                    )
+                    .push(save),
+            )
+    }
+
+    /// Field label, with an optional highlighted hint
+    fn label<'a>(name: &str, hint: Option<&'a str>) -> Row<'a, Message, iced::Renderer> {
+        let mut r = Row::new()
+            .spacing(10)
+            .push(Text::new(name.to_string()).vertical_alignment(alignment::Vertical::Center));
+
+        if let Some(h) = hint {
+            r = r.push(
+                Text::new(h)
+                    .style(Color::from_rgb8(0xD0, 0x20, 0x20))
+                    .vertical_alignment(alignment::Vertical::Center),
+            );
+        }
+
+        r
+    }
+
+    /// Device picker options, configured devices followed by connected but unconfigured devices
+    fn device_options(&self) -> Vec<String> {
+        let configured: Vec<_> = self.config.iter().map(|(n, _c)| n).collect();
+        let mut options: Vec<_> = configured.iter().map(|n| self.device_label(n)).collect();
+
+        for d in &self.devices {
+            let n = d.to_string();
+            if !configured.contains(&n) {
+                options.push(format!("{}{}", self.device_label(&n), UNCONFIGURED));
+            }
+        }
+
+        options
+    }
+
+    /// Display label for a device name, the alias where one exists
+    fn device_label(&self, name: &str) -> String {
+        match name.parse::<UsbDevice>().ok().and_then(|d| self.config.alias_for(&d)) {
+            Some(a) => a.to_string(),
+            None => name.to_string(),
+        }
+    }
+
+    /// Reset the alias input for the selected device
+    fn refresh_alias(&mut self) {
+        let alias = self.device.parse::<UsbDevice>().ok().and_then(|d| self.config.alias_for(&d).map(String::from));
+        self.alias_text = alias.unwrap_or_default();
+        self.alias_error = None;
+    }
+
+    /// Assign the alias input to the selected device, an empty alias removes it
+    fn set_alias(&mut self) -> Result<(), String> {
+        let d = self.device.parse::<UsbDevice>()
+            .map_err(|_| "the default config cannot be aliased".to_string())?;
+        let alias = self.alias_text.trim().to_string();
+
+        if alias == "default" {
+            return Err("'default' is reserved".to_string());
+        }
+        if alias.parse::<UsbDevice>().is_ok() {
+            return Err("alias cannot be a vid:pid".to_string());
+        }
+        match self.config.aliases.get(&alias) {
+            Some(v) if v.vid != d.vid || v.pid != d.pid => {
+                return Err(format!("'{}' is already used for {}", alias, v.to_string()));
+            }
+            _ => (),
+        }
+
+        self.config.aliases.retain(|_a, v| v.vid != d.vid || v.pid != d.pid);
+
+        match alias.is_empty() {
+            true => info!("Removing alias for device: {}", self.device),
+            false => {
+                info!("Setting alias for device {}: {}", self.device, alias);
+                self.config.aliases.insert(alias.clone(), UsbDevice { name: None, ..d.clone() });
+            }
+        }
+
+        // Display the alias for connected devices until the daemon reports it
+        for v in self.devices.iter_mut().filter(|v| v.vid == d.vid && v.pid == d.pid) {
+            v.name = Some(alias.clone()).filter(|a| !a.is_empty());
+        }
+
+        self.alias_text = alias;
+
+        Ok(())
+    }
+
+    /// Alias input for the selected device, with inline errors
+    fn alias_controls(&self) -> Column<'_, Message, iced::Renderer> {
+        let mut set = Button::new(Text::new("rename"));
+        if self.device != "default" {
+            set = set.on_press(Message::SetAlias);
+        }
+
+        let mut c = Column::new()
+            .spacing(5)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(
+                        TextInput::new("alias (eg. spacemouse)", &self.alias_text, Message::AliasTextChanged)
+                            .on_submit(Message::SetAlias)
+                            .width(Length::Fill),
+                    )
+                    .push(set),
+            );
+
+        if let Some(e) = &self.alias_error {
+            c = c.push(Text::new(e.clone()).size(14).style(Color::from_rgb8(0xD0, 0x20, 0x20)));
+        }
+
+        c
+    }
+
+    /// Issue a request, responses are correlated by request id where supported by the daemon,
+    /// otherwise they arrive as client events
+    fn command(client: ReconnectingClient, cmd: vmouse::Command) -> Command<Message> {
         Command::perform(
             async move {
-                debug!("Issuing config get request");
-                client.send(cmd).await?;
-                Ok(())
+                debug!("Issuing request: {:?}", cmd);
+                client.request(cmd).await
             },
-            |r: Result<(), anyhow::Error>| match r {
-                Ok(_c) => Message::Tick,
+            |r: Result<Option<vmouse::Command>, vmouse::Error>| match r {
+                Ok(Some(c)) => Message::Command(c),
+                Ok(None) => Message::Tick,
                 Err(e) => {
                     error!("Connection failed: {:?}", e);
-                    Message::Tick
+                    Message::Error(format!("Request failed: {}", e))
                 }
             },
         )
     }
 }
 
-struct Idk {
-    client: Client,
+/// Status bar messages
+#[derive(Clone, PartialEq, Debug)]
+enum Status {
+    Info(String),
+    Error(String),
+}
+
+/// Targets for copying the selected axis config
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CopyTarget {
+    Axis(Axis),
+    Linear,
+    Rotational,
+    All,
+}
+
+impl CopyTarget {
+    /// Copy targets for the selected axis
+    fn options(selected: Axis) -> Vec<Self> {
+        AXIS.iter()
+            .filter(|a| **a != selected)
+            .map(|a| CopyTarget::Axis(*a))
+            .chain([CopyTarget::Linear, CopyTarget::Rotational, CopyTarget::All])
+            .collect()
+    }
+
+    /// Axes covered by a copy target
+    fn axes(&self) -> &[Axis] {
+        match self {
+            CopyTarget::Axis(a) => std::slice::from_ref(a),
+            CopyTarget::Linear => AXIS_LIN,
+            CopyTarget::Rotational => AXIS_ROT,
+            CopyTarget::All => AXIS,
+        }
+    }
+}
+
+impl std::fmt::Display for CopyTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CopyTarget::Axis(a) => write!(f, "{}", a),
+            CopyTarget::Linear => write!(f, "all linear"),
+            CopyTarget::Rotational => write!(f, "all rotational"),
+            CopyTarget::All => write!(f, "all axes"),
+        }
+    }
 }
 
-impl<H, I> Recipe<H, I> for Idk
+/// Actions requiring confirmation while there are unsaved changes
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Confirm {
+    Revert,
+    Disconnect,
+}
+
+/// Compare configs ignoring float noise
+fn config_approx_eq(a: &Config, b: &Config) -> bool {
+    fn axes_eq(a: &AxisCollection<AxisConfig>, b: &AxisCollection<AxisConfig>) -> bool {
+        const EPS: f32 = 1e-4;
+
+        AXIS.iter().all(|x| {
+            let (a, b) = (&a[*x], &b[*x]);
+            a.map == b.map
+                && a.curve.curve_type() == b.curve.curve_type()
+                && match (a.curve, b.curve) {
+                    (CurveKind::Table(t1), CurveKind::Table(t2)) => t1 == t2,
+                    _ => (a.curve.param().unwrap_or_default() - b.curve.param().unwrap_or_default()).abs() < EPS,
+                }
+                && (a.scale - b.scale).abs() < EPS
+                && (a.deadzone - b.deadzone).abs() < EPS
+                && a.is_asymmetric() == b.is_asymmetric()
+                && (a.scale_for(-1.0) - b.scale_for(-1.0)).abs() < EPS
+                && (a.deadzone_for(-1.0) - b.deadzone_for(-1.0)).abs() < EPS
+                && a.range == b.range
+        })
+    }
+
+    axes_eq(&a.default, &b.default)
+        && a.devices.len() == b.devices.len()
+        && a.devices.iter().all(|(d, c)| b.get(&d.to_string()).map(|c2| axes_eq(c, c2)).unwrap_or(false))
+        && a.active == b.active
+        && a.split_outputs == b.split_outputs
+        && a.abs_range == b.abs_range
+        && a.abs_pointer == b.abs_pointer
+        && a.idle_timeout_s == b.idle_timeout_s
+        && a.idle_destroy == b.idle_destroy
+        && a.max_output_hz == b.max_output_hz
+        && a.passthrough == b.passthrough
+        && a.watch_config == b.watch_config
+        && a.output_limits == b.output_limits
+        && a.axis_snap == b.axis_snap
+        && a.input_kind == b.input_kind
+        && a.mouse_divisor == b.mouse_divisor
+        && a.aggregate == b.aggregate
+        && a.auto_zero_ms == b.auto_zero_ms
+        && a.feedback == b.feedback
+        && a.profiles.len() == b.profiles.len()
+        && a.profiles.iter().all(|p| b.profile(&p.device, &p.name).map(|p2| axes_eq(&p.axes, &p2.axes)).unwrap_or(false))
+}
+
+struct ClientRecipe {
+    client: ReconnectingClient,
+}
+
+impl<H, I> Recipe<H, I> for ClientRecipe
 where
     H: std::hash::Hasher,
 {
@@ -520,9 +1738,128 @@ where
     }
 
     fn stream(self: Box<Self>, _input: BoxStream<I>) -> BoxStream<Self::Output> {
-        Box::pin(self.client.map(|r| match r {
-            Ok(v) => Message::Command(v),
-            Err(_e) => Message::Tick,
-        }))
+        // Coalesce state updates so high broadcast rates don't outpace rendering
+        throttle::throttle(Box::pin(self.client.events().map(|e| match e {
+            ClientEvent::Connected => Message::Connected,
+            ClientEvent::Reconnecting(d, e) => Message::Reconnecting(d, e),
+            ClientEvent::Message(v) => Message::Command(v),
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let flags = Flags { socket: "/nonexistent/vmouse.sock".to_string(), device: None };
+        App::new(flags).0
+    }
+
+    #[test]
+    fn scale_text_refreshed_on_device_change() {
+        let mut app = app();
+        let dev = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+        let mut axes = app.config.default;
+        axes[Axis::X].scale = 2.5;
+        app.config.devices.insert(dev.clone(), axes);
+        app.config.default[Axis::X].scale = 1.0;
+
+        let _ = app.update(Message::SelectAxis(Axis::X));
+        assert_eq!(app.scale_text, "1.0000");
+
+        // Unapplied (and invalid) edits are discarded when switching devices
+        let _ = app.update(Message::ScaleChanged(Axis::X, "12".to_string()));
+        assert!(app.scale_invalid);
+
+        let _ = app.update(Message::SelectDevice(dev.to_string()));
+        assert_eq!(app.scale_text, "2.5000");
+        assert!(!app.scale_invalid);
+
+        // Applying writes the selected device value, leaving the default untouched
+        let _ = app.update(Message::ScaleChanged(Axis::X, "3".to_string()));
+        let _ = app.update(Message::ApplyScale);
+        assert_eq!(app.config.devices[&dev][Axis::X].scale, 3.0);
+        assert_eq!(app.config.default[Axis::X].scale, 1.0);
+
+        let _ = app.update(Message::SelectDevice("default".to_string()));
+        assert_eq!(app.scale_text, "1.0000");
+    }
+
+    #[test]
+    fn invalid_scale_not_applied() {
+        let mut app = app();
+        let scale = app.config.default[Axis::X].scale;
+
+        let _ = app.update(Message::ScaleChanged(Axis::X, "-10.5".to_string()));
+        assert!(app.scale_invalid);
+
+        let _ = app.update(Message::ApplyScale);
+        assert_eq!(app.config.default[Axis::X].scale, scale);
+    }
+
+    #[test]
+    fn copy_targets_exclude_selected() {
+        let t = CopyTarget::options(Axis::RX);
+        assert!(!t.contains(&CopyTarget::Axis(Axis::RX)));
+        assert_eq!(t.len(), AXIS.len() + 2);
+
+        assert_eq!(CopyTarget::Axis(Axis::Y).axes(), &[Axis::Y]);
+        assert_eq!(CopyTarget::Linear.axes(), AXIS_LIN);
+        assert_eq!(CopyTarget::Rotational.axes(), AXIS_ROT);
+        assert_eq!(CopyTarget::All.axes(), AXIS);
+    }
+
+    #[test]
+    fn copy_axis_to_group() {
+        let mut app = app();
+        let _ = app.update(Message::SelectDevice("default".to_string()));
+
+        app.config.default[Axis::X].scale = 2.5;
+        app.config.default[Axis::X].deadzone = 0.2;
+        let before = app.config.default;
+
+        let _ = app.update(Message::SelectAxis(Axis::X));
+        let _ = app.update(Message::SelectCopyTarget(CopyTarget::Rotational));
+        let _ = app.update(Message::CopyAxis);
+
+        for a in AXIS_ROT {
+            assert_eq!(app.config.default[*a], before[Axis::X], "{}", a);
+        }
+        for a in AXIS_LIN {
+            assert_eq!(app.config.default[*a], before[*a], "{}", a);
+        }
+
+        // Copies are independent of the source
+        app.config.default[Axis::RX].scale = 0.5;
+        assert_eq!(app.config.default[Axis::X].scale, 2.5);
+        assert_eq!(app.config.default[Axis::RY].scale, 2.5);
+    }
+
+    #[test]
+    fn copy_device_config() {
+        let mut app = app();
+        let dev = UsbDevice { vid: 0x256f, pid: 0xc635, name: None };
+
+        let mut axes = app.config.default;
+        axes[Axis::Z].scale = 4.0;
+        app.config.devices.insert(dev.clone(), axes);
+        app.config.default[Axis::Z].scale = 1.0;
+
+        // Duplicate the default config onto the device
+        let _ = app.update(Message::SelectDevice(dev.to_string()));
+        let _ = app.update(Message::SelectCopySource("default".to_string()));
+        let _ = app.update(Message::CopyDevice);
+        assert_eq!(app.config.devices[&dev], app.config.default);
+
+        // The device config is a copy, later default edits do not apply to it
+        app.config.default[Axis::Z].scale = 2.0;
+        assert_eq!(app.config.devices[&dev][Axis::Z].scale, 1.0);
+
+        // Unknown sources leave the config unchanged
+        let _ = app.update(Message::SelectCopySource("046d:c626".to_string()));
+        let _ = app.update(Message::CopyDevice);
+        assert_eq!(app.config.devices[&dev][Axis::Z].scale, 1.0);
     }
 }
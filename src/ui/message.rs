@@ -1,27 +1,58 @@
-use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use vmouse::{Axis, Client, Command, Map};
+use vmouse::{Axis, Command, CurveType, Map};
+
+use crate::CopyTarget;
 
 #[derive(Clone, Debug)]
 pub enum Message {
     None,
     ScaleChanged(Axis, String),
     ApplyScale,
+    CurveTypeChanged(Axis, CurveType),
     CurveChanged(Axis, f32),
+    CurveTextChanged(Axis, String),
     DeadzoneChanged(Axis, f32),
+    DeadzoneTextChanged(Axis, String),
+    ToggleLinked(Axis),
+    ScaleNegChanged(Axis, String),
+    ApplyScaleNeg,
+    DeadzoneNegChanged(Axis, f32),
     ValueChanged(Axis, f32),
     MappingChanged(Map),
+    AxisSnapChanged(f32),
     SelectDevice(String),
+    AliasTextChanged(String),
+    SetAlias,
+    RemoveDeviceConfig,
     SelectAxis(Axis),
+    SelectCopyTarget(CopyTarget),
+    CopyAxis,
+    SelectCopySource(String),
+    CopyDevice,
+    ToggleSimulate,
+    ToggleProfiles,
+    SelectProfile(String),
+    ProfileNameChanged(String),
+    CreateProfile,
+    RenameProfile,
+    DeleteProfile,
+    ActivateProfile,
     Tick,
+    Error(String),
+    Info(String),
     SocketChanged(String),
     Connect,
     Disconnect,
-    Connected(Arc<Mutex<Option<Client>>>),
+    Connected,
+    Reconnecting(Duration, String),
+    Retry,
     Command(Command),
     ApplyConfig,
     RevertConfig,
     WriteConfig,
+    SaveAsPathChanged(String),
+    SaveConfigAs,
     Attach,
     Detach,
 }
@@ -0,0 +1,42 @@
+//! Per-axis update rate tracking
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Maximum number of update timestamps retained
+const RATE_LEN: usize = 128;
+
+/// Window over which the update rate is computed
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Ring buffer of update timestamps for computing an update rate
+#[derive(Clone, Debug, Default)]
+pub struct RateCounter {
+    times: VecDeque<Instant>,
+}
+
+impl RateCounter {
+    /// Record an update
+    pub fn push(&mut self, now: Instant) {
+        if self.times.len() >= RATE_LEN {
+            self.times.pop_front();
+        }
+        self.times.push_back(now);
+    }
+
+    /// Time of the last update
+    pub fn last(&self) -> Option<Instant> {
+        self.times.back().copied()
+    }
+
+    /// Update rate in Hz over the rate window
+    pub fn rate(&self, now: Instant) -> f32 {
+        let n = self.times.iter().rev().take_while(|t| now.duration_since(**t) <= RATE_WINDOW).count();
+        n as f32 / RATE_WINDOW.as_secs_f32()
+    }
+
+    /// Clear recorded updates
+    pub fn clear(&mut self) {
+        self.times.clear();
+    }
+}
@@ -0,0 +1,91 @@
+//! Persisted GUI state, stored under `$XDG_STATE_HOME/vmouse/ui.toml`
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+/// GUI state persisted between launches
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    /// Last used daemon socket
+    pub socket: Option<String>,
+}
+
+impl UiState {
+    /// Load saved state, returning defaults if unavailable
+    pub fn load() -> Self {
+        let p = match vmouse::ui_state_path() {
+            Some(p) => p,
+            None => return Self::default(),
+        };
+
+        let s = match std::fs::read_to_string(&p) {
+            Ok(s) => s,
+            Err(e) => {
+                debug!("No GUI state loaded from '{}': {:?}", p, e);
+                return Self::default();
+            }
+        };
+
+        match toml::from_str(&s) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Failed to parse GUI state '{}': {:?}", p, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save state, creating the state directory if required
+    pub fn save(&self) -> anyhow::Result<()> {
+        let p = vmouse::ui_state_path()
+            .ok_or_else(|| anyhow::anyhow!("No state directory available (XDG_STATE_HOME / HOME unset)"))?;
+
+        if let Some(d) = std::path::Path::new(&p).parent() {
+            std::fs::create_dir_all(d)?;
+        }
+
+        std::fs::write(&p, toml::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+}
+
+/// Resolve the startup socket, command line flag > saved state > default
+pub fn resolve_socket(flag: Option<String>, saved: Option<String>, default: impl FnOnce() -> String) -> String {
+    flag.or(saved).unwrap_or_else(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(s: &str) -> Option<String> {
+        Some(s.to_string())
+    }
+
+    #[test]
+    fn socket_precedence() {
+        let default = || "/run/vmouse.sock".to_string();
+
+        assert_eq!(resolve_socket(some("/tmp/flag.sock"), some("/tmp/saved.sock"), default), "/tmp/flag.sock");
+        assert_eq!(resolve_socket(some("/tmp/flag.sock"), None, default), "/tmp/flag.sock");
+        assert_eq!(resolve_socket(None, some("/tmp/saved.sock"), default), "/tmp/saved.sock");
+        assert_eq!(resolve_socket(None, None, default), "/run/vmouse.sock");
+    }
+
+    #[test]
+    fn default_only_when_required() {
+        let r = resolve_socket(some("/tmp/flag.sock"), None, || panic!("default resolved"));
+        assert_eq!(r, "/tmp/flag.sock");
+    }
+
+    #[test]
+    fn state_toml() {
+        let s = UiState { socket: some("/tmp/saved.sock") };
+        assert_eq!(toml::from_str::<UiState>(&toml::to_string_pretty(&s).unwrap()).unwrap(), s);
+
+        // Missing fields use defaults
+        assert_eq!(toml::from_str::<UiState>("").unwrap(), UiState::default());
+    }
+}
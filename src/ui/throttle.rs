@@ -0,0 +1,112 @@
+//! Coalescing of high rate daemon messages for display
+
+use std::time::{Duration, Instant};
+
+use futures::{stream::{self, BoxStream}, StreamExt};
+
+use crate::message::Message;
+
+/// Minimum interval between batches of messages, bounds graph updates to ~60 Hz
+const FRAME: Duration = Duration::from_micros(16_667);
+
+/// Maximum number of pending messages collected into a single batch
+const BATCH: usize = 1024;
+
+/// Drop `State` messages superseded by a later `State` for the same device,
+/// all other messages are passed through in order
+fn coalesce(messages: Vec<Message>) -> Vec<Message> {
+    let mut out: Vec<Message> = Vec::with_capacity(messages.len());
+
+    for m in messages {
+        if let Message::Command(vmouse::Command::State { device, .. }) = &m {
+            out.retain(|o| !matches!(o, Message::Command(vmouse::Command::State { device: d, .. }) if d == device));
+        }
+        out.push(m);
+    }
+
+    out
+}
+
+/// Batch pending messages at most once per [`FRAME`], coalescing state updates within each batch
+pub fn throttle(s: BoxStream<'static, Message>) -> BoxStream<'static, Message> {
+    let mut next = Instant::now();
+
+    let batches = s.ready_chunks(BATCH).then(move |batch| {
+        // Hold the batch until the next frame, messages received meanwhile form the next batch
+        let now = Instant::now();
+        let wait = next.saturating_duration_since(now);
+        next = now.max(next) + FRAME;
+
+        async move {
+            if !wait.is_zero() {
+                async_std::task::sleep(wait).await;
+            }
+            stream::iter(coalesce(batch))
+        }
+    });
+
+    Box::pin(batches.flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use vmouse::{Axis, AxisState, Command, UsbDevice};
+
+    use super::*;
+
+    /// State update for a device (by pid) tagged by the raw x value
+    fn state(pid: Option<u16>, x: f32) -> Message {
+        let mut state = AxisState::default();
+        state.raw[Axis::X] = x;
+
+        Message::Command(Command::State { device: pid.map(|pid| UsbDevice { vid: 0x256f, pid, name: None }), state })
+    }
+
+    /// Summarise messages for comparison
+    fn summary(messages: &[Message]) -> Vec<String> {
+        messages.iter()
+            .map(|m| match m {
+                Message::Command(Command::State { device, state }) => {
+                    format!("{}={}", device.as_ref().map(|d| d.to_string()).unwrap_or_default(), state.raw[Axis::X])
+                }
+                m => format!("{:?}", m),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn coalesce_keeps_latest_state_per_device() {
+        let m = vec![
+            state(None, 1.0),
+            state(Some(1), 2.0),
+            state(None, 3.0),
+            state(Some(2), 4.0),
+            state(Some(1), 5.0),
+        ];
+
+        assert_eq!(summary(&coalesce(m)), vec!["=3", "256f:0002=4", "256f:0001=5"]);
+    }
+
+    #[test]
+    fn coalesce_passes_other_messages_in_order() {
+        let m = vec![
+            Message::None,
+            state(None, 1.0),
+            Message::Command(Command::Ok),
+            state(None, 2.0),
+            Message::ApplyScale,
+        ];
+
+        assert_eq!(summary(&coalesce(m)), vec!["None", "Command(Ok)", "=2", "ApplyScale"]);
+        assert!(coalesce(vec![]).is_empty());
+    }
+
+    #[test]
+    fn throttle_coalesces_ready_messages() {
+        let m: Vec<_> = (0..100).map(|i| state(Some(i % 2), i as f32)).collect();
+
+        let out: Vec<_> = async_std::task::block_on(throttle(stream::iter(m).boxed()).collect());
+
+        assert_eq!(summary(&out), vec!["256f:0000=98", "256f:0001=99"]);
+    }
+}
@@ -0,0 +1,347 @@
+//! Wire protocol encoding and length-prefixed message framing
+//!
+//! Each frame is a little-endian `u32` length, followed by a [`WireFormat`]
+//! tag byte and the encoded [`Command`], allowing frames to be split across
+//! or coalesced within reads. JSON is used by default as the externally
+//! tagged encoding tolerates variants being added or reordered.
+//!
+//! Frames may wrap the command in a [`Request`] / [`Response`] envelope carrying an id,
+//! allowing multiple requests in flight. Enveloped frames set [`ENVELOPE`] in the format
+//! tag, bare commands remain accepted for peers predating request ids.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Command, Error};
+
+/// Frame header length (length and format tag)
+const HEADER_LEN: usize = 5;
+
+/// Format tag flag for enveloped frames
+pub const ENVELOPE: u8 = 0x80;
+
+/// Response id for broadcasts and responses to bare commands
+pub const BROADCAST_ID: u64 = 0;
+
+/// Request envelope, the id is echoed in the matching [`Response`]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub id: u64,
+    pub cmd: Command,
+}
+
+/// Response envelope, with the id of the matching [`Request`] or [`BROADCAST_ID`]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub id: u64,
+    pub cmd: Command,
+}
+
+impl Response {
+    /// Wrap a broadcast (or response to a bare command)
+    pub fn broadcast(cmd: Command) -> Self {
+        Self { id: BROADCAST_ID, cmd }
+    }
+}
+
+/// Decoded frame, with the envelope id where enveloped
+#[derive(Clone, PartialEq, Debug)]
+pub struct Frame {
+    pub id: Option<u64>,
+    pub cmd: Command,
+}
+
+/// Maximum frame payload length
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// Frame payload encodings
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum WireFormat {
+    /// Compact bincode encoding, requires identical [`Command`] definitions on both ends
+    Bincode = 1,
+    /// JSON encoding, tolerant of additive changes
+    Json = 2,
+}
+
+impl WireFormat {
+    /// Default format for outgoing frames
+    pub const DEFAULT: WireFormat = WireFormat::Json;
+
+    /// Parse a frame format tag
+    pub fn from_tag(t: u8) -> Option<Self> {
+        match t {
+            1 => Some(WireFormat::Bincode),
+            2 => Some(WireFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Encode a command into a frame using the default format
+pub fn encode(cmd: &Command) -> Result<Vec<u8>, Error> {
+    encode_with(cmd, WireFormat::DEFAULT)
+}
+
+/// Encode a command into a frame using the provided format
+pub fn encode_with(cmd: &Command, format: WireFormat) -> Result<Vec<u8>, Error> {
+    encode_frame(cmd, format as u8, format)
+}
+
+/// Encode an enveloped request into a frame using the default format
+pub fn encode_request(req: &Request) -> Result<Vec<u8>, Error> {
+    let format = WireFormat::DEFAULT;
+    encode_frame(req, format as u8 | ENVELOPE, format)
+}
+
+/// Encode an enveloped response into a frame using the default format
+pub fn encode_response(resp: &Response) -> Result<Vec<u8>, Error> {
+    let format = WireFormat::DEFAULT;
+    encode_frame(resp, format as u8 | ENVELOPE, format)
+}
+
+/// Encode a frame body with the provided format tag
+fn encode_frame<T: Serialize>(body: &T, tag: u8, format: WireFormat) -> Result<Vec<u8>, Error> {
+    let body = match format {
+        WireFormat::Bincode => bincode::serialize(body).map_err(|e| Error::Encode(e.to_string()))?,
+        WireFormat::Json => serde_json::to_vec(body).map_err(|e| Error::Encode(e.to_string()))?,
+    };
+
+    if body.len() > MAX_FRAME_LEN {
+        return Err(Error::Encode(format!("frame length {} exceeds maximum {}", body.len(), MAX_FRAME_LEN)));
+    }
+
+    let mut b = Vec::with_capacity(HEADER_LEN + body.len());
+    b.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    b.push(tag);
+    b.extend_from_slice(&body);
+
+    Ok(b)
+}
+
+/// Streaming frame decoder, buffers partial frames across reads
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    buff: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append received data to the decoder buffer
+    pub fn push(&mut self, data: &[u8]) {
+        self.buff.extend_from_slice(data);
+    }
+
+    /// Decode the next complete frame, returns `None` if more data is required
+    ///
+    /// Envelope ids are discarded, see [`Decoder::decode_frame`].
+    pub fn decode(&mut self) -> Result<Option<Command>, Error> {
+        Ok(self.decode_frame()?.map(|f| f.cmd))
+    }
+
+    /// Decode the next complete frame with its envelope id, returns `None` if more data is required
+    pub fn decode_frame(&mut self) -> Result<Option<Frame>, Error> {
+        if self.buff.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let mut h = [0u8; 4];
+        h.copy_from_slice(&self.buff[..4]);
+        let len = u32::from_le_bytes(h) as usize;
+
+        // Discard buffer on invalid length or format, framing cannot be recovered
+        if len > MAX_FRAME_LEN {
+            self.buff.clear();
+            return Err(Error::Decode(format!("frame length {} exceeds maximum {}", len, MAX_FRAME_LEN)));
+        }
+
+        let envelope = self.buff[4] & ENVELOPE != 0;
+        let format = match WireFormat::from_tag(self.buff[4] & !ENVELOPE) {
+            Some(f) => f,
+            None => {
+                let t = self.buff[4];
+                self.buff.clear();
+                return Err(Error::Decode(format!("unknown frame format {:#04x}, peer may use an incompatible protocol version", t)));
+            }
+        };
+
+        if self.buff.len() < HEADER_LEN + len {
+            return Ok(None);
+        }
+
+        // Requests and responses share an encoding
+        let body = &self.buff[HEADER_LEN..][..len];
+        let f = match envelope {
+            true => decode_body::<Request>(body, format).map(|r| Frame { id: Some(r.id), cmd: r.cmd }),
+            false => decode_body::<Command>(body, format).map(|cmd| Frame { id: None, cmd }),
+        };
+        self.buff.drain(..HEADER_LEN + len);
+
+        Ok(Some(f?))
+    }
+}
+
+/// Decode a frame body in the provided format
+fn decode_body<T: serde::de::DeserializeOwned>(body: &[u8], format: WireFormat) -> Result<T, Error> {
+    match format {
+        WireFormat::Bincode => bincode::deserialize(body).map_err(|e| Error::Decode(e.to_string())),
+        WireFormat::Json => serde_json::from_slice(body).map_err(|e| Error::Decode(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, UsbDevice};
+
+    use super::*;
+
+    fn decode_all(d: &mut Decoder) -> Vec<Command> {
+        std::iter::from_fn(|| d.decode().unwrap()).collect()
+    }
+
+    #[test]
+    fn decode_byte_by_byte() {
+        let cmds = vec![Command::Ping, Command::ListDevices, Command::Enable { enabled: true, device: None }];
+        let b: Vec<u8> = cmds.iter().flat_map(|c| encode(c).unwrap()).collect();
+
+        let mut d = Decoder::new();
+        let mut decoded = vec![];
+        for x in &b {
+            d.push(&[*x]);
+            decoded.extend(decode_all(&mut d));
+        }
+
+        assert_eq!(decoded, cmds);
+    }
+
+    #[test]
+    fn decode_multiple_frames_per_push() {
+        let cmds = vec![Command::Ping, Command::Ok, Command::GetConfig, Command::Disconnect];
+        let mut b: Vec<u8> = cmds.iter().flat_map(|c| encode(c).unwrap()).collect();
+
+        // Trailing partial frame is held until complete
+        let next = encode(&Command::WriteConfig).unwrap();
+        b.extend_from_slice(&next[..3]);
+
+        let mut d = Decoder::new();
+        d.push(&b);
+        assert_eq!(decode_all(&mut d), cmds);
+
+        d.push(&next[3..]);
+        assert_eq!(d.decode().unwrap(), Some(Command::WriteConfig));
+        assert_eq!(d.decode().unwrap(), None);
+    }
+
+    #[test]
+    fn decode_oversize_frame() {
+        let mut d = Decoder::new();
+        d.push(&((MAX_FRAME_LEN + 1) as u32).to_le_bytes());
+        d.push(&[WireFormat::Json as u8]);
+
+        assert!(matches!(d.decode(), Err(Error::Decode(_))));
+
+        // Buffer is discarded, subsequent frames decode
+        d.push(&encode(&Command::Ping).unwrap());
+        assert_eq!(d.decode().unwrap(), Some(Command::Ping));
+    }
+
+    #[test]
+    fn decode_unknown_format() {
+        let mut d = Decoder::new();
+        d.push(&[2, 0, 0, 0, 0x7f, b'{', b'}']);
+
+        match d.decode() {
+            Err(Error::Decode(e)) => assert!(e.contains("unknown frame format")),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        d.push(&encode(&Command::Ok).unwrap());
+        assert_eq!(d.decode().unwrap(), Some(Command::Ok));
+    }
+
+    #[test]
+    fn decode_large_config() {
+        let mut c = Config::default();
+        for pid in 0..64 {
+            c.devices.insert(UsbDevice { vid: 0x256f, pid, name: None }, c.default);
+        }
+
+        let cmd = Command::SetConfig(c);
+        let b = encode(&cmd).unwrap();
+        assert!(b.len() > 1024);
+
+        // Split across reads as a client read buffer would
+        let mut d = Decoder::new();
+        let mut decoded = vec![];
+        for chunk in b.chunks(1024) {
+            d.push(chunk);
+            decoded.extend(decode_all(&mut d));
+        }
+
+        assert_eq!(decoded, vec![cmd]);
+    }
+
+    #[test]
+    fn golden_json() {
+        let mut b = vec![6, 0, 0, 0, 2];
+        b.extend_from_slice(b"\"Ping\"");
+        assert_eq!(encode_with(&Command::Ping, WireFormat::Json).unwrap(), b);
+
+        let mut b = vec![41, 0, 0, 0, 2];
+        b.extend_from_slice(br#"{"Enable":{"enabled":true,"device":null}}"#);
+        assert_eq!(encode_with(&Command::Enable { enabled: true, device: None }, WireFormat::Json).unwrap(), b);
+    }
+
+    #[test]
+    fn golden_bincode() {
+        // Variant index as a little-endian u32
+        assert_eq!(encode_with(&Command::Ping, WireFormat::Bincode).unwrap(), vec![4, 0, 0, 0, 1, 1, 0, 0, 0]);
+
+        let b = vec![6, 0, 0, 0, 1, 16, 0, 0, 0, 1, 0];
+        assert_eq!(encode_with(&Command::Enable { enabled: true, device: None }, WireFormat::Bincode).unwrap(), b);
+
+        let mut d = Decoder::new();
+        d.push(&b);
+        assert_eq!(d.decode().unwrap(), Some(Command::Enable { enabled: true, device: None }));
+    }
+
+    #[test]
+    fn golden_envelope() {
+        let mut b = vec![21, 0, 0, 0, 0x82];
+        b.extend_from_slice(br#"{"id":7,"cmd":"Ping"}"#);
+        assert_eq!(encode_request(&Request { id: 7, cmd: Command::Ping }).unwrap(), b);
+
+        let mut d = Decoder::new();
+        d.push(&b);
+        assert_eq!(d.decode_frame().unwrap(), Some(Frame { id: Some(7), cmd: Command::Ping }));
+
+        let mut b = vec![19, 0, 0, 0, 0x82];
+        b.extend_from_slice(br#"{"id":0,"cmd":"Ok"}"#);
+        assert_eq!(encode_response(&Response::broadcast(Command::Ok)).unwrap(), b);
+    }
+
+    #[test]
+    fn decode_legacy_hello() {
+        // Bincode framed hello from a client using a single counter version
+        let mut b = vec![16, 0, 0, 0, 1];
+        b.extend_from_slice(&[0, 0, 0, 0]);
+        b.extend_from_slice(&5u32.to_le_bytes());
+        b.extend_from_slice(&0u64.to_le_bytes());
+
+        let mut d = Decoder::new();
+        d.push(&b);
+        let version = match d.decode().unwrap() {
+            Some(Command::Hello { version, features }) if features.is_empty() => version,
+            r => panic!("unexpected hello: {:?}", r),
+        };
+        assert_eq!(version, 5);
+        assert!(!crate::protocol_compatible(version));
+
+        // Unframed bincode from clients predating framing is rejected
+        let mut d = Decoder::new();
+        d.push(&b[5..]);
+        assert!(matches!(d.decode(), Err(Error::Decode(_))));
+    }
+}
@@ -0,0 +1,26 @@
+#!/bin/bash
+# Smoke test for the vmoused D-Bus interface (requires `--features dbus` and `vmoused --dbus`,
+# with org.vmouse.Daemon.conf installed and run as root or a member of the input group)
+
+set -e
+
+DEST=org.vmouse.Daemon
+OBJ=/org/vmouse/Daemon
+IFACE=org.vmouse.Daemon
+
+# Check the interface is exported
+busctl introspect $DEST $OBJ $IFACE
+
+# Fetch devices and config
+busctl call $DEST $OBJ $IFACE ListDevices
+CONFIG=$(busctl --json=short call $DEST $OBJ $IFACE GetConfig | sed -e 's/.*"data":\["\(.*\)"\]}/\1/' -e 's/\\"/"/g')
+echo "Config: $CONFIG"
+
+# Round-trip config
+busctl call $DEST $OBJ $IFACE SetConfig s "$CONFIG"
+
+# Toggle output
+busctl call $DEST $OBJ $IFACE Enable b false
+busctl call $DEST $OBJ $IFACE Enable b true
+
+echo "D-Bus smoke test OK"
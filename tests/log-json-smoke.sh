@@ -0,0 +1,19 @@
+#!/bin/bash
+# Smoke test for vmoused JSON logging, checks every output line parses as JSON
+
+set -e
+
+VMOUSED=${VMOUSED:-target/debug/vmoused}
+DIR=$(mktemp -d)
+trap 'rm -rf $DIR' EXIT
+
+# Run briefly with user paths in a scratch directory, startup may fail without uinput access
+XDG_RUNTIME_DIR=$DIR XDG_CONFIG_HOME=$DIR timeout 2 $VMOUSED --user --log-format json > $DIR/log.json || true
+
+test -s $DIR/log.json
+
+while read -r line; do
+    echo "$line" | python3 -m json.tool > /dev/null
+done < $DIR/log.json
+
+echo "JSON logging smoke test OK ($(wc -l < $DIR/log.json) lines)"